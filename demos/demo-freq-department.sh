@@ -0,0 +1,5 @@
+#!/bin/bash
+set -euo pipefail
+cd "$(dirname "$0")/.."
+source scripts/demo-lib.sh
+run_demo "batched" "pipe-run" "specs/freq-department.pipe" "specs/input-fixed-80.data" "work/sample-pipe-outputs/freq-department.out"
@@ -0,0 +1,82 @@
+//! Throughput of a pure filter chain on the scalar fast path versus the
+//! general `Vec`-per-stage path.
+//!
+//! Run with `cargo bench -p naive-pipe --bench filter_chain`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use naive_pipe::{RecordStage, command_to_record_stage, execute_rat};
+use pipelines_rs::{Record, parse_commands};
+
+const PIPELINE: &str = "CONSOLE | LOCATE /SALES/ | NLOCATE /SMITH/ | FILTER 28,8 BETWEEN \"00040000\" AND \"99999999\"";
+
+/// Employee rows in the layout of `specs/input-fixed-80.data`.
+fn input() -> Vec<Record> {
+    let depts = ["SALES", "ENGINEER", "MARKETING", "FINANCE"];
+    let names = ["SMITH", "JONES", "DOE", "BROWN", "WILSON"];
+    (0..10_000)
+        .map(|i| {
+            let line = format!(
+                "{:<8}{:<10}{:<10}{:08}",
+                names[i % names.len()],
+                "FIRST",
+                depts[i % depts.len()],
+                30_000 + (i * 37) % 50_000
+            );
+            Record::from_str(&line)
+        })
+        .collect()
+}
+
+/// Forwards to the wrapped stage but reports no fan-out bound, which
+/// keeps the executor on the general path.
+struct Unbounded(Box<dyn RecordStage>);
+
+impl RecordStage for Unbounded {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.0.process(record)
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.0.flush()
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+fn stages(scalar: bool) -> Vec<Box<dyn RecordStage>> {
+    parse_commands(PIPELINE)
+        .unwrap()
+        .iter()
+        .map(|command| {
+            let stage = command_to_record_stage(command);
+            if scalar {
+                stage
+            } else {
+                Box::new(Unbounded(stage)) as Box<dyn RecordStage>
+            }
+        })
+        .collect()
+}
+
+fn filter_chain(c: &mut Criterion) {
+    let input = input();
+    assert_eq!(
+        execute_rat(input.clone(), &mut stages(true)),
+        execute_rat(input.clone(), &mut stages(false))
+    );
+
+    let mut group = c.benchmark_group("filter_chain");
+    for (label, scalar) in [("scalar", true), ("general", false)] {
+        group.bench_function(label, |b| {
+            b.iter(|| execute_rat(black_box(input.clone()), &mut stages(scalar)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, filter_chain);
+criterion_main!(benches);
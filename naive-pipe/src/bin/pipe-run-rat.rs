@@ -1,7 +1,8 @@
 //! CLI tool to run pipeline (.pipe) files using the record-at-a-time executor.
 
 use clap::Parser;
-use naive_pipe::execute_pipeline_rat;
+use naive_pipe::execute_pipeline_rat_with_options;
+use pipelines_rs::{ExecuteOptions, split_header};
 use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
@@ -26,6 +27,26 @@ struct Cli {
     /// Show paths, executor, and record counts on stderr
     #[arg(short, long)]
     verbose: bool,
+
+    /// Exit with an error if input records produce no output records
+    #[arg(long)]
+    fail_on_empty: bool,
+
+    /// Expand tabs in input to spaces with tab stops every N columns
+    #[arg(long, value_name = "N")]
+    expand_tabs: Option<usize>,
+
+    /// Exit with an error unless the last stage is CONSOLE or HOLE
+    #[arg(long)]
+    require_sink: bool,
+
+    /// Treat the first input line as a header: skip it (and show it with --verbose)
+    #[arg(long)]
+    skip_header: bool,
+
+    /// Join an input line ending in C with the next line into one record
+    #[arg(long, value_name = "C")]
+    continuation: Option<char>,
 }
 
 fn main() {
@@ -51,10 +72,21 @@ fn main() {
         eprintln!("Pipeline: {}", cli.pipeline);
         eprintln!("Input:    {}", cli.input);
         eprintln!("Output:   {}", cli.output.as_deref().unwrap_or("(stdout)"));
+        if cli.skip_header {
+            eprintln!("Header:   {}", split_header(&input_text).0.unwrap_or(""));
+        }
         eprintln!("Executor: record-at-a-time");
     }
 
-    match execute_pipeline_rat(&input_text, &pipeline_text) {
+    let options = ExecuteOptions {
+        fail_on_empty: cli.fail_on_empty,
+        expand_tabs: cli.expand_tabs,
+        require_explicit_sink: cli.require_sink,
+        skip_header: cli.skip_header,
+        continuation: cli.continuation,
+        ..ExecuteOptions::default()
+    };
+    match execute_pipeline_rat_with_options(&input_text, &pipeline_text, &options) {
         Ok((output, input_count, output_count)) => {
             if let Some(out_path) = &cli.output {
                 if let Some(parent) = Path::new(out_path.as_str()).parent()
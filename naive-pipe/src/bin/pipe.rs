@@ -0,0 +1,190 @@
+//! CLI filter: run a pipeline over stdin (or a file) and write the output to stdout.
+
+use clap::Parser;
+use naive_pipe::{RatDebugTrace, execute_pipeline_rat, execute_pipeline_rat_debug};
+use pipelines_rs::{
+    DebugCallbacks, DebugInfo, Record, execute_pipeline, execute_pipeline_debug, parse_commands,
+};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+/// Run a pipeline over stdin (or --input) and write its output to stdout.
+#[derive(Parser)]
+#[command(name = "pipe")]
+struct Cli {
+    /// Pipeline text, e.g. "PIPE CONSOLE | TAKE 5 | CONSOLE"
+    #[arg(
+        required_unless_present = "pipeline_file",
+        conflicts_with = "pipeline_file"
+    )]
+    pipeline: Option<String>,
+
+    /// Read the pipeline from a file (.pipe) instead of the command line
+    #[arg(short, long = "pipeline", value_name = "FILE")]
+    pipeline_file: Option<String>,
+
+    /// Read input records from a file instead of stdin
+    #[arg(short, long, value_name = "FILE")]
+    input: Option<String>,
+
+    /// Use the record-at-a-time executor instead of the batched one
+    #[arg(long)]
+    rat: bool,
+
+    /// Print each stage's output records (batched) or each record's path
+    /// through the stages (--rat) on stderr
+    #[arg(long)]
+    trace: bool,
+
+    /// Print per-stage input and output record counts on stderr
+    #[arg(long)]
+    stats: bool,
+}
+
+/// Per-stage `(name, input_count, output_count)` rows for `--stats`.
+type StageStats = Vec<(String, usize, usize)>;
+
+fn main() {
+    let cli = Cli::parse();
+
+    let pipeline_text = match &cli.pipeline_file {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Error reading pipeline file '{path}': {e}");
+            process::exit(1);
+        }),
+        // clap guarantees pipeline text unless --pipeline was given
+        None => cli.pipeline.clone().unwrap_or_default(),
+    };
+
+    let input_text = match &cli.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Error reading input file '{path}': {e}");
+            process::exit(1);
+        }),
+        None => {
+            let mut text = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut text) {
+                eprintln!("Error reading stdin: {e}");
+                process::exit(1);
+            }
+            text
+        }
+    };
+
+    let result = if cli.trace || cli.stats {
+        run_debug(&cli, &input_text, &pipeline_text)
+    } else if cli.rat {
+        execute_pipeline_rat(&input_text, &pipeline_text).map(|(output, _, _)| output)
+    } else {
+        execute_pipeline(&input_text, &pipeline_text).map(|(output, _, _)| output)
+    };
+
+    match result {
+        Ok(output) => {
+            if let Err(e) = io::stdout().write_all(output.as_bytes()) {
+                eprintln!("Error writing output: {e}");
+                process::exit(1);
+            }
+            if !output.is_empty() && !output.ends_with('\n') {
+                println!();
+            }
+        }
+        Err(e) => {
+            eprintln!("Pipeline error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Run with a debug executor, printing the trace and/or stats on stderr.
+fn run_debug(cli: &Cli, input_text: &str, pipeline_text: &str) -> Result<String, String> {
+    let (output, stats) = if cli.rat {
+        let (output, input_count, _, trace) =
+            execute_pipeline_rat_debug(input_text, pipeline_text)?;
+        if cli.trace {
+            print_rat_trace(&trace);
+        }
+        // The RAT trace starts after the source stage
+        let source = parse_commands(pipeline_text)?[0].name().to_string();
+        let stats: StageStats = std::iter::once((source, 0, input_count))
+            .chain(
+                trace
+                    .stage_names
+                    .iter()
+                    .zip(trace.stage_counts())
+                    .map(|(name, (input, output))| (name.clone(), input, output)),
+            )
+            .collect();
+        (output, stats)
+    } else {
+        let (output, _, _, infos) =
+            execute_pipeline_debug(input_text, pipeline_text, &Some(DebugCallbacks::new()))?;
+        if cli.trace {
+            print_batch_trace(&infos);
+        }
+        let stats: StageStats = infos
+            .iter()
+            .map(|info| (info.stage_name.clone(), info.input_count, info.output_count))
+            .collect();
+        (output, stats)
+    };
+    if cli.stats {
+        for (index, (name, input, output)) in stats.iter().enumerate() {
+            eprintln!("{index:>3}  {name:<10} {input:>8} in {output:>8} out");
+        }
+    }
+    Ok(output)
+}
+
+/// Print each batched stage's output records.
+fn print_batch_trace(infos: &[DebugInfo]) {
+    for (index, info) in infos.iter().enumerate() {
+        eprintln!(
+            "--- stage {index} {}: {} in, {} out",
+            info.stage_name, info.input_count, info.output_count
+        );
+        for record in info.output_records.iter().flatten() {
+            eprintln!("    {}", text(record));
+        }
+    }
+}
+
+/// Print each input record's (and each flush's) path through the RAT stages.
+fn print_rat_trace(trace: &RatDebugTrace) {
+    for (index, record_trace) in trace.record_traces.iter().enumerate() {
+        let Some((input, points)) = record_trace.pipe_points.split_first() else {
+            continue;
+        };
+        eprintln!("--- record {}: {}", index + 1, texts(input));
+        for (name, records) in trace.stage_names.iter().zip(points) {
+            eprintln!("    {name:<10} -> {}", texts(records));
+        }
+    }
+    for flush in &trace.flush_traces {
+        let Some((flushed, points)) = flush.pipe_points.split_first() else {
+            continue;
+        };
+        let name = &trace.stage_names[flush.stage_index];
+        eprintln!("--- flush from {name}: {}", texts(flushed));
+        for (name, records) in trace.stage_names[flush.stage_index + 1..]
+            .iter()
+            .zip(points)
+        {
+            eprintln!("    {name:<10} -> {}", texts(records));
+        }
+    }
+}
+
+/// A record's text without trailing padding.
+fn text(record: &Record) -> &str {
+    record.as_str().trim_end()
+}
+
+/// Records at one pipe point, on one line.
+fn texts(records: &[Record]) -> String {
+    if records.is_empty() {
+        return "(none)".to_string();
+    }
+    records.iter().map(text).collect::<Vec<_>>().join(" | ")
+}
@@ -0,0 +1,137 @@
+//! Builder for record-at-a-time stage chains.
+//!
+//! `StageChain` mixes stages parsed from DSL text with hand-written
+//! `RecordStage`s, such as a `ClosureStage`, and runs them with
+//! `execute_rat`.
+//!
+//! # Example
+//!
+//! ```
+//! use naive_pipe::StageChain;
+//! use pipelines_rs::Record;
+//!
+//! let mut chain = StageChain::from_dsl("LOCATE /SALES/")
+//!     .unwrap()
+//!     .closure("TAG", |mut r: Record| {
+//!         r.set_field(40, 4, "HIT");
+//!         vec![r]
+//!     })
+//!     .then_dsl("COUNT")
+//!     .unwrap();
+//!
+//! let input = vec![Record::from_str("SALES"), Record::from_str("OTHER")];
+//! let output = chain.run(input);
+//! assert_eq!(output[0].as_str().trim_end(), "1");
+//! ```
+
+use pipelines_rs::{Record, parse_commands};
+
+use crate::executor::execute_rat;
+use crate::record_stage::{ClosureStage, RecordStage, command_to_record_stage};
+
+/// An ordered list of record stages, built up one stage at a time.
+#[derive(Default)]
+pub struct StageChain {
+    stages: Vec<Box<dyn RecordStage>>,
+}
+
+impl StageChain {
+    /// Create an empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a chain from DSL stage text, e.g. `"LOCATE /A/ | COUNT"`.
+    ///
+    /// Every command becomes a stage; there is no source stage, since input
+    /// records are passed to [`StageChain::run`] directly.
+    pub fn from_dsl(text: &str) -> Result<Self, String> {
+        Self::new().then_dsl(text)
+    }
+
+    /// Append stages parsed from DSL text.
+    pub fn then_dsl(mut self, text: &str) -> Result<Self, String> {
+        let commands = parse_commands(text)?;
+        self.stages
+            .extend(commands.iter().map(command_to_record_stage));
+        Ok(self)
+    }
+
+    /// Append any `RecordStage`.
+    pub fn then(mut self, stage: impl RecordStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Append a `ClosureStage` running `f` on each record.
+    pub fn closure(
+        self,
+        name: impl Into<String>,
+        f: impl FnMut(Record) -> Vec<Record> + 'static,
+    ) -> Self {
+        self.then(ClosureStage::new(name, f))
+    }
+
+    /// Stage names in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+
+    /// Run the chain over `input` with the record-at-a-time executor.
+    pub fn run(&mut self, input: Vec<Record>) -> Vec<Record> {
+        execute_rat(input, &mut self.stages)
+    }
+
+    /// Take the stages, e.g. for `execute_rat_traced`.
+    pub fn into_stages(self) -> Vec<Box<dyn RecordStage>> {
+        self.stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closure_between_dsl_stages() {
+        let chain = StageChain::from_dsl("UPPER")
+            .unwrap()
+            .closure("SUFFIX", |r: Record| {
+                let text = format!("{}!", r.as_str().trim_end());
+                vec![Record::from_str(&text)]
+            })
+            .then_dsl("DUPLICATE 2")
+            .unwrap();
+        assert_eq!(chain.names(), vec!["UPPER", "SUFFIX", "DUPLICATE"]);
+
+        let mut chain = chain;
+        let output = chain.run(vec![Record::from_str("hi")]);
+        let lines: Vec<&str> = output.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(lines, vec!["HI!", "HI!"]);
+    }
+
+    #[test]
+    fn test_closure_output_reaches_flushing_stage() {
+        // A filtering closure feeds COUNT, whose flush is propagated as usual
+        let mut chain = StageChain::new()
+            .closure("EVEN", |r: Record| {
+                let keep = r
+                    .as_str()
+                    .trim_end()
+                    .parse::<u32>()
+                    .is_ok_and(|n| n % 2 == 0);
+                if keep { vec![r] } else { vec![] }
+            })
+            .then_dsl("COUNT | CONSOLE")
+            .unwrap();
+        let input = (1..=5).map(|i| Record::from_str(&i.to_string())).collect();
+        let output = chain.run(input);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].as_str().trim_end(), "2");
+    }
+
+    #[test]
+    fn test_from_dsl_error() {
+        assert!(StageChain::from_dsl("BOGUS").is_err());
+    }
+}
@@ -2,8 +2,11 @@
 //!
 //! These types capture the journey of each record through the pipeline,
 //! enabling visualization of record-at-a-time execution flow.
+//! [`TraceCursor`] steps through a captured trace one pipe point at a time.
 
-use pipelines_rs::Record;
+use std::ops::Range;
+
+use pipelines_rs::{Record, RecordLayout};
 
 /// Trace of one input record's journey through the pipeline.
 ///
@@ -37,6 +40,295 @@ pub struct RatDebugTrace {
     pub record_traces: Vec<RecordTrace>,
     /// One trace per stage that produced flush output.
     pub flush_traces: Vec<FlushTrace>,
+    /// Field layout declared by a `# LAYOUT` directive, if any.
+    pub layout: Option<RecordLayout>,
+}
+
+impl RatDebugTrace {
+    /// Total `(input, output)` record counts for each stage, in
+    /// `stage_names` order, summed over the record and flush traces.
+    #[must_use]
+    pub fn stage_counts(&self) -> Vec<(usize, usize)> {
+        let mut counts = vec![(0, 0); self.stage_names.len()];
+        for trace in &self.record_traces {
+            for (stage, points) in trace.pipe_points.windows(2).enumerate() {
+                counts[stage].0 += points[0].len();
+                counts[stage].1 += points[1].len();
+            }
+        }
+        for trace in &self.flush_traces {
+            // Flush output counts toward its own stage, then feeds the rest
+            let Some((flushed, downstream)) = trace.pipe_points.split_first() else {
+                continue;
+            };
+            counts[trace.stage_index].1 += flushed.len();
+            let mut input = flushed.len();
+            for (offset, records) in downstream.iter().enumerate() {
+                let stage = trace.stage_index + 1 + offset;
+                counts[stage].0 += input;
+                counts[stage].1 += records.len();
+                input = records.len();
+            }
+        }
+        counts
+    }
+}
+
+/// Where a [`TraceCursor`] stands: the trace entry being revealed and the
+/// pipe point revealed last.
+///
+/// Pipe points are numbered as in [`trace_to_csv`]: point 0 is the input to
+/// the first stage and point `p` the output of stage `p - 1`, so the last
+/// point, `stage_names.len()`, is the pipeline's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorPosition {
+    /// Stepping through `record_traces[index]`.
+    Record { index: usize, pipe_point: usize },
+    /// Stepping through `flush_traces[index]`, which starts at the pipe
+    /// point after the stage that flushed.
+    Flush { index: usize, pipe_point: usize },
+}
+
+/// A stepping position within a [`RatDebugTrace`].
+///
+/// Each step reveals one more pipe point of the current entry: every
+/// record trace in input order, then every flush trace. An entry ends at
+/// its last pipe point, or at its first empty one (a filtered record has
+/// nothing further to show). Step 0 reveals nothing; step
+/// [`total_steps`](Self::total_steps) reveals everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceCursor {
+    trace: RatDebugTrace,
+    /// Steps in each record trace, then in each flush trace.
+    entry_steps: Vec<usize>,
+    step: usize,
+}
+
+/// Steps to reveal `pipe_points`, stopping after the first empty one.
+fn entry_steps(pipe_points: &[Vec<Record>]) -> usize {
+    pipe_points
+        .iter()
+        .position(Vec::is_empty)
+        .map_or(pipe_points.len(), |i| i + 1)
+}
+
+impl TraceCursor {
+    /// A cursor at step 0 of `trace`.
+    #[must_use]
+    pub fn new(trace: RatDebugTrace) -> Self {
+        let entry_steps = trace
+            .record_traces
+            .iter()
+            .map(|t| entry_steps(&t.pipe_points))
+            .chain(
+                trace
+                    .flush_traces
+                    .iter()
+                    .map(|t| entry_steps(&t.pipe_points)),
+            )
+            .collect();
+        Self {
+            trace,
+            entry_steps,
+            step: 0,
+        }
+    }
+
+    /// The trace being stepped through.
+    #[must_use]
+    pub fn trace(&self) -> &RatDebugTrace {
+        &self.trace
+    }
+
+    /// The current step, from 0 to [`total_steps`](Self::total_steps).
+    #[must_use]
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Steps needed to reveal the whole trace.
+    #[must_use]
+    pub fn total_steps(&self) -> usize {
+        self.entry_steps.iter().sum()
+    }
+
+    /// Reveal the next pipe point. Returns `false` if already at the end.
+    pub fn step_forward(&mut self) -> bool {
+        let moved = self.step < self.total_steps();
+        if moved {
+            self.step += 1;
+        }
+        moved
+    }
+
+    /// Hide the last revealed pipe point. Returns `false` if already at
+    /// step 0.
+    pub fn step_back(&mut self) -> bool {
+        let moved = self.step > 0;
+        if moved {
+            self.step -= 1;
+        }
+        moved
+    }
+
+    /// Jump to `step`, clamped to [`total_steps`](Self::total_steps).
+    pub fn goto(&mut self, step: usize) {
+        self.step = step.min(self.total_steps());
+    }
+
+    /// The entry being revealed and the position of its last revealed pipe
+    /// point, or `None` at step 0.
+    #[must_use]
+    pub fn position(&self) -> Option<CursorPosition> {
+        let (entry, revealed) = self.current()?;
+        let (first, _) = self.entry_points(entry);
+        let pipe_point = first + revealed - 1;
+        let records = self.trace.record_traces.len();
+        Some(if entry < records {
+            CursorPosition::Record {
+                index: entry,
+                pipe_point,
+            }
+        } else {
+            CursorPosition::Flush {
+                index: entry - records,
+                pipe_point,
+            }
+        })
+    }
+
+    /// Records of the current entry at `pipe_point`, empty if that point
+    /// is not revealed yet or the entry does not pass through it.
+    #[must_use]
+    pub fn records_at(&self, pipe_point: usize) -> &[Record] {
+        let Some((entry, revealed)) = self.current() else {
+            return &[];
+        };
+        let (first, points) = self.entry_points(entry);
+        pipe_point
+            .checked_sub(first)
+            .filter(|&i| i < revealed)
+            .and_then(|i| points.get(i))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Pipe points of the current entry revealed so far, empty at step 0.
+    #[must_use]
+    pub fn revealed_points(&self) -> Range<usize> {
+        self.current().map_or(0..0, |(entry, revealed)| {
+            let (first, _) = self.entry_points(entry);
+            first..first + revealed
+        })
+    }
+
+    /// Steps taken into the current entry and the steps it has in all, or
+    /// `None` at step 0.
+    #[must_use]
+    pub fn entry_progress(&self) -> Option<(usize, usize)> {
+        let (entry, revealed) = self.current()?;
+        Some((revealed, self.entry_steps[entry]))
+    }
+
+    /// Records that have left the last stage so far, in output order.
+    ///
+    /// An entry's output counts once the entry is fully revealed, so the
+    /// result at [`total_steps`](Self::total_steps) is the pipeline output.
+    #[must_use]
+    pub fn accumulated_output(&self) -> Vec<&Record> {
+        let mut remaining = self.step;
+        let mut output = Vec::new();
+        for (entry, &steps) in self.entry_steps.iter().enumerate() {
+            if remaining < steps {
+                break;
+            }
+            remaining -= steps;
+            let (_, points) = self.entry_points(entry);
+            // An entry that stopped early ends on an empty point
+            if points.len() == steps {
+                output.extend(points.last().into_iter().flatten());
+            }
+        }
+        output
+    }
+
+    /// The entry holding the current step and how many of its pipe points
+    /// are revealed (at least one).
+    fn current(&self) -> Option<(usize, usize)> {
+        let mut remaining = self.step;
+        for (entry, &steps) in self.entry_steps.iter().enumerate() {
+            if remaining == 0 {
+                return None;
+            }
+            if remaining <= steps {
+                return Some((entry, remaining));
+            }
+            remaining -= steps;
+        }
+        None
+    }
+
+    /// An entry's pipe points and the pipe point number of the first one.
+    fn entry_points(&self, entry: usize) -> (usize, &[Vec<Record>]) {
+        let records = self.trace.record_traces.len();
+        match entry.checked_sub(records) {
+            None => (0, &self.trace.record_traces[entry].pipe_points),
+            Some(index) => {
+                let flush = &self.trace.flush_traces[index];
+                (flush.stage_index + 1, &flush.pipe_points)
+            }
+        }
+    }
+}
+
+/// Export a trace as CSV, one row per pipe point, for offline analysis.
+///
+/// Columns are `record_index, pipe_point_index, stage_name, record_count,
+/// first_record_preview`, after a header row. Input record `i` gets one
+/// row per pipe point: point 0 is the input (stage name `INPUT`) and point
+/// `p` the output of stage `p - 1`. Flush output follows with `flush` as
+/// its record index, numbered by the same pipe points: the stage that
+/// flushed, then each stage downstream. The preview is the first record at
+/// the point without trailing padding, empty when there is none. Fields
+/// holding a comma, quote or line break are quoted.
+#[must_use]
+pub fn trace_to_csv(trace: &RatDebugTrace) -> String {
+    let mut csv = String::from(
+        "record_index,pipe_point_index,stage_name,record_count,first_record_preview\n",
+    );
+    let mut row = |index: &str, point: usize, stage: &str, records: &[Record]| {
+        let preview = records.first().map_or("", |r| r.as_str().trim_end());
+        csv.push_str(&format!(
+            "{index},{point},{},{},{}\n",
+            csv_field(stage),
+            records.len(),
+            csv_field(preview)
+        ));
+    };
+    for (index, record_trace) in trace.record_traces.iter().enumerate() {
+        for (point, records) in record_trace.pipe_points.iter().enumerate() {
+            let stage = match point {
+                0 => "INPUT",
+                _ => &trace.stage_names[point - 1],
+            };
+            row(&index.to_string(), point, stage, records);
+        }
+    }
+    for flush in &trace.flush_traces {
+        for (offset, records) in flush.pipe_points.iter().enumerate() {
+            let stage = flush.stage_index + offset;
+            row("flush", stage + 1, &trace.stage_names[stage], records);
+        }
+    }
+    csv
+}
+
+/// Quote a CSV field if it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
 }
 
 #[cfg(test)]
@@ -66,12 +358,216 @@ mod tests {
         assert_eq!(trace.pipe_points.len(), 1);
     }
 
+    #[test]
+    fn test_stage_counts_match_batch() {
+        use pipelines_rs::{DebugCallbacks, execute_pipeline_debug};
+
+        let spec = concat!(env!("CARGO_MANIFEST_DIR"), "/../specs/input-fixed-80.data");
+        let input = &std::fs::read_to_string(spec).unwrap();
+        let pipeline =
+            "PIPE CONSOLE | LOCATE /E/ | DUPLICATE 2 | SORT 28,8 | TAKE 3 | COUNT | CONSOLE";
+        let (_, _, _, trace) = crate::execute_pipeline_rat_debug(input, pipeline).unwrap();
+        let (_, _, _, infos) =
+            execute_pipeline_debug(input, pipeline, &Some(DebugCallbacks::new())).unwrap();
+        let batch: Vec<(usize, usize)> = infos[1..]
+            .iter()
+            .map(|info| (info.input_count, info.output_count))
+            .collect();
+        assert_eq!(trace.stage_counts(), batch);
+        // SORT emits everything on flush; TAKE still sees all of it
+        assert_eq!(trace.stage_counts()[2..4], [(16, 16), (16, 3)]);
+    }
+
+    #[test]
+    fn test_trace_to_csv_rows() {
+        let input = "say \"hi\", ok\nb\nc";
+        let pipeline = "PIPE CONSOLE | UPPER | COUNT | CONSOLE";
+        let (_, _, _, trace) = crate::execute_pipeline_rat_debug(input, pipeline).unwrap();
+        let csv = trace_to_csv(&trace);
+        let rows: Vec<&str> = csv.lines().collect();
+        // Header, 3 records x 4 pipe points, COUNT's flush at 2 pipe points
+        assert_eq!(rows.len(), 1 + 3 * 4 + 2);
+        assert_eq!(
+            rows[0],
+            "record_index,pipe_point_index,stage_name,record_count,first_record_preview"
+        );
+        assert_eq!(rows[1], r#"0,0,INPUT,1,"say ""hi"", ok""#);
+        assert_eq!(rows[2], r#"0,1,UPPER,1,"SAY ""HI"", OK""#);
+        assert_eq!(rows[3], "0,2,COUNT,0,");
+        assert_eq!(rows[13], "flush,2,COUNT,1,3");
+        assert_eq!(rows[14], "flush,3,CONSOLE,1,3");
+    }
+
+    /// Trace of `PIPE CONSOLE | LOCATE /a/ | COUNT | CONSOLE` over
+    /// `ab`, `cd`, `ax`: pipe points 0 (input) to 3 (output).
+    fn cursor_over_count() -> TraceCursor {
+        let pipeline = "PIPE CONSOLE | LOCATE /a/ | COUNT | CONSOLE";
+        let (_, _, _, trace) = crate::execute_pipeline_rat_debug("ab\ncd\nax", pipeline).unwrap();
+        TraceCursor::new(trace)
+    }
+
+    fn texts(records: &[&Record]) -> Vec<String> {
+        records
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_cursor_steps_stop_at_filtered_points() {
+        let mut cursor = cursor_over_count();
+        // ab: 3 points (COUNT holds it), cd: 2 (LOCATE drops it),
+        // ax: 3, then COUNT's flush at points 2 and 3
+        assert_eq!(cursor.total_steps(), 3 + 2 + 3 + 2);
+        assert_eq!(cursor.position(), None);
+        let mut positions = Vec::new();
+        while cursor.step_forward() {
+            positions.push(cursor.position().unwrap());
+        }
+        use CursorPosition::{Flush, Record as Rec};
+        assert_eq!(
+            positions,
+            vec![
+                Rec {
+                    index: 0,
+                    pipe_point: 0
+                },
+                Rec {
+                    index: 0,
+                    pipe_point: 1
+                },
+                Rec {
+                    index: 0,
+                    pipe_point: 2
+                },
+                Rec {
+                    index: 1,
+                    pipe_point: 0
+                },
+                Rec {
+                    index: 1,
+                    pipe_point: 1
+                },
+                Rec {
+                    index: 2,
+                    pipe_point: 0
+                },
+                Rec {
+                    index: 2,
+                    pipe_point: 1
+                },
+                Rec {
+                    index: 2,
+                    pipe_point: 2
+                },
+                Flush {
+                    index: 0,
+                    pipe_point: 2
+                },
+                Flush {
+                    index: 0,
+                    pipe_point: 3
+                },
+            ]
+        );
+        assert_eq!(cursor.step(), 10);
+        assert!(!cursor.step_forward());
+    }
+
+    #[test]
+    fn test_cursor_records_at_revealed_points_only() {
+        let mut cursor = cursor_over_count();
+        assert!(cursor.records_at(0).is_empty());
+        assert_eq!(cursor.revealed_points(), 0..0);
+        assert_eq!(cursor.entry_progress(), None);
+        cursor.goto(2);
+        assert_eq!(cursor.revealed_points(), 0..2);
+        assert_eq!(cursor.entry_progress(), Some((2, 3)));
+        assert_eq!(cursor.records_at(0)[0].as_str().trim_end(), "ab");
+        assert_eq!(cursor.records_at(1)[0].as_str().trim_end(), "ab");
+        assert!(cursor.records_at(2).is_empty(), "not revealed yet");
+
+        // First flush step: the flushing stage's output is point 2
+        cursor.goto(9);
+        assert_eq!(cursor.revealed_points(), 2..3);
+        assert_eq!(cursor.entry_progress(), Some((1, 2)));
+        assert!(cursor.records_at(1).is_empty(), "flush starts after COUNT");
+        assert_eq!(cursor.records_at(2)[0].as_str().trim_end(), "2");
+        assert!(cursor.records_at(3).is_empty());
+        assert!(cursor.step_forward());
+        assert_eq!(cursor.records_at(3)[0].as_str().trim_end(), "2");
+        assert!(cursor.records_at(4).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_accumulates_output_as_entries_complete() {
+        let pipeline = "PIPE CONSOLE | UPPER | CONSOLE";
+        let (_, _, _, trace) = crate::execute_pipeline_rat_debug("a\nb", pipeline).unwrap();
+        let mut cursor = TraceCursor::new(trace);
+        assert_eq!(cursor.total_steps(), 6);
+        cursor.goto(2);
+        assert!(cursor.accumulated_output().is_empty());
+        cursor.goto(3);
+        assert_eq!(texts(&cursor.accumulated_output()), ["A"]);
+        cursor.goto(usize::MAX);
+        assert_eq!(cursor.step(), 6);
+        assert_eq!(texts(&cursor.accumulated_output()), ["A", "B"]);
+
+        let mut cursor = cursor_over_count();
+        cursor.goto(9);
+        assert!(cursor.accumulated_output().is_empty());
+        cursor.goto(10);
+        assert_eq!(texts(&cursor.accumulated_output()), ["2"]);
+    }
+
+    #[test]
+    fn test_cursor_step_back_mirrors_step_forward() {
+        let mut cursor = cursor_over_count();
+        assert!(!cursor.step_back());
+        cursor.goto(cursor.total_steps());
+        let mut positions = vec![cursor.position()];
+        while cursor.step_back() {
+            positions.push(cursor.position());
+        }
+        assert_eq!(cursor.step(), 0);
+        positions.reverse();
+        let mut forward = cursor_over_count();
+        let mut expected = vec![forward.position()];
+        while forward.step_forward() {
+            expected.push(forward.position());
+        }
+        assert_eq!(positions, expected);
+
+        // Stepping back out of the flush phase lands on the last record
+        cursor.goto(9);
+        assert!(cursor.step_back());
+        assert_eq!(
+            cursor.position(),
+            Some(CursorPosition::Record {
+                index: 2,
+                pipe_point: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_cursor_over_empty_trace() {
+        let pipeline = "PIPE CONSOLE | UPPER | CONSOLE";
+        let (_, _, _, trace) = crate::execute_pipeline_rat_debug("", pipeline).unwrap();
+        let mut cursor = TraceCursor::new(trace);
+        assert_eq!(cursor.total_steps(), 0);
+        assert!(!cursor.step_forward());
+        assert_eq!(cursor.position(), None);
+        assert!(cursor.accumulated_output().is_empty());
+    }
+
     #[test]
     fn test_rat_debug_trace_structure() {
         let trace = RatDebugTrace {
             stage_names: vec!["FILTER".to_string(), "COUNT".to_string()],
             record_traces: vec![],
             flush_traces: vec![],
+            layout: None,
         };
         assert_eq!(trace.stage_names.len(), 2);
         assert!(trace.record_traces.is_empty());
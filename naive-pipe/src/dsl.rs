@@ -1,12 +1,20 @@
 //! RAT-specific pipeline execution wrappers.
 //!
-//! Provides `execute_pipeline_rat` and `execute_pipeline_rat_debug` which
-//! parse DSL text and execute using the record-at-a-time executor.
+//! Provides `execute_pipeline_rat`, `execute_pipeline_rat_debug` and
+//! `execute_pipeline_rat_stats` which parse DSL text and execute using the
+//! record-at-a-time executor, plus `execute_parsed_rat` for running commands
+//! that were parsed earlier.
 
-use pipelines_rs::{Command, Record, parse_commands};
+use pipelines_rs::{
+    Command, ExecuteOptions, PipelineError, Record, check_includes_expanded, generate_records,
+    parse_commands, parse_commands_with_options, parse_layout, read_input_records,
+};
 
 use crate::debug_trace::RatDebugTrace;
-use crate::executor::{execute_rat, execute_rat_traced};
+use crate::executor::{
+    execute_rat_checked, execute_rat_expansion_limited, execute_rat_limited, execute_rat_stats,
+    execute_rat_traced,
+};
 use crate::record_stage::{RecordStage, command_to_record_stage};
 
 /// Execute a pipeline in record-at-a-time mode.
@@ -17,8 +25,35 @@ pub fn execute_pipeline_rat(
     input_text: &str,
     pipeline_text: &str,
 ) -> Result<(String, usize, usize), String> {
-    let commands = parse_commands(pipeline_text)?;
+    execute_pipeline_rat_with_options(input_text, pipeline_text, &ExecuteOptions::default())
+}
 
+/// Execute a pipeline in record-at-a-time mode with explicit options.
+///
+/// Honors `options.fail_on_empty`, `options.require_explicit_sink`,
+/// `options.max_output_records`, `options.expansion_factor_limit`,
+/// `options.skip_header`, `options.continuation`, `options.transforms`,
+/// `options.pipelines` and `options.expand_tabs`; `warn_truncation` is a
+/// batch-only report and is ignored here.
+pub fn execute_pipeline_rat_with_options(
+    input_text: &str,
+    pipeline_text: &str,
+    options: &ExecuteOptions,
+) -> Result<(String, usize, usize), String> {
+    let commands = parse_commands_with_options(pipeline_text, options)?;
+    execute_parsed_rat(&commands, input_text, options)
+}
+
+/// Execute an already-parsed pipeline in record-at-a-time mode.
+///
+/// Lets callers parse once and run many times; `options.transforms` and
+/// `options.pipelines` are not consulted because they are resolved at parse
+/// time.
+pub fn execute_parsed_rat(
+    commands: &[Command],
+    input_text: &str,
+    options: &ExecuteOptions,
+) -> Result<(String, usize, usize), String> {
     if commands.is_empty() {
         return Err("Pipeline is empty".to_string());
     }
@@ -33,15 +68,19 @@ pub fn execute_pipeline_rat(
             first.name()
         ));
     }
+    check_includes_expanded(commands)?;
+    options.check_sink(commands)?;
+    options.check_bounds(commands)?;
 
     let input_records: Vec<Record> = match first {
-        Command::Console => input_text
-            .lines()
-            .filter(|line| !line.is_empty())
-            .map(Record::from_str)
-            .collect(),
+        Command::Console => options.console_records(input_text),
         Command::Literal { text } => vec![Record::from_str(text)],
-        Command::Hole => vec![],
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => generate_records(*count, template, *seed),
+        Command::Hole { .. } => vec![],
         _ => return Err(format!("Unhandled source stage: {}", first.name())),
     };
 
@@ -50,27 +89,96 @@ pub fn execute_pipeline_rat(
     let mut stages: Vec<Box<dyn RecordStage>> =
         commands[1..].iter().map(command_to_record_stage).collect();
 
-    let output_records = execute_rat(input_records, &mut stages);
+    let output_records = match (options.max_output_records, options.expansion_factor_limit) {
+        (None, None) => execute_rat_checked(input_records, &mut stages).map_err(stage_error)?,
+        (Some(limit), None) => {
+            execute_rat_limited(input_records, &mut stages, limit).map_err(|e| e.to_string())?
+        }
+        (max_output, Some(limit)) => {
+            execute_rat_expansion_limited(input_records, &mut stages, limit, max_output)
+                .map_err(|e| e.to_string())?
+        }
+    };
     let output_count = output_records.len();
+    options.check_output(input_count, output_count)?;
 
-    let output_text = output_records
-        .iter()
-        .map(|r| r.as_str().trim_end())
-        .collect::<Vec<_>>()
-        .join("\n");
+    Ok((output_text(&output_records), input_count, output_count))
+}
 
-    Ok((output_text, input_count, output_count))
+/// A RAT stage failure as the batch executor reports it: the stage's own
+/// error, without the stage and record `StageFailed` adds.
+fn stage_error(error: PipelineError) -> String {
+    match error {
+        PipelineError::StageFailed { source, .. } => source.to_string(),
+        error => error.to_string(),
+    }
 }
 
 /// Execute a pipeline in record-at-a-time mode with debug tracing.
 ///
 /// Returns (output_text, input_count, output_count, trace) on success.
+/// The trace carries the pipeline's `# LAYOUT` directive, if any.
 pub fn execute_pipeline_rat_debug(
     input_text: &str,
     pipeline_text: &str,
 ) -> Result<(String, usize, usize, RatDebugTrace), String> {
     let commands = parse_commands(pipeline_text)?;
+    let layout = parse_layout(pipeline_text)?;
+    let (input_records, mut stages) = source_and_stages(&commands, input_text)?;
+    let input_count = input_records.len();
+
+    let (output_records, mut trace) =
+        execute_rat_traced(input_records, &mut stages).map_err(stage_error)?;
+    trace.layout = layout;
+    let output_count = output_records.len();
+
+    Ok((
+        output_text(&output_records),
+        input_count,
+        output_count,
+        trace,
+    ))
+}
 
+/// Per-stage `(name, input_count, output_count)` rows from
+/// [`execute_pipeline_rat_stats`].
+pub type StageCounts = Vec<(String, usize, usize)>;
+
+/// A pipeline's input records and the RAT stages that follow its source.
+type SourceAndStages = (Vec<Record>, Vec<Box<dyn RecordStage>>);
+
+/// Execute a pipeline in record-at-a-time mode, counting each stage's
+/// records.
+///
+/// Returns the output text and one `(name, input_count, output_count)` row
+/// per stage, starting with the source stage, whose input count is 0 and
+/// whose output count is the number of records it read. Cheaper than
+/// [`execute_pipeline_rat_debug`] when only the counts are wanted.
+pub fn execute_pipeline_rat_stats(
+    input_text: &str,
+    pipeline_text: &str,
+) -> Result<(String, StageCounts), String> {
+    let commands = parse_commands(pipeline_text)?;
+    let (input_records, mut stages) = source_and_stages(&commands, input_text)?;
+    let source = (commands[0].name().to_string(), 0, input_records.len());
+
+    let (output_records, counts) =
+        execute_rat_stats(input_records, &mut stages).map_err(stage_error)?;
+    let stats = std::iter::once(source)
+        .chain(
+            stages
+                .iter()
+                .zip(counts)
+                .map(|(stage, (input, output))| (stage.name().to_string(), input, output)),
+        )
+        .collect();
+
+    Ok((output_text(&output_records), stats))
+}
+
+/// Validate a pipeline for the debug executors and build its input records
+/// and RAT stages. Unlike [`execute_parsed_rat`], no options are applied.
+fn source_and_stages(commands: &[Command], input_text: &str) -> Result<SourceAndStages, String> {
     if commands.is_empty() {
         return Err("Pipeline is empty".to_string());
     }
@@ -87,29 +195,236 @@ pub fn execute_pipeline_rat_debug(
     }
 
     let input_records: Vec<Record> = match first {
-        Command::Console => input_text
-            .lines()
-            .filter(|line| !line.is_empty())
-            .map(Record::from_str)
-            .collect(),
+        Command::Console => read_input_records(input_text, None),
         Command::Literal { text } => vec![Record::from_str(text)],
-        Command::Hole => vec![],
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => generate_records(*count, template, *seed),
+        Command::Hole { .. } => vec![],
         _ => return Err(format!("Unhandled source stage: {}", first.name())),
     };
 
-    let input_count = input_records.len();
-
-    let mut stages: Vec<Box<dyn RecordStage>> =
-        commands[1..].iter().map(command_to_record_stage).collect();
-
-    let (output_records, trace) = execute_rat_traced(input_records, &mut stages);
-    let output_count = output_records.len();
+    let stages = commands[1..].iter().map(command_to_record_stage).collect();
+    Ok((input_records, stages))
+}
 
-    let output_text = output_records
+/// Join output records into text, one line each with trailing spaces trimmed.
+fn output_text(records: &[Record]) -> String {
+    records
         .iter()
         .map(|r| r.as_str().trim_end())
         .collect::<Vec<_>>()
-        .join("\n");
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pipelines_rs::PipelineError;
+
+    #[test]
+    fn test_debug_trace_carries_layout() {
+        let pipeline = "# LAYOUT Name 0,5\nPIPE CONSOLE\n| CONSOLE\n?";
+        let (_, _, _, trace) = execute_pipeline_rat_debug("alice", pipeline).unwrap();
+        let layout = trace.layout.unwrap();
+        assert_eq!(layout.fields, vec![("Name".to_string(), 0, 5)]);
+
+        let (_, _, _, trace) =
+            execute_pipeline_rat_debug("alice", "PIPE CONSOLE | CONSOLE").unwrap();
+        assert_eq!(trace.layout, None);
+    }
+
+    #[test]
+    fn test_stats_rows_start_with_source() {
+        let pipeline = "PIPE CONSOLE | LOCATE /A/ | DUPLICATE 2 | CONSOLE";
+        let (output, stats) = execute_pipeline_rat_stats("A1\nB2\nA3", pipeline).unwrap();
+        assert_eq!(
+            output,
+            execute_pipeline_rat("A1\nB2\nA3", pipeline).unwrap().0
+        );
+        let rows: Vec<(&str, usize, usize)> = stats
+            .iter()
+            .map(|(name, input, output)| (name.as_str(), *input, *output))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("CONSOLE", 0, 3),
+                ("LOCATE", 3, 2),
+                ("DUPLICATE", 2, 4),
+                ("CONSOLE", 4, 4),
+            ]
+        );
+        assert!(execute_pipeline_rat_stats("A1", "LOCATE /A/ | CONSOLE").is_err());
+    }
+
+    #[test]
+    fn test_fail_on_empty_output() {
+        let pipeline = "PIPE CONSOLE | LOCATE /ZZZ/ | CONSOLE";
+        let options = ExecuteOptions {
+            fail_on_empty: true,
+            ..ExecuteOptions::default()
+        };
+        assert!(execute_pipeline_rat("A\nB", pipeline).is_ok());
+        assert!(execute_pipeline_rat_with_options("A\nB", pipeline, &options).is_err());
+        assert!(execute_pipeline_rat_with_options("ZZZ", pipeline, &options).is_ok());
+    }
+
+    #[test]
+    fn test_require_explicit_sink() {
+        let pipeline = "PIPE CONSOLE | FILTER 0,1 = \"A\"";
+        let options = ExecuteOptions {
+            require_explicit_sink: true,
+            ..ExecuteOptions::default()
+        };
+        assert!(execute_pipeline_rat("A\nB", pipeline).is_ok());
+        let err = execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap_err();
+        assert!(err.contains("FILTER"), "{err}");
+        let pipeline = "PIPE CONSOLE | FILTER 0,1 = \"A\" | HOLE";
+        assert!(execute_pipeline_rat_with_options("A\nB", pipeline, &options).is_ok());
+    }
+
+    #[test]
+    fn test_strict_bounds() {
+        let pipeline = "PIPE CONSOLE | SELECT 0,8,200 | CONSOLE";
+        let options = ExecuteOptions {
+            strict_bounds: true,
+            ..ExecuteOptions::default()
+        };
+        assert!(execute_pipeline_rat("A", pipeline).is_ok());
+        let err = execute_pipeline_rat_with_options("A", pipeline, &options).unwrap_err();
+        let expected = PipelineError::FieldOutOfBounds {
+            stage: "SELECT".to_string(),
+            pos: 200,
+            len: 8,
+        };
+        assert_eq!(err, expected.to_string());
+    }
+
+    #[test]
+    fn test_include_matches_batch() {
+        use pipelines_rs::{PipelineRegistry, execute_pipeline_with_options, parse_snippet};
+        let options = ExecuteOptions {
+            pipelines: PipelineRegistry::default()
+                .with("norm", parse_snippet("UPPER | CHANGE \"-\" \" \"").unwrap()),
+            ..ExecuteOptions::default()
+        };
+        let pipeline = "PIPE CONSOLE | INCLUDE norm | TAKE 2 | CONSOLE";
+        let input = "a-b\nc-d\ne-f";
+        let (rat, _, _) = execute_pipeline_rat_with_options(input, pipeline, &options).unwrap();
+        let (batch, _, _, _) = execute_pipeline_with_options(input, pipeline, &options).unwrap();
+        assert_eq!(rat, "A B\nC D");
+        assert_eq!(rat, batch);
+
+        let snippet = parse_snippet("CONSOLE | INCLUDE norm | CONSOLE").unwrap();
+        assert!(execute_parsed_rat(&snippet, input, &options).is_err());
+    }
+
+    #[test]
+    fn test_max_output_records() {
+        let pipeline = "PIPE CONSOLE | DUPLICATE 1000000 | CONSOLE";
+        let options = ExecuteOptions {
+            max_output_records: Some(10),
+            ..ExecuteOptions::default()
+        };
+        let err = execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 10 }.to_string()
+        );
 
-    Ok((output_text, input_count, output_count, trace))
+        let pipeline = "PIPE CONSOLE | DUPLICATE 5 | CONSOLE";
+        let (_, _, output_count) =
+            execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap();
+        assert_eq!(output_count, 10);
+    }
+
+    #[test]
+    fn test_expansion_factor_limit() {
+        let pipeline = "PIPE CONSOLE | DUPLICATE 1000 | CONSOLE";
+        let options = ExecuteOptions {
+            expansion_factor_limit: Some(10),
+            ..ExecuteOptions::default()
+        };
+        let err = execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap_err();
+        let expected = PipelineError::ExpansionLimitExceeded {
+            stage_index: 0,
+            stage: "DUPLICATE".to_string(),
+            limit: 10,
+        };
+        assert_eq!(err, expected.to_string());
+
+        // Both limits apply together
+        let pipeline = "PIPE CONSOLE | DUPLICATE 5 | CONSOLE";
+        let (_, _, output_count) =
+            execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap();
+        assert_eq!(output_count, 10);
+        let options = ExecuteOptions {
+            max_output_records: Some(9),
+            ..options
+        };
+        let err = execute_pipeline_rat_with_options("A\nB", pipeline, &options).unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 9 }.to_string()
+        );
+    }
+
+    #[test]
+    fn test_skip_header_option() {
+        let pipeline = "PIPE CONSOLE | COUNT | CONSOLE";
+        let options = ExecuteOptions {
+            skip_header: true,
+            ..ExecuteOptions::default()
+        };
+        let input = "NAME\nA\nB";
+        let (output, input_count, _) = execute_pipeline_rat(input, pipeline).unwrap();
+        assert_eq!((output.trim(), input_count), ("3", 3));
+        let (output, input_count, _) =
+            execute_pipeline_rat_with_options(input, pipeline, &options).unwrap();
+        assert_eq!((output.trim(), input_count), ("2", 2));
+    }
+
+    #[test]
+    fn test_continuation_option() {
+        let options = ExecuteOptions {
+            continuation: Some('\\'),
+            ..ExecuteOptions::default()
+        };
+        let pipeline = "PIPE CONSOLE | CONSOLE";
+        let input = "A\\\nB\nC";
+        let (output, input_count, _) =
+            execute_pipeline_rat_with_options(input, pipeline, &options).unwrap();
+        assert_eq!((output.as_str(), input_count), ("AB\nC", 2));
+    }
+
+    #[test]
+    fn test_expand_tabs_option() {
+        let options = ExecuteOptions {
+            expand_tabs: Some(4),
+            ..ExecuteOptions::default()
+        };
+        let pipeline = "PIPE CONSOLE | SELECT 4,3,0 | CONSOLE";
+        let (output, _, _) =
+            execute_pipeline_rat_with_options("AB\tXYZ", pipeline, &options).unwrap();
+        assert_eq!(output, "XYZ");
+    }
+
+    #[test]
+    fn test_execute_parsed_rat_reuses_commands() {
+        let commands = parse_commands("PIPE CONSOLE | LOCATE /A/ | CONSOLE").unwrap();
+        let options = ExecuteOptions::default();
+        let (output, input_count, output_count) =
+            execute_parsed_rat(&commands, "AX\nBY\nCA", &options).unwrap();
+        assert_eq!(output, "AX\nCA");
+        assert_eq!((input_count, output_count), (3, 2));
+        let (output, _, _) = execute_parsed_rat(&commands, "ZZ\nAA", &options).unwrap();
+        assert_eq!(output, "AA");
+        assert_eq!(
+            execute_parsed_rat(&commands, "AX\nBY\nCA", &options).unwrap(),
+            execute_pipeline_rat("AX\nBY\nCA", "PIPE CONSOLE | LOCATE /A/ | CONSOLE").unwrap()
+        );
+    }
 }
@@ -5,23 +5,188 @@
 //! the batch executor which processes all records through one stage before
 //! moving to the next.
 
-use pipelines_rs::Record;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use pipelines_rs::{MAIN_CHANNEL, PipelineError, Record, check_expansion};
 
 use crate::debug_trace::{FlushTrace, RatDebugTrace, RecordTrace};
 use crate::record_stage::RecordStage;
+use crate::stream::{RecordSink, RecordSource};
+
+/// How [`drive`] runs one executor: what a stage does with each item, what
+/// flushing a stage yields, and where items leaving the last stage go.
+/// Each `execute_rat_*` variant is one implementation.
+trait Driver {
+    /// What flows between stages: a record, or a record and its tag.
+    type Item;
+    /// What stops the run; `Infallible` for executors that cannot fail.
+    type Error;
+
+    /// Run `stage`, at `index` in the full pipeline, on one item.
+    fn process(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+        item: Self::Item,
+    ) -> Result<Vec<Self::Item>, Self::Error>;
+
+    /// Flush `stage`, at `index` in the full pipeline.
+    fn flush(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Self::Item>, Self::Error>;
+
+    /// Take the items that left the last stage.
+    fn emit(&mut self, items: Vec<Self::Item>) -> Result<(), Self::Error>;
+
+    /// Called once each input item has gone through every stage.
+    fn input_done(&mut self) {}
+
+    /// Push items through `stages`, the first of which is at `first_index`.
+    fn push(
+        &mut self,
+        items: Vec<Self::Item>,
+        stages: &mut [Box<dyn RecordStage>],
+        first_index: usize,
+    ) -> Result<Vec<Self::Item>, Self::Error> {
+        push_each(self, items, stages, first_index)
+    }
+}
 
-/// Push records through a slice of stages, processing each record
-/// through each stage in sequence.
-fn push_through_stages(records: Vec<Record>, stages: &mut [Box<dyn RecordStage>]) -> Vec<Record> {
-    let mut current = records;
-    for stage in stages.iter_mut() {
+/// Push items through a slice of stages, processing each item through each
+/// stage in sequence. The default for [`Driver::push`].
+fn push_each<D: Driver + ?Sized>(
+    driver: &mut D,
+    items: Vec<D::Item>,
+    stages: &mut [Box<dyn RecordStage>],
+    first_index: usize,
+) -> Result<Vec<D::Item>, D::Error> {
+    let mut current = items;
+    for (i, stage) in stages.iter_mut().enumerate() {
         let mut next = Vec::new();
-        for r in current {
-            next.extend(stage.process(r));
+        for item in current {
+            next.extend(driver.process(first_index + i, stage.as_mut(), item)?);
         }
         current = next;
     }
-    current
+    Ok(current)
+}
+
+/// Run `input` through `stages` record-at-a-time.
+///
+/// Each input item flows through the entire stage chain before the next is
+/// read. After the input runs out, stages are flushed in order, with flush
+/// output propagated through downstream stages. Stops at the first error
+/// from `driver`.
+fn drive<D: Driver>(
+    input: impl IntoIterator<Item = D::Item>,
+    stages: &mut [Box<dyn RecordStage>],
+    driver: &mut D,
+) -> Result<(), D::Error> {
+    for item in input {
+        let output = driver.push(vec![item], stages, 0)?;
+        driver.emit(output)?;
+        driver.input_done();
+    }
+
+    for i in 0..stages.len() {
+        let flush_output = driver.flush(i, stages[i].as_mut())?;
+        if !flush_output.is_empty() {
+            let output = driver.push(flush_output, &mut stages[i + 1..], i + 1)?;
+            driver.emit(output)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether every stage emits at most one record per input (see
+/// [`RecordStage::max_fanout`]), so records can go through
+/// [`push_scalar`].
+fn is_scalar_chain(stages: &[Box<dyn RecordStage>]) -> bool {
+    stages.iter().all(|stage| stage.max_fanout() <= 1)
+}
+
+/// Push records through a scalar chain, the first stage of which is at
+/// `first_index`, one record at a time. `process_one` runs a stage, given
+/// its index, on one record; a record stops at the first stage that drops
+/// it. Produces what [`push_each`] would, without a `Vec` per stage.
+fn push_scalar<E>(
+    records: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+    first_index: usize,
+    mut process_one: impl FnMut(usize, &mut dyn RecordStage, Record) -> Result<Option<Record>, E>,
+) -> Result<Vec<Record>, E> {
+    let mut output = Vec::new();
+    'records: for mut record in records {
+        for (i, stage) in stages.iter_mut().enumerate() {
+            match process_one(first_index + i, stage.as_mut(), record)? {
+                Some(next) => record = next,
+                None => continue 'records,
+            }
+        }
+        output.push(record);
+    }
+    Ok(output)
+}
+
+/// Plain `process`/`flush` driver handing output to `emit`, which may stop
+/// the run. Takes the scalar path when `scalar` is set.
+struct Plain<F> {
+    scalar: bool,
+    emit: F,
+}
+
+impl<F, E> Driver for Plain<F>
+where
+    F: FnMut(Vec<Record>) -> Result<(), E>,
+{
+    type Item = Record;
+    type Error = E;
+
+    fn process(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, E> {
+        Ok(stage.process(record))
+    }
+
+    fn flush(&mut self, _index: usize, stage: &mut dyn RecordStage) -> Result<Vec<Record>, E> {
+        Ok(stage.flush())
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), E> {
+        (self.emit)(records)
+    }
+
+    fn push(
+        &mut self,
+        records: Vec<Record>,
+        stages: &mut [Box<dyn RecordStage>],
+        first_index: usize,
+    ) -> Result<Vec<Record>, E> {
+        if self.scalar {
+            push_scalar(records, stages, first_index, |_, stage, record| {
+                Ok(stage.process_one(record))
+            })
+        } else {
+            push_each(self, records, stages, first_index)
+        }
+    }
+}
+
+impl<F> Plain<F> {
+    /// A driver for `stages`, on the scalar path when they allow it.
+    fn new(stages: &[Box<dyn RecordStage>], emit: F) -> Self {
+        Plain {
+            scalar: is_scalar_chain(stages),
+            emit,
+        }
+    }
 }
 
 /// Execute a pipeline in record-at-a-time mode.
@@ -31,31 +196,607 @@ fn push_through_stages(records: Vec<Record>, stages: &mut [Box<dyn RecordStage>]
 /// in order, with flush output propagated through downstream stages.
 pub fn execute_rat(input: Vec<Record>, stages: &mut [Box<dyn RecordStage>]) -> Vec<Record> {
     let mut output = Vec::new();
+    let mut driver = Plain::new(stages, |records: Vec<Record>| {
+        output.extend(records);
+        Ok::<_, Infallible>(())
+    });
+    let Ok(()) = drive(input, stages, &mut driver);
+    output
+}
 
-    // Process each input record through the entire stage chain
-    for record in input {
-        output.extend(push_through_stages(vec![record], stages));
+/// Execute a pipeline in record-at-a-time mode, streaming from `source`
+/// to `sink`.
+///
+/// Behaves like [`execute_rat`], but input records are pulled one at a
+/// time and each output record goes to the sink as soon as it leaves the
+/// last stage, so memory use is bounded by what the stages themselves
+/// buffer. Returns the `(input, output)` record counts.
+pub fn execute_rat_io(
+    source: &mut impl RecordSource,
+    stages: &mut [Box<dyn RecordStage>],
+    sink: &mut impl RecordSink,
+) -> (usize, usize) {
+    let (mut input_count, mut output_count) = (0, 0);
+    let input = std::iter::from_fn(|| source.next()).inspect(|_| input_count += 1);
+    let mut driver = Plain::new(stages, |records: Vec<Record>| {
+        for record in &records {
+            sink.write(record);
+        }
+        output_count += records.len();
+        Ok::<_, Infallible>(())
+    });
+    let Ok(()) = drive(input, stages, &mut driver);
+    (input_count, output_count)
+}
+
+/// Execute a pipeline in record-at-a-time mode, failing once any stage
+/// outputs more than `limit` records.
+///
+/// Behaves like [`execute_rat`] but counts each stage's output, flush
+/// output included, as it is made, so a runaway stage (say `DUPLICATE
+/// 1000000`) stops with `PipelineError::OutputLimitExceeded` at the first
+/// record past the limit instead of building all of its output. A stage
+/// fails even if a later stage would drop some of its records, as in the
+/// batch executor. Exactly `limit` records is not an error.
+///
+/// Stages run through `RecordStage::try_process`, so a stage failure
+/// (say `CHANGE ... REPEAT` that never converges) also stops the run and
+/// is returned as the stage reported it.
+pub fn execute_rat_limited(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+    limit: usize,
+) -> Result<Vec<Record>, PipelineError> {
+    let mut driver = Limited {
+        scalar: is_scalar_chain(stages),
+        limit,
+        outputs: vec![0; stages.len()],
+        output: Vec::new(),
+    };
+    drive(input, stages, &mut driver)?;
+    Ok(driver.output)
+}
+
+/// Driver using `try_process_iter` and checking each stage's output count
+/// (`outputs`) against `limit` as it grows. On the scalar path when
+/// `scalar` is set, using `try_process_one` instead.
+struct Limited {
+    scalar: bool,
+    limit: usize,
+    outputs: Vec<usize>,
+    output: Vec<Record>,
+}
+
+impl Driver for Limited {
+    type Item = Record;
+    type Error = PipelineError;
+
+    fn process(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let limit = self.limit;
+        let output = process_until(stage, record, &mut self.outputs[index], |n| n > limit)?;
+        check_output_limit(self.outputs[index], limit)?;
+        Ok(output)
     }
 
-    // Flush propagation: flush each stage and push output through remaining stages
-    for i in 0..stages.len() {
-        let flush_output = stages[i].flush();
-        if !flush_output.is_empty() {
-            output.extend(push_through_stages(flush_output, &mut stages[i + 1..]));
+    fn flush(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let output = stage.flush();
+        self.outputs[index] += output.len();
+        check_output_limit(self.outputs[index], self.limit)?;
+        Ok(output)
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), PipelineError> {
+        self.output.extend(records);
+        check_output_limit(self.output.len(), self.limit)
+    }
+
+    fn push(
+        &mut self,
+        records: Vec<Record>,
+        stages: &mut [Box<dyn RecordStage>],
+        first_index: usize,
+    ) -> Result<Vec<Record>, PipelineError> {
+        if !self.scalar {
+            return push_each(self, records, stages, first_index);
         }
+        let (limit, outputs) = (self.limit, &mut self.outputs);
+        push_scalar(records, stages, first_index, |index, stage, record| {
+            let output = stage.try_process_one(record)?;
+            outputs[index] += usize::from(output.is_some());
+            check_output_limit(outputs[index], limit)?;
+            Ok(output)
+        })
     }
+}
 
-    output
+/// Run `stage` on one record through `try_process_iter`, adding each output
+/// record to `*outputs` and stopping early once `over(*outputs)` holds, so
+/// a cap cuts a large fan-out short. The caller reports the failure.
+fn process_until(
+    stage: &mut dyn RecordStage,
+    record: Record,
+    outputs: &mut usize,
+    over: impl Fn(usize) -> bool,
+) -> Result<Vec<Record>, PipelineError> {
+    let mut output = Vec::new();
+    for r in stage.try_process_iter(record)? {
+        output.push(r);
+        *outputs += 1;
+        if over(*outputs) {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+/// `PipelineError::OutputLimitExceeded` once `len` records is past `limit`.
+fn check_output_limit(len: usize, limit: usize) -> Result<(), PipelineError> {
+    if len > limit {
+        Err(PipelineError::OutputLimitExceeded { limit })
+    } else {
+        Ok(())
+    }
+}
+
+/// Driver counting each stage's input and output records, flush output
+/// included, in `counts` (one `(input, output)` pair per stage). Uses
+/// [`checked_process`].
+struct Tally {
+    counts: Vec<(usize, usize)>,
+    output: Vec<Record>,
+}
+
+impl Driver for Tally {
+    type Item = Record;
+    type Error = PipelineError;
+
+    fn process(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let output = checked_process(index, stage, record)?;
+        let (inputs, outputs) = &mut self.counts[index];
+        *inputs += 1;
+        *outputs += output.len();
+        Ok(output)
+    }
+
+    fn flush(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let output = stage.flush();
+        self.counts[index].1 += output.len();
+        Ok(output)
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), PipelineError> {
+        self.output.extend(records);
+        Ok(())
+    }
+}
+
+impl Tally {
+    fn new(stages: &[Box<dyn RecordStage>]) -> Self {
+        Tally {
+            counts: vec![(0, 0); stages.len()],
+            output: Vec::new(),
+        }
+    }
+}
+
+/// Driver using `try_process_iter`, checking a [`Tally`]'s counts with
+/// [`check_expansion`], and each stage's output count against
+/// `max_output`, as they grow.
+struct ExpansionLimited {
+    tally: Tally,
+    limit: usize,
+    max_output: Option<usize>,
+}
+
+impl ExpansionLimited {
+    fn check(&self, index: usize, stage: &dyn RecordStage) -> Result<(), PipelineError> {
+        let (inputs, outputs) = self.tally.counts[index];
+        check_expansion(index, stage.name(), inputs, outputs, self.limit)?;
+        match self.max_output {
+            Some(limit) => check_output_limit(outputs, limit),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Driver for ExpansionLimited {
+    type Item = Record;
+    type Error = PipelineError;
+
+    fn process(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let (inputs, outputs) = &mut self.tally.counts[index];
+        *inputs += 1;
+        let cap = self
+            .max_output
+            .unwrap_or(usize::MAX)
+            .min(self.limit.saturating_mul(*inputs));
+        let output = process_until(stage, record, outputs, |n| n > cap)?;
+        self.check(index, stage)?;
+        Ok(output)
+    }
+
+    fn flush(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, PipelineError> {
+        let output = self.tally.flush(index, stage)?;
+        self.check(index, stage)?;
+        Ok(output)
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), PipelineError> {
+        self.tally.emit(records)?;
+        match self.max_output {
+            Some(limit) => check_output_limit(self.tally.output.len(), limit),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Execute a pipeline in record-at-a-time mode, failing once a stage
+/// outputs more than `limit` records per input record.
+///
+/// Behaves like [`execute_rat`] but keeps each stage's input and output
+/// counts and checks them with [`check_expansion`] after every call, so a
+/// runaway stage (say `DUPLICATE 1000000` under a limit of 10) stops with
+/// `PipelineError::ExpansionLimitExceeded` at its first record.
+///
+/// A `max_output` of `Some(n)` also applies the output cap of
+/// [`execute_rat_limited`] as records are made, so both limits stop the
+/// run as soon as either is crossed. As there, stages run through
+/// `RecordStage::try_process` and a stage failure is returned as is.
+pub fn execute_rat_expansion_limited(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+    limit: usize,
+    max_output: Option<usize>,
+) -> Result<Vec<Record>, PipelineError> {
+    let mut driver = ExpansionLimited {
+        tally: Tally::new(stages),
+        limit,
+        max_output,
+    };
+    drive(input, stages, &mut driver)?;
+    Ok(driver.tally.output)
+}
+
+/// Output records and per-stage `(input, output)` counts.
+type RecordsAndCounts = (Vec<Record>, Vec<(usize, usize)>);
+
+/// Execute a pipeline in record-at-a-time mode, counting each stage's
+/// records.
+///
+/// Produces the same output as [`execute_rat_checked`], plus one
+/// `(input, output)` record count per stage in pipeline order, flush output
+/// included. The counts equal `RatDebugTrace::stage_counts` from
+/// [`execute_rat_traced`], but no records are kept to get them. A stage
+/// failure is returned as `PipelineError::StageFailed`.
+pub fn execute_rat_stats(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+) -> Result<RecordsAndCounts, PipelineError> {
+    let mut driver = Tally::new(stages);
+    drive(input, stages, &mut driver)?;
+    Ok((driver.output, driver.counts))
+}
+
+/// Driver calling `progress` after every `every` input records.
+struct Progress<F> {
+    every: usize,
+    processed: usize,
+    progress: F,
+    output: Vec<Record>,
+}
+
+impl<F: FnMut(usize)> Driver for Progress<F> {
+    type Item = Record;
+    type Error = Infallible;
+
+    fn process(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, Infallible> {
+        Ok(stage.process(record))
+    }
+
+    fn flush(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, Infallible> {
+        Ok(stage.flush())
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), Infallible> {
+        self.output.extend(records);
+        Ok(())
+    }
+
+    fn input_done(&mut self) {
+        self.processed += 1;
+        if self.every > 0 && self.processed.is_multiple_of(self.every) {
+            (self.progress)(self.processed);
+        }
+    }
+}
+
+/// Execute a pipeline in record-at-a-time mode, reporting progress.
+///
+/// Behaves like [`execute_rat`] but calls `progress(records_processed)`
+/// after every `every` input records, then once more with the input total
+/// after the stages are flushed, so the last call always means "done"
+/// (even when the total is a multiple of `every`). An `every` of 0 reports
+/// only that final total. Unlike [`execute_rat_traced`], no records are
+/// cloned.
+pub fn execute_rat_progress(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+    every: usize,
+    progress: impl FnMut(usize),
+) -> Vec<Record> {
+    let mut driver = Progress {
+        every,
+        processed: 0,
+        progress,
+        output: Vec::new(),
+    };
+    let Ok(()) = drive(input, stages, &mut driver);
+    (driver.progress)(driver.processed);
+    driver.output
+}
+
+/// Driver passing each record's tag along with `process_tagged`.
+struct Tagged {
+    output: Vec<(Record, Option<usize>)>,
+}
+
+impl Driver for Tagged {
+    type Item = (Record, Option<usize>);
+    type Error = Infallible;
+
+    fn process(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+        (record, line): Self::Item,
+    ) -> Result<Vec<Self::Item>, Infallible> {
+        Ok(stage.process_tagged(record, line))
+    }
+
+    fn flush(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Self::Item>, Infallible> {
+        Ok(stage.flush_tagged())
+    }
+
+    fn emit(&mut self, items: Vec<Self::Item>) -> Result<(), Infallible> {
+        self.output.extend(items);
+        Ok(())
+    }
+}
+
+/// Execute a pipeline in record-at-a-time mode, tracking where each output
+/// record came from.
+///
+/// Behaves like [`execute_rat`] but pairs each output record with the
+/// 0-based index of the input record that produced it. Records that pass
+/// through, are rewritten (SELECT, CHANGE, ...) or copied (DUPLICATE) keep
+/// their input's index, including across SORT; records a stage makes up
+/// (LITERAL text, COUNT totals, ...) get `None`. See
+/// [`RecordStage::process_tagged`].
+pub fn execute_rat_provenance(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+) -> Vec<(Record, Option<usize>)> {
+    let input = input
+        .into_iter()
+        .enumerate()
+        .map(|(line, record)| (record, Some(line)));
+    let mut driver = Tagged { output: Vec::new() };
+    let Ok(()) = drive(input, stages, &mut driver);
+    driver.output
+}
+
+/// Driver using `process_routed`. Records sent to a named channel are added
+/// to `routed` and go no further; main-channel records that pass every
+/// stage are added to `main`.
+struct Routed {
+    routed: HashMap<String, Vec<Record>>,
+    main: Vec<Record>,
+}
+
+impl Driver for Routed {
+    type Item = Record;
+    type Error = Infallible;
+
+    fn process(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, Infallible> {
+        let mut next = Vec::new();
+        for (record, channel) in stage.process_routed(record) {
+            match channel {
+                Some(channel) => self.routed.entry(channel).or_default().push(record),
+                None => next.push(record),
+            }
+        }
+        Ok(next)
+    }
+
+    fn flush(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, Infallible> {
+        Ok(stage.flush())
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), Infallible> {
+        self.main.extend(records);
+        Ok(())
+    }
+}
+
+/// Execute a pipeline in record-at-a-time mode, collecting ROUTE output
+/// channels.
+///
+/// Behaves like [`execute_rat`] but returns a map from channel name to
+/// records. A ROUTE stage takes the records it routes out of the pipeline
+/// into their named channel; stages after it see only the records it
+/// leaves on the main channel. The records that reach the end are under
+/// [`MAIN_CHANNEL`], which is always present; named channels appear once
+/// they receive a record. See [`RecordStage::process_routed`].
+pub fn execute_rat_routed(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+) -> HashMap<String, Vec<Record>> {
+    let mut driver = Routed {
+        routed: HashMap::new(),
+        main: Vec::new(),
+    };
+    let Ok(()) = drive(input, stages, &mut driver);
+    let mut routed = driver.routed;
+    routed.insert(MAIN_CHANNEL.to_string(), driver.main);
+    routed
+}
+
+/// Driver using `try_process`, reporting a failure as
+/// `PipelineError::StageFailed` with the failing stage's index. On the
+/// scalar path when `scalar` is set, using `try_process_one` instead.
+struct Checked {
+    scalar: bool,
+    output: Vec<Record>,
+}
+
+/// `PipelineError::StageFailed` for `stage`, at `index`, rejecting `record`.
+fn stage_failed(
+    index: usize,
+    stage: &dyn RecordStage,
+    record: Record,
+    error: PipelineError,
+) -> PipelineError {
+    PipelineError::StageFailed {
+        stage_index: index,
+        stage: stage.name().to_string(),
+        record,
+        source: Box::new(error),
+    }
+}
+
+/// Run `stage`, at `index`, on one record through `try_process`, reporting
+/// a failure with [`stage_failed`].
+fn checked_process(
+    index: usize,
+    stage: &mut dyn RecordStage,
+    record: Record,
+) -> Result<Vec<Record>, PipelineError> {
+    stage
+        .try_process(record.clone())
+        .map_err(|e| stage_failed(index, stage, record, e))
+}
+
+impl Driver for Checked {
+    type Item = Record;
+    type Error = PipelineError;
+
+    fn process(
+        &mut self,
+        index: usize,
+        stage: &mut dyn RecordStage,
+        record: Record,
+    ) -> Result<Vec<Record>, PipelineError> {
+        checked_process(index, stage, record)
+    }
+
+    fn flush(
+        &mut self,
+        _index: usize,
+        stage: &mut dyn RecordStage,
+    ) -> Result<Vec<Record>, PipelineError> {
+        Ok(stage.flush())
+    }
+
+    fn emit(&mut self, records: Vec<Record>) -> Result<(), PipelineError> {
+        self.output.extend(records);
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        records: Vec<Record>,
+        stages: &mut [Box<dyn RecordStage>],
+        first_index: usize,
+    ) -> Result<Vec<Record>, PipelineError> {
+        if !self.scalar {
+            return push_each(self, records, stages, first_index);
+        }
+        push_scalar(records, stages, first_index, |index, stage, record| {
+            stage
+                .try_process_one(record.clone())
+                .map_err(|e| stage_failed(index, stage, record, e))
+        })
+    }
+}
+
+/// Execute a pipeline in record-at-a-time mode, stopping at the first
+/// stage runtime failure.
+///
+/// Behaves like [`execute_rat`] but calls `RecordStage::try_process`. The
+/// first error is returned as `PipelineError::StageFailed`, carrying the
+/// index and name of the failing stage and the record it rejected; output
+/// produced before the failure is discarded.
+pub fn execute_rat_checked(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+) -> Result<Vec<Record>, PipelineError> {
+    let mut driver = Checked {
+        scalar: is_scalar_chain(stages),
+        output: Vec::new(),
+    };
+    drive(input, stages, &mut driver)?;
+    Ok(driver.output)
 }
 
 /// Execute a pipeline in record-at-a-time mode with debug tracing.
 ///
 /// Captures a `RatDebugTrace` showing each record's journey through
-/// the pipeline and each stage's flush output.
+/// the pipeline and each stage's flush output. Stages run through
+/// `RecordStage::try_process`, and a failure is returned as
+/// `PipelineError::StageFailed`, as from [`execute_rat_checked`].
 pub fn execute_rat_traced(
     input: Vec<Record>,
     stages: &mut [Box<dyn RecordStage>],
-) -> (Vec<Record>, RatDebugTrace) {
+) -> Result<(Vec<Record>, RatDebugTrace), PipelineError> {
     let stage_names: Vec<String> = stages.iter().map(|s| s.name().to_string()).collect();
     let num_stages = stages.len();
     let mut output = Vec::new();
@@ -68,10 +809,10 @@ pub fn execute_rat_traced(
         pipe_points.push(vec![record.clone()]);
 
         let mut current = vec![record];
-        for stage in stages.iter_mut() {
+        for (index, stage) in stages.iter_mut().enumerate() {
             let mut next = Vec::new();
             for r in current {
-                next.extend(stage.process(r));
+                next.extend(checked_process(index, stage.as_mut(), r)?);
             }
             pipe_points.push(next.clone());
             current = next;
@@ -89,10 +830,10 @@ pub fn execute_rat_traced(
             pipe_points.push(flush_output.clone());
 
             let mut current = flush_output;
-            for stage in stages[i + 1..].iter_mut() {
+            for (index, stage) in stages.iter_mut().enumerate().skip(i + 1) {
                 let mut next = Vec::new();
                 for r in current {
-                    next.extend(stage.process(r));
+                    next.extend(checked_process(index, stage.as_mut(), r)?);
                 }
                 pipe_points.push(next.clone());
                 current = next;
@@ -110,53 +851,21 @@ pub fn execute_rat_traced(
         stage_names,
         record_traces,
         flush_traces,
+        layout: None,
     };
 
-    (output, trace)
+    Ok((output, trace))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::record_stage::command_to_record_stage;
-    use pipelines_rs::{Command, execute_pipeline, parse_commands};
+    use crate::record_stage::{ClosureStage, command_to_record_stage};
+    use crate::testkit::assert_executors_agree;
+    use pipelines_rs::Command;
     use std::fs;
     use std::path::Path;
 
-    /// Helper: run batch executor on a spec file and return trimmed output.
-    fn run_batch(input: &str, pipeline: &str) -> String {
-        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
-        output
-    }
-
-    /// Helper: run RAT executor on a spec file and return trimmed output.
-    fn run_rat(input: &str, pipeline: &str) -> String {
-        let commands = parse_commands(pipeline).unwrap();
-        assert!(commands.len() >= 2);
-
-        let first = &commands[0];
-        let input_records: Vec<Record> = match first {
-            Command::Console => input
-                .lines()
-                .filter(|line| !line.is_empty())
-                .map(Record::from_str)
-                .collect(),
-            Command::Literal { text } => vec![Record::from_str(text)],
-            Command::Hole => vec![],
-            _ => panic!("Unhandled source stage: {}", first.name()),
-        };
-
-        let mut stages: Vec<Box<dyn RecordStage>> =
-            commands[1..].iter().map(command_to_record_stage).collect();
-
-        let output_records = execute_rat(input_records, &mut stages);
-        output_records
-            .iter()
-            .map(|r| r.as_str().trim_end())
-            .collect::<Vec<_>>()
-            .join("\n")
-    }
-
     /// Assert RAT and batch executors produce identical output for a spec file.
     fn assert_equivalence(spec_name: &str) {
         let spec_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -165,13 +874,9 @@ mod tests {
         let input = fs::read_to_string(spec_dir.join("input-fixed-80.data")).unwrap();
         let pipeline = fs::read_to_string(spec_dir.join(spec_name)).unwrap();
 
-        let batch_output = run_batch(&input, &pipeline);
-        let rat_output = run_rat(&input, &pipeline);
-
-        assert_eq!(
-            batch_output, rat_output,
-            "RAT output differs from batch for {spec_name}"
-        );
+        if let Err(mismatch) = assert_executors_agree(&input, &pipeline) {
+            panic!("RAT output differs from batch for {spec_name}: {mismatch}");
+        }
     }
 
     // --- Unit tests ---
@@ -198,6 +903,7 @@ mod tests {
             command_to_record_stage(&Command::Locate {
                 pattern: "SALES".to_string(),
                 field: None,
+                trim: false,
             }),
             command_to_record_stage(&Command::Count),
         ];
@@ -206,6 +912,131 @@ mod tests {
         assert_eq!(output[0].as_str().trim(), "2");
     }
 
+    #[test]
+    fn test_expansion_limit_trips_on_first_record() {
+        let mut stages = stages_for("LOCATE /A/ | DUPLICATE 1000 | COUNT");
+        let input = vec![Record::from_str("A"), Record::from_str("B")];
+        let err = execute_rat_expansion_limited(input, &mut stages, 10, None).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::ExpansionLimitExceeded { stage_index: 1, ref stage, limit: 10 }
+                if stage == "DUPLICATE"
+        ));
+    }
+
+    #[test]
+    fn test_expansion_limit_counts_flush_output() {
+        let input: Vec<Record> = ["A", "B"].into_iter().map(Record::from_str).collect();
+        // LITERAL adds one record to two: 3 <= 2 x 2
+        let mut stages = stages_for("LITERAL X | DUPLICATE 2 | COUNT");
+        let output = execute_rat_expansion_limited(input.clone(), &mut stages, 2, None).unwrap();
+        assert_eq!(output[0].as_str().trim_end(), "6");
+        // With no input, LITERAL's flushed record still passes
+        let mut stages = stages_for("LITERAL X | DUPLICATE 2");
+        assert_eq!(
+            execute_rat_expansion_limited(vec![], &mut stages, 2, None)
+                .unwrap()
+                .len(),
+            2
+        );
+        // GENERATE's flushed records are checked like any other output
+        let mut stages = stages_for("GENERATE 3 /G/ | UPPER");
+        assert!(matches!(
+            execute_rat_expansion_limited(vec![], &mut stages, 2, None),
+            Err(PipelineError::ExpansionLimitExceeded { stage_index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_expansion_limit_with_output_cap_stops_early() {
+        let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = seen.clone();
+        let mut stages = stages_for("DUPLICATE 5");
+        stages.push(Box::new(ClosureStage::new("SEEN", move |r| {
+            counter.set(counter.get() + 1);
+            vec![r]
+        })));
+        let input: Vec<Record> = ["A", "B", "C"].into_iter().map(Record::from_str).collect();
+        let err = execute_rat_expansion_limited(input, &mut stages, 10, Some(4)).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 4 }
+        ));
+        // DUPLICATE failed at its fifth copy, before SEEN ran
+        assert_eq!(seen.get(), 0);
+    }
+
+    #[test]
+    fn test_output_limit_stops_fan_out_part_way() {
+        let mut stages = stages_for("DUPLICATE 100000 | DUPLICATE 100000");
+        let err = execute_rat_limited(vec![Record::from_str("A")], &mut stages, 10).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 10 }
+        ));
+
+        // A stage over the limit fails even though LOCATE would drop its output
+        let mut stages = stages_for("DUPLICATE 11 | LOCATE /Z/");
+        let err = execute_rat_limited(vec![Record::from_str("A")], &mut stages, 10).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 10 }
+        ));
+        let mut stages = stages_for("DUPLICATE 10 | LOCATE /Z/");
+        assert!(
+            execute_rat_limited(vec![Record::from_str("A")], &mut stages, 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// Employee records from the fixed-width spec data.
+    fn employees() -> Vec<Record> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../specs/input-fixed-80.data");
+        pipelines_rs::read_input_records(&fs::read_to_string(path).unwrap(), None)
+    }
+
+    #[test]
+    fn test_routed_splits_sales_from_the_rest() {
+        let mut stages = stages_for("ROUTE 18,10 = \"SALES\" TO sales ELSE other");
+        let channels = execute_rat_routed(employees(), &mut stages);
+        let mut names: Vec<&str> = channels.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["main", "other", "sales"]);
+
+        let (sales, other) = (&channels["sales"], &channels["other"]);
+        assert!(!sales.is_empty() && !other.is_empty());
+        assert!(sales.iter().all(|r| r.field_eq(18, 10, "SALES")));
+        assert!(other.iter().all(|r| !r.field_eq(18, 10, "SALES")));
+        assert_eq!(sales.len() + other.len(), employees().len());
+        assert!(channels[MAIN_CHANNEL].is_empty());
+    }
+
+    #[test]
+    fn test_routed_later_stages_see_main_channel_only() {
+        let mut stages = stages_for("ROUTE 18,10 = \"SALES\" TO sales | COUNT");
+        let channels = execute_rat_routed(employees(), &mut stages);
+        let sales = channels["sales"].len();
+        let rest = employees().len() - sales;
+        assert_eq!(channels[MAIN_CHANNEL].len(), 1);
+        assert_eq!(
+            channels[MAIN_CHANNEL][0].as_str().trim_end(),
+            rest.to_string()
+        );
+
+        // Without routing, the main channel is the ordinary output
+        let mut stages = stages_for("ROUTE 18,10 = \"SALES\" TO sales | SELECT 0,8,0");
+        let main = execute_rat_routed(employees(), &mut stages).remove(MAIN_CHANNEL);
+        let mut stages = stages_for("ROUTE 18,10 = \"SALES\" TO sales | SELECT 0,8,0");
+        assert_eq!(main, Some(execute_rat(employees(), &mut stages)));
+    }
+
+    #[test]
+    fn test_route_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | ROUTE 0,1 = \"A\" TO a | CONSOLE";
+        assert_eq!(assert_executors_agree("A1\nB2\nA3", pipeline), Ok(()));
+    }
+
     #[test]
     fn test_literal_prepends() {
         let input = vec![Record::from_str("A"), Record::from_str("B")];
@@ -241,11 +1072,258 @@ mod tests {
         assert_eq!(output.len(), 3);
     }
 
+    /// Build stages for `pipeline` (stage lines only, no PIPE or `?`).
+    fn stages_for(pipeline: &str) -> Vec<Box<dyn RecordStage>> {
+        pipelines_rs::parse_commands(pipeline)
+            .unwrap()
+            .iter()
+            .map(command_to_record_stage)
+            .collect()
+    }
+
+    #[test]
+    fn test_scalar_chain_detection() {
+        assert!(is_scalar_chain(&stages_for(
+            "CONSOLE | LOCATE /A/ | NLOCATE /B/ | FILTER 0,1 = \"A\" | OMIT 0,1 = \"C\""
+        )));
+        assert!(is_scalar_chain(&stages_for("DUPLICATE 1 | LOCATE /A/")));
+        assert!(!is_scalar_chain(&stages_for("LOCATE /A/ | DUPLICATE 2")));
+        assert!(is_scalar_chain(&stages_for(
+            "LOCATE /A/ | UPPER | SELECT 0,5,0 | CHANGE /A/B/ | LENGTH 1, | TAKE 2"
+        )));
+        assert!(!is_scalar_chain(&stages_for("UPPER | CHOP 10")));
+        assert!(is_scalar_chain(&[]));
+    }
+
+    #[test]
+    fn test_scalar_path_matches_vec_path() {
+        let input: Vec<Record> = ["SMITH SALES", "JONES ENG", "ADAMS SALES", "", "BROWN ENG"]
+            .into_iter()
+            .map(Record::from_str)
+            .collect();
+        let chain = "CONSOLE | LOCATE /S/ | NLOCATE /ENG/ | FILTER 0,1 != \"J\" | DUPLICATE 1";
+        let mixed =
+            "LOCATE /S/ | LOWER | CHANGE /sales/dept/ | EXTRACT / / | SKIP 1 | RESEQ 10,3 1,1";
+        for pipeline in [chain, "LOCATE /S/ | NLOCATE /ADAMS/", mixed] {
+            let mut stages = stages_for(pipeline);
+            assert!(is_scalar_chain(&stages));
+            let scalar = execute_rat(input.clone(), &mut stages);
+            let mut stages = stages_for(pipeline);
+            let mut general = Vec::new();
+            let mut driver = Plain {
+                scalar: false,
+                emit: |records: Vec<Record>| {
+                    general.extend(records);
+                    Ok::<_, Infallible>(())
+                },
+            };
+            let Ok(()) = drive(input.clone(), &mut stages, &mut driver);
+            assert_eq!(scalar, general, "{pipeline}");
+        }
+    }
+
+    #[test]
+    fn test_scalar_chain_short_circuits_on_drop() {
+        let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = seen.clone();
+        /// Passes records through, counting them, with a fan-out of 1.
+        struct Counting(std::rc::Rc<std::cell::Cell<usize>>);
+        impl RecordStage for Counting {
+            fn process(&mut self, record: Record) -> Vec<Record> {
+                self.0.set(self.0.get() + 1);
+                vec![record]
+            }
+            fn max_fanout(&self) -> usize {
+                1
+            }
+            fn name(&self) -> &str {
+                "COUNTING"
+            }
+        }
+        let mut stages = stages_for("LOCATE /A/");
+        stages.push(Box::new(Counting(counter)));
+        let input = vec![Record::from_str("A"), Record::from_str("B")];
+        let output = execute_rat(input, &mut stages);
+        assert_eq!(output.len(), 1);
+        assert_eq!(seen.get(), 1);
+    }
+
+    #[test]
+    fn test_io_streams_source_to_sink() {
+        use crate::stream::{LineSource, WriterSink};
+
+        /// Records the order in which the source is read and the sink written.
+        struct Log<'a>(&'a std::cell::RefCell<Vec<String>>);
+        impl RecordSink for Log<'_> {
+            fn write(&mut self, record: &Record) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("out {}", record.as_str().trim_end()));
+            }
+        }
+        struct Logged<'a>(
+            std::vec::IntoIter<Record>,
+            &'a std::cell::RefCell<Vec<String>>,
+        );
+        impl RecordSource for Logged<'_> {
+            fn next(&mut self) -> Option<Record> {
+                let record = Iterator::next(&mut self.0)?;
+                self.1
+                    .borrow_mut()
+                    .push(format!("in {}", record.as_str().trim_end()));
+                Some(record)
+            }
+        }
+
+        let stages = || -> Vec<Box<dyn RecordStage>> {
+            vec![
+                command_to_record_stage(&Command::Duplicate { n: 2 }),
+                command_to_record_stage(&Command::Upper),
+            ]
+        };
+        let log = std::cell::RefCell::new(Vec::new());
+        let input = vec![Record::from_str("a"), Record::from_str("b")];
+        let counts = execute_rat_io(
+            &mut Logged(input.into_iter(), &log),
+            &mut stages(),
+            &mut Log(&log),
+        );
+        assert_eq!(counts, (2, 4));
+        // Each record reaches the sink before the next is read
+        assert_eq!(
+            log.into_inner(),
+            ["in a", "out A", "out A", "in b", "out B", "out B"]
+        );
+
+        let mut source = LineSource::new(std::io::Cursor::new("x\ny\n"));
+        let mut sink = WriterSink::new(Vec::new());
+        execute_rat_io(&mut source, &mut stages(), &mut sink);
+        assert_eq!(sink.into_inner(), b"X\nX\nY\nY\n");
+
+        let mut output = Vec::new();
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![command_to_record_stage(&Command::Count)];
+        let input = vec![Record::from_str("a"), Record::from_str("b")];
+        assert_eq!(
+            execute_rat_io(&mut input.into_iter(), &mut stages, &mut output),
+            (2, 1)
+        );
+        assert_eq!(output[0].as_str().trim_end(), "2");
+    }
+
+    #[test]
+    fn test_limited_stops_at_boundary() {
+        let stages = || -> Vec<Box<dyn RecordStage>> {
+            vec![command_to_record_stage(&Command::Duplicate { n: 4 })]
+        };
+        let input = || vec![Record::from_str("A"), Record::from_str("B")];
+
+        let output = execute_rat_limited(input(), &mut stages(), 8).unwrap();
+        assert_eq!(output.len(), 8);
+        let err = execute_rat_limited(input(), &mut stages(), 7).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 7 }
+        ));
+    }
+
+    #[test]
+    fn test_limited_stops_before_reading_all_input() {
+        // A runaway stage fails on the first record; later input is never read
+        let input = (0..1000).map(|i| Record::from_str(&i.to_string()));
+        let mut stages: Vec<Box<dyn RecordStage>> =
+            vec![command_to_record_stage(&Command::Duplicate { n: 100_000 })];
+        assert!(execute_rat_limited(input.collect(), &mut stages, 50_000).is_err());
+    }
+
+    #[test]
+    fn test_limited_checks_flushed_records() {
+        // COUNT emits its record only on flush
+        let input = vec![Record::from_str("A")];
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![
+            command_to_record_stage(&Command::Count),
+            command_to_record_stage(&Command::Duplicate { n: 3 }),
+        ];
+        assert!(execute_rat_limited(input, &mut stages, 2).is_err());
+    }
+
+    #[test]
+    fn test_progress_reports_every_n_and_total() {
+        let input = |n: usize| (0..n).map(|i| Record::from_str(&i.to_string())).collect();
+        let stages = || -> Vec<Box<dyn RecordStage>> {
+            vec![
+                command_to_record_stage(&Command::Duplicate { n: 2 }),
+                command_to_record_stage(&Command::Count),
+            ]
+        };
+        let run = |n: usize, every: usize| {
+            let mut calls = Vec::new();
+            let output = execute_rat_progress(input(n), &mut stages(), every, |done| {
+                calls.push(done);
+            });
+            assert_eq!(output, execute_rat(input(n), &mut stages()));
+            calls
+        };
+        assert_eq!(run(10, 3), vec![3, 6, 9, 10]);
+        // The final report repeats a total that is a multiple of `every`
+        assert_eq!(run(9, 3), vec![3, 6, 9, 9]);
+        assert_eq!(run(1000, 100).len(), 11);
+        assert_eq!(run(5, 0), vec![5]);
+        assert_eq!(run(0, 3), vec![0]);
+    }
+
+    #[test]
+    fn test_provenance_survives_filter_and_reorder() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../specs/input-fixed-80.data");
+        let text = fs::read_to_string(path).unwrap();
+        let input: Vec<Record> = text.lines().map(Record::from_str).collect();
+        let commands = pipelines_rs::parse_commands(
+            r#"PIPE CONSOLE | FILTER 18,10 = "SALES" | SORT 0,8 DESC | REVERSE | CONSOLE"#,
+        )
+        .unwrap();
+        let mut stages: Vec<Box<dyn RecordStage>> =
+            commands[1..].iter().map(command_to_record_stage).collect();
+        let output = execute_rat_provenance(input.clone(), &mut stages);
+
+        let lines: Vec<Option<usize>> = output.iter().map(|(_, line)| *line).collect();
+        // SMITH, GARCIA, DOE are input records 0, 5 and 2
+        assert_eq!(lines, vec![Some(0), Some(5), Some(2)]);
+        for (record, line) in &output {
+            let original: String = input[line.unwrap()]
+                .as_str()
+                .trim_end()
+                .chars()
+                .rev()
+                .collect();
+            assert_eq!(record.as_str().trim_end(), original);
+        }
+    }
+
+    #[test]
+    fn test_provenance_copies_and_synthetic_records() {
+        let input = vec![Record::from_str("A"), Record::from_str("B")];
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![
+            command_to_record_stage(&Command::Literal {
+                text: "HEAD".to_string(),
+            }),
+            command_to_record_stage(&Command::Duplicate { n: 2 }),
+        ];
+        let lines: Vec<Option<usize>> = execute_rat_provenance(input, &mut stages)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect();
+        assert_eq!(lines, vec![None, None, Some(0), Some(0), Some(1), Some(1)]);
+
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![command_to_record_stage(&Command::Count)];
+        let output = execute_rat_provenance(vec![Record::from_str("A")], &mut stages);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].1, None);
+    }
+
     #[test]
     fn test_traced_captures_pipe_points() {
         let input = vec![Record::from_str("A"), Record::from_str("B")];
         let mut stages: Vec<Box<dyn RecordStage>> = vec![command_to_record_stage(&Command::Upper)];
-        let (output, trace) = execute_rat_traced(input, &mut stages);
+        let (output, trace) = execute_rat_traced(input, &mut stages).unwrap();
         assert_eq!(output.len(), 2);
         assert_eq!(trace.stage_names, vec!["UPPER"]);
         assert_eq!(trace.record_traces.len(), 2);
@@ -259,7 +1337,7 @@ mod tests {
     fn test_traced_captures_flush() {
         let input = vec![Record::from_str("A")];
         let mut stages: Vec<Box<dyn RecordStage>> = vec![command_to_record_stage(&Command::Count)];
-        let (output, trace) = execute_rat_traced(input, &mut stages);
+        let (output, trace) = execute_rat_traced(input, &mut stages).unwrap();
         assert_eq!(output.len(), 1);
         assert_eq!(output[0].as_str().trim(), "1");
         assert_eq!(trace.record_traces.len(), 1);
@@ -271,6 +1349,17 @@ mod tests {
         assert_eq!(trace.flush_traces[0].pipe_points[0].len(), 1);
     }
 
+    #[test]
+    fn test_stats_match_trace_counts() {
+        let pipeline = "LOCATE /E/ | DUPLICATE 2 | SORT 28,8 | TAKE 3 | COUNT | CONSOLE";
+        let (expected, trace) = execute_rat_traced(employees(), &mut stages_for(pipeline)).unwrap();
+        let (output, counts) = execute_rat_stats(employees(), &mut stages_for(pipeline)).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(counts, trace.stage_counts());
+        // COUNT's only output is its flushed total
+        assert_eq!(counts[4], (3, 1));
+    }
+
     #[test]
     fn test_traced_equivalence() {
         let input = vec![
@@ -295,10 +1384,262 @@ mod tests {
         ];
 
         let plain = execute_rat(input.clone(), &mut stages);
-        let (traced, _trace) = execute_rat_traced(input, &mut stages2);
+        let (traced, _trace) = execute_rat_traced(input, &mut stages2).unwrap();
         assert_eq!(plain, traced);
     }
 
+    /// Test stage that rejects records whose first column is not a digit.
+    struct DigitsOnly;
+
+    impl RecordStage for DigitsOnly {
+        fn process(&mut self, record: Record) -> Vec<Record> {
+            vec![record]
+        }
+
+        fn try_process(&mut self, record: Record) -> Result<Vec<Record>, PipelineError> {
+            if record.as_str().starts_with(|c: char| c.is_ascii_digit()) {
+                Ok(vec![record])
+            } else {
+                Err(PipelineError::Stage("not numeric".to_string()))
+            }
+        }
+
+        fn name(&self) -> &str {
+            "DIGITS"
+        }
+    }
+
+    #[test]
+    fn test_checked_matches_unchecked_for_infallible_stages() {
+        let input = vec![Record::from_str("a"), Record::from_str("b")];
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![
+            command_to_record_stage(&Command::Upper),
+            command_to_record_stage(&Command::Count),
+        ];
+        let output = execute_rat_checked(input, &mut stages).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].as_str().trim(), "2");
+    }
+
+    #[test]
+    fn test_checked_reports_failing_stage_and_record() {
+        let input = vec![
+            Record::from_str("1"),
+            Record::from_str("x"),
+            Record::from_str("2"),
+        ];
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![
+            command_to_record_stage(&Command::Upper),
+            Box::new(DigitsOnly),
+        ];
+        match execute_rat_checked(input, &mut stages) {
+            Err(PipelineError::StageFailed {
+                stage_index,
+                stage,
+                record,
+                source,
+            }) => {
+                assert_eq!(stage_index, 1);
+                assert_eq!(stage, "DIGITS");
+                assert_eq!(record.as_str().trim_end(), "X");
+                assert!(matches!(*source, PipelineError::Stage(_)));
+            }
+            other => panic!("expected StageFailed, got {other:?}"),
+        }
+    }
+
+    /// [`DigitsOnly`] with a fan-out of 1, so it runs on the scalar path.
+    struct ScalarDigitsOnly;
+
+    impl RecordStage for ScalarDigitsOnly {
+        fn process(&mut self, record: Record) -> Vec<Record> {
+            vec![record]
+        }
+
+        fn try_process_one(&mut self, record: Record) -> Result<Option<Record>, PipelineError> {
+            Ok(DigitsOnly.try_process(record)?.pop())
+        }
+
+        fn max_fanout(&self) -> usize {
+            1
+        }
+
+        fn name(&self) -> &str {
+            "DIGITS"
+        }
+    }
+
+    #[test]
+    fn test_fallible_executors_take_the_scalar_path() {
+        let digits = || -> Vec<Box<dyn RecordStage>> {
+            let mut stages = stages_for("LOCATE /1/ | UPPER");
+            stages.push(Box::new(ScalarDigitsOnly));
+            stages
+        };
+        assert!(is_scalar_chain(&digits()));
+        let input = vec![Record::from_str("1a"), Record::from_str("2b")];
+        let expected = vec![Record::from_str("1A")];
+        assert_eq!(
+            execute_rat_checked(input.clone(), &mut digits()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            execute_rat_limited(input, &mut digits(), 1).unwrap(),
+            expected
+        );
+
+        let input = vec![Record::from_str("1"), Record::from_str("x1")];
+        match execute_rat_checked(input.clone(), &mut digits()) {
+            Err(PipelineError::StageFailed {
+                stage_index,
+                record,
+                ..
+            }) => {
+                assert_eq!(stage_index, 2);
+                assert_eq!(record.as_str().trim_end(), "X1");
+            }
+            other => panic!("expected StageFailed, got {other:?}"),
+        }
+        assert!(matches!(
+            execute_rat_limited(input, &mut digits(), 10),
+            Err(PipelineError::Stage(_))
+        ));
+        // The output limit still applies to each stage
+        let input = vec![Record::from_str("1"), Record::from_str("11")];
+        assert!(matches!(
+            execute_rat_limited(input, &mut stages_for("LOCATE /1/ | UPPER"), 1),
+            Err(PipelineError::OutputLimitExceeded { limit: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_traced_and_stats_report_stage_failures() {
+        let input = || vec![Record::from_str("1"), Record::from_str("x")];
+        let stages = || -> Vec<Box<dyn RecordStage>> {
+            vec![
+                command_to_record_stage(&Command::Upper),
+                Box::new(DigitsOnly),
+            ]
+        };
+        let expected = execute_rat_checked(input(), &mut stages()).unwrap_err();
+        let traced = execute_rat_traced(input(), &mut stages()).unwrap_err();
+        let stats = execute_rat_stats(input(), &mut stages()).unwrap_err();
+        assert_eq!(traced.to_string(), expected.to_string());
+        assert_eq!(stats.to_string(), expected.to_string());
+        assert!(matches!(
+            traced,
+            PipelineError::StageFailed { stage_index: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_checked_reports_failure_in_flushed_records() {
+        // With no input, LITERAL emits "END" on flush, which DIGITS rejects
+        let input: Vec<Record> = vec![];
+        let mut stages: Vec<Box<dyn RecordStage>> = vec![
+            command_to_record_stage(&Command::Literal {
+                text: "END".to_string(),
+            }),
+            Box::new(DigitsOnly),
+        ];
+        let err = execute_rat_checked(input, &mut stages).unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::StageFailed { stage_index: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_generate_agrees_with_batch() {
+        for pipeline in [
+            "PIPE GENERATE 5 /R{SEQ:3} {RAND:6}/ SEED 9 | LOCATE /R00/ | CONSOLE",
+            "PIPE CONSOLE | GENERATE 2 /G{SEQ:1}/ | TAKE 3 | CONSOLE",
+            "PIPE HOLE | GENERATE 2 /G{SEQ:1}/ | CONSOLE",
+        ] {
+            assert_eq!(
+                assert_executors_agree("A\nB", pipeline),
+                Ok(()),
+                "{pipeline}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | HEADER {COUNT} IN | TAKE 2 | CONSOLE";
+        assert_eq!(assert_executors_agree("A\nB\nC", pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_apply_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | APPLY squeeze | APPLY rot13 | CONSOLE";
+        assert_eq!(
+            assert_executors_agree("a   b\nHello  World", pipeline),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_topn_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | TOPN 3 0,1 | CONSOLE";
+        let input = "5 a\nx b\n9 c\n5 d\n1 e\n5 f";
+        assert_eq!(assert_executors_agree(input, pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_crosstab_agrees_with_batch() {
+        let spec_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../specs");
+        let input = fs::read_to_string(spec_dir.join("input-fixed-80.data")).unwrap();
+        let pipeline = "PIPE CONSOLE | CROSSTAB 18,10 BY 0,1 | CONSOLE";
+        assert_eq!(assert_executors_agree(&input, pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_context_agrees_with_batch() {
+        let input = "a ok\nb ok\nc ERROR\nd ERROR\ne ok\nf ok\ng ok\nh ERROR\ni ok";
+        for counts in ["1", "2,0", "0,2", "3"] {
+            let pipeline = format!("PIPE CONSOLE | CONTEXT /ERROR/ {counts} | CONSOLE");
+            assert_eq!(assert_executors_agree(input, &pipeline), Ok(()), "{counts}");
+        }
+    }
+
+    #[test]
+    fn test_onchange_agrees_with_batch() {
+        let spec_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../specs");
+        let input = fs::read_to_string(spec_dir.join("input-fixed-80.data")).unwrap();
+        let pipeline = "PIPE CONSOLE | SORT 18,10 | ONCHANGE 18,10 | CONSOLE";
+        assert_eq!(assert_executors_agree(&input, pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_pivot_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | PIVOT group=0,2 key=3,4 value=8,8 NAME:0 DEPT:8 | CONSOLE";
+        let input = "E1 NAME SMITH\nE1 DEPT SALES\n \nE2 DEPT ADMIN\nE3 NAME JONES";
+        assert_eq!(assert_executors_agree(input, pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_insert_every_agrees_with_batch() {
+        for stage in ["INSERT EVERY 2 /---/", "INSERT EVERY 2 /---/ TRAILING"] {
+            let pipeline = format!("PIPE CONSOLE | {stage} | CONSOLE");
+            for input in ["1\n2\n3\n4\n5", "1\n2\n3\n4", ""] {
+                assert_eq!(assert_executors_agree(input, &pipeline), Ok(()), "{stage}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | ZIP | CONSOLE";
+        assert_eq!(assert_executors_agree("A\nB\nC", pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_hole_count_agrees_with_batch() {
+        let pipeline = "PIPE CONSOLE | NLOCATE /KEEP/ | HOLE COUNT | CONSOLE";
+        assert_eq!(assert_executors_agree("KEEP\nDROP\nDROP", pipeline), Ok(()));
+    }
+
     // --- Equivalence tests for all spec files ---
 
     macro_rules! equiv_test {
@@ -318,6 +1659,7 @@ mod tests {
     equiv_test!(equiv_duplicate_triple, "duplicate-triple.pipe");
     equiv_test!(equiv_engineers_only, "engineers-only.pipe");
     equiv_test!(equiv_filter_sales, "filter-sales.pipe");
+    equiv_test!(equiv_freq_department, "freq-department.pipe");
     equiv_test!(equiv_literal_footer, "literal-footer.pipe");
     equiv_test!(equiv_literal_header_footer, "literal-header-footer.pipe");
     equiv_test!(equiv_locate_errors, "locate-errors.pipe");
@@ -5,12 +5,35 @@
 //! record is read, contrasting with the batch executor which processes all
 //! records through one stage before moving to the next.
 
+pub mod chain;
 pub mod debug_trace;
 pub mod dsl;
 pub mod executor;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profile;
 pub mod record_stage;
+pub mod stream;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testkit;
 
-pub use debug_trace::{FlushTrace, RatDebugTrace, RecordTrace};
-pub use dsl::{execute_pipeline_rat, execute_pipeline_rat_debug};
-pub use executor::{execute_rat, execute_rat_traced};
-pub use record_stage::{RecordStage, command_to_record_stage};
+pub use chain::StageChain;
+pub use debug_trace::{
+    CursorPosition, FlushTrace, RatDebugTrace, RecordTrace, TraceCursor, trace_to_csv,
+};
+pub use dsl::{
+    StageCounts, execute_parsed_rat, execute_pipeline_rat, execute_pipeline_rat_debug,
+    execute_pipeline_rat_stats, execute_pipeline_rat_with_options,
+};
+pub use executor::{
+    execute_rat, execute_rat_checked, execute_rat_expansion_limited, execute_rat_io,
+    execute_rat_limited, execute_rat_progress, execute_rat_provenance, execute_rat_routed,
+    execute_rat_stats, execute_rat_traced,
+};
+#[cfg(feature = "parallel")]
+pub use parallel::execute_rat_parallel;
+#[cfg(not(target_arch = "wasm32"))]
+pub use profile::{StageTiming, execute_rat_profiled};
+pub use record_stage::{ClosureStage, RecordStage, command_to_record_stage};
+pub use stream::{LineSource, RecordSink, RecordSource, WriterSink};
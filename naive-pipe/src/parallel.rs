@@ -0,0 +1,216 @@
+//! Data-parallel record-at-a-time execution (the `parallel` feature).
+//!
+//! [`execute_rat_parallel`] splits the input into contiguous shards, runs
+//! each shard through its own stage chain on a `std::thread`, and joins
+//! the outputs in input order. That only matches [`execute_rat_checked`] when
+//! every stage handles each record on its own, so chains containing an
+//! order-dependent stage (see [`RecordStage::is_order_dependent`]) are
+//! refused.
+//!
+//! Stages are stateful and not `Send`, so each thread builds its own chain
+//! from a factory rather than sharing one.
+
+use std::thread;
+
+use pipelines_rs::{PipelineError, Record};
+
+use crate::executor::execute_rat_checked;
+use crate::record_stage::RecordStage;
+
+/// Execute a pipeline on up to `threads` threads, one stage chain per
+/// shard of the input.
+///
+/// `stage_factory` is called once to check the chain and once per shard.
+/// Output is the shards' outputs concatenated in input order, the same
+/// as [`execute_rat_checked`] would produce. `threads` of 0 or 1, or input
+/// too small to split, runs serially on the calling thread.
+///
+/// Returns `PipelineError::Stage` naming the first order-dependent stage
+/// if the chain cannot be split, or the failure of the first shard in
+/// input order whose stages failed, as `PipelineError::StageFailed`.
+///
+/// # Example
+///
+/// ```
+/// use naive_pipe::{RecordStage, command_to_record_stage, execute_rat_parallel};
+/// use pipelines_rs::{Command, Record};
+///
+/// let input: Vec<Record> = ["a", "b", "c"].iter().map(|s| Record::from_str(s)).collect();
+/// let upper = || -> Vec<Box<dyn RecordStage>> { vec![command_to_record_stage(&Command::Upper)] };
+/// let output = execute_rat_parallel(input, upper, 2).unwrap();
+/// assert_eq!(output[2].as_str().trim_end(), "C");
+/// ```
+pub fn execute_rat_parallel<F>(
+    input: Vec<Record>,
+    stage_factory: F,
+    threads: usize,
+) -> Result<Vec<Record>, PipelineError>
+where
+    F: Fn() -> Vec<Box<dyn RecordStage>> + Sync,
+{
+    let mut stages = stage_factory();
+    if let Some(stage) = stages.iter().find(|s| s.is_order_dependent()) {
+        return Err(PipelineError::Stage(format!(
+            "{} depends on record order and cannot run in parallel",
+            stage.name()
+        )));
+    }
+    let shard_size = input.len().div_ceil(threads.max(1)).max(1);
+    if threads <= 1 || input.len() <= shard_size {
+        return execute_rat_checked(input, &mut stages);
+    }
+
+    let mut records = input.into_iter();
+    let shards: Vec<Vec<Record>> = std::iter::from_fn(|| {
+        let shard: Vec<Record> = records.by_ref().take(shard_size).collect();
+        (!shard.is_empty()).then_some(shard)
+    })
+    .collect();
+
+    let factory = &stage_factory;
+    let outputs: Vec<Result<Vec<Record>, PipelineError>> = thread::scope(|scope| {
+        let handles: Vec<_> = shards
+            .into_iter()
+            .map(|shard| scope.spawn(move || execute_rat_checked(shard, &mut factory())))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    let outputs = outputs.into_iter().collect::<Result<Vec<_>, _>>()?;
+    Ok(outputs.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::execute_rat;
+    use crate::record_stage::{ClosureStage, command_to_record_stage};
+    use pipelines_rs::Command;
+
+    fn map_chain() -> Vec<Box<dyn RecordStage>> {
+        vec![
+            command_to_record_stage(&Command::Console),
+            command_to_record_stage(&Command::Upper),
+            command_to_record_stage(&Command::Reverse { full: false }),
+            Box::new(ClosureStage::stateless("SPLIT", |r: Record| {
+                // Drop some records and duplicate others
+                match r.as_str().trim_end().len() % 3 {
+                    0 => vec![],
+                    1 => vec![r.clone(), r],
+                    _ => vec![r],
+                }
+            })),
+        ]
+    }
+
+    fn numbered(n: usize) -> Vec<Record> {
+        (0..n)
+            .map(|i| Record::from_str(&format!("rec{}", "x".repeat(i % 7))))
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_matches_serial_for_map_chain() {
+        for n in [0, 1, 5, 97] {
+            let serial = execute_rat(numbered(n), &mut map_chain());
+            for threads in [0, 1, 2, 4, 8] {
+                let parallel = execute_rat_parallel(numbered(n), map_chain, threads).unwrap();
+                assert_eq!(parallel, serial, "n={n} threads={threads}");
+            }
+        }
+    }
+
+    /// Stateless test stage that rejects records containing "xxx".
+    struct NoTripleX;
+
+    impl RecordStage for NoTripleX {
+        fn process(&mut self, record: Record) -> Vec<Record> {
+            vec![record]
+        }
+
+        fn try_process(&mut self, record: Record) -> Result<Vec<Record>, PipelineError> {
+            if record.as_str().contains("xxx") {
+                Err(PipelineError::Stage("too many x".to_string()))
+            } else {
+                Ok(vec![record])
+            }
+        }
+
+        fn is_order_dependent(&self) -> bool {
+            false
+        }
+
+        fn name(&self) -> &str {
+            "NOXXX"
+        }
+    }
+
+    #[test]
+    fn test_parallel_reports_first_stage_failure() {
+        let chain = || -> Vec<Box<dyn RecordStage>> {
+            vec![
+                command_to_record_stage(&Command::Upper),
+                Box::new(NoTripleX),
+            ]
+        };
+        let lower = || -> Vec<Box<dyn RecordStage>> { vec![Box::new(NoTripleX)] };
+        // Record 3 ("recxxx") is the first one NOXXX rejects
+        for threads in [1, 4] {
+            match execute_rat_parallel(numbered(10), lower, threads) {
+                Err(PipelineError::StageFailed {
+                    stage_index,
+                    record,
+                    ..
+                }) => {
+                    assert_eq!(stage_index, 0, "threads={threads}");
+                    assert_eq!(record.as_str().trim_end(), "recxxx");
+                }
+                other => panic!("expected StageFailed, got {other:?}"),
+            }
+        }
+        // UPPER turns "x" into "X", so nothing fails
+        assert_eq!(
+            execute_rat_parallel(numbered(10), chain, 4).unwrap().len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_parallel_refuses_order_dependent_stage() {
+        let chain = || -> Vec<Box<dyn RecordStage>> {
+            vec![
+                command_to_record_stage(&Command::Upper),
+                command_to_record_stage(&Command::Take { n: 2 }),
+            ]
+        };
+        let err = execute_rat_parallel(numbered(10), chain, 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            PipelineError::Stage("TAKE depends on record order and cannot run in parallel".into())
+                .to_string()
+        );
+
+        // A closure may keep state, so it is refused unless marked stateless
+        let chain = || -> Vec<Box<dyn RecordStage>> {
+            let mut seen = 0;
+            vec![Box::new(ClosureStage::new("NUMBER", move |r: Record| {
+                seen += 1;
+                vec![Record::from_str(&format!("{seen} {}", r.as_str()))]
+            }))]
+        };
+        let err = execute_rat_parallel(numbered(10), chain, 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            PipelineError::Stage(
+                "NUMBER depends on record order and cannot run in parallel".into()
+            )
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Per-stage wall-clock profiling for the record-at-a-time executor.
+//!
+//! Timing uses `std::time::Instant`, which is unavailable on
+//! `wasm32-unknown-unknown`, so this module is only compiled for native
+//! targets.
+//!
+//! Each `process` and `flush` call is bracketed by a pair of `Instant::now()`
+//! reads. That adds a fixed overhead per call (typically tens of
+//! nanoseconds), which dominates for trivial stages such as `CONSOLE`.
+//! Compare stages against each other rather than reading the totals as
+//! absolute costs.
+
+use std::time::{Duration, Instant};
+
+use pipelines_rs::Record;
+
+use crate::record_stage::RecordStage;
+
+/// Cumulative time spent in one stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageTiming {
+    /// Stage name, as reported by `RecordStage::name`.
+    pub name: String,
+    /// Total time spent in `process` and `flush`.
+    pub total_duration: Duration,
+    /// Number of `process` and `flush` calls.
+    pub calls: usize,
+}
+
+impl StageTiming {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            total_duration: Duration::ZERO,
+            calls: 0,
+        }
+    }
+
+    /// Run `f`, adding its elapsed time to this stage's total.
+    fn measure<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.total_duration += start.elapsed();
+        self.calls += 1;
+        result
+    }
+}
+
+/// Push records through stages, timing each `process` call.
+fn push_through_timed(
+    records: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+    timings: &mut [StageTiming],
+) -> Vec<Record> {
+    let mut current = records;
+    for (stage, timing) in stages.iter_mut().zip(timings.iter_mut()) {
+        let mut next = Vec::new();
+        for r in current {
+            next.extend(timing.measure(|| stage.process(r)));
+        }
+        current = next;
+    }
+    current
+}
+
+/// Execute a pipeline in record-at-a-time mode, timing each stage.
+///
+/// Produces the same output as `execute_rat`, plus one `StageTiming` per
+/// stage in pipeline order.
+pub fn execute_rat_profiled(
+    input: Vec<Record>,
+    stages: &mut [Box<dyn RecordStage>],
+) -> (Vec<Record>, Vec<StageTiming>) {
+    let mut timings: Vec<StageTiming> = stages.iter().map(|s| StageTiming::new(s.name())).collect();
+    let mut output = Vec::new();
+
+    for record in input {
+        output.extend(push_through_timed(vec![record], stages, &mut timings));
+    }
+
+    for i in 0..stages.len() {
+        let flush_output = timings[i].measure(|| stages[i].flush());
+        if !flush_output.is_empty() {
+            output.extend(push_through_timed(
+                flush_output,
+                &mut stages[i + 1..],
+                &mut timings[i + 1..],
+            ));
+        }
+    }
+
+    (output, timings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::execute_rat;
+    use crate::record_stage::command_to_record_stage;
+    use pipelines_rs::parse_commands;
+
+    fn stages_for(pipeline: &str) -> Vec<Box<dyn RecordStage>> {
+        parse_commands(pipeline)
+            .unwrap()
+            .iter()
+            .map(command_to_record_stage)
+            .collect()
+    }
+
+    #[test]
+    fn test_profiled_output_matches_execute_rat() {
+        let pipeline = "LOCATE /A/ | DUPLICATE 2 | COUNT | CONSOLE";
+        let input: Vec<Record> = ["A1", "B2", "A3"].map(Record::from_str).to_vec();
+
+        let expected = execute_rat(input.clone(), &mut stages_for(pipeline));
+        let (output, timings) = execute_rat_profiled(input, &mut stages_for(pipeline));
+        assert_eq!(output, expected);
+
+        let names: Vec<&str> = timings.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["LOCATE", "DUPLICATE", "COUNT", "CONSOLE"]);
+    }
+
+    #[test]
+    fn test_profiled_call_counts() {
+        let pipeline = "LOCATE /A/ | DUPLICATE 2 | COUNT | CONSOLE";
+        let input: Vec<Record> = ["A1", "B2", "A3"].map(Record::from_str).to_vec();
+        let (_, timings) = execute_rat_profiled(input, &mut stages_for(pipeline));
+
+        let calls: Vec<usize> = timings.iter().map(|t| t.calls).collect();
+        // Each stage: process per arriving record, plus one flush.
+        // LOCATE sees 3, DUPLICATE 2, COUNT 4; CONSOLE only sees COUNT's flush.
+        assert_eq!(calls, vec![4, 3, 5, 2]);
+    }
+}
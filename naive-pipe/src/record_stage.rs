@@ -4,8 +4,49 @@
 //! output records. This enables the record-at-a-time (RAT) executor to show
 //! individual record flow through the pipeline.
 
+use pipelines_rs::BuildItem;
 use pipelines_rs::Command;
+use pipelines_rs::CompareOp;
+use pipelines_rs::DateFormat;
+use pipelines_rs::FieldKind;
+use pipelines_rs::JustifySide;
+use pipelines_rs::LengthStats;
+use pipelines_rs::MatchMode;
+use pipelines_rs::PipelineError;
 use pipelines_rs::Record;
+use pipelines_rs::RecordHasher;
+use pipelines_rs::SortKey;
+use pipelines_rs::TransformFn;
+use pipelines_rs::build_record;
+use pipelines_rs::change_text;
+use pipelines_rs::change_until_stable;
+use pipelines_rs::chop_record;
+use pipelines_rs::compare_by_keys;
+use pipelines_rs::crosstab_records;
+use pipelines_rs::decode_field;
+use pipelines_rs::default_field;
+use pipelines_rs::expand_header;
+use pipelines_rs::expand_record;
+use pipelines_rs::field_record;
+use pipelines_rs::filter_keeps;
+use pipelines_rs::flag_record;
+use pipelines_rs::frequency_records;
+use pipelines_rs::generate_records;
+use pipelines_rs::json_record;
+use pipelines_rs::mask_record;
+use pipelines_rs::norm_record;
+use pipelines_rs::pivot_group;
+use pipelines_rs::pivot_group_ends;
+use pipelines_rs::reformat_date;
+use pipelines_rs::segment_header;
+use pipelines_rs::select_separated;
+use pipelines_rs::shuffle_records;
+use pipelines_rs::sort_items;
+use pipelines_rs::verify_record;
+use pipelines_rs::wrap_record;
+use pipelines_rs::zip_pair;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 
 /// A pipeline stage that processes records one at a time.
 ///
@@ -16,6 +57,29 @@ pub trait RecordStage {
     /// Process a single input record, returning zero or more output records.
     fn process(&mut self, record: Record) -> Vec<Record>;
 
+    /// Process a single input record, reporting bad data as an error.
+    ///
+    /// Strict stages override this to reject records they cannot handle;
+    /// the default never fails and delegates to [`process`](Self::process).
+    /// The fallible executors (`execute_rat_checked`, `execute_rat_limited`,
+    /// `execute_rat_expansion_limited`) and `execute_parsed_rat` call this.
+    fn try_process(&mut self, record: Record) -> Result<Vec<Record>, PipelineError> {
+        Ok(self.process(record))
+    }
+
+    /// Process a single input record like [`try_process`](Self::try_process),
+    /// handing the output over one record at a time.
+    ///
+    /// Executors enforcing an output cap read from this so they can stop a
+    /// large fan-out part way. The default yields `try_process`'s output;
+    /// DUPLICATE and EXPAND make their copies as they are read.
+    fn try_process_iter(
+        &mut self,
+        record: Record,
+    ) -> Result<Box<dyn Iterator<Item = Record> + '_>, PipelineError> {
+        Ok(Box::new(self.try_process(record)?.into_iter()))
+    }
+
     /// Flush any accumulated state, returning final output records.
     ///
     /// Called after all input records have been processed. Stages like
@@ -24,6 +88,90 @@ pub trait RecordStage {
         vec![]
     }
 
+    /// Process a record tagged with the input line it came from.
+    ///
+    /// The default gives every output record the input's `line`, which is
+    /// right for stages that pass, rewrite or copy the record in hand.
+    /// Stages that emit records of their own (LITERAL) or hold records back
+    /// until flush (SORT) override this and [`flush_tagged`](Self::flush_tagged).
+    /// Only `execute_rat_provenance` calls this.
+    fn process_tagged(
+        &mut self,
+        record: Record,
+        line: Option<usize>,
+    ) -> Vec<(Record, Option<usize>)> {
+        self.process(record)
+            .into_iter()
+            .map(|r| (r, line))
+            .collect()
+    }
+
+    /// Flush, tagging each record with the input line it came from.
+    ///
+    /// The default tags flushed records `None`: a COUNT total, say, comes
+    /// from no single line.
+    fn flush_tagged(&mut self) -> Vec<(Record, Option<usize>)> {
+        self.flush().into_iter().map(|r| (r, None)).collect()
+    }
+
+    /// Process a record, naming the output channel of each output record.
+    ///
+    /// `None` is the main channel, which flows on to the next stage; a
+    /// named channel takes the record out of the pipeline. The default
+    /// keeps every record on the main channel; ROUTE overrides it. Only
+    /// `execute_rat_routed` calls this.
+    fn process_routed(&mut self, record: Record) -> Vec<(Record, Option<String>)> {
+        self.process(record)
+            .into_iter()
+            .map(|r| (r, None))
+            .collect()
+    }
+
+    /// Whether this stage's output for a record depends on the records
+    /// before it, or it emits records of its own at flush.
+    ///
+    /// SORT, COUNT, TAKE and the like must see the whole stream in order;
+    /// stages that rewrite, drop or copy each record on its own override
+    /// this to return `false`, so they can run on separate shards of the
+    /// input, which is what `execute_rat_parallel` requires. The default
+    /// is `true`, so a stage that keeps state is never split by mistake.
+    fn is_order_dependent(&self) -> bool {
+        true
+    }
+
+    /// The most records [`process`](Self::process) can emit for one input.
+    ///
+    /// The default, `usize::MAX`, means no bound is known. Filters and
+    /// one-to-one rewrites such as UPPER return 1, and DUPLICATE n returns
+    /// n. When every stage of a chain returns at most 1, the executor moves
+    /// each record through [`process_one`](Self::process_one) without
+    /// building a `Vec` per stage, stopping at the first drop.
+    fn max_fanout(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Process a record into at most one output record.
+    ///
+    /// Only called on stages whose [`max_fanout`](Self::max_fanout) is at
+    /// most 1. The default takes the record from [`process`](Self::process);
+    /// stages with a fan-out of 1 implement this directly and build
+    /// `process` on top of it.
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        self.process(record).pop()
+    }
+
+    /// Process a record into at most one output record, reporting bad data
+    /// as an error.
+    ///
+    /// The fallible executors call this in place of
+    /// [`try_process`](Self::try_process) on a chain of stages whose
+    /// [`max_fanout`](Self::max_fanout) is at most 1. The default never
+    /// fails and delegates to [`process_one`](Self::process_one), so a
+    /// strict stage with a fan-out of 1 overrides both.
+    fn try_process_one(&mut self, record: Record) -> Result<Option<Record>, PipelineError> {
+        Ok(self.process_one(record))
+    }
+
     /// The display name of this stage.
     fn name(&self) -> &str;
 }
@@ -37,7 +185,19 @@ pub struct ConsoleStage;
 
 impl RecordStage for ConsoleStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        vec![record]
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
@@ -45,6 +205,31 @@ impl RecordStage for ConsoleStage {
     }
 }
 
+/// A `#!` disabled stage - passes records through unchanged.
+pub struct DisabledStage;
+
+impl RecordStage for DisabledStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "DISABLED"
+    }
+}
+
 /// FILTER pos,len = "value" - keeps records where field equals value.
 pub struct FilterEqStage {
     pos: usize,
@@ -54,11 +239,21 @@ pub struct FilterEqStage {
 
 impl RecordStage for FilterEqStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        if record.field_eq(self.pos, self.len, &self.value) {
-            vec![record]
-        } else {
-            vec![]
-        }
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        record
+            .field_eq(self.pos, self.len, &self.value)
+            .then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
@@ -75,11 +270,19 @@ pub struct FilterNeStage {
 
 impl RecordStage for FilterNeStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        if !record.field_eq(self.pos, self.len, &self.value) {
-            vec![record]
-        } else {
-            vec![]
-        }
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        (!record.field_eq(self.pos, self.len, &self.value)).then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
@@ -87,246 +290,1842 @@ impl RecordStage for FilterNeStage {
     }
 }
 
-/// SELECT - extracts and repositions fields.
-pub struct SelectStage {
-    fields: Vec<(usize, usize, usize)>,
+/// FILTER BETWEEN - passes records whose field lies in an inclusive range.
+pub struct FilterRangeStage {
+    pos: usize,
+    len: usize,
+    low: String,
+    high: String,
 }
 
-impl RecordStage for SelectStage {
+impl RecordStage for FilterRangeStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        let mut output = Record::new();
-        for &(src, len, dest) in &self.fields {
-            output.set_field(dest, len, record.field(src, len));
-        }
-        vec![output]
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        record
+            .field_in_range(self.pos, self.len, &self.low, &self.high)
+            .then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "SELECT"
+        "FILTER"
     }
 }
 
-/// TAKE n - keeps the first n records, discards the rest.
-pub struct TakeStage {
-    n: usize,
-    seen: usize,
+/// FILTER pos,len op FIELD pos,len - passes records where one field
+/// compares to another as `op` says.
+pub struct FilterFieldCmpStage {
+    left: (usize, usize),
+    right: (usize, usize),
+    op: CompareOp,
 }
 
-impl RecordStage for TakeStage {
+impl RecordStage for FilterFieldCmpStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        if self.seen < self.n {
-            self.seen += 1;
-            vec![record]
-        } else {
-            vec![]
-        }
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        let ordering = record.compare_fields(self.left.0, self.left.1, self.right.0, self.right.1);
+        self.op.holds(ordering).then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "TAKE"
+        "FILTER"
     }
 }
 
-/// SKIP n - skips the first n records, passes the rest.
-pub struct SkipStage {
-    n: usize,
-    seen: usize,
+/// ROUTE ... TO name [ELSE name] - sends the records its FILTER condition
+/// would keep to channel `to`, and the rest to `otherwise`.
+///
+/// Outside `execute_rat_routed` the routed records are dropped, leaving
+/// the main channel: the rest, when there is no ELSE.
+pub struct RouteStage {
+    filter: Command,
+    to: String,
+    otherwise: Option<String>,
 }
 
-impl RecordStage for SkipStage {
-    fn process(&mut self, record: Record) -> Vec<Record> {
-        if self.seen < self.n {
-            self.seen += 1;
-            vec![]
+impl RouteStage {
+    /// The channel `record` goes to; `None` is the main channel.
+    fn channel(&self, record: &Record) -> Option<&str> {
+        if filter_keeps(&self.filter, record) {
+            Some(&self.to)
         } else {
-            vec![record]
+            self.otherwise.as_deref()
         }
     }
+}
 
-    fn name(&self) -> &str {
-        "SKIP"
+impl RecordStage for RouteStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
     }
-}
 
-/// LOCATE - keeps records containing a pattern.
-pub struct LocateStage {
-    pattern: String,
-    field: Option<(usize, usize)>,
-}
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        self.channel(&record).is_none().then_some(record)
+    }
 
-impl RecordStage for LocateStage {
-    fn process(&mut self, record: Record) -> Vec<Record> {
-        let matches = match self.field {
-            Some((pos, len)) => record.field_contains(pos, len, &self.pattern),
-            None => record.as_str().contains(self.pattern.as_str()),
-        };
-        if matches { vec![record] } else { vec![] }
+    fn process_routed(&mut self, record: Record) -> Vec<(Record, Option<String>)> {
+        let channel = self.channel(&record).map(str::to_string);
+        vec![(record, channel)]
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "LOCATE"
+        "ROUTE"
     }
 }
 
-/// NLOCATE - keeps records NOT containing a pattern.
-pub struct NlocateStage {
-    pattern: String,
-    field: Option<(usize, usize)>,
+/// OMIT - drops the records its FILTER condition would keep.
+pub struct OmitStage {
+    filter: Command,
 }
 
-impl RecordStage for NlocateStage {
+impl RecordStage for OmitStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        let matches = match self.field {
-            Some((pos, len)) => record.field_contains(pos, len, &self.pattern),
-            None => record.as_str().contains(self.pattern.as_str()),
-        };
-        if matches { vec![] } else { vec![record] }
+        self.process_one(record).into_iter().collect()
     }
 
-    fn name(&self) -> &str {
-        "NLOCATE"
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        (!filter_keeps(&self.filter, &record)).then_some(record)
     }
-}
-
-/// COUNT - counts records and emits summary on flush.
-pub struct CountStage {
-    count: usize,
-}
 
-impl RecordStage for CountStage {
-    fn process(&mut self, _record: Record) -> Vec<Record> {
-        self.count += 1;
-        vec![]
+    fn max_fanout(&self) -> usize {
+        1
     }
 
-    fn flush(&mut self) -> Vec<Record> {
-        vec![Record::from_str(&self.count.to_string())]
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "COUNT"
+        "OMIT"
     }
 }
 
-/// CHANGE "old" "new" - replaces occurrences in each record.
-pub struct ChangeStage {
-    old: String,
-    new: String,
+/// ONCHANGE pos,len - passes a record when its field differs from the
+/// previous record's. The first record always passes.
+pub struct OnChangeStage {
+    pos: usize,
+    len: usize,
+    last: Option<String>,
 }
 
-impl RecordStage for ChangeStage {
+impl RecordStage for OnChangeStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        let content = record.as_str().replace(&self.old, &self.new);
-        vec![Record::from_str(&content)]
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        let key = record.field(self.pos, self.len);
+        if self.last.as_deref() == Some(key) {
+            return None;
+        }
+        self.last = Some(key.to_string());
+        Some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.last = None;
+        vec![]
     }
 
     fn name(&self) -> &str {
-        "CHANGE"
+        "ONCHANGE"
     }
 }
 
-/// LITERAL "text" - emits a literal record before the first input record.
+/// SELECT - extracts and repositions fields. Later fields win overlaps.
 ///
-/// On `flush()`, emits the literal if no input records were received
-/// (matching batch behavior where LITERAL prepends to an empty stream).
-pub struct LiteralStage {
-    text: String,
-    emitted: bool,
+/// With a separator, fields are laid out in destination order with the
+/// separator between them.
+pub struct SelectStage {
+    fields: Vec<(usize, usize, usize)>,
+    separator: Option<String>,
 }
 
-impl RecordStage for LiteralStage {
+impl RecordStage for SelectStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        if !self.emitted {
-            self.emitted = true;
-            vec![Record::from_str(&self.text), record]
-        } else {
-            vec![record]
-        }
+        self.process_one(record).into_iter().collect()
     }
 
-    fn flush(&mut self) -> Vec<Record> {
-        if !self.emitted {
-            self.emitted = true;
-            vec![Record::from_str(&self.text)]
-        } else {
-            vec![]
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if let Some(sep) = &self.separator {
+            return Some(select_separated(&record, &self.fields, sep));
+        }
+        let mut output = Record::new();
+        for &(src, len, dest) in &self.fields {
+            output.set_field(dest, len, record.field(src, len));
         }
+        Some(output)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "LITERAL"
+        "SELECT"
     }
 }
 
-/// UPPER - converts records to uppercase.
-pub struct UpperStage;
+/// FIELD pos,len [PAD] - emits only one field, moved to column 0.
+pub struct FieldStage {
+    pos: usize,
+    len: usize,
+    trim: bool,
+}
 
-impl RecordStage for UpperStage {
+impl RecordStage for FieldStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        vec![Record::from_str(&record.as_str().to_uppercase())]
+        self.process_one(record).into_iter().collect()
     }
 
-    fn name(&self) -> &str {
-        "UPPER"
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(field_record(&record, self.pos, self.len, self.trim))
     }
-}
 
-/// LOWER - converts records to lowercase.
-pub struct LowerStage;
+    fn max_fanout(&self) -> usize {
+        1
+    }
 
-impl RecordStage for LowerStage {
-    fn process(&mut self, record: Record) -> Vec<Record> {
-        vec![Record::from_str(&record.as_str().to_lowercase())]
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "LOWER"
+        "FIELD"
     }
 }
 
-/// REVERSE - reverses characters in each record.
-pub struct ReverseStage;
+/// TOJSON name=pos,len ... - rewrites each record as a JSON object.
+pub struct ToJsonStage {
+    fields: Vec<(String, usize, usize)>,
+}
 
-impl RecordStage for ReverseStage {
+impl RecordStage for ToJsonStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        let reversed: String = record.as_str().trim_end().chars().rev().collect();
-        vec![Record::from_str(&reversed)]
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(json_record(&record, &self.fields))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
     }
 
     fn name(&self) -> &str {
-        "REVERSE"
+        "TOJSON"
     }
 }
 
-/// DUPLICATE n - repeats each record n times.
-pub struct DuplicateStage {
+/// TAKE n - keeps the first n records, discards the rest.
+pub struct TakeStage {
     n: usize,
+    seen: usize,
 }
 
-impl RecordStage for DuplicateStage {
+impl RecordStage for TakeStage {
     fn process(&mut self, record: Record) -> Vec<Record> {
-        std::iter::repeat_n(record, self.n).collect()
+        self.process_one(record).into_iter().collect()
     }
 
-    fn name(&self) -> &str {
-        "DUPLICATE"
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if self.seen < self.n {
+            self.seen += 1;
+            Some(record)
+        } else {
+            None
+        }
     }
-}
 
-/// HOLE - discards all input, outputs nothing.
-pub struct HoleStage;
-
-impl RecordStage for HoleStage {
-    fn process(&mut self, _record: Record) -> Vec<Record> {
-        vec![]
+    fn max_fanout(&self) -> usize {
+        1
     }
 
     fn name(&self) -> &str {
-        "HOLE"
+        "TAKE"
     }
 }
 
-// ---------------------------------------------------------------------------
+/// SKIP n - skips the first n records, passes the rest.
+pub struct SkipStage {
+    n: usize,
+    seen: usize,
+}
+
+impl RecordStage for SkipStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if self.seen < self.n {
+            self.seen += 1;
+            None
+        } else {
+            Some(record)
+        }
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "SKIP"
+    }
+}
+
+/// LOCATE - keeps records containing a pattern.
+pub struct LocateStage {
+    pattern: String,
+    field: Option<(usize, usize)>,
+    trim: bool,
+}
+
+impl RecordStage for LocateStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        let matches = match self.field {
+            Some((pos, len)) if self.trim => record.field_contains_trimmed(pos, len, &self.pattern),
+            Some((pos, len)) => record.field_contains(pos, len, &self.pattern),
+            None => record.as_str().contains(self.pattern.as_str()),
+        };
+        matches.then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "LOCATE"
+    }
+}
+
+/// LOCATE ANY|ALL - keeps records containing any (or all) of several patterns.
+pub struct LocateMultiStage {
+    patterns: Vec<String>,
+    mode: MatchMode,
+    field: Option<(usize, usize)>,
+}
+
+impl RecordStage for LocateMultiStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        self.mode
+            .matches(&record, &self.patterns, self.field)
+            .then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "LOCATE"
+    }
+}
+
+/// NLOCATE - keeps records NOT containing a pattern.
+pub struct NlocateStage {
+    pattern: String,
+    field: Option<(usize, usize)>,
+}
+
+impl RecordStage for NlocateStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        let matches = match self.field {
+            Some((pos, len)) => record.field_contains(pos, len, &self.pattern),
+            None => record.as_str().contains(self.pattern.as_str()),
+        };
+        (!matches).then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "NLOCATE"
+    }
+}
+
+/// CONTEXT /pattern/ before,after - matching records plus their neighbors.
+///
+/// Holds the last `before` unmatched records in a ring, released when a
+/// match arrives, and counts down `after` records following each match.
+/// A record is emitted at most once, so overlapping windows merge.
+pub struct ContextStage {
+    pattern: String,
+    before: usize,
+    after: usize,
+    recent: VecDeque<Record>,
+    trailing: usize,
+}
+
+impl RecordStage for ContextStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        if record.as_str().contains(self.pattern.as_str()) {
+            self.trailing = self.after;
+            self.recent.drain(..).chain([record]).collect()
+        } else if self.trailing > 0 {
+            self.trailing -= 1;
+            vec![record]
+        } else {
+            if self.before > 0 {
+                if self.recent.len() == self.before {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back(record);
+            }
+            vec![]
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        // Leading context with no match after it is dropped
+        self.recent.clear();
+        self.trailing = 0;
+        vec![]
+    }
+
+    fn name(&self) -> &str {
+        "CONTEXT"
+    }
+}
+
+/// COUNT - counts records and emits summary on flush.
+pub struct CountStage {
+    count: usize,
+}
+
+impl RecordStage for CountStage {
+    fn process(&mut self, _record: Record) -> Vec<Record> {
+        self.count += 1;
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        vec![Record::from_str(&self.count.to_string())]
+    }
+
+    fn name(&self) -> &str {
+        "COUNT"
+    }
+}
+
+/// HASH - folds every record into a hash, emits `HASH=<hex>` on flush.
+pub struct HashStage {
+    hasher: RecordHasher,
+}
+
+impl RecordStage for HashStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.hasher.update(&record);
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        vec![self.hasher.to_record()]
+    }
+
+    fn name(&self) -> &str {
+        "HASH"
+    }
+}
+
+/// STATS [PASSTHROUGH] - emits record count and length summary records on
+/// flush, passing the records through first with PASSTHROUGH.
+pub struct StatsStage {
+    passthrough: bool,
+    stats: LengthStats,
+}
+
+impl RecordStage for StatsStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.stats.update(&record);
+        if self.passthrough {
+            vec![record]
+        } else {
+            vec![]
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.stats.to_records()
+    }
+
+    fn name(&self) -> &str {
+        "STATS"
+    }
+}
+
+/// CHANGE "old" "new" [FIRST|n] - replaces occurrences in each record.
+///
+/// With REPEAT, a record that never stops changing is an error:
+/// `try_process` reports it as `PipelineError::ChangeDidNotConverge`, as
+/// the batch executor does. `process` cannot fail, so it flags such a
+/// record with `ASSERT_MARKER` (as ASSERT does) rather than passing it on
+/// as if it had converged; use an executor that calls `try_process`.
+pub struct ChangeStage {
+    old: String,
+    new: String,
+    limit: Option<usize>,
+    repeat: bool,
+}
+
+impl RecordStage for ChangeStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if self.repeat {
+            let changed = change_until_stable(&record, &self.old, &self.new, self.limit);
+            return Some(changed.unwrap_or_else(|_| flag_record(&record)));
+        }
+        let content = change_text(record.as_str(), &self.old, &self.new, self.limit);
+        Some(Record::from_str(&content))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn try_process(&mut self, record: Record) -> Result<Vec<Record>, PipelineError> {
+        Ok(self.try_process_one(record)?.into_iter().collect())
+    }
+
+    fn try_process_one(&mut self, record: Record) -> Result<Option<Record>, PipelineError> {
+        if self.repeat {
+            return Ok(Some(change_until_stable(
+                &record, &self.old, &self.new, self.limit,
+            )?));
+        }
+        Ok(self.process_one(record))
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "CHANGE"
+    }
+}
+
+/// CHANGE "old" "new" WHERE condition - replaces text only in records the
+/// FILTER condition keeps, passing every record on.
+pub struct ChangeWhereStage {
+    condition: Command,
+    old: String,
+    new: String,
+}
+
+impl RecordStage for ChangeWhereStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if filter_keeps(&self.condition, &record) {
+            let content = change_text(record.as_str(), &self.old, &self.new, None);
+            Some(Record::from_str(&content))
+        } else {
+            Some(record)
+        }
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "CHANGE"
+    }
+}
+
+/// LITERAL "text" - emits a literal record before the first input record.
+///
+/// On `flush()`, emits the literal if no input records were received
+/// (matching batch behavior where LITERAL prepends to an empty stream).
+pub struct LiteralStage {
+    text: String,
+    emitted: bool,
+}
+
+impl RecordStage for LiteralStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        if !self.emitted {
+            self.emitted = true;
+            vec![Record::from_str(&self.text), record]
+        } else {
+            vec![record]
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        if !self.emitted {
+            self.emitted = true;
+            vec![Record::from_str(&self.text)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn process_tagged(
+        &mut self,
+        record: Record,
+        line: Option<usize>,
+    ) -> Vec<(Record, Option<usize>)> {
+        if !self.emitted {
+            self.emitted = true;
+            vec![(Record::from_str(&self.text), None), (record, line)]
+        } else {
+            vec![(record, line)]
+        }
+    }
+
+    fn name(&self) -> &str {
+        "LITERAL"
+    }
+}
+
+/// GENERATE n /template/ - emits the generated records before the first
+/// input record.
+///
+/// Like LITERAL, the records are emitted on `flush()` when no input
+/// arrived. They are built only when first emitted.
+pub struct GenerateStage {
+    count: usize,
+    template: String,
+    seed: u64,
+    emitted: bool,
+}
+
+impl GenerateStage {
+    /// The generated records the first time, nothing after.
+    fn take_generated(&mut self) -> Vec<Record> {
+        if std::mem::replace(&mut self.emitted, true) {
+            vec![]
+        } else {
+            generate_records(self.count, &self.template, self.seed)
+        }
+    }
+}
+
+impl RecordStage for GenerateStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let mut out = self.take_generated();
+        out.push(record);
+        out
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.take_generated()
+    }
+
+    fn process_tagged(
+        &mut self,
+        record: Record,
+        line: Option<usize>,
+    ) -> Vec<(Record, Option<usize>)> {
+        let mut out: Vec<_> = self
+            .take_generated()
+            .into_iter()
+            .map(|r| (r, None))
+            .collect();
+        out.push((record, line));
+        out
+    }
+
+    fn name(&self) -> &str {
+        "GENERATE"
+    }
+}
+
+/// HEADER text - emits an expanded header before all records.
+///
+/// `{COUNT}` needs the total record count, which is only known at flush,
+/// so this stage holds every record until then and emits the header
+/// followed by the records. Memory use grows with the input.
+pub struct HeaderStage {
+    template: String,
+    buffer: Vec<Record>,
+}
+
+impl RecordStage for HeaderStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.buffer.push(record);
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        let header = Record::from_str(&expand_header(&self.template, self.buffer.len()));
+        std::iter::once(header)
+            .chain(std::mem::take(&mut self.buffer))
+            .collect()
+    }
+
+    fn name(&self) -> &str {
+        "HEADER"
+    }
+}
+
+/// UPPER - converts records to uppercase.
+pub struct UpperStage;
+
+impl RecordStage for UpperStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(record.map_chars(|c| c.to_ascii_uppercase()))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "UPPER"
+    }
+}
+
+/// LOWER - converts records to lowercase.
+pub struct LowerStage;
+
+impl RecordStage for LowerStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(record.map_chars(|c| c.to_ascii_lowercase()))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "LOWER"
+    }
+}
+
+/// REVERSE [FULL] - reverses characters in each record.
+pub struct ReverseStage {
+    /// Reverse all 80 columns instead of just the trimmed content.
+    full: bool,
+}
+
+impl RecordStage for ReverseStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if self.full {
+            let mut bytes = *record.as_bytes();
+            bytes.reverse();
+            return Some(Record::from_bytes(&bytes));
+        }
+        let reversed: String = record.as_str().trim_end().chars().rev().collect();
+        Some(Record::from_str(&reversed))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "REVERSE"
+    }
+}
+
+/// DUPLICATE n - repeats each record n times.
+pub struct DuplicateStage {
+    n: usize,
+}
+
+impl RecordStage for DuplicateStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        std::iter::repeat_n(record, self.n).collect()
+    }
+
+    fn try_process_iter(
+        &mut self,
+        record: Record,
+    ) -> Result<Box<dyn Iterator<Item = Record> + '_>, PipelineError> {
+        Ok(Box::new(std::iter::repeat_n(record, self.n)))
+    }
+
+    fn max_fanout(&self) -> usize {
+        self.n
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "DUPLICATE"
+    }
+}
+
+/// EXPAND pos,len [CLEAR] - repeats each record by its own count field.
+pub struct ExpandStage {
+    pos: usize,
+    len: usize,
+    clear: bool,
+}
+
+impl RecordStage for ExpandStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        expand_record(&record, self.pos, self.len, self.clear).collect()
+    }
+
+    fn try_process_iter(
+        &mut self,
+        record: Record,
+    ) -> Result<Box<dyn Iterator<Item = Record> + '_>, PipelineError> {
+        Ok(Box::new(expand_record(
+            &record, self.pos, self.len, self.clear,
+        )))
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "EXPAND"
+    }
+}
+
+/// DATEFMT pos,len FROM fmt TO fmt [FLAG] - rewrites a date field's layout.
+pub struct DateFmtStage {
+    pos: usize,
+    len: usize,
+    from: DateFormat,
+    to: DateFormat,
+    flag: bool,
+}
+
+impl RecordStage for DateFmtStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(reformat_date(
+            &record, self.pos, self.len, self.from, self.to, self.flag,
+        ))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "DATEFMT"
+    }
+}
+
+/// DECODE pos,len /code=text;.../ [DEFAULT /text/] - looks up a field's code.
+pub struct DecodeStage {
+    pos: usize,
+    len: usize,
+    map: HashMap<String, String>,
+    default: Option<String>,
+}
+
+impl RecordStage for DecodeStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(decode_field(
+            &record,
+            self.pos,
+            self.len,
+            &self.map,
+            self.default.as_deref(),
+        ))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "DECODE"
+    }
+}
+
+/// DEFAULT pos,len /value/ [LEFT|RIGHT] - fills a blank field with a value.
+pub struct DefaultStage {
+    pos: usize,
+    len: usize,
+    value: String,
+    justify: JustifySide,
+}
+
+impl RecordStage for DefaultStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(default_field(
+            &record,
+            self.pos,
+            self.len,
+            &self.value,
+            self.justify,
+        ))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "DEFAULT"
+    }
+}
+
+/// MASK pos,len; ... [FILL c] - overwrites fields with a fill character.
+pub struct MaskStage {
+    fields: Vec<(usize, usize)>,
+    fill: char,
+}
+
+impl RecordStage for MaskStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(mask_record(&record, &self.fields, self.fill))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "MASK"
+    }
+}
+
+/// PIVOT - buffers one group of key/value records at a time, emitting the
+/// group's wide record when the next group starts and on flush.
+pub struct PivotStage {
+    key: (usize, usize),
+    value: (usize, usize),
+    group: Option<(usize, usize)>,
+    columns: Vec<(String, usize)>,
+    current: Vec<Record>,
+}
+
+impl PivotStage {
+    fn emit(&mut self) -> Vec<Record> {
+        if self.current.is_empty() {
+            return vec![];
+        }
+        let group = std::mem::take(&mut self.current);
+        vec![pivot_group(&group, self.key, self.value, &self.columns)]
+    }
+}
+
+impl RecordStage for PivotStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let output = match self.current.first() {
+            Some(first) if pivot_group_ends(first, &record, self.group) => self.emit(),
+            _ => vec![],
+        };
+        if !record.is_blank() {
+            self.current.push(record);
+        }
+        output
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.emit()
+    }
+
+    fn name(&self) -> &str {
+        "PIVOT"
+    }
+}
+
+/// BLOCK n - emits a blank 80-byte record after every nth record.
+///
+/// A final partial group is not followed by a separator.
+pub struct BlockStage {
+    n: usize,
+    seen: usize,
+}
+
+impl RecordStage for BlockStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.seen += 1;
+        if self.seen.is_multiple_of(self.n) {
+            vec![record, Record::new()]
+        } else {
+            vec![record]
+        }
+    }
+
+    fn name(&self) -> &str {
+        "BLOCK"
+    }
+}
+
+/// SORTITEMS pos,len,width - sorts the fixed-width items within a field.
+pub struct SortItemsStage {
+    pos: usize,
+    len: usize,
+    item_width: usize,
+}
+
+impl RecordStage for SortItemsStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(sort_items(&record, self.pos, self.len, self.item_width))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "SORTITEMS"
+    }
+}
+
+/// SEGMENT n - emits a numbered header before each group of n records.
+pub struct SegmentStage {
+    n: usize,
+    seen: usize,
+}
+
+impl RecordStage for SegmentStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let index = self.seen;
+        self.seen += 1;
+        if index.is_multiple_of(self.n) {
+            vec![segment_header(index / self.n + 1), record]
+        } else {
+            vec![record]
+        }
+    }
+
+    fn name(&self) -> &str {
+        "SEGMENT"
+    }
+}
+
+/// INSERT EVERY n /text/ [TRAILING] - emits a text record after every nth
+/// record.
+///
+/// Whether the nth record is also the last is unknown until flush, so the
+/// text is held back and emitted ahead of the next record (or at flush
+/// with `trailing`).
+pub struct InsertEveryStage {
+    n: usize,
+    text: String,
+    trailing: bool,
+    seen: usize,
+}
+
+impl InsertEveryStage {
+    fn pending(&self) -> bool {
+        self.seen > 0 && self.seen.is_multiple_of(self.n)
+    }
+}
+
+impl RecordStage for InsertEveryStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let mut output = Vec::with_capacity(2);
+        if self.pending() {
+            output.push(Record::from_str(&self.text));
+        }
+        self.seen += 1;
+        output.push(record);
+        output
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        let output = if self.trailing && self.pending() {
+            vec![Record::from_str(&self.text)]
+        } else {
+            vec![]
+        };
+        self.seen = 0;
+        output
+    }
+
+    fn name(&self) -> &str {
+        "INSERT"
+    }
+}
+
+/// APPLY name - rewrites each record with a named transform.
+pub struct ApplyStage {
+    transform: TransformFn,
+}
+
+impl RecordStage for ApplyStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(Record::from_str(&(self.transform)(
+            record.as_str().trim_end(),
+        )))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "APPLY"
+    }
+}
+
+/// CHOP width - splits each record's content into width-column records.
+pub struct ChopStage {
+    width: usize,
+}
+
+impl RecordStage for ChopStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        chop_record(&record, self.width)
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "CHOP"
+    }
+}
+
+/// WRAP width [CONT col] - word-wraps each record's content into
+/// width-column records.
+pub struct WrapStage {
+    width: usize,
+    cont_col: Option<usize>,
+}
+
+impl RecordStage for WrapStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        wrap_record(&record, self.width, self.cont_col)
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "WRAP"
+    }
+}
+
+/// HOLE - discards all input, outputs nothing.
+///
+/// With `count` set (HOLE COUNT), emits a `DROPPED=n` summary on flush.
+pub struct HoleStage {
+    count: bool,
+    dropped: usize,
+}
+
+impl RecordStage for HoleStage {
+    fn process(&mut self, _record: Record) -> Vec<Record> {
+        self.dropped += 1;
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        if self.count {
+            vec![Record::from_str(&format!("DROPPED={}", self.dropped))]
+        } else {
+            vec![]
+        }
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        // The DROPPED= count is a total over all records
+        self.count
+    }
+
+    fn name(&self) -> &str {
+        "HOLE"
+    }
+}
+
+/// JUSTIFY pos,len RIGHT|LEFT - re-justifies a field within its columns.
+pub struct JustifyStage {
+    pos: usize,
+    len: usize,
+    side: JustifySide,
+    fill: char,
+}
+
+impl RecordStage for JustifyStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, mut record: Record) -> Option<Record> {
+        let field = record.field(self.pos, self.len);
+        let justified = self.side.justify(field, field.len(), self.fill);
+        record.set_field(self.pos, self.len, &justified);
+        Some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "JUSTIFY"
+    }
+}
+
+/// NORM pos,len - rewrites a numeric field as a canonical integer.
+pub struct NormStage {
+    pos: usize,
+    len: usize,
+}
+
+impl RecordStage for NormStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(norm_record(&record, self.pos, self.len))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "NORM"
+    }
+}
+
+/// ASSERT pos,len NUMERIC|ALPHA - passes records whose field has the
+/// required type; flags the rest with `*ERR*`, or drops them when strict.
+pub struct AssertStage {
+    pos: usize,
+    len: usize,
+    kind: FieldKind,
+    strict: bool,
+}
+
+impl RecordStage for AssertStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        if self.kind.check(&record, self.pos, self.len) {
+            Some(record)
+        } else if self.strict {
+            None
+        } else {
+            Some(flag_record(&record))
+        }
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "ASSERT"
+    }
+}
+
+/// VERIFY pos,len TYPE; ... - passes records whose fields have the given
+/// types and replaces the rest with one diagnostic per failed check.
+pub struct VerifyStage {
+    checks: Vec<(usize, usize, FieldKind)>,
+}
+
+impl RecordStage for VerifyStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        verify_record(&record, &self.checks)
+    }
+
+    fn max_fanout(&self) -> usize {
+        self.checks.len().max(1)
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "VERIFY"
+    }
+}
+
+/// EXTRACT /marker/ - keeps only the text following a marker.
+pub struct ExtractStage {
+    marker: String,
+    keep_unmatched: bool,
+}
+
+impl RecordStage for ExtractStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        match record.as_str().find(self.marker.as_str()) {
+            Some(idx) => Some(Record::from_str(
+                &record.as_str()[idx + self.marker.len()..],
+            )),
+            None if self.keep_unmatched => Some(Record::new()),
+            None => None,
+        }
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "EXTRACT"
+    }
+}
+
+/// LENGTH min,max - keeps records whose trimmed character count is in range.
+pub struct LengthStage {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl RecordStage for LengthStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        let n = record.as_str().trim_end().chars().count();
+        (n >= self.min && self.max.is_none_or(|max| n <= max)).then_some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "LENGTH"
+    }
+}
+
+/// FREQ pos,len - tallies distinct field values, emits them on flush.
+pub struct FreqStage {
+    pos: usize,
+    len: usize,
+    counts: HashMap<String, usize>,
+}
+
+impl RecordStage for FreqStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let value = record.field(self.pos, self.len).trim().to_string();
+        *self.counts.entry(value).or_default() += 1;
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        frequency_records(std::mem::take(&mut self.counts))
+    }
+
+    fn name(&self) -> &str {
+        "FREQ"
+    }
+}
+
+/// CROSSTAB pos,len BY pos,len - counts by row and column value, emitted
+/// as a table at flush.
+pub struct CrosstabStage {
+    row_field: (usize, usize),
+    col_field: (usize, usize),
+    counts: HashMap<String, HashMap<String, usize>>,
+}
+
+impl RecordStage for CrosstabStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let (row_pos, row_len) = self.row_field;
+        let (col_pos, col_len) = self.col_field;
+        let row = record.field(row_pos, row_len).trim().to_string();
+        let col = record.field(col_pos, col_len).trim().to_string();
+        *self.counts.entry(row).or_default().entry(col).or_default() += 1;
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        crosstab_records(std::mem::take(&mut self.counts))
+    }
+
+    fn name(&self) -> &str {
+        "CROSSTAB"
+    }
+}
+
+/// BUILD pos,len /lit/ ... - assembles a record from fields and literals.
+pub struct BuildStage {
+    items: Vec<BuildItem>,
+}
+
+impl RecordStage for BuildStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, record: Record) -> Option<Record> {
+        Some(build_record(&record, &self.items))
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        "BUILD"
+    }
+}
+
+/// ZIP - pairs consecutive records side by side.
+pub struct ZipStage {
+    pending: Option<Record>,
+}
+
+impl RecordStage for ZipStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        match self.pending.take() {
+            Some(left) => vec![zip_pair(&left, Some(&record))],
+            None => {
+                self.pending = Some(record);
+                vec![]
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.pending
+            .take()
+            .map(|left| zip_pair(&left, None))
+            .into_iter()
+            .collect()
+    }
+
+    fn name(&self) -> &str {
+        "ZIP"
+    }
+}
+
+/// PAGE n,size - keeps one page of records, like SKIP then TAKE.
+pub struct PageStage {
+    /// Records to skip before the page starts.
+    start: usize,
+    size: usize,
+    seen: usize,
+}
+
+impl RecordStage for PageStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let index = self.seen;
+        self.seen += 1;
+        if index >= self.start && index - self.start < self.size {
+            vec![record]
+        } else {
+            vec![]
+        }
+    }
+
+    fn name(&self) -> &str {
+        "PAGE"
+    }
+}
+
+/// Per-record function boxed by a [`ClosureStage`].
+pub type RecordFn = Box<dyn FnMut(Record) -> Vec<Record>>;
+
+/// A stage backed by arbitrary Rust code instead of a DSL command.
+///
+/// Use this for logic the DSL cannot express. Closure stages cannot be
+/// parsed from DSL text, so they never appear in `specs/` equivalence
+/// tests, but they still take part in flush propagation and need a name
+/// for debug traces.
+///
+/// A closure stage made with [`new`](Self::new) reports itself
+/// order-dependent, since the closure may keep state between records; make
+/// one with [`stateless`](Self::stateless) to run it through
+/// `execute_rat_parallel`.
+pub struct ClosureStage {
+    name: String,
+    f: RecordFn,
+    stateless: bool,
+}
+
+impl ClosureStage {
+    /// Create a closure stage. `name` is shown in traces and timings.
+    pub fn new(name: impl Into<String>, f: impl FnMut(Record) -> Vec<Record> + 'static) -> Self {
+        Self {
+            name: name.into(),
+            f: Box::new(f),
+            stateless: false,
+        }
+    }
+
+    /// Create a closure stage whose output for a record depends only on
+    /// that record, so it may run on separate shards of the input.
+    pub fn stateless(
+        name: impl Into<String>,
+        f: impl FnMut(Record) -> Vec<Record> + 'static,
+    ) -> Self {
+        Self {
+            stateless: true,
+            ..Self::new(name, f)
+        }
+    }
+}
+
+impl RecordStage for ClosureStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        (self.f)(record)
+    }
+
+    fn is_order_dependent(&self) -> bool {
+        !self.stateless
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// RESEQ pos,len start,step - overwrites a field with a sequence number.
+pub struct ReseqStage {
+    pos: usize,
+    len: usize,
+    next: i64,
+    step: i64,
+    fill: char,
+}
+
+impl RecordStage for ReseqStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_one(record).into_iter().collect()
+    }
+
+    fn process_one(&mut self, mut record: Record) -> Option<Record> {
+        record.set_field_i64_padded(self.pos, self.len, self.next, self.fill);
+        self.next = self.next.wrapping_add(self.step);
+        Some(record)
+    }
+
+    fn max_fanout(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "RESEQ"
+    }
+}
+
+/// A buffered TOPN candidate, ordered by field value then arrival.
+///
+/// Higher values rank higher; among equal values the earlier record ranks
+/// higher, matching the batch executor's stable sort.
+struct Ranked {
+    value: Option<i64>,
+    seq: Reverse<usize>,
+    record: Record,
+}
+
+impl Ranked {
+    fn key(&self) -> (Option<i64>, Reverse<usize>) {
+        (self.value, self.seq)
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// TOPN n pos,len - keeps the n records with the largest numeric field.
+///
+/// Holds at most `n` records in a min-heap, evicting the lowest-ranked
+/// record when a better one arrives.
+pub struct TopNStage {
+    n: usize,
+    pos: usize,
+    len: usize,
+    seen: usize,
+    heap: BinaryHeap<Reverse<Ranked>>,
+}
+
+impl RecordStage for TopNStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        let ranked = Ranked {
+            value: record.field_i64(self.pos, self.len),
+            seq: Reverse(self.seen),
+            record,
+        };
+        self.seen += 1;
+        if self.heap.len() < self.n {
+            self.heap.push(Reverse(ranked));
+        } else if self
+            .heap
+            .peek()
+            .is_some_and(|Reverse(lowest)| ranked > *lowest)
+        {
+            self.heap.pop();
+            self.heap.push(Reverse(ranked));
+        }
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        // Ascending order of Reverse<Ranked> is descending rank
+        std::mem::take(&mut self.heap)
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ranked)| ranked.record)
+            .collect()
+    }
+
+    fn name(&self) -> &str {
+        "TOPN"
+    }
+}
+
+/// SORT pos,len [ASC|DESC] [NUM]; ... - buffers all records, emits them
+/// sorted on flush.
+///
+/// Each buffered record keeps its input line tag, so provenance survives
+/// the reordering.
+pub struct SortStage {
+    keys: Vec<SortKey>,
+    buffer: Vec<(Record, Option<usize>)>,
+}
+
+impl RecordStage for SortStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.process_tagged(record, None);
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        self.flush_tagged().into_iter().map(|(r, _)| r).collect()
+    }
+
+    fn process_tagged(
+        &mut self,
+        record: Record,
+        line: Option<usize>,
+    ) -> Vec<(Record, Option<usize>)> {
+        self.buffer.push((record, line));
+        vec![]
+    }
+
+    fn flush_tagged(&mut self) -> Vec<(Record, Option<usize>)> {
+        let mut records = std::mem::take(&mut self.buffer);
+        records.sort_by(|(a, _), (b, _)| compare_by_keys(a, b, &self.keys));
+        records
+    }
+
+    fn name(&self) -> &str {
+        "SORT"
+    }
+}
+
+/// SHUFFLE seed - buffers all records, emits them in seeded random order on flush.
+pub struct ShuffleStage {
+    seed: u64,
+    buffer: Vec<Record>,
+}
+
+impl RecordStage for ShuffleStage {
+    fn process(&mut self, record: Record) -> Vec<Record> {
+        self.buffer.push(record);
+        vec![]
+    }
+
+    fn flush(&mut self) -> Vec<Record> {
+        let mut records = std::mem::take(&mut self.buffer);
+        shuffle_records(&mut records, self.seed);
+        records
+    }
+
+    fn name(&self) -> &str {
+        "SHUFFLE"
+    }
+}
+
+// ---------------------------------------------------------------------------
 // Factory
 // ---------------------------------------------------------------------------
 
@@ -344,21 +2143,111 @@ pub fn command_to_record_stage(cmd: &Command) -> Box<dyn RecordStage> {
             len: *len,
             value: value.clone(),
         }),
-        Command::Select { fields } => Box::new(SelectStage {
+        Command::FilterRange {
+            pos,
+            len,
+            low,
+            high,
+        } => Box::new(FilterRangeStage {
+            pos: *pos,
+            len: *len,
+            low: low.clone(),
+            high: high.clone(),
+        }),
+        Command::FilterFieldCmp { left, right, op } => Box::new(FilterFieldCmpStage {
+            left: *left,
+            right: *right,
+            op: *op,
+        }),
+        Command::Omit { filter } => Box::new(OmitStage {
+            filter: (**filter).clone(),
+        }),
+        Command::Route {
+            filter,
+            to,
+            otherwise,
+        } => Box::new(RouteStage {
+            filter: (**filter).clone(),
+            to: to.clone(),
+            otherwise: otherwise.clone(),
+        }),
+        Command::OnChange { pos, len } => Box::new(OnChangeStage {
+            pos: *pos,
+            len: *len,
+            last: None,
+        }),
+        Command::Select { fields, separator } => Box::new(SelectStage {
+            fields: fields.clone(),
+            separator: separator.clone(),
+        }),
+        Command::Field { pos, len, trim } => Box::new(FieldStage {
+            pos: *pos,
+            len: *len,
+            trim: *trim,
+        }),
+        Command::ToJson { fields } => Box::new(ToJsonStage {
             fields: fields.clone(),
         }),
         Command::Take { n } => Box::new(TakeStage { n: *n, seen: 0 }),
         Command::Skip { n } => Box::new(SkipStage { n: *n, seen: 0 }),
-        Command::Locate { pattern, field } => Box::new(LocateStage {
+        Command::Locate {
+            pattern,
+            field,
+            trim,
+        } => Box::new(LocateStage {
             pattern: pattern.clone(),
             field: *field,
+            trim: *trim,
+        }),
+        Command::LocateMulti {
+            patterns,
+            mode,
+            field,
+        } => Box::new(LocateMultiStage {
+            patterns: patterns.clone(),
+            mode: *mode,
+            field: *field,
         }),
         Command::Nlocate { pattern, field } => Box::new(NlocateStage {
             pattern: pattern.clone(),
             field: *field,
         }),
+        Command::Context {
+            pattern,
+            before,
+            after,
+        } => Box::new(ContextStage {
+            pattern: pattern.clone(),
+            before: *before,
+            after: *after,
+            recent: VecDeque::with_capacity(*before),
+            trailing: 0,
+        }),
         Command::Count => Box::new(CountStage { count: 0 }),
-        Command::Change { old, new } => Box::new(ChangeStage {
+        Command::Hash => Box::new(HashStage {
+            hasher: RecordHasher::new(),
+        }),
+        Command::Stats { passthrough } => Box::new(StatsStage {
+            passthrough: *passthrough,
+            stats: LengthStats::default(),
+        }),
+        Command::Change {
+            old,
+            new,
+            limit,
+            repeat,
+        } => Box::new(ChangeStage {
+            old: old.clone(),
+            new: new.clone(),
+            limit: *limit,
+            repeat: *repeat,
+        }),
+        Command::ChangeWhere {
+            condition,
+            old,
+            new,
+        } => Box::new(ChangeWhereStage {
+            condition: (**condition).clone(),
             old: old.clone(),
             new: new.clone(),
         }),
@@ -366,11 +2255,201 @@ pub fn command_to_record_stage(cmd: &Command) -> Box<dyn RecordStage> {
             text: text.clone(),
             emitted: false,
         }),
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => Box::new(GenerateStage {
+            count: *count,
+            template: template.clone(),
+            seed: *seed,
+            emitted: false,
+        }),
         Command::Upper => Box::new(UpperStage),
         Command::Lower => Box::new(LowerStage),
-        Command::Reverse => Box::new(ReverseStage),
+        Command::Reverse { full } => Box::new(ReverseStage { full: *full }),
         Command::Duplicate { n } => Box::new(DuplicateStage { n: *n }),
-        Command::Hole => Box::new(HoleStage),
+        Command::Block { n } => Box::new(BlockStage { n: *n, seen: 0 }),
+        Command::Segment { n } => Box::new(SegmentStage { n: *n, seen: 0 }),
+        Command::SortItems {
+            pos,
+            len,
+            item_width,
+        } => Box::new(SortItemsStage {
+            pos: *pos,
+            len: *len,
+            item_width: *item_width,
+        }),
+        Command::Chop { width } => Box::new(ChopStage { width: *width }),
+        Command::Apply { transform, .. } => Box::new(ApplyStage {
+            transform: *transform,
+        }),
+        Command::Header { template } => Box::new(HeaderStage {
+            template: template.clone(),
+            buffer: Vec::new(),
+        }),
+        // Parsing expands INCLUDE; execute_parsed_rat rejects any left over
+        Command::Disabled { .. } | Command::Include { .. } => Box::new(DisabledStage),
+        Command::Hole { count } => Box::new(HoleStage {
+            count: *count,
+            dropped: 0,
+        }),
+        Command::Justify {
+            pos,
+            len,
+            side,
+            fill,
+        } => Box::new(JustifyStage {
+            pos: *pos,
+            len: *len,
+            side: *side,
+            fill: *fill,
+        }),
+        Command::Assert {
+            pos,
+            len,
+            kind,
+            strict,
+        } => Box::new(AssertStage {
+            pos: *pos,
+            len: *len,
+            kind: *kind,
+            strict: *strict,
+        }),
+        Command::Verify { checks } => Box::new(VerifyStage {
+            checks: checks.clone(),
+        }),
+        Command::Extract {
+            marker,
+            keep_unmatched,
+        } => Box::new(ExtractStage {
+            marker: marker.clone(),
+            keep_unmatched: *keep_unmatched,
+        }),
+        Command::Length { min, max } => Box::new(LengthStage {
+            min: *min,
+            max: *max,
+        }),
+        Command::Freq { pos, len } => Box::new(FreqStage {
+            pos: *pos,
+            len: *len,
+            counts: HashMap::new(),
+        }),
+        Command::Crosstab {
+            row_field,
+            col_field,
+        } => Box::new(CrosstabStage {
+            row_field: *row_field,
+            col_field: *col_field,
+            counts: HashMap::new(),
+        }),
+        Command::Build { items } => Box::new(BuildStage {
+            items: items.clone(),
+        }),
+        Command::Zip => Box::new(ZipStage { pending: None }),
+        Command::Reseq {
+            pos,
+            len,
+            start,
+            step,
+            fill,
+        } => Box::new(ReseqStage {
+            pos: *pos,
+            len: *len,
+            next: *start,
+            step: *step,
+            fill: *fill,
+        }),
+        Command::Sort { keys } => Box::new(SortStage {
+            keys: keys.clone(),
+            buffer: Vec::new(),
+        }),
+        Command::Expand { pos, len, clear } => Box::new(ExpandStage {
+            pos: *pos,
+            len: *len,
+            clear: *clear,
+        }),
+        Command::DateFmt {
+            pos,
+            len,
+            from,
+            to,
+            flag,
+        } => Box::new(DateFmtStage {
+            pos: *pos,
+            len: *len,
+            from: *from,
+            to: *to,
+            flag: *flag,
+        }),
+        Command::Decode {
+            pos,
+            len,
+            map,
+            default,
+        } => Box::new(DecodeStage {
+            pos: *pos,
+            len: *len,
+            map: map.clone().into_iter().collect(),
+            default: default.clone(),
+        }),
+        Command::Default {
+            pos,
+            len,
+            value,
+            justify,
+        } => Box::new(DefaultStage {
+            pos: *pos,
+            len: *len,
+            value: value.clone(),
+            justify: *justify,
+        }),
+        Command::InsertEvery { n, text, trailing } => Box::new(InsertEveryStage {
+            n: *n,
+            text: text.clone(),
+            trailing: *trailing,
+            seen: 0,
+        }),
+        Command::Norm { pos, len } => Box::new(NormStage {
+            pos: *pos,
+            len: *len,
+        }),
+        Command::Pivot {
+            key,
+            value,
+            group,
+            columns,
+        } => Box::new(PivotStage {
+            key: *key,
+            value: *value,
+            group: *group,
+            columns: columns.clone(),
+            current: Vec::new(),
+        }),
+        Command::Mask { fields, fill } => Box::new(MaskStage {
+            fields: fields.clone(),
+            fill: *fill,
+        }),
+        Command::Wrap { width, cont_col } => Box::new(WrapStage {
+            width: *width,
+            cont_col: *cont_col,
+        }),
+        Command::Shuffle { seed } => Box::new(ShuffleStage {
+            seed: *seed,
+            buffer: Vec::new(),
+        }),
+        Command::TopN { n, pos, len } => Box::new(TopNStage {
+            n: *n,
+            pos: *pos,
+            len: *len,
+            seen: 0,
+            heap: BinaryHeap::new(),
+        }),
+        Command::Page { page, size } => Box::new(PageStage {
+            start: (page - 1).saturating_mul(*size),
+            size: *size,
+            seen: 0,
+        }),
     }
 }
 
@@ -408,6 +2487,46 @@ mod tests {
         assert!(stage.process(r).is_empty());
     }
 
+    #[test]
+    fn test_filter_range_numeric_and_blank() {
+        let mut stage = FilterRangeStage {
+            pos: 0,
+            len: 8,
+            low: "00050000".to_string(),
+            high: "00070000".to_string(),
+        };
+        assert_eq!(stage.process(Record::from_str("00050000")).len(), 1);
+        assert_eq!(stage.process(Record::from_str("65000")).len(), 1);
+        assert!(stage.process(Record::from_str("00075000")).is_empty());
+        assert!(stage.process(Record::from_str("")).is_empty());
+    }
+
+    #[test]
+    fn test_filter_field_cmp_stage() {
+        let mut stage = FilterFieldCmpStage {
+            left: (0, 5),
+            right: (6, 5),
+            op: CompareOp::Gt,
+        };
+        assert_eq!(stage.process(Record::from_str("00010   9")).len(), 1);
+        assert!(stage.process(Record::from_str("9     00010")).is_empty());
+        assert!(stage.process(Record::from_str("7     7")).is_empty());
+    }
+
+    #[test]
+    fn test_omit_stage_drops_matches() {
+        let mut stage = command_to_record_stage(&Command::Omit {
+            filter: Box::new(Command::FilterEq {
+                pos: 0,
+                len: 5,
+                value: "SALES".to_string(),
+            }),
+        });
+        assert!(stage.process(Record::from_str("SALES")).is_empty());
+        assert_eq!(stage.process(Record::from_str("ADMIN")).len(), 1);
+        assert_eq!(stage.name(), "OMIT");
+    }
+
     #[test]
     fn test_filter_ne_pass() {
         let mut stage = FilterNeStage {
@@ -434,6 +2553,7 @@ mod tests {
     fn test_select_stage() {
         let mut stage = SelectStage {
             fields: vec![(0, 5, 0), (10, 5, 5)],
+            separator: None,
         };
         let out = stage.process(Record::from_str("ABCDE     FGHIJ"));
         assert_eq!(&out[0].as_str()[..10], "ABCDEFGHIJ");
@@ -460,6 +2580,7 @@ mod tests {
         let mut stage = LocateStage {
             pattern: "SALES".to_string(),
             field: None,
+            trim: false,
         };
         assert_eq!(
             stage
@@ -479,6 +2600,7 @@ mod tests {
         let mut stage = LocateStage {
             pattern: "SALES".to_string(),
             field: Some((18, 10)),
+            trim: false,
         };
         assert_eq!(
             stage
@@ -493,6 +2615,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_locate_field_trailing_space() {
+        let record = || Record::from_str("SMITH   JOHN      SALES     00050000");
+        let mut padded = LocateStage {
+            pattern: "SALES ".to_string(),
+            field: Some((18, 10)),
+            trim: false,
+        };
+        let mut trimmed = LocateStage {
+            pattern: "SALES ".to_string(),
+            field: Some((18, 10)),
+            trim: true,
+        };
+        assert_eq!(padded.process(record()).len(), 1);
+        assert!(trimmed.process(record()).is_empty());
+    }
+
+    #[test]
+    fn test_context_stage_merges_adjacent_matches() {
+        let mut stage = command_to_record_stage(&Command::Context {
+            pattern: "ERROR".to_string(),
+            before: 1,
+            after: 1,
+        });
+        let mut out = Vec::new();
+        for s in ["a", "b", "c ERROR", "d ERROR", "e", "f", "g", "h ERROR"] {
+            out.extend(stage.process(Record::from_str(s)));
+        }
+        out.extend(stage.flush());
+        let out: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(out, vec!["b", "c ERROR", "d ERROR", "e", "g", "h ERROR"]);
+    }
+
+    #[test]
+    fn test_onchange_stage() {
+        let mut stage = OnChangeStage {
+            pos: 0,
+            len: 1,
+            last: None,
+        };
+        let mut out = Vec::new();
+        for s in ["A 1", "A 2", "B 3", "B 4", "A 5"] {
+            out.extend(stage.process(Record::from_str(s)));
+        }
+        let out: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(out, vec!["A 1", "B 3", "A 5"]);
+        // A flushed stage starts over: the next record passes again
+        assert!(stage.flush().is_empty());
+        assert_eq!(stage.process(Record::from_str("A 6")).len(), 1);
+    }
+
     #[test]
     fn test_nlocate_stage() {
         let mut stage = NlocateStage {
@@ -536,11 +2709,25 @@ mod tests {
         let mut stage = ChangeStage {
             old: "HELLO".to_string(),
             new: "WORLD".to_string(),
+            repeat: false,
+            limit: None,
         };
         let out = stage.process(Record::from_str("HELLO THERE"));
         assert!(out[0].as_str().starts_with("WORLD THERE"));
     }
 
+    #[test]
+    fn test_change_stage_limit() {
+        let mut stage = ChangeStage {
+            old: "A".to_string(),
+            new: "B".to_string(),
+            repeat: false,
+            limit: Some(2),
+        };
+        let out = stage.process(Record::from_str("A-A-A"));
+        assert_eq!(out[0].as_str().trim_end(), "B-B-A");
+    }
+
     #[test]
     fn test_literal_with_input() {
         let mut stage = LiteralStage {
@@ -570,11 +2757,36 @@ mod tests {
         assert_eq!(flushed[0].as_str().trim(), "HEADER");
     }
 
+    #[test]
+    fn test_generate_stage_prepends_once() {
+        let mut stage = GenerateStage {
+            count: 2,
+            template: "G{SEQ:2}".to_string(),
+            seed: 0,
+            emitted: false,
+        };
+        let out: Vec<String> = stage
+            .process(Record::from_str("A"))
+            .iter()
+            .chain(&stage.process(Record::from_str("B")))
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(out, ["G01", "G02", "A", "B"]);
+        assert!(stage.flush().is_empty());
+    }
+
     #[test]
     fn test_upper_stage() {
         let mut stage = UpperStage;
         let out = stage.process(Record::from_str("hello world"));
         assert_eq!(out[0].as_str().trim(), "HELLO WORLD");
+
+        // Every column keeps its place, trailing spaces included
+        let mut record = Record::from_str("a");
+        record.set_field(79, 1, "b");
+        let out = stage.process(record);
+        assert_eq!(out[0].as_bytes().len(), 80);
+        assert_eq!(out[0].field(0, 80), format!("A{}B", " ".repeat(78)));
     }
 
     #[test]
@@ -586,11 +2798,20 @@ mod tests {
 
     #[test]
     fn test_reverse_stage() {
-        let mut stage = ReverseStage;
+        let mut stage = ReverseStage { full: false };
         let out = stage.process(Record::from_str("ABC"));
         assert_eq!(out[0].as_str().trim(), "CBA");
     }
 
+    #[test]
+    fn test_reverse_full_stage_round_trip() {
+        let mut stage = ReverseStage { full: true };
+        let original = Record::from_str(" A B ");
+        let once = stage.process(original.clone()).remove(0);
+        assert_eq!(once.as_str().trim_start(), "B A ");
+        assert_eq!(stage.process(once), vec![original]);
+    }
+
     #[test]
     fn test_duplicate_stage() {
         let mut stage = DuplicateStage { n: 3 };
@@ -603,11 +2824,27 @@ mod tests {
 
     #[test]
     fn test_hole_stage() {
-        let mut stage = HoleStage;
+        let mut stage = HoleStage {
+            count: false,
+            dropped: 0,
+        };
         assert!(stage.process(Record::from_str("A")).is_empty());
         assert!(stage.flush().is_empty());
     }
 
+    #[test]
+    fn test_hole_count_stage() {
+        let mut stage = HoleStage {
+            count: true,
+            dropped: 0,
+        };
+        assert!(stage.process(Record::from_str("A")).is_empty());
+        assert!(stage.process(Record::from_str("B")).is_empty());
+        let flushed = stage.flush();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].as_str().trim(), "DROPPED=2");
+    }
+
     #[test]
     fn test_factory_upper() {
         let cmd = Command::Upper;
@@ -636,4 +2873,619 @@ mod tests {
         let out = stage.process(Record::from_str("X"));
         assert_eq!(out.len(), 2);
     }
+
+    #[test]
+    fn test_justify_stage() {
+        let mut right = JustifyStage {
+            pos: 5,
+            len: 8,
+            side: JustifySide::Right,
+            fill: ' ',
+        };
+        let out = right.process(Record::from_str("ABCDE42      X"));
+        assert_eq!(out[0].as_str().trim_end(), "ABCDE      42X");
+
+        let mut left = JustifyStage {
+            pos: 5,
+            len: 8,
+            side: JustifySide::Left,
+            fill: ' ',
+        };
+        let out = left.process(Record::from_str("ABCDE   42   X"));
+        assert_eq!(out[0].as_str().trim_end(), "ABCDE42      X");
+    }
+
+    #[test]
+    fn test_extract_stage() {
+        let mut stage = ExtractStage {
+            marker: "id=".to_string(),
+            keep_unmatched: true,
+        };
+        let out = stage.process(Record::from_str("id=42 ok"));
+        assert_eq!(out[0].as_str().trim_end(), "42 ok");
+        let out = stage.process(Record::from_str("name=x id=7"));
+        assert_eq!(out[0].as_str().trim_end(), "7");
+        let out = stage.process(Record::from_str("nothing"));
+        assert!(out[0].is_blank());
+
+        let mut drop = ExtractStage {
+            marker: "id=".to_string(),
+            keep_unmatched: false,
+        };
+        assert!(drop.process(Record::from_str("nothing")).is_empty());
+    }
+
+    #[test]
+    fn test_length_stage_boundaries() {
+        let mut stage = LengthStage {
+            min: 2,
+            max: Some(3),
+        };
+        assert!(stage.process(Record::from_str("a")).is_empty());
+        assert_eq!(stage.process(Record::from_str("ab")).len(), 1);
+        assert_eq!(stage.process(Record::from_str("abc")).len(), 1);
+        assert!(stage.process(Record::from_str("abcd")).is_empty());
+        // Trailing padding is not counted
+        assert_eq!(stage.process(Record::from_str("ab   ")).len(), 1);
+    }
+
+    #[test]
+    fn test_freq_stage() {
+        let mut stage = FreqStage {
+            pos: 0,
+            len: 3,
+            counts: HashMap::new(),
+        };
+        for s in ["bb", "a", "bb", "c", "a"] {
+            assert!(stage.process(Record::from_str(s)).is_empty());
+        }
+        let out: Vec<String> = stage
+            .flush()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(out, vec!["a 2", "bb 2", "c 1"]);
+    }
+
+    #[test]
+    fn test_order_dependent_stages() {
+        let dependent = |cmd: Command| command_to_record_stage(&cmd).is_order_dependent();
+        assert!(!dependent(Command::Upper));
+        assert!(!dependent(Command::Hole { count: false }));
+        assert!(dependent(Command::Hole { count: true }));
+        assert!(dependent(Command::Take { n: 1 }));
+        assert!(dependent(Command::Count));
+        assert!(dependent(Command::Sort { keys: vec![] }));
+        assert!(ClosureStage::new("ID", |r| vec![r]).is_order_dependent());
+        assert!(!ClosureStage::stateless("ID", |r| vec![r]).is_order_dependent());
+    }
+
+    #[test]
+    fn test_crosstab_stage() {
+        let mut stage = CrosstabStage {
+            row_field: (0, 1),
+            col_field: (2, 1),
+            counts: HashMap::new(),
+        };
+        for s in ["A x", "B y", "A x", "A y"] {
+            assert!(stage.process(Record::from_str(s)).is_empty());
+        }
+        let out: Vec<String> = stage
+            .flush()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(out, vec!["  x y", "A 2 1", "B 0 1"]);
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_build_stage() {
+        let mut stage = BuildStage {
+            items: vec![
+                BuildItem::Field { pos: 0, len: 5 },
+                BuildItem::Literal(",".to_string()),
+                BuildItem::Field { pos: 5, len: 5 },
+            ],
+        };
+        let out = stage.process(Record::from_str("AB   CD   "));
+        assert_eq!(out[0].as_str().trim_end(), "AB,CD");
+    }
+
+    #[test]
+    fn test_zip_stage_pairs_and_flushes_odd_record() {
+        let mut stage = ZipStage { pending: None };
+        assert!(stage.process(Record::from_str("A")).is_empty());
+        let out = stage.process(Record::from_str("B"));
+        assert_eq!(out[0].field(0, 40).trim_end(), "A");
+        assert_eq!(out[0].field(40, 40).trim_end(), "B");
+
+        assert!(stage.process(Record::from_str("C")).is_empty());
+        let out = stage.flush();
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].as_str().trim_end(), "C");
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_page_stage() {
+        let mut stage = PageStage {
+            start: 3,
+            size: 3,
+            seen: 0,
+        };
+        let kept: Vec<String> = (1..=8)
+            .flat_map(|i| stage.process(Record::from_str(&i.to_string())))
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(kept, vec!["4", "5", "6"]);
+    }
+
+    #[test]
+    fn test_closure_stage() {
+        let mut stage = ClosureStage::new("DOUBLE", |r: Record| vec![r.clone(), r]);
+        assert_eq!(stage.name(), "DOUBLE");
+        assert_eq!(stage.process(Record::from_str("x")).len(), 2);
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_reseq_stage() {
+        let mut stage = ReseqStage {
+            pos: 0,
+            len: 4,
+            next: 100,
+            step: 5,
+            fill: '0',
+        };
+        let out: Vec<String> = ["xxxx A", "xxxx B", "xxxx C"]
+            .iter()
+            .flat_map(|s| stage.process(Record::from_str(s)))
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(out, vec!["0100 A", "0105 B", "0110 C"]);
+
+        stage.fill = ' ';
+        let out = stage.process(Record::from_str("xxxx D"));
+        assert_eq!(out[0].as_str().trim_end(), " 115 D");
+    }
+
+    #[test]
+    fn test_topn_stage_bounded_and_sorted() {
+        let mut stage = TopNStage {
+            n: 2,
+            pos: 0,
+            len: 1,
+            seen: 0,
+            heap: BinaryHeap::new(),
+        };
+        for s in ["5 a", "x b", "9 c", "5 d", "1 e"] {
+            assert!(stage.process(Record::from_str(s)).is_empty());
+            assert!(stage.heap.len() <= 2);
+        }
+        let out: Vec<String> = stage
+            .flush()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        // The earlier of the two 5s wins the tie
+        assert_eq!(out, vec!["9 c", "5 a"]);
+    }
+
+    #[test]
+    fn test_select_stage_separator() {
+        let mut stage = SelectStage {
+            fields: vec![(0, 5, 0), (10, 5, 6)],
+            separator: Some(",".to_string()),
+        };
+        let out = stage.process(Record::from_str("ABCDE     FGHIJ"));
+        assert_eq!(out[0].as_str().trim_end(), "ABCDE,FGHIJ");
+    }
+
+    #[test]
+    fn test_block_stage_separators() {
+        let mut stage = BlockStage { n: 3, seen: 0 };
+        let sizes: Vec<usize> = (1..=7)
+            .map(|i| stage.process(Record::from_str(&i.to_string())).len())
+            .collect();
+        // Separators follow records 3 and 6 only
+        assert_eq!(sizes, vec![1, 1, 2, 1, 1, 2, 1]);
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_verify_stage_replaces_failing_records() {
+        let mut stage = VerifyStage {
+            checks: vec![(0, 3, FieldKind::Alpha), (4, 3, FieldKind::Numeric)],
+        };
+        let out = stage.process(Record::from_str("ABC 123"));
+        assert_eq!(out[0].as_str().trim_end(), "ABC 123");
+        let out = stage.process(Record::from_str("A1C 1X3"));
+        let texts: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(
+            texts,
+            ["*ERR* 0,3 ALPHA A1C 1X3", "*ERR* 4,3 NUMERIC A1C 1X3"]
+        );
+    }
+
+    #[test]
+    fn test_change_where_stage_passes_non_matching() {
+        let mut stage = ChangeWhereStage {
+            condition: pipelines_rs::parse_commands("FILTER 0,1 = \"A\"").unwrap()[0].clone(),
+            old: "X".to_string(),
+            new: "Y".to_string(),
+        };
+        let out: Vec<Record> = ["AXX", "BXX"]
+            .into_iter()
+            .flat_map(|text| stage.process(Record::from_str(text)))
+            .collect();
+        let texts: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(texts, ["AYY", "BXX"]);
+    }
+
+    #[test]
+    fn test_change_stage_repeat() {
+        let mut stage = ChangeStage {
+            old: "--".to_string(),
+            new: "-".to_string(),
+            limit: None,
+            repeat: true,
+        };
+        let out = stage.try_process(Record::from_str("a----b")).unwrap();
+        assert_eq!(out[0].as_str().trim_end(), "a-b");
+
+        let mut growing = ChangeStage {
+            old: "a".to_string(),
+            new: "ab".to_string(),
+            limit: None,
+            repeat: true,
+        };
+        let expected = change_until_stable(&Record::from_str("a"), "a", "ab", None).unwrap();
+        assert_eq!(expected.len_content(), pipelines_rs::RECORD_WIDTH);
+        let out = growing.try_process(Record::from_str("a")).unwrap();
+        assert_eq!(out, vec![expected.clone()]);
+        assert_eq!(growing.process(Record::from_str("a")), vec![expected]);
+    }
+
+    #[test]
+    fn test_sortitems_stage() {
+        let mut stage = SortItemsStage {
+            pos: 4,
+            len: 12,
+            item_width: 4,
+        };
+        let out = stage.process(Record::from_str("KEY:zzzzmmmmaaaa!"));
+        assert_eq!(out[0].as_str().trim_end(), "KEY:aaaammmmzzzz!");
+    }
+
+    #[test]
+    fn test_segment_stage_headers() {
+        let mut stage = SegmentStage { n: 3, seen: 0 };
+        let out: Vec<String> = (1..=7)
+            .flat_map(|i| stage.process(Record::from_str(&i.to_string())))
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        let headers: Vec<&String> = out.iter().filter(|s| s.starts_with("---")).collect();
+        assert_eq!(
+            headers,
+            [
+                "--- SEGMENT 1 ---",
+                "--- SEGMENT 2 ---",
+                "--- SEGMENT 3 ---"
+            ]
+        );
+        assert_eq!(out.len(), 10);
+        assert_eq!(out[4], "--- SEGMENT 2 ---");
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_assert_stage_flag_and_strict() {
+        let mut flag = AssertStage {
+            pos: 0,
+            len: 5,
+            kind: FieldKind::Numeric,
+            strict: false,
+        };
+        let mut strict = AssertStage {
+            pos: 0,
+            len: 5,
+            kind: FieldKind::Numeric,
+            strict: true,
+        };
+        for (text, ok) in [("00042", true), ("4X2", false), ("-7", true), ("", false)] {
+            let out = flag.process(Record::from_str(text));
+            assert_eq!(out[0].as_str().starts_with("*ERR*"), !ok, "{text:?}");
+            assert_eq!(
+                strict.process(Record::from_str(text)).len(),
+                usize::from(ok)
+            );
+        }
+    }
+
+    #[test]
+    fn test_chop_stage() {
+        let mut stage = ChopStage { width: 4 };
+        let out = stage.process(Record::from_str("ABCDEFGHIJ"));
+        let texts: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(texts, vec!["ABCD", "EFGH", "IJ"]);
+    }
+
+    #[test]
+    fn test_header_stage_count() {
+        let mut stage = HeaderStage {
+            template: "{COUNT} ROWS".to_string(),
+            buffer: Vec::new(),
+        };
+        assert!(stage.process(Record::from_str("A")).is_empty());
+        assert!(stage.process(Record::from_str("B")).is_empty());
+        let out = stage.flush();
+        let texts: Vec<&str> = out.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(texts, vec!["2 ROWS", "A", "B"]);
+    }
+
+    #[test]
+    fn test_apply_stage() {
+        let mut stage = ApplyStage {
+            transform: |s| s.replace(' ', "_"),
+        };
+        let out = stage.process(Record::from_str("A B  C"));
+        assert_eq!(out[0].as_str().trim_end(), "A_B__C");
+    }
+
+    #[test]
+    fn test_field_stage() {
+        let mut stage = FieldStage {
+            pos: 18,
+            len: 10,
+            trim: true,
+        };
+        let out = stage.process(Record::from_str("SMITH   JOHN      SALES     00050000"));
+        assert_eq!(out[0].as_str().trim_end(), "SALES");
+    }
+
+    #[test]
+    fn test_tojson_stage() {
+        let mut stage = ToJsonStage {
+            fields: vec![("Last".to_string(), 0, 8), ("Dept".to_string(), 18, 10)],
+        };
+        let out = stage.process(Record::from_str("SMITH   JOHN      SALES     00050000"));
+        assert_eq!(
+            out[0].as_str().trim_end(),
+            r#"{"Last":"SMITH","Dept":"SALES"}"#
+        );
+    }
+
+    #[test]
+    fn test_locate_multi_stage() {
+        let mut stage = LocateMultiStage {
+            patterns: vec!["ERROR".to_string(), "disk".to_string()],
+            mode: MatchMode::All,
+            field: None,
+        };
+        assert_eq!(stage.process(Record::from_str("ERROR disk full")).len(), 1);
+        assert!(stage.process(Record::from_str("ERROR net down")).is_empty());
+
+        stage.mode = MatchMode::Any;
+        assert_eq!(stage.process(Record::from_str("ERROR net down")).len(), 1);
+        assert!(stage.process(Record::from_str("INFO ok")).is_empty());
+    }
+
+    #[test]
+    fn test_hash_stage_emits_on_flush() {
+        let mut stage = HashStage {
+            hasher: RecordHasher::new(),
+        };
+        assert!(stage.process(Record::from_str("A")).is_empty());
+        let mut expected = RecordHasher::new();
+        expected.update(&Record::from_str("A"));
+        assert_eq!(stage.flush(), vec![expected.to_record()]);
+    }
+
+    #[test]
+    fn test_stats_stage_summarizes_on_flush() {
+        let mut stage = StatsStage {
+            passthrough: true,
+            stats: LengthStats::default(),
+        };
+        assert_eq!(stage.process(Record::from_str("ABC")).len(), 1);
+        assert_eq!(stage.process(Record::from_str("A")).len(), 1);
+        let summary: Vec<String> = stage
+            .flush()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(
+            summary,
+            ["RECORDS=2", "MINLEN=1", "MAXLEN=3", "AVGLEN=2.00"]
+        );
+
+        stage.passthrough = false;
+        assert!(stage.process(Record::from_str("AB")).is_empty());
+    }
+
+    #[test]
+    fn test_expand_stage_counts() {
+        let mut stage = ExpandStage {
+            pos: 4,
+            len: 1,
+            clear: false,
+        };
+        assert!(stage.process(Record::from_str("ZERO0")).is_empty());
+        assert_eq!(stage.process(Record::from_str("ONE 1")).len(), 1);
+        let copies = stage.process(Record::from_str("TRI 3"));
+        assert_eq!(copies.len(), 3);
+        assert!(copies.iter().all(|r| r.as_str().trim_end() == "TRI 3"));
+        assert_eq!(stage.process(Record::from_str("BAD x")).len(), 1);
+    }
+
+    #[test]
+    fn test_datefmt_stage_ymd_to_mdy() {
+        let mut stage = DateFmtStage {
+            pos: 0,
+            len: 8,
+            from: DateFormat::Ymd,
+            to: DateFormat::Mdy,
+            flag: true,
+        };
+        let out = stage.process(Record::from_str("20240115  X"));
+        assert_eq!(out[0].as_str().trim_end(), "01/15/2024X");
+        let out = stage.process(Record::from_str("2024XX15 X"));
+        assert_eq!(out[0].as_str().trim_end(), "*ERR* 2024XX15 X");
+    }
+
+    #[test]
+    fn test_decode_stage_maps_codes() {
+        let mut stage = DecodeStage {
+            pos: 18,
+            len: 10,
+            map: HashMap::from([("ENG".to_string(), "ENGINEER".to_string())]),
+            default: Some("UNKNOWN".to_string()),
+        };
+        let out = stage.process(Record::from_str("JONES   MARY      ENG       00075000"));
+        assert_eq!(out[0].field(18, 10), "ENGINEER  ");
+        let out = stage.process(Record::from_str("WILSON  BOB       MKT       00055000"));
+        assert_eq!(out[0].field(18, 10), "UNKNOWN   ");
+    }
+
+    #[test]
+    fn test_default_stage_fills_only_blank_fields() {
+        let mut stage = DefaultStage {
+            pos: 18,
+            len: 10,
+            value: "UNKNOWN".to_string(),
+            justify: JustifySide::Left,
+        };
+        let out = stage.process(Record::from_str("DOE     JANE                00060000"));
+        assert_eq!(
+            out[0].as_str().trim_end(),
+            "DOE     JANE      UNKNOWN   00060000"
+        );
+        let sales = Record::from_str("SMITH   JOHN      SALES     00050000");
+        assert_eq!(stage.process(sales.clone()), vec![sales]);
+    }
+
+    #[test]
+    fn test_mask_stage_keeps_other_columns() {
+        let mut stage = MaskStage {
+            fields: vec![(28, 8)],
+            fill: '#',
+        };
+        let out = stage.process(Record::from_str("SMITH   JOHN      SALES     00050000 END"));
+        assert_eq!(
+            out[0].as_str().trim_end(),
+            "SMITH   JOHN      SALES     ######## END"
+        );
+    }
+
+    #[test]
+    fn test_wrap_stage_marks_continued_pieces() {
+        let mut stage = WrapStage {
+            width: 20,
+            cont_col: Some(71),
+        };
+        let out = stage.process(Record::from_str(
+            "The quick brown fox jumps over the lazy dog near the riverbank",
+        ));
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[1].field(0, 20).trim_end(), "jumps over the lazy");
+        assert!(out[..3].iter().all(|r| r.field(71, 1) == "+"));
+        assert_eq!(out[3].as_str().trim_end(), "riverbank");
+    }
+
+    #[test]
+    fn test_insert_every_stage_holds_text_until_next_record() {
+        let mut stage = InsertEveryStage {
+            n: 2,
+            text: "---".to_string(),
+            trailing: false,
+            seen: 0,
+        };
+        assert_eq!(stage.process(Record::from_str("1")).len(), 1);
+        assert_eq!(stage.process(Record::from_str("2")).len(), 1);
+        let out = stage.process(Record::from_str("3"));
+        assert_eq!(out, vec![Record::from_str("---"), Record::from_str("3")]);
+        assert_eq!(stage.process(Record::from_str("4")).len(), 1);
+        assert!(stage.flush().is_empty());
+
+        stage.trailing = true;
+        stage.process(Record::from_str("1"));
+        stage.process(Record::from_str("2"));
+        assert_eq!(stage.flush(), vec![Record::from_str("---")]);
+    }
+
+    #[test]
+    fn test_norm_stage() {
+        let mut stage = NormStage { pos: 0, len: 8 };
+        let out = stage.process(Record::from_str("0001234J  tail"));
+        assert_eq!(out[0].as_str().trim_end(), "  -12341  tail");
+    }
+
+    #[test]
+    fn test_pivot_stage_emits_when_group_ends() {
+        let mut stage = PivotStage {
+            key: (0, 4),
+            value: (5, 10),
+            group: None,
+            columns: vec![("NAME".to_string(), 0), ("DEPT".to_string(), 8)],
+            current: Vec::new(),
+        };
+        assert!(stage.process(Record::from_str("NAME SMITH")).is_empty());
+        assert!(stage.process(Record::from_str("DEPT SALES")).is_empty());
+        let out = stage.process(Record::new());
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].as_str().trim_end(), "SMITH   SALES");
+        assert!(stage.process(Record::new()).is_empty());
+        assert!(stage.process(Record::from_str("NAME JONES")).is_empty());
+        let out = stage.flush();
+        assert_eq!(out[0].as_str().trim_end(), "JONES");
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_shuffle_stage_matches_batch_order() {
+        let input: Vec<Record> = (0..10).map(|i| Record::from_str(&i.to_string())).collect();
+        let mut stage = ShuffleStage {
+            seed: 42,
+            buffer: Vec::new(),
+        };
+        for record in input.clone() {
+            assert!(stage.process(record).is_empty());
+        }
+        let mut expected = input;
+        shuffle_records(&mut expected, 42);
+        assert_eq!(stage.flush(), expected);
+        assert!(stage.flush().is_empty());
+    }
+
+    #[test]
+    fn test_sort_stage_multiple_keys() {
+        use pipelines_rs::SortDir;
+        let mut stage = SortStage {
+            keys: vec![
+                SortKey {
+                    pos: 0,
+                    len: 1,
+                    dir: SortDir::Asc,
+                    numeric: false,
+                },
+                SortKey {
+                    pos: 2,
+                    len: 2,
+                    dir: SortDir::Desc,
+                    numeric: true,
+                },
+            ],
+            buffer: Vec::new(),
+        };
+        for line in ["B 1", "A 2", "B 10", "A 30", "A 2x"] {
+            assert!(stage.process(Record::from_str(line)).is_empty());
+        }
+        let out: Vec<String> = stage
+            .flush()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(out, vec!["A 30", "A 2", "A 2x", "B 10", "B 1"]);
+    }
 }
@@ -0,0 +1,212 @@
+//! Pluggable record sources and sinks for the record-at-a-time executor.
+//!
+//! [`execute_rat_io`](crate::execute_rat_io) pulls records one at a time
+//! from a [`RecordSource`] and hands each output record to a
+//! [`RecordSink`] as soon as the stages emit it, so neither the input nor
+//! the output has to fit in a `Vec`.
+//!
+//! Provided adaptors:
+//! - `vec.into_iter()` is a source; `Vec<Record>` is a sink
+//! - [`LineSource`] reads lines from any `BufRead` (a file, stdin, ...)
+//! - [`WriterSink`] writes one line per record to any `Write` (stdout, ...)
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Stdout, Write};
+use std::path::Path;
+
+use pipelines_rs::Record;
+
+/// Supplies input records one at a time.
+pub trait RecordSource {
+    /// The next record, or `None` once the input is exhausted.
+    fn next(&mut self) -> Option<Record>;
+}
+
+/// Receives output records one at a time.
+pub trait RecordSink {
+    /// Accept one output record.
+    fn write(&mut self, record: &Record);
+}
+
+impl RecordSource for std::vec::IntoIter<Record> {
+    fn next(&mut self) -> Option<Record> {
+        Iterator::next(self)
+    }
+}
+
+impl RecordSink for Vec<Record> {
+    fn write(&mut self, record: &Record) {
+        self.push(record.clone());
+    }
+}
+
+/// Reads one record per non-empty line, like a `CONSOLE` source.
+///
+/// `\n` and `\r\n` end a line. A read error ends the input; it is kept
+/// for [`error`](Self::error).
+pub struct LineSource<R> {
+    reader: R,
+    line: String,
+    continuation: Option<char>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead> LineSource<R> {
+    /// Read lines from `reader`.
+    pub fn new(reader: R) -> Self {
+        LineSource {
+            reader,
+            line: String::new(),
+            continuation: None,
+            error: None,
+        }
+    }
+
+    /// Join a line ending in `marker` to the line after it, dropping the
+    /// marker, as [`ExecuteOptions::continuation`] does.
+    ///
+    /// [`ExecuteOptions::continuation`]: pipelines_rs::ExecuteOptions::continuation
+    pub fn continuation(mut self, marker: char) -> Self {
+        self.continuation = Some(marker);
+        self
+    }
+
+    /// Append the next line, without its terminator, to `self.line`.
+    /// Returns false at the end of input or on a read error.
+    fn read_line(&mut self) -> bool {
+        match self.reader.read_line(&mut self.line) {
+            Ok(0) => false,
+            Ok(_) => {
+                let end = self.line.trim_end_matches(['\n', '\r']).len();
+                self.line.truncate(end);
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    /// The read error that ended the input, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl LineSource<BufReader<File>> {
+    /// Read lines from the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(LineSource::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> RecordSource for LineSource<R> {
+    fn next(&mut self) -> Option<Record> {
+        loop {
+            self.line.clear();
+            if !self.read_line() {
+                return None;
+            }
+            while let Some(marker) = self.continuation
+                && self.line.ends_with(marker)
+            {
+                self.line.pop();
+                if !self.read_line() {
+                    // Nothing left to join: keep the line as it was
+                    self.line.push(marker);
+                    break;
+                }
+            }
+            if !self.line.is_empty() {
+                return Some(Record::from_str(&self.line));
+            }
+        }
+    }
+}
+
+/// Writes each record's text, without trailing padding, as one line.
+///
+/// The first write error is kept for [`error`](Self::error); later records
+/// are dropped.
+pub struct WriterSink<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> WriterSink<W> {
+    /// Write lines to `writer`.
+    pub fn new(writer: W) -> Self {
+        WriterSink {
+            writer,
+            error: None,
+        }
+    }
+
+    /// The write error that stopped output, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl WriterSink<Stdout> {
+    /// Write lines to standard output.
+    pub fn stdout() -> Self {
+        WriterSink::new(io::stdout())
+    }
+}
+
+impl<W: Write> RecordSink for WriterSink<W> {
+    fn write(&mut self, record: &Record) {
+        if self.error.is_none()
+            && let Err(e) = writeln!(self.writer, "{}", record.as_str().trim_end())
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_line_source_skips_empty_lines() {
+        let mut source = LineSource::new(Cursor::new("A\r\n\nB\nC"));
+        let mut texts = Vec::new();
+        while let Some(record) = source.next() {
+            texts.push(record.as_str().trim_end().to_string());
+        }
+        assert_eq!(texts, vec!["A", "B", "C"]);
+        assert!(source.error().is_none());
+    }
+
+    #[test]
+    fn test_line_source_joins_continued_lines() {
+        let mut source = LineSource::new(Cursor::new("A \\\r\nB\\\nC\nD\\")).continuation('\\');
+        let mut texts = Vec::new();
+        while let Some(record) = source.next() {
+            texts.push(record.as_str().trim_end().to_string());
+        }
+        assert_eq!(texts, vec!["A BC", "D\\"]);
+        assert_eq!(
+            texts.join("\n"),
+            pipelines_rs::join_continuations("A \\\r\nB\\\nC\nD\\", '\\').trim_end()
+        );
+    }
+
+    #[test]
+    fn test_writer_sink_writes_trimmed_lines() {
+        let mut sink = WriterSink::new(Vec::new());
+        sink.write(&Record::from_str("A"));
+        sink.write(&Record::from_str("  B"));
+        assert!(sink.error().is_none());
+        assert_eq!(sink.into_inner(), b"A\n  B\n");
+    }
+}
@@ -0,0 +1,232 @@
+//! Test utilities for checking batch and RAT executor equivalence.
+//!
+//! Enabled with the `test-util` feature so downstream crates that define
+//! their own stages can assert that both executors agree on arbitrary
+//! pipelines, not just the bundled spec files.
+
+use std::fmt;
+
+use pipelines_rs::execute_pipeline;
+
+use crate::dsl::execute_pipeline_rat;
+
+/// Result shape shared by `execute_pipeline` and `execute_pipeline_rat`.
+type ExecResult = Result<(String, usize, usize), String>;
+
+/// Describes the first point where the batch and RAT executors disagree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutorMismatch {
+    /// The executors disagree on whether (or how) the pipeline failed.
+    ///
+    /// Each side is `None` if that executor succeeded.
+    Error {
+        batch: Option<String>,
+        rat: Option<String>,
+    },
+    /// The executors read a different number of input records.
+    InputCount { batch: usize, rat: usize },
+    /// The output differs at record `index`.
+    ///
+    /// A side is `None` if that executor produced fewer records.
+    Record {
+        index: usize,
+        batch: Option<String>,
+        rat: Option<String>,
+    },
+}
+
+impl fmt::Display for ExecutorMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutorMismatch::Error { batch, rat } => write!(
+                f,
+                "executors disagree on failure: batch={batch:?}, rat={rat:?}"
+            ),
+            ExecutorMismatch::InputCount { batch, rat } => {
+                write!(f, "input count differs: batch={batch}, rat={rat}")
+            }
+            ExecutorMismatch::Record { index, batch, rat } => write!(
+                f,
+                "output differs at record {index}: batch={batch:?}, rat={rat:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExecutorMismatch {}
+
+/// Run a pipeline through both executors and compare the results.
+///
+/// Returns `Ok(())` if both succeed with identical output (or both fail
+/// with the same error), otherwise the first mismatch found.
+///
+/// # Example
+///
+/// ```
+/// use naive_pipe::testkit::assert_executors_agree;
+///
+/// let pipeline = "PIPE CONSOLE | UPPER | CONSOLE";
+/// assert!(assert_executors_agree("hello\nworld", pipeline).is_ok());
+/// ```
+pub fn assert_executors_agree(input: &str, pipeline: &str) -> Result<(), ExecutorMismatch> {
+    let batch = execute_pipeline(input, pipeline);
+    let rat = execute_pipeline_rat(input, pipeline);
+    match first_mismatch(&batch, &rat) {
+        Some(mismatch) => Err(mismatch),
+        None => Ok(()),
+    }
+}
+
+/// Find the first difference between two executor results.
+fn first_mismatch(batch: &ExecResult, rat: &ExecResult) -> Option<ExecutorMismatch> {
+    let (batch_out, rat_out) = match (batch, rat) {
+        (Ok(b), Ok(r)) => (b, r),
+        (Err(b), Err(r)) if b == r => return None,
+        _ => {
+            return Some(ExecutorMismatch::Error {
+                batch: batch.as_ref().err().cloned(),
+                rat: rat.as_ref().err().cloned(),
+            });
+        }
+    };
+
+    if batch_out.1 != rat_out.1 {
+        return Some(ExecutorMismatch::InputCount {
+            batch: batch_out.1,
+            rat: rat_out.1,
+        });
+    }
+
+    let batch_lines = output_lines(&batch_out.0, batch_out.2);
+    let rat_lines = output_lines(&rat_out.0, rat_out.2);
+    let len = batch_lines.len().max(rat_lines.len());
+
+    (0..len).find_map(|index| {
+        let b = batch_lines.get(index);
+        let r = rat_lines.get(index);
+        (b != r).then(|| ExecutorMismatch::Record {
+            index,
+            batch: b.map(|s| s.to_string()),
+            rat: r.map(|s| s.to_string()),
+        })
+    })
+}
+
+/// Split joined output text back into one line per output record.
+fn output_lines(text: &str, count: usize) -> Vec<&str> {
+    if count == 0 {
+        vec![]
+    } else {
+        text.split('\n').collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agree_on_simple_pipeline() {
+        let pipeline = r#"PIPE CONSOLE
+| LOCATE /SALES/
+| COUNT
+| CONSOLE
+?"#;
+        let input = "SMITH   SALES\nJONES   ENGINEER\nDOE     SALES";
+        assert_eq!(assert_executors_agree(input, pipeline), Ok(()));
+    }
+
+    #[test]
+    fn test_agree_on_line_endings() {
+        let pipeline = "PIPE CONSOLE | COUNT | CONSOLE";
+        for input in [
+            "A\nB\nC",
+            "A\nB\nC\n",
+            "A\r\nB\r\nC\r\n",
+            "A\rB\rC",
+            "A\r\nB\rC\n",
+        ] {
+            assert_eq!(assert_executors_agree(input, pipeline), Ok(()), "{input:?}");
+            let (output, input_count, _) = execute_pipeline(input, pipeline).unwrap();
+            assert_eq!((output.trim(), input_count), ("3", 3), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_agree_on_same_error() {
+        assert_eq!(assert_executors_agree("A", "PIPE BOGUS | CONSOLE"), Ok(()));
+    }
+
+    #[test]
+    fn test_agree_on_change_that_converges_late() {
+        let pipeline = "PIPE CONSOLE | CHANGE /ab/baab/ REPEAT | CONSOLE";
+        assert_eq!(assert_executors_agree("ab\na", pipeline), Ok(()));
+        assert!(execute_pipeline_rat("ab", pipeline).is_ok());
+        // The limited RAT paths make as many passes
+        let options = pipelines_rs::ExecuteOptions {
+            max_output_records: Some(10),
+            ..pipelines_rs::ExecuteOptions::default()
+        };
+        let rat = crate::dsl::execute_pipeline_rat_with_options("ab", pipeline, &options);
+        assert_eq!(rat, execute_pipeline("ab", pipeline));
+        let options = pipelines_rs::ExecuteOptions {
+            expansion_factor_limit: Some(10),
+            ..options
+        };
+        let rat = crate::dsl::execute_pipeline_rat_with_options("ab", pipeline, &options);
+        assert_eq!(rat, execute_pipeline("ab", pipeline));
+    }
+
+    #[test]
+    fn test_mismatch_record() {
+        let batch = Ok(("A\nB\nC".to_string(), 3, 3));
+        let rat = Ok(("A\nX\nC".to_string(), 3, 3));
+        assert_eq!(
+            first_mismatch(&batch, &rat),
+            Some(ExecutorMismatch::Record {
+                index: 1,
+                batch: Some("B".to_string()),
+                rat: Some("X".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_mismatch_length() {
+        let batch = Ok(("A\nB".to_string(), 2, 2));
+        let rat = Ok(("A".to_string(), 2, 1));
+        assert_eq!(
+            first_mismatch(&batch, &rat),
+            Some(ExecutorMismatch::Record {
+                index: 1,
+                batch: Some("B".to_string()),
+                rat: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_mismatch_error() {
+        let batch = Ok((String::new(), 0, 0));
+        let rat = Err("boom".to_string());
+        let mismatch = first_mismatch(&batch, &rat).unwrap();
+        assert_eq!(
+            mismatch,
+            ExecutorMismatch::Error {
+                batch: None,
+                rat: Some("boom".to_string()),
+            }
+        );
+        assert!(mismatch.to_string().contains("boom"));
+    }
+
+    #[test]
+    fn test_mismatch_input_count() {
+        let batch = Ok((String::new(), 2, 0));
+        let rat = Ok((String::new(), 3, 0));
+        assert_eq!(
+            first_mismatch(&batch, &rat),
+            Some(ExecutorMismatch::InputCount { batch: 2, rat: 3 })
+        );
+    }
+}
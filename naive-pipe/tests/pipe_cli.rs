@@ -0,0 +1,101 @@
+//! Runs the `pipe` binary over the sample employee data.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const SPECS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../specs");
+
+fn sample_input() -> String {
+    std::fs::read_to_string(format!("{SPECS}/input-fixed-80.data")).unwrap()
+}
+
+/// Run `pipe` with `args`, feeding `stdin` to it.
+fn pipe(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pipe"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+const SALES: &str = "SMITH   JOHN      SALES     00050000\n\
+                     DOE     JANE      SALES     00060000\n\
+                     GARCIA  CARLOS    SALES     00045000\n";
+
+#[test]
+fn test_filters_stdin_with_either_executor() {
+    let pipeline = r#"PIPE CONSOLE | FILTER 18,10 = "SALES" | CONSOLE"#;
+    assert_eq!(stdout(&pipe(&[pipeline], &sample_input())), SALES);
+    assert_eq!(stdout(&pipe(&["--rat", pipeline], &sample_input())), SALES);
+}
+
+#[test]
+fn test_reads_pipeline_and_input_files() {
+    let pipeline = format!("{SPECS}/filter-sales.pipe");
+    let input = format!("{SPECS}/input-fixed-80.data");
+    let output = pipe(&["--pipeline", &pipeline, "--input", &input], "");
+    assert_eq!(stdout(&output), SALES);
+}
+
+#[test]
+fn test_stats_report_per_stage_counts() {
+    let pipeline = "PIPE CONSOLE | LOCATE /ENGINEER/ | COUNT | CONSOLE";
+    for executor in [&[][..], &["--rat"][..]] {
+        let args = [executor, &["--stats", pipeline]].concat();
+        let output = pipe(&args, &sample_input());
+        assert_eq!(stdout(&output), "3\n");
+        let stats = String::from_utf8(output.stderr).unwrap();
+        let rows: Vec<Vec<&str>> = stats
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["0", "CONSOLE", "0", "in", "8", "out"],
+                vec!["1", "LOCATE", "8", "in", "3", "out"],
+                vec!["2", "COUNT", "3", "in", "1", "out"],
+                vec!["3", "CONSOLE", "1", "in", "1", "out"],
+            ],
+            "{executor:?}"
+        );
+    }
+}
+
+#[test]
+fn test_trace_goes_to_stderr() {
+    let output = pipe(
+        &["--rat", "--trace", "PIPE CONSOLE | UPPER | CONSOLE"],
+        "ab\ncd",
+    );
+    assert_eq!(stdout(&output), "AB\nCD\n");
+    let trace = String::from_utf8(output.stderr).unwrap();
+    assert!(trace.contains("--- record 2: cd"), "{trace}");
+    assert!(trace.contains("UPPER      -> CD"), "{trace}");
+}
+
+#[test]
+fn test_pipeline_error_exits_nonzero() {
+    let output = pipe(&["PIPE CONSOLE | BOGUS | CONSOLE"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Unknown command: BOGUS"), "{stderr}");
+}
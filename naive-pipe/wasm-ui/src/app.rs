@@ -3,13 +3,13 @@
 use gloo::timers::callback::Timeout;
 use std::cell::RefCell;
 use std::rc::Rc;
-use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
 use web_sys::{Blob, HtmlAnchorElement, HtmlInputElement, HtmlSelectElement, Url};
 use yew::prelude::*;
 
 use crate::components::{InputPanel, OutputPanel, PipelinePanel};
-use crate::debugger::{DebuggerPanel, DebuggerState};
+use crate::debugger::{BreakCondition, DebuggerPanel, DebuggerState};
 use crate::dsl::{execute_pipeline, execute_pipeline_debug, parse_pipeline_lines};
 
 /// Render CSS-animated countdown with cycling dots.
@@ -255,20 +255,13 @@ fn initialize_debugger(state: &mut AppState) {
         Ok((output, input_count, output_count, trace)) => {
             let stage_count = trace.stage_names.len();
             state.debugger_state.active = true;
-            state.debugger_state.trace = Some(trace);
-            state.debugger_state.current_step = 0;
-            state.debugger_state.trace_idx = 0;
-            state.debugger_state.visible_pp = 0;
-            state.debugger_state.in_flush_phase = false;
-            state.debugger_state.accumulated_output = String::new();
+            state.debugger_state.load_trace(trace);
             state.debugger_state.stage_count = stage_count;
             state.debugger_state.output_text = output;
             state.debugger_state.input_count = input_count;
             state.debugger_state.output_count = output_count;
             state.debugger_state.pipeline_lines = lines;
             state.debugger_state.error = None;
-            state.debugger_state.hit_breakpoint = None;
-            state.debugger_state.total_steps = state.debugger_state.compute_total_steps();
             state
                 .debugger_state
                 .watches
@@ -280,13 +273,7 @@ fn initialize_debugger(state: &mut AppState) {
         }
         Err(e) => {
             state.debugger_state.active = true;
-            state.debugger_state.trace = None;
-            state.debugger_state.current_step = 0;
-            state.debugger_state.total_steps = 0;
-            state.debugger_state.trace_idx = 0;
-            state.debugger_state.visible_pp = 0;
-            state.debugger_state.in_flush_phase = false;
-            state.debugger_state.accumulated_output = String::new();
+            state.debugger_state.cursor = None;
             state.debugger_state.stage_count = 0;
             state.debugger_state.output_text.clear();
             state.debugger_state.pipeline_lines = lines;
@@ -589,7 +576,7 @@ pub fn app() -> Html {
 
             // Already finished — do nothing (user must Reset or Load)
             if new_state.debugger_state.active
-                && new_state.debugger_state.current_step >= new_state.debugger_state.total_steps
+                && new_state.debugger_state.current_step() >= new_state.debugger_state.total_steps()
             {
                 return;
             }
@@ -597,13 +584,15 @@ pub fn app() -> Html {
             // If active and not finished, continue until breakpoint or end
             if new_state.debugger_state.active {
                 new_state.debugger_state.hit_breakpoint = None;
-                while new_state.debugger_state.current_step < new_state.debugger_state.total_steps {
+                while new_state.debugger_state.current_step()
+                    < new_state.debugger_state.total_steps()
+                {
                     let hit = new_state.debugger_state.advance();
                     if hit {
                         break;
                     }
                 }
-                new_state.output_text = new_state.debugger_state.accumulated_output.clone();
+                new_state.output_text = new_state.debugger_state.accumulated_output();
                 let out_lines = new_state.output_text.lines().count();
                 new_state.stats = format!(
                     "Input: {} records | Output: {} records",
@@ -628,7 +617,65 @@ pub fn app() -> Html {
             new_state.debugger_state.advance();
 
             // Update output panel progressively
-            new_state.output_text = new_state.debugger_state.accumulated_output.clone();
+            new_state.output_text = new_state.debugger_state.accumulated_output();
+            let out_lines = new_state.output_text.lines().count();
+            new_state.stats = format!(
+                "Input: {} records | Output: {} records",
+                new_state.debugger_state.input_count, out_lines,
+            );
+            new_state.error = None;
+
+            state.set(new_state);
+        })
+    };
+
+    // Debugger: step back one pipe point
+    let on_debug_step_back = {
+        let state = state.clone();
+        Callback::from(move |_: ()| {
+            let mut new_state = (*state).clone();
+            new_state.debugger_state.step_back();
+
+            // Output panel shows only what had reached the sink at this step
+            new_state.output_text = new_state.debugger_state.accumulated_output();
+            let out_lines = new_state.output_text.lines().count();
+            new_state.stats = format!(
+                "Input: {} records | Output: {} records",
+                new_state.debugger_state.input_count, out_lines,
+            );
+            new_state.error = None;
+
+            state.set(new_state);
+        })
+    };
+
+    // Debugger: jump to an arbitrary step
+    let on_debug_goto_step = {
+        let state = state.clone();
+        Callback::from(move |target: usize| {
+            let mut new_state = (*state).clone();
+            new_state.debugger_state.goto_step(target);
+
+            new_state.output_text = new_state.debugger_state.accumulated_output();
+            let out_lines = new_state.output_text.lines().count();
+            new_state.stats = format!(
+                "Input: {} records | Output: {} records",
+                new_state.debugger_state.input_count, out_lines,
+            );
+            new_state.error = None;
+
+            state.set(new_state);
+        })
+    };
+
+    // Debugger: run to the first flush step
+    let on_debug_run_to_flush = {
+        let state = state.clone();
+        Callback::from(move |_: ()| {
+            let mut new_state = (*state).clone();
+            new_state.debugger_state.run_to_flush();
+
+            new_state.output_text = new_state.debugger_state.accumulated_output();
             let out_lines = new_state.output_text.lines().count();
             new_state.stats = format!(
                 "Input: {} records | Output: {} records",
@@ -645,12 +692,7 @@ pub fn app() -> Html {
         let state = state.clone();
         Callback::from(move |_: ()| {
             let mut new_state = (*state).clone();
-            new_state.debugger_state.current_step = 0;
-            new_state.debugger_state.trace_idx = 0;
-            new_state.debugger_state.visible_pp = 0;
-            new_state.debugger_state.in_flush_phase = false;
-            new_state.debugger_state.accumulated_output = String::new();
-            new_state.debugger_state.hit_breakpoint = None;
+            new_state.debugger_state.reset();
             // Clear output panel on reset
             new_state.output_text.clear();
             new_state.stats.clear();
@@ -679,6 +721,18 @@ pub fn app() -> Html {
         })
     };
 
+    // Debugger: set or clear a breakpoint condition
+    let on_set_breakpoint_condition = {
+        let state = state.clone();
+        Callback::from(move |(stage_index, text): (usize, String)| {
+            let mut new_state = (*state).clone();
+            new_state
+                .debugger_state
+                .set_breakpoint_condition(stage_index, BreakCondition::parse(&text));
+            state.set(new_state);
+        })
+    };
+
     // Debugger: remove watch by label
     let on_remove_watch = {
         let state = state.clone();
@@ -886,9 +940,13 @@ pub fn app() -> Html {
                                 state={state.debugger_state.clone()}
                                 on_run={on_debug_run}
                                 on_step={on_debug_step}
+                                on_step_back={on_debug_step_back}
+                                on_goto_step={on_debug_goto_step}
+                                on_run_to_flush={on_debug_run_to_flush}
                                 on_reset={on_debug_reset}
                                 on_toggle_watch={on_toggle_watch}
                                 on_toggle_breakpoint={on_toggle_breakpoint}
+                                on_set_breakpoint_condition={on_set_breakpoint_condition}
                                 on_remove_watch={on_remove_watch}
                                 on_load_example={on_debug_load_example}
                                 on_load_file={on_debug_load_file}
@@ -1,12 +1,13 @@
 //! Visual debugger for record-at-a-time pipeline inspection.
 //!
-//! Stepping is per-pipe-point: each step reveals the next pipe point
-//! in a record's journey through the pipeline. When a record is filtered
-//! (empty pipe point), the next step moves to the next record. After all
-//! records, flush traces are stepped similarly.
+//! Stepping is per-pipe-point, driven by [`TraceCursor`]: each step reveals
+//! the next pipe point in a record's journey through the pipeline. When a
+//! record is filtered (empty pipe point), the next step moves to the next
+//! record. After all records, flush traces are stepped similarly.
 //!
-//! As records reach the final stage, their output appears progressively
-//! in the output panel (not buffered until the end).
+//! The last step of each entry reveals the pipeline output below the last
+//! stage, so output appears progressively in the output panel (not
+//! buffered until the end), flush output of the last stage included.
 //!
 //! **Indexing note:** `execute_pipeline_rat_debug` handles the source
 //! stage separately. `trace.stage_names` excludes the source;
@@ -14,8 +15,9 @@
 //! `pipeline_lines` includes ALL stages (source at index 0). The pipe
 //! point between pipeline stage `i` and `i+1` maps to `pipe_points[i]`.
 
-use naive_pipe::RatDebugTrace;
-use web_sys::HtmlSelectElement;
+use naive_pipe::{CursorPosition, RatDebugTrace, TraceCursor};
+use pipelines_rs::Record;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
 use crate::app::TUTORIALS;
@@ -36,25 +38,68 @@ pub struct Watch {
 #[derive(Clone, PartialEq)]
 pub struct Breakpoint {
     pub stage_index: usize,
+    /// Only fire when a record at the pipe point matches (None = always).
+    pub condition: Option<BreakCondition>,
+}
+
+/// Condition a record must satisfy for a conditional breakpoint to fire.
+#[derive(Clone, PartialEq)]
+pub enum BreakCondition {
+    /// The record contains the substring anywhere.
+    Contains(String),
+    /// The field at `pos,len` equals `value` (trailing spaces ignored).
+    FieldEq {
+        pos: usize,
+        len: usize,
+        value: String,
+    },
+}
+
+impl BreakCondition {
+    /// Parse a condition from the UI text box.
+    ///
+    /// `pos,len=value` is a field comparison; any other text is a substring
+    /// match. Blank text means no condition.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let field_eq = text.split_once('=').and_then(|(spec, value)| {
+            let (pos, len) = spec.split_once(',')?;
+            Some(BreakCondition::FieldEq {
+                pos: pos.trim().parse().ok()?,
+                len: len.trim().parse().ok()?,
+                value: value.to_string(),
+            })
+        });
+        Some(field_eq.unwrap_or_else(|| BreakCondition::Contains(text.to_string())))
+    }
+
+    /// True if any of the records satisfies the condition.
+    pub fn matches(&self, records: &[pipelines_rs::Record]) -> bool {
+        records.iter().any(|r| match self {
+            BreakCondition::Contains(s) => r.as_str().contains(s.as_str()),
+            BreakCondition::FieldEq { pos, len, value } => r.field_eq(*pos, *len, value),
+        })
+    }
+}
+
+impl std::fmt::Display for BreakCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BreakCondition::Contains(s) => write!(f, "{s}"),
+            BreakCondition::FieldEq { pos, len, value } => write!(f, "{pos},{len}={value}"),
+        }
+    }
 }
 
 /// Debugger state (stored in AppState).
 #[derive(Clone, PartialEq)]
 pub struct DebuggerState {
     pub active: bool,
-    pub trace: Option<RatDebugTrace>,
-    /// Global step counter (0 = initial, 1..=total_steps).
-    pub current_step: usize,
-    /// Total granular steps across all records and flushes.
-    pub total_steps: usize,
-    /// Index into record_traces or flush_traces.
-    pub trace_idx: usize,
-    /// Pipe points revealed for current trace entry (0 = none).
-    pub visible_pp: usize,
-    /// True when stepping through flush traces.
-    pub in_flush_phase: bool,
-    /// Output accumulated so far (records that reached the sink).
-    pub accumulated_output: String,
+    /// Stepping position in the pipeline's debug trace.
+    pub cursor: Option<TraceCursor>,
     pub watches: Vec<Watch>,
     pub next_watch_id: usize,
     pub breakpoints: Vec<Breakpoint>,
@@ -72,13 +117,7 @@ impl Default for DebuggerState {
     fn default() -> Self {
         Self {
             active: false,
-            trace: None,
-            current_step: 0,
-            total_steps: 0,
-            trace_idx: 0,
-            visible_pp: 0,
-            in_flush_phase: false,
-            accumulated_output: String::new(),
+            cursor: None,
             watches: Vec::new(),
             next_watch_id: 1,
             breakpoints: Vec::new(),
@@ -93,34 +132,70 @@ impl Default for DebuggerState {
     }
 }
 
-/// Max UI pipe points to reveal for a record trace.
-/// Stops at the first empty pipe point (filter) + 1.
-fn max_pp_for_record(rt: &naive_pipe::RecordTrace) -> usize {
-    // Last pipe_point is final output (not a UI pipe point).
-    let ui_count = rt.pipe_points.len().saturating_sub(1);
-    for i in 0..ui_count {
-        if rt.pipe_points[i].is_empty() {
-            return i + 1;
-        }
+impl DebuggerState {
+    pub fn new() -> Self {
+        Self::default()
     }
-    ui_count
-}
 
-/// Max UI pipe points to reveal for a flush trace.
-fn max_pp_for_flush(ft: &naive_pipe::FlushTrace, num_ui_pp: usize) -> usize {
-    let start = ft.stage_index + 1;
-    let viewable = num_ui_pp.saturating_sub(start).min(ft.pipe_points.len());
-    for i in 0..viewable {
-        if ft.pipe_points[i].is_empty() {
-            return i + 1;
-        }
+    /// Start stepping through `trace` from step 0.
+    pub fn load_trace(&mut self, trace: RatDebugTrace) {
+        self.cursor = Some(TraceCursor::new(trace));
+        self.hit_breakpoint = None;
     }
-    viewable
-}
 
-impl DebuggerState {
-    pub fn new() -> Self {
-        Self::default()
+    /// The trace being stepped through, if the pipeline ran.
+    pub fn trace(&self) -> Option<&RatDebugTrace> {
+        self.cursor.as_ref().map(TraceCursor::trace)
+    }
+
+    /// Global step counter (0 = initial, 1..=total_steps).
+    pub fn current_step(&self) -> usize {
+        self.cursor.as_ref().map_or(0, TraceCursor::step)
+    }
+
+    /// Total granular steps across all records and flushes.
+    pub fn total_steps(&self) -> usize {
+        self.cursor.as_ref().map_or(0, TraceCursor::total_steps)
+    }
+
+    /// True when stepping through flush traces.
+    pub fn in_flush_phase(&self) -> bool {
+        matches!(self.position(), Some(CursorPosition::Flush { .. }))
+    }
+
+    /// Records that have reached the sink so far, in arrival order.
+    pub fn accumulated_records(&self) -> Vec<&Record> {
+        self.cursor
+            .as_ref()
+            .map(TraceCursor::accumulated_output)
+            .unwrap_or_default()
+    }
+
+    /// The sink-reached records as output panel text.
+    pub fn accumulated_output(&self) -> String {
+        self.accumulated_records()
+            .iter()
+            .map(|r| r.as_str().trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Records of the current trace entry at a revealed pipe point.
+    pub fn records_at(&self, pipe_point: usize) -> &[Record] {
+        self.cursor
+            .as_ref()
+            .map_or(&[], |cursor| cursor.records_at(pipe_point))
+    }
+
+    /// True if `pipe_point` of the current trace entry has been revealed.
+    pub fn is_revealed(&self, pipe_point: usize) -> bool {
+        self.cursor
+            .as_ref()
+            .is_some_and(|cursor| cursor.revealed_points().contains(&pipe_point))
+    }
+
+    fn position(&self) -> Option<CursorPosition> {
+        self.cursor.as_ref()?.position()
     }
 
     pub fn toggle_watch(&mut self, stage_index: usize) {
@@ -156,7 +231,10 @@ impl DebuggerState {
         {
             self.breakpoints.remove(pos);
         } else {
-            self.breakpoints.push(Breakpoint { stage_index });
+            self.breakpoints.push(Breakpoint {
+                stage_index,
+                condition: None,
+            });
         }
     }
 
@@ -166,172 +244,157 @@ impl DebuggerState {
             .any(|b| b.stage_index == stage_index)
     }
 
-    fn record_count(&self) -> usize {
-        self.trace
-            .as_ref()
-            .map(|t| t.record_traces.len())
-            .unwrap_or(0)
-    }
-
-    fn flush_count(&self) -> usize {
-        self.trace
-            .as_ref()
-            .map(|t| t.flush_traces.len())
-            .unwrap_or(0)
+    /// Set or clear the condition on an existing breakpoint.
+    pub fn set_breakpoint_condition(
+        &mut self,
+        stage_index: usize,
+        condition: Option<BreakCondition>,
+    ) {
+        if let Some(bp) = self
+            .breakpoints
+            .iter_mut()
+            .find(|b| b.stage_index == stage_index)
+        {
+            bp.condition = condition;
+        }
     }
 
-    fn num_ui_pipe_points(&self) -> usize {
-        self.pipeline_lines.len().saturating_sub(1)
+    pub fn breakpoint_condition(&self, stage_index: usize) -> Option<&BreakCondition> {
+        self.breakpoints
+            .iter()
+            .find(|b| b.stage_index == stage_index)
+            .and_then(|b| b.condition.as_ref())
     }
 
-    fn current_max_pp(&self) -> usize {
-        let trace = match &self.trace {
-            Some(t) => t,
-            None => return 0,
+    /// True if a breakpoint at `stage_index` fires for the revealed records.
+    fn breakpoint_fires(&self, stage_index: usize) -> bool {
+        let Some(bp) = self
+            .breakpoints
+            .iter()
+            .find(|b| b.stage_index == stage_index)
+        else {
+            return false;
         };
-        if !self.in_flush_phase {
-            trace
-                .record_traces
-                .get(self.trace_idx)
-                .map(max_pp_for_record)
-                .unwrap_or(0)
-        } else {
-            let num_ui = self.num_ui_pipe_points();
-            trace
-                .flush_traces
-                .get(self.trace_idx)
-                .map(|ft| max_pp_for_flush(ft, num_ui))
-                .unwrap_or(0)
+        match &bp.condition {
+            None => true,
+            Some(cond) => cond.matches(self.records_at(stage_index)),
         }
     }
 
-    /// Compute total granular steps for all traces.
-    pub fn compute_total_steps(&self) -> usize {
-        let trace = match &self.trace {
-            Some(t) => t,
-            None => return 0,
-        };
-        let num_ui = self.num_ui_pipe_points();
-        let record_steps: usize = trace.record_traces.iter().map(max_pp_for_record).sum();
-        let flush_steps: usize = trace
-            .flush_traces
-            .iter()
-            .map(|ft| max_pp_for_flush(ft, num_ui))
-            .sum();
-        record_steps + flush_steps
+    fn record_count(&self) -> usize {
+        self.trace().map_or(0, |t| t.record_traces.len())
+    }
+
+    fn flush_count(&self) -> usize {
+        self.trace().map_or(0, |t| t.flush_traces.len())
     }
 
     /// Returns the pipe point index most recently revealed.
     pub fn currently_revealed_pipe_point(&self) -> Option<usize> {
-        if self.current_step == 0 || self.visible_pp == 0 {
-            return None;
-        }
-        if !self.in_flush_phase {
-            Some(self.visible_pp - 1)
-        } else {
-            let ft = self.trace.as_ref()?.flush_traces.get(self.trace_idx)?;
-            Some(ft.stage_index + self.visible_pp)
+        match self.position()? {
+            CursorPosition::Record { pipe_point, .. }
+            | CursorPosition::Flush { pipe_point, .. } => Some(pipe_point),
         }
     }
 
-    /// Advance one granular step. Collects output when a trace completes.
-    /// Returns `true` if a breakpoint was hit.
+    /// Advance one granular step. Returns `true` if a breakpoint was hit.
     pub fn advance(&mut self) -> bool {
-        if self.current_step >= self.total_steps {
+        let Some(cursor) = &mut self.cursor else {
+            return false;
+        };
+        if !cursor.step_forward() {
             return false;
         }
-        let max_pp = self.current_max_pp();
-        if self.visible_pp < max_pp {
-            self.visible_pp += 1;
-            if self.visible_pp == max_pp {
-                self.collect_output();
-            }
-        } else {
-            let rc = self.record_count();
-            if !self.in_flush_phase {
-                self.trace_idx += 1;
-                if self.trace_idx >= rc {
-                    self.in_flush_phase = true;
-                    self.trace_idx = 0;
-                }
-            } else {
-                self.trace_idx += 1;
+        if let Some(pp) = self.currently_revealed_pipe_point()
+            && self.breakpoint_fires(pp)
+        {
+            self.hit_breakpoint = Some(pp);
+            return true;
+        }
+        false
+    }
+
+    /// Return to step 0, clearing revealed pipe points and accumulated output.
+    pub fn reset(&mut self) {
+        if let Some(cursor) = &mut self.cursor {
+            cursor.goto(0);
+        }
+        self.hit_breakpoint = None;
+    }
+
+    /// Step back one granular step, ignoring breakpoints.
+    pub fn step_back(&mut self) {
+        if let Some(cursor) = &mut self.cursor
+            && cursor.step_back()
+        {
+            self.hit_breakpoint = None;
+        }
+    }
+
+    /// Jump to step `target` (clamped to `total_steps`).
+    ///
+    /// Moving forward advances from the current step and stops early at the
+    /// first breakpoint hit. Moving backward ignores breakpoints. Returns
+    /// `true` if a breakpoint was hit.
+    pub fn goto_step(&mut self, target: usize) -> bool {
+        let target = target.min(self.total_steps());
+        self.hit_breakpoint = None;
+        if target < self.current_step() {
+            if let Some(cursor) = &mut self.cursor {
+                cursor.goto(target);
             }
-            self.visible_pp = 1;
+            return false;
         }
-        self.current_step += 1;
-        if let Some(pp) = self.currently_revealed_pipe_point() {
-            if self.has_breakpoint(pp) {
-                self.hit_breakpoint = Some(pp);
+        while self.current_step() < target {
+            if self.advance() {
                 return true;
             }
         }
         false
     }
 
-    /// Collect output records from the current trace entry's final pipe point.
-    fn collect_output(&mut self) {
-        let records_text: Vec<String> = {
-            let trace = match &self.trace {
-                Some(t) => t,
-                None => return,
-            };
-            let final_pp = if !self.in_flush_phase {
-                trace
-                    .record_traces
-                    .get(self.trace_idx)
-                    .and_then(|rt| rt.pipe_points.last())
-            } else {
-                trace
-                    .flush_traces
-                    .get(self.trace_idx)
-                    .and_then(|ft| ft.pipe_points.last())
-            };
-            match final_pp {
-                Some(records) if !records.is_empty() => records
-                    .iter()
-                    .map(|r| r.as_str().trim_end().to_string())
-                    .collect(),
-                _ => return,
-            }
-        };
-        for text in &records_text {
-            if !self.accumulated_output.is_empty() {
-                self.accumulated_output.push('\n');
+    /// Advance until the first flush step (or the end), so buffering stages
+    /// like COUNT show what they emit without stepping through every record.
+    ///
+    /// Stops early at the first breakpoint hit. Does nothing once already in
+    /// the flush phase. Returns `true` if a breakpoint was hit.
+    pub fn run_to_flush(&mut self) -> bool {
+        self.hit_breakpoint = None;
+        while !self.in_flush_phase() && self.current_step() < self.total_steps() {
+            if self.advance() {
+                return true;
             }
-            self.accumulated_output.push_str(text);
         }
+        false
     }
 
     /// Step counter label: "Record 2 of 8 (1/3)" or "Flush 1 of 2 (1/1)".
     fn step_label(&self) -> String {
-        if !self.active || self.total_steps == 0 || self.current_step == 0 {
+        if !self.active {
             return String::new();
         }
-        let max_pp = self.current_max_pp();
+        let (Some(position), Some((revealed, steps))) = (
+            self.position(),
+            self.cursor.as_ref().and_then(TraceCursor::entry_progress),
+        ) else {
+            return String::new();
+        };
         let prefix = if self.hit_breakpoint.is_some() {
             "[BP] "
         } else {
             ""
         };
-        if !self.in_flush_phase {
-            let rc = self.record_count();
-            format!(
-                "{prefix}Record {} of {} ({}/{})",
-                self.trace_idx + 1,
-                rc,
-                self.visible_pp,
-                max_pp
-            )
-        } else {
-            let fc = self.flush_count();
-            format!(
-                "{prefix}Flush {} of {} ({}/{})",
-                self.trace_idx + 1,
-                fc,
-                self.visible_pp,
-                max_pp
-            )
+        match position {
+            CursorPosition::Record { index, .. } => format!(
+                "{prefix}Record {} of {} ({revealed}/{steps})",
+                index + 1,
+                self.record_count()
+            ),
+            CursorPosition::Flush { index, .. } => format!(
+                "{prefix}Flush {} of {} ({revealed}/{steps})",
+                index + 1,
+                self.flush_count()
+            ),
         }
     }
 }
@@ -341,9 +404,13 @@ pub struct DebuggerProps {
     pub state: DebuggerState,
     pub on_run: Callback<()>,
     pub on_step: Callback<()>,
+    pub on_step_back: Callback<()>,
+    pub on_goto_step: Callback<usize>,
+    pub on_run_to_flush: Callback<()>,
     pub on_reset: Callback<()>,
     pub on_toggle_watch: Callback<usize>,
     pub on_toggle_breakpoint: Callback<usize>,
+    pub on_set_breakpoint_condition: Callback<(usize, String)>,
     pub on_remove_watch: Callback<String>,
     pub on_load_example: Callback<usize>,
     pub on_load_file: Callback<web_sys::Event>,
@@ -385,15 +452,35 @@ pub fn debugger_panel(props: &DebuggerProps) -> Html {
         let cb = props.on_step.clone();
         Callback::from(move |_: MouseEvent| cb.emit(()))
     };
+    let on_step_back = {
+        let cb = props.on_step_back.clone();
+        Callback::from(move |_: MouseEvent| cb.emit(()))
+    };
+    let on_run_to_flush = {
+        let cb = props.on_run_to_flush.clone();
+        Callback::from(move |_: MouseEvent| cb.emit(()))
+    };
     let on_reset = {
         let cb = props.on_reset.clone();
         Callback::from(move |_: MouseEvent| cb.emit(()))
     };
+    let on_goto = {
+        let cb = props.on_goto_step.clone();
+        Callback::from(move |e: Event| {
+            let target: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(step) = target.value().trim().parse::<usize>() {
+                cb.emit(step);
+            }
+        })
+    };
 
     let step_label = state.step_label();
-    let run_disabled = state.active && state.current_step >= state.total_steps;
-    let step_disabled = !state.active || state.current_step >= state.total_steps;
-    let reset_disabled = !state.active || state.current_step == 0;
+    let run_disabled = state.active && state.current_step() >= state.total_steps();
+    let step_disabled = !state.active || state.current_step() >= state.total_steps();
+    let step_back_disabled = !state.active || state.current_step() == 0;
+    let run_to_flush_disabled = step_disabled || state.in_flush_phase();
+    let reset_disabled = !state.active || state.current_step() == 0;
+    let goto_disabled = !state.active || state.total_steps() == 0;
 
     html! {
         <div class="panel debugger-panel">
@@ -419,6 +506,13 @@ pub fn debugger_panel(props: &DebuggerProps) -> Html {
                         title="Run pipeline">
                         {"Run"}
                     </button>
+                    <button class="debug-btn debug-btn-step"
+                        onclick={on_step_back}
+                        disabled={step_back_disabled}
+                        title="Step back to previous pipe point"
+                    >
+                        {"Step \u{25C0}"}
+                    </button>
                     <button class="debug-btn debug-btn-step"
                         onclick={on_step}
                         disabled={step_disabled}
@@ -426,6 +520,13 @@ pub fn debugger_panel(props: &DebuggerProps) -> Html {
                     >
                         {"Step \u{25B6}"}
                     </button>
+                    <button class="debug-btn debug-btn-flush"
+                        onclick={on_run_to_flush}
+                        disabled={run_to_flush_disabled}
+                        title="Run to the first flush step (stops at breakpoints)"
+                    >
+                        {"Run to Flush"}
+                    </button>
                     <button class="debug-btn debug-btn-reset"
                         onclick={on_reset}
                         disabled={reset_disabled}
@@ -433,12 +534,24 @@ pub fn debugger_panel(props: &DebuggerProps) -> Html {
                     >
                         {"Reset"}
                     </button>
+                    <input type="number" class="debug-goto-input"
+                        min="0" max={state.total_steps().to_string()}
+                        value={state.current_step().to_string()}
+                        disabled={goto_disabled}
+                        onchange={on_goto}
+                        title="Jump to step"
+                    />
                     <span class="step-counter">{step_label}</span>
                 </div>
             </div>
             <div class="panel-content debugger-content">
                 { render_error(state) }
-                { render_stage_list(state, &props.on_toggle_watch, &props.on_toggle_breakpoint) }
+                { render_stage_list(
+                    state,
+                    &props.on_toggle_watch,
+                    &props.on_toggle_breakpoint,
+                    &props.on_set_breakpoint_condition,
+                ) }
                 { render_watch_list(state, &props.on_remove_watch) }
             </div>
         </div>
@@ -457,6 +570,7 @@ fn render_stage_list(
     state: &DebuggerState,
     on_toggle_watch: &Callback<usize>,
     on_toggle_breakpoint: &Callback<usize>,
+    on_set_condition: &Callback<(usize, String)>,
 ) -> Html {
     if !state.active {
         return html! {
@@ -482,7 +596,13 @@ fn render_stage_list(
                             <span class="stage-number">{format!("stage {stage_idx}")}</span>
                         </div>
                         { if i < lines.len() - 1 {
-                            render_pipe_point(state, stage_idx, on_toggle_watch, on_toggle_breakpoint)
+                            render_pipe_point(
+                                state,
+                                stage_idx,
+                                on_toggle_watch,
+                                on_toggle_breakpoint,
+                                on_set_condition,
+                            )
                         } else {
                             html! {}
                         }}
@@ -493,32 +613,12 @@ fn render_stage_list(
     }
 }
 
-/// Stage is "completed" when the step has progressed past it.
+/// Stage is "completed" when the step has revealed its output pipe point.
 fn stage_class(state: &DebuggerState, stage_idx: usize) -> &'static str {
-    if state.current_step == 0 {
-        return "stage-pending";
-    }
-    if !state.in_flush_phase {
-        if stage_idx < state.visible_pp {
-            "stage-completed"
-        } else {
-            "stage-pending"
-        }
+    if state.is_revealed(stage_idx) {
+        "stage-completed"
     } else {
-        let trace = match &state.trace {
-            Some(t) => t,
-            None => return "stage-pending",
-        };
-        if let Some(ft) = trace.flush_traces.get(state.trace_idx) {
-            let flush_start = ft.stage_index + 1;
-            if stage_idx >= flush_start && (stage_idx - flush_start) < state.visible_pp {
-                "stage-completed"
-            } else {
-                "stage-pending"
-            }
-        } else {
-            "stage-pending"
-        }
+        "stage-pending"
     }
 }
 
@@ -527,6 +627,7 @@ fn render_pipe_point(
     stage_index: usize,
     on_toggle_watch: &Callback<usize>,
     on_toggle_breakpoint: &Callback<usize>,
+    on_set_condition: &Callback<(usize, String)>,
 ) -> Html {
     let watches = state.watches_at(stage_index);
     let record_info = pipe_point_info(state, stage_index);
@@ -552,7 +653,20 @@ fn render_pipe_point(
         })
     };
 
-    let has_data = state.current_step > 0 && !record_info.starts_with('\u{00B7}');
+    let on_condition_change = {
+        let cb = on_set_condition.clone();
+        let idx = stage_index;
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            cb.emit((idx, input.value()));
+        })
+    };
+    let condition_text = state
+        .breakpoint_condition(stage_index)
+        .map(|c| c.to_string())
+        .unwrap_or_default();
+
+    let has_data = state.current_step() > 0 && !record_info.starts_with('\u{00B7}');
     let base_class = if has_data {
         "pipe-point pipe-reached"
     } else {
@@ -577,6 +691,18 @@ fn render_pipe_point(
             <span class={bp_class} onclick={on_bp_click} title="Toggle breakpoint">
                 {"\u{24B7}"}
             </span>
+            { if has_bp {
+                html! {
+                    <input type="text" class="pipe-bp-condition"
+                        placeholder="condition"
+                        value={condition_text}
+                        onchange={on_condition_change}
+                        title="Break only when a record contains this text, or pos,len=value"
+                    />
+                }
+            } else {
+                html! {}
+            }}
             { for watches.iter().map(|w| {
                 html! { <span class="watch-label">{&w.label}</span> }
             })}
@@ -587,38 +713,7 @@ fn render_pipe_point(
 
 /// Pipe point info: only shows data for revealed pipe points.
 fn pipe_point_info(state: &DebuggerState, stage_index: usize) -> String {
-    if state.current_step == 0 {
-        return DOTS.to_string();
-    }
-    let trace = match &state.trace {
-        Some(t) => t,
-        None => return DOTS.to_string(),
-    };
-    if !state.in_flush_phase {
-        if let Some(rt) = trace.record_traces.get(state.trace_idx) {
-            if stage_index < state.visible_pp && stage_index < rt.pipe_points.len() {
-                format_pipe_point_records(&rt.pipe_points[stage_index])
-            } else {
-                DOTS.to_string()
-            }
-        } else {
-            DOTS.to_string()
-        }
-    } else if let Some(ft) = trace.flush_traces.get(state.trace_idx) {
-        let flush_start = ft.stage_index + 1;
-        if stage_index >= flush_start {
-            let offset = stage_index - flush_start;
-            if offset < state.visible_pp && offset < ft.pipe_points.len() {
-                format_pipe_point_records(&ft.pipe_points[offset])
-            } else {
-                DOTS.to_string()
-            }
-        } else {
-            DOTS.to_string()
-        }
-    } else {
-        DOTS.to_string()
-    }
+    format_pipe_point_records(state.records_at(stage_index))
 }
 
 fn format_pipe_point_records(records: &[pipelines_rs::Record]) -> String {
@@ -708,43 +803,20 @@ fn render_watch_item(
 }
 
 fn render_watch_records(state: &DebuggerState, stage_index: usize) -> Html {
-    if state.current_step == 0 {
+    if state.current_step() == 0 {
         return html! {
             <span class="watch-not-reached">{"step to see data"}</span>
         };
     }
-    let trace = match &state.trace {
-        Some(t) => t,
-        None => {
-            return html! {
-                <span class="watch-empty">{"no data"}</span>
-            };
-        }
-    };
+    if state.trace().is_none() {
+        return html! {
+            <span class="watch-empty">{"no data"}</span>
+        };
+    }
 
-    let records = if !state.in_flush_phase {
-        trace.record_traces.get(state.trace_idx).and_then(|rt| {
-            if stage_index < state.visible_pp {
-                rt.pipe_points.get(stage_index)
-            } else {
-                None
-            }
-        })
-    } else {
-        trace.flush_traces.get(state.trace_idx).and_then(|ft| {
-            let flush_start = ft.stage_index + 1;
-            if stage_index >= flush_start {
-                let offset = stage_index - flush_start;
-                if offset < state.visible_pp {
-                    ft.pipe_points.get(offset)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-    };
+    let records = state
+        .is_revealed(stage_index)
+        .then(|| state.records_at(stage_index));
 
     match records {
         Some(recs) if recs.is_empty() => html! {},
@@ -768,3 +840,229 @@ fn render_watch_records(state: &DebuggerState, stage_index: usize) -> Html {
         None => html! {},
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::{execute_pipeline_debug, parse_pipeline_lines};
+
+    /// Build an active debugger at step 0 for a pipeline.
+    fn debugger_for(input: &str, pipeline: &str) -> DebuggerState {
+        let (output, input_count, output_count, trace) =
+            execute_pipeline_debug(input, pipeline).unwrap();
+        let mut state = DebuggerState::new();
+        state.active = true;
+        state.stage_count = trace.stage_names.len();
+        state.load_trace(trace);
+        state.output_text = output;
+        state.input_count = input_count;
+        state.output_count = output_count;
+        state.pipeline_lines = parse_pipeline_lines(pipeline);
+        state
+    }
+
+    /// Step forward to the end, returning the state after every step.
+    fn forward_snapshots(state: &mut DebuggerState) -> Vec<DebuggerState> {
+        let mut snapshots = vec![state.clone()];
+        while state.current_step() < state.total_steps() {
+            state.advance();
+            snapshots.push(state.clone());
+        }
+        snapshots
+    }
+
+    const PIPELINE: &str = "PIPE CONSOLE\n| LOCATE /A/\n| COUNT\n| CONSOLE\n?";
+
+    #[test]
+    fn test_disabled_stage_keeps_line_indices_aligned() {
+        let pipeline = "PIPE CONSOLE\n#! | LOCATE /A/\n# note\n| UPPER\n| #! TAKE 1\n| CONSOLE\n?";
+        let state = debugger_for("a\nb", pipeline);
+        let lines = &state.pipeline_lines;
+        // Source + 4 stages, one line each, indices in order
+        assert_eq!(lines.len(), 5);
+        assert_eq!(state.trace().unwrap().stage_names.len(), 4);
+        let indices: Vec<usize> = lines.iter().map(|l| l.stage_index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+        assert_eq!(lines[1].text, "#! | LOCATE /A/");
+        assert_eq!(lines[2].text, "UPPER");
+        assert_eq!(state.trace().unwrap().stage_names[0], "DISABLED");
+        assert_eq!(state.output_text, "A\nB");
+    }
+
+    #[test]
+    fn test_step_back_rebuilds_each_step() {
+        let mut state = debugger_for("A1\nB2\nA3", PIPELINE);
+        let snapshots = forward_snapshots(&mut state);
+        assert_eq!(state.accumulated_output(), "2");
+
+        for expected in snapshots.iter().rev().skip(1) {
+            state.step_back();
+            assert!(
+                state == *expected,
+                "mismatch at step {}",
+                state.current_step()
+            );
+        }
+        assert_eq!(state.current_step(), 0);
+        assert!(state.accumulated_output().is_empty());
+    }
+
+    #[test]
+    fn test_accumulated_records_grow_with_output() {
+        let mut state = debugger_for("", "PIPE LITERAL HELLO\n| CONSOLE\n?");
+        assert!(state.accumulated_records().is_empty());
+        let mut lengths = vec![0];
+        while state.current_step() < state.total_steps() {
+            state.advance();
+            lengths.push(state.accumulated_records().len());
+        }
+        assert_eq!(lengths.last(), Some(&1));
+        assert!(lengths.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(state.accumulated_records()[0].as_str().trim_end(), "HELLO");
+        assert_eq!(state.accumulated_output(), "HELLO");
+
+        state.reset();
+        assert!(state.accumulated_records().is_empty());
+    }
+
+    #[test]
+    fn test_accumulated_records_match_output_text() {
+        let mut state = debugger_for("A1\nB2\nA3", "PIPE CONSOLE\n| LOCATE /A/\n| CONSOLE\n?");
+        state.goto_step(state.total_steps());
+        let texts: Vec<&str> = state
+            .accumulated_records()
+            .iter()
+            .map(|r| r.as_str().trim_end())
+            .collect();
+        assert_eq!(texts, vec!["A1", "A3"]);
+        assert_eq!(texts.join("\n"), state.accumulated_output());
+    }
+
+    #[test]
+    fn test_step_back_at_start_is_noop() {
+        let mut state = debugger_for("A1", PIPELINE);
+        let before = state.clone();
+        state.step_back();
+        assert!(state == before);
+    }
+
+    #[test]
+    fn test_step_back_ignores_breakpoints() {
+        let mut state = debugger_for("A1\nA2", "PIPE CONSOLE\n| UPPER\n| CONSOLE\n?");
+        state.toggle_breakpoint(0);
+        state.advance();
+        state.advance();
+        state.step_back();
+        assert_eq!(state.current_step(), 1);
+        assert_eq!(state.hit_breakpoint, None);
+    }
+
+    #[test]
+    fn test_goto_step_matches_stepping() {
+        let mut state = debugger_for("A1\nB2\nA3", PIPELINE);
+        let snapshots = forward_snapshots(&mut state.clone());
+
+        for target in [3, 1, state.total_steps(), 0, 2] {
+            assert!(!state.goto_step(target));
+            assert!(state == snapshots[target], "mismatch at step {target}");
+        }
+    }
+
+    #[test]
+    fn test_goto_step_clamps_to_total() {
+        let mut state = debugger_for("A1\nA2", PIPELINE);
+        state.goto_step(usize::MAX);
+        assert_eq!(state.current_step(), state.total_steps());
+        assert_eq!(state.accumulated_output(), "2");
+    }
+
+    #[test]
+    fn test_goto_step_stops_at_breakpoint_going_forward() {
+        let mut state = debugger_for("A1\nA2", "PIPE CONSOLE\n| UPPER\n| CONSOLE\n?");
+        state.toggle_breakpoint(0);
+        assert!(state.goto_step(state.total_steps()));
+        assert_eq!(state.current_step(), 1);
+        assert_eq!(state.hit_breakpoint, Some(0));
+
+        // Jumping back past a breakpoint does not stop at it
+        state.goto_step(4);
+        assert!(!state.goto_step(0));
+        assert_eq!(state.current_step(), 0);
+        assert_eq!(state.hit_breakpoint, None);
+    }
+
+    #[test]
+    fn test_run_to_flush_lands_on_first_flush_step() {
+        let mut state = debugger_for("A1\nB2\nA3", "PIPE CONSOLE\n| COUNT\n| CONSOLE\n?");
+        let snapshots = forward_snapshots(&mut state.clone());
+        let first_flush = snapshots.iter().position(|s| s.in_flush_phase()).unwrap();
+
+        assert!(!state.run_to_flush());
+        assert!(state == snapshots[first_flush]);
+        assert!(state.in_flush_phase());
+        assert_eq!(state.step_label(), "Flush 1 of 1 (1/2)");
+
+        // Already flushing: nothing to do
+        assert!(!state.run_to_flush());
+        assert_eq!(state.current_step(), first_flush);
+    }
+
+    #[test]
+    fn test_run_to_flush_stops_at_breakpoint() {
+        let mut state = debugger_for("A1\nB2", "PIPE CONSOLE\n| COUNT\n| CONSOLE\n?");
+        state.toggle_breakpoint(0);
+        assert!(state.run_to_flush());
+        assert_eq!(state.current_step(), 1);
+        assert_eq!(state.hit_breakpoint, Some(0));
+        assert!(!state.in_flush_phase());
+    }
+
+    #[test]
+    fn test_break_condition_parse() {
+        assert!(BreakCondition::parse("  ").is_none());
+        assert!(
+            BreakCondition::parse("0,5=SMITH")
+                == Some(BreakCondition::FieldEq {
+                    pos: 0,
+                    len: 5,
+                    value: "SMITH".to_string()
+                })
+        );
+        assert!(BreakCondition::parse("a=b") == Some(BreakCondition::Contains("a=b".to_string())));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint_fires_on_matching_record() {
+        let mut state = debugger_for("A1\nB2\nA3", "PIPE CONSOLE\n| UPPER\n| CONSOLE\n?");
+        state.toggle_breakpoint(0);
+        state.set_breakpoint_condition(0, BreakCondition::parse("B"));
+        assert!(state.goto_step(state.total_steps()));
+        // Each record takes three steps; B2 reaches pipe point 0 on step 4
+        assert_eq!(state.current_step(), 4);
+        assert_eq!(state.hit_breakpoint, Some(0));
+
+        state.hit_breakpoint = None;
+        assert!(!state.goto_step(state.total_steps()));
+        assert_eq!(state.current_step(), state.total_steps());
+    }
+
+    #[test]
+    fn test_conditional_breakpoint_field_eq() {
+        let mut state = debugger_for("A1\nB2\nA3", "PIPE CONSOLE\n| UPPER\n| CONSOLE\n?");
+        state.toggle_breakpoint(0);
+        state.set_breakpoint_condition(0, BreakCondition::parse("1,1=3"));
+        assert!(state.goto_step(state.total_steps()));
+        assert_eq!(state.current_step(), 7);
+    }
+
+    #[test]
+    fn test_flush_from_last_stage_reaches_output() {
+        let mut state = debugger_for("A1\nB2\nA3", "PIPE CONSOLE\n| COUNT\n?");
+        assert!(!state.run_to_flush());
+        // COUNT's flush output is the pipeline output, revealed in one step
+        assert_eq!(state.step_label(), "Flush 1 of 1 (1/1)");
+        assert_eq!(state.current_step(), state.total_steps());
+        assert_eq!(state.accumulated_output(), "3");
+        assert_eq!(state.accumulated_output(), state.output_text);
+    }
+}
@@ -4,6 +4,19 @@
 //! `execute_pipeline_rat` and `execute_pipeline_rat_debug` functions.
 
 use naive_pipe::RatDebugTrace;
+use pipelines_rs::{ExecuteOptions, disabled_stage};
+
+/// Most output records a run may produce before it is stopped, so a
+/// runaway `DUPLICATE` or `EXPAND` can't hang the browser tab.
+pub const MAX_OUTPUT_RECORDS: usize = 100_000;
+
+/// Execution options for the UI: defaults plus the output record limit.
+pub fn ui_options() -> ExecuteOptions {
+    ExecuteOptions {
+        max_output_records: Some(MAX_OUTPUT_RECORDS),
+        ..ExecuteOptions::default()
+    }
+}
 
 /// Execute a pipeline using the record-at-a-time executor.
 ///
@@ -12,16 +25,20 @@ pub fn execute_pipeline(
     input_text: &str,
     pipeline_text: &str,
 ) -> Result<(String, usize, usize), String> {
-    naive_pipe::execute_pipeline_rat(input_text, pipeline_text)
+    naive_pipe::execute_pipeline_rat_with_options(input_text, pipeline_text, &ui_options())
 }
 
 /// Execute a pipeline with debug tracing using the record-at-a-time executor.
 ///
 /// Returns (output_text, input_count, output_count, trace) on success.
+/// The pipeline is first run under [`ui_options`], so one that would pass
+/// [`MAX_OUTPUT_RECORDS`] fails before the trace, which keeps every record,
+/// is built.
 pub fn execute_pipeline_debug(
     input_text: &str,
     pipeline_text: &str,
 ) -> Result<(String, usize, usize, RatDebugTrace), String> {
+    execute_pipeline(input_text, pipeline_text)?;
     naive_pipe::execute_pipeline_rat_debug(input_text, pipeline_text)
 }
 
@@ -35,6 +52,10 @@ pub struct PipelineLine {
 }
 
 /// Parse pipeline text into display lines, each tagged with its stage index.
+///
+/// Stage indices match the library parser: comments are skipped, but a
+/// `#!` disabled stage keeps its line (and its index) so the debugger's
+/// stages line up with the executed pipeline.
 pub fn parse_pipeline_lines(pipeline_text: &str) -> Vec<PipelineLine> {
     let mut lines = Vec::new();
     let mut stage_index: usize = 0;
@@ -42,6 +63,15 @@ pub fn parse_pipeline_lines(pipeline_text: &str) -> Vec<PipelineLine> {
     for line in pipeline_text.lines() {
         let trimmed = line.trim();
 
+        if disabled_stage(trimmed).is_some() {
+            lines.push(PipelineLine {
+                text: trimmed.to_string(),
+                stage_index,
+            });
+            stage_index += 1;
+            continue;
+        }
+
         // Skip empty lines and comments
         if trimmed.is_empty() || trimmed.starts_with('#') {
             continue;
@@ -65,7 +95,9 @@ pub fn parse_pipeline_lines(pipeline_text: &str) -> Vec<PipelineLine> {
         let cmd_text = cmd_text.trim_end_matches('|').trim();
         let cmd_text = cmd_text.trim_end_matches('?').trim();
 
-        if cmd_text.is_empty() {
+        // "| # note" is a comment, but "| #! STAGE" is a disabled stage
+        if cmd_text.is_empty() || (cmd_text.starts_with('#') && disabled_stage(cmd_text).is_none())
+        {
             continue;
         }
 
@@ -0,0 +1,313 @@
+//! JSON stepping API for debugger front ends other than the Yew UI
+//! (the `json-api` feature).
+//!
+//! [`run_pipeline_stats_json`] is a lighter call that runs a pipeline once
+//! and returns only each stage's record counts ([`StageStats`]), enough to
+//! show where records are dropped.
+//!
+//! [`debugger_init`] and [`debugger_step`] step with the same
+//! [`TraceCursor`] as the debugger panel, but hand the state back and forth
+//! as JSON text so a plain JavaScript page can hold it. The state is a
+//! [`DebuggerSnapshot`]: the input and pipeline it was built from, the
+//! current step, and what is visible at that step. The trace itself is not
+//! serialized; each step re-runs the pipeline and moves the cursor to the
+//! next step.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::dsl::execute_pipeline_debug;
+use naive_pipe::{CursorPosition, TraceCursor, execute_pipeline_rat_stats};
+use pipelines_rs::Record;
+
+/// Debugger state as exchanged with JavaScript.
+///
+/// The field names are the serialized shape, so they must not be renamed.
+/// Records are given as text with trailing spaces trimmed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebuggerSnapshot {
+    /// Input records the pipeline runs on.
+    pub input: String,
+    /// Pipeline text.
+    pub pipeline: String,
+    /// Steps taken: 0 before the first, up to `total_steps`.
+    pub current_step: usize,
+    /// Steps needed to reveal every pipe point of every trace.
+    pub total_steps: usize,
+    /// True once stepping has moved from record traces to flush traces.
+    pub in_flush_phase: bool,
+    /// Index of the current record (or flush) trace.
+    pub trace_index: usize,
+    /// Pipe points of the current trace revealed so far, in order.
+    pub revealed_pipe_points: Vec<RevealedPipePoint>,
+    /// Records that have reached the end of the pipeline so far.
+    pub accumulated_output: Vec<String>,
+    /// Why the pipeline could not run; there are no steps when set.
+    pub error: Option<String>,
+}
+
+/// The records at one revealed pipe point.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevealedPipePoint {
+    /// Pipe point index: 0 is between the source and the first stage.
+    pub pipe_point: usize,
+    /// Records at the pipe point; empty where a stage dropped the record.
+    pub records: Vec<String>,
+}
+
+impl DebuggerSnapshot {
+    /// Run `pipeline` on `input` and step to `step` (clamped to the last step).
+    pub fn at(input: &str, pipeline: &str, step: usize) -> Self {
+        match execute_pipeline_debug(input, pipeline) {
+            Ok((_, _, _, trace)) => {
+                let mut cursor = TraceCursor::new(trace);
+                cursor.goto(step);
+                Self::from_cursor(input, pipeline, &cursor)
+            }
+            Err(e) => Self {
+                input: input.to_string(),
+                pipeline: pipeline.to_string(),
+                current_step: 0,
+                total_steps: 0,
+                in_flush_phase: false,
+                trace_index: 0,
+                revealed_pipe_points: Vec::new(),
+                accumulated_output: Vec::new(),
+                error: Some(e),
+            },
+        }
+    }
+
+    /// Project `cursor`, over the trace of `pipeline` run on `input`, into
+    /// a snapshot.
+    pub fn from_cursor(input: &str, pipeline: &str, cursor: &TraceCursor) -> Self {
+        let (in_flush_phase, trace_index) = match cursor.position() {
+            None => (false, 0),
+            Some(CursorPosition::Record { index, .. }) => (false, index),
+            Some(CursorPosition::Flush { index, .. }) => (true, index),
+        };
+        Self {
+            input: input.to_string(),
+            pipeline: pipeline.to_string(),
+            current_step: cursor.step(),
+            total_steps: cursor.total_steps(),
+            in_flush_phase,
+            trace_index,
+            revealed_pipe_points: cursor
+                .revealed_points()
+                .map(|pipe_point| RevealedPipePoint {
+                    pipe_point,
+                    records: texts(cursor.records_at(pipe_point)),
+                })
+                .collect(),
+            accumulated_output: texts(cursor.accumulated_output()),
+            error: None,
+        }
+    }
+}
+
+/// Records as text with trailing spaces trimmed.
+fn texts<'a>(records: impl IntoIterator<Item = &'a Record>) -> Vec<String> {
+    records
+        .into_iter()
+        .map(|r| r.as_str().trim_end().to_string())
+        .collect()
+}
+
+/// Record counts for one stage, as returned by [`run_pipeline_stats_json`].
+///
+/// Serialized as `{"stage", "name", "in", "out"}`; these names are the
+/// JSON shape, so they must not be renamed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageStats {
+    /// Stage index in the pipeline: 0 is the source stage.
+    pub stage: usize,
+    /// Stage name, e.g. `FILTER`.
+    pub name: String,
+    /// Records the stage received; 0 for the source stage.
+    #[serde(rename = "in")]
+    pub input: usize,
+    /// Records the stage emitted, flush output included.
+    #[serde(rename = "out")]
+    pub output: usize,
+}
+
+/// Run `pipeline` on `input` and count each stage's records.
+///
+/// Returns a JSON array of [`StageStats`], one per stage in pipeline order.
+/// No trace is kept, so this is cheaper than [`debugger_init`]. Throws if
+/// the pipeline cannot run.
+#[wasm_bindgen]
+pub fn run_pipeline_stats_json(input: &str, pipeline: &str) -> Result<String, JsError> {
+    stats_json(input, pipeline).map_err(|e| JsError::new(&e))
+}
+
+fn stats_json(input: &str, pipeline: &str) -> Result<String, String> {
+    let (_, rows) = execute_pipeline_rat_stats(input, pipeline)?;
+    let stats: Vec<StageStats> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(stage, (name, input, output))| StageStats {
+            stage,
+            name,
+            input,
+            output,
+        })
+        .collect();
+    serde_json::to_string(&stats).map_err(|e| e.to_string())
+}
+
+/// Start debugging `pipeline` on `input`.
+///
+/// Returns the step-0 [`DebuggerSnapshot`] as a JSON string. A pipeline
+/// that fails to run gives a snapshot with `error` set.
+#[wasm_bindgen]
+pub fn debugger_init(input: &str, pipeline: &str) -> JsValue {
+    JsValue::from_str(&init_json(input, pipeline))
+}
+
+/// Take one step from the JSON [`DebuggerSnapshot`] `state_json`.
+///
+/// Returns the next snapshot as a JSON string; at the last step the
+/// snapshot is returned unchanged. Throws if `state_json` is not a
+/// snapshot.
+#[wasm_bindgen]
+pub fn debugger_step(state_json: &str) -> Result<JsValue, JsError> {
+    step_json(state_json)
+        .map(|json| JsValue::from_str(&json))
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn init_json(input: &str, pipeline: &str) -> String {
+    to_json(&DebuggerSnapshot::at(input, pipeline, 0))
+}
+
+fn step_json(state_json: &str) -> Result<String, serde_json::Error> {
+    let state: DebuggerSnapshot = serde_json::from_str(state_json)?;
+    let next = DebuggerSnapshot::at(&state.input, &state.pipeline, state.current_step + 1);
+    Ok(to_json(&next))
+}
+
+fn to_json(snapshot: &DebuggerSnapshot) -> String {
+    // Strings, numbers and lists only, so serializing cannot fail
+    serde_json::to_string(snapshot).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "A1\nB2\nA3";
+    const PIPELINE: &str = "PIPE CONSOLE\n| LOCATE /A/\n| COUNT\n| CONSOLE\n?";
+
+    #[test]
+    fn test_snapshot_round_trips_through_json() {
+        let snapshot = DebuggerSnapshot::at(INPUT, PIPELINE, 2);
+        let json = to_json(&snapshot);
+        let parsed: DebuggerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+        assert_eq!(to_json(&parsed), json);
+    }
+
+    #[test]
+    fn test_serialized_shape() {
+        let value: serde_json::Value = serde_json::from_str(&init_json(INPUT, PIPELINE)).unwrap();
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "accumulated_output",
+                "current_step",
+                "error",
+                "in_flush_phase",
+                "input",
+                "pipeline",
+                "revealed_pipe_points",
+                "total_steps",
+                "trace_index",
+            ]
+        );
+        assert_eq!(value["current_step"], 0);
+        assert_eq!(value["revealed_pipe_points"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_json_steps_match_debugger_state() {
+        let mut json = init_json(INPUT, PIPELINE);
+        let total_steps = DebuggerSnapshot::at(INPUT, PIPELINE, 0).total_steps;
+        assert!(total_steps > 0);
+        for step in 1..=total_steps {
+            json = step_json(&json).unwrap();
+            let snapshot: DebuggerSnapshot = serde_json::from_str(&json).unwrap();
+            assert_eq!(snapshot.current_step, step);
+            assert_eq!(snapshot, DebuggerSnapshot::at(INPUT, PIPELINE, step));
+        }
+        let last: DebuggerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(last.accumulated_output, ["2"]);
+        // Stepping past the end stays on the last step
+        assert_eq!(step_json(&json).unwrap(), json);
+    }
+
+    #[test]
+    fn test_stats_json_for_filter_over_default_data() {
+        let pipeline = "PIPE CONSOLE\n| FILTER 18,10 = \"SALES\"\n| CONSOLE\n?";
+        let json = stats_json(crate::app::DEFAULT_INPUT, pipeline).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!([
+                {"stage": 0, "name": "CONSOLE", "in": 0, "out": 8},
+                {"stage": 1, "name": "FILTER", "in": 8, "out": 3},
+                {"stage": 2, "name": "CONSOLE", "in": 3, "out": 3},
+            ])
+        );
+        assert!(stats_json("A", "PIPE CONSOLE\n?").is_err());
+    }
+
+    #[test]
+    fn test_revealed_pipe_points_follow_the_record() {
+        // First record A1 passes LOCATE and is held by COUNT
+        let snapshot = DebuggerSnapshot::at(INPUT, PIPELINE, 2);
+        let points: Vec<(usize, Vec<String>)> = snapshot
+            .revealed_pipe_points
+            .into_iter()
+            .map(|pp| (pp.pipe_point, pp.records))
+            .collect();
+        assert_eq!(
+            points,
+            [(0, vec!["A1".to_string()]), (1, vec!["A1".to_string()])]
+        );
+        assert!(!snapshot.in_flush_phase);
+    }
+
+    #[test]
+    fn test_flush_from_last_stage_reaches_output() {
+        let snapshot = DebuggerSnapshot::at(INPUT, "PIPE CONSOLE\n| COUNT\n?", usize::MAX);
+        assert!(snapshot.in_flush_phase);
+        assert_eq!(snapshot.revealed_pipe_points.len(), 1);
+        assert_eq!(snapshot.revealed_pipe_points[0].pipe_point, 1);
+        assert_eq!(snapshot.accumulated_output, ["3"]);
+    }
+
+    #[test]
+    fn test_failed_pipeline_reports_error() {
+        let snapshot: DebuggerSnapshot =
+            serde_json::from_str(&init_json(INPUT, "PIPE CONSOLE\n| BOGUS\n?")).unwrap();
+        assert!(snapshot.error.is_some());
+        assert_eq!(snapshot.total_steps, 0);
+        let next: DebuggerSnapshot =
+            serde_json::from_str(&step_json(&to_json(&snapshot)).unwrap()).unwrap();
+        assert_eq!(next, snapshot);
+    }
+
+    #[test]
+    fn test_step_rejects_invalid_state() {
+        assert!(step_json("not json").is_err());
+        assert!(step_json("{\"input\": \"A1\"}").is_err());
+    }
+}
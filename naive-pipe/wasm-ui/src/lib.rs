@@ -7,6 +7,17 @@ mod app;
 mod components;
 mod debugger;
 mod dsl;
+#[cfg(feature = "json-api")]
+mod json_api;
+mod parsed;
+
+#[cfg(feature = "json-api")]
+pub use json_api::{
+    DebuggerSnapshot, RevealedPipePoint, StageStats, debugger_init, debugger_step,
+    run_pipeline_stats_json,
+};
+
+pub use parsed::ParsedPipeline;
 
 use wasm_bindgen::prelude::*;
 
@@ -0,0 +1,43 @@
+//! Parse-once pipeline handle exported to JavaScript.
+//!
+//! An editor that runs the pipeline on every keystroke of the input can
+//! parse the pipeline text once and re-run the cached commands, instead of
+//! going through `execute_pipeline` (which reparses) each time.
+
+use pipelines_rs::{Command, parse_commands};
+use wasm_bindgen::prelude::*;
+
+use crate::dsl::ui_options;
+
+/// A pipeline parsed once and runnable against any number of inputs.
+#[wasm_bindgen]
+pub struct ParsedPipeline {
+    commands: Vec<Command>,
+}
+
+#[wasm_bindgen]
+impl ParsedPipeline {
+    /// Parse pipeline text into a reusable handle.
+    ///
+    /// Errors carry the offending line, e.g. `Line 2: Unknown command: FOO`.
+    pub fn parse(text: &str) -> Result<ParsedPipeline, JsValue> {
+        let commands = parse_commands(text).map_err(|e| JsValue::from_str(&e))?;
+        Ok(ParsedPipeline { commands })
+    }
+
+    /// Run the cached commands on `input` with the record-at-a-time executor.
+    ///
+    /// Returns the output text. Runs are capped like the UI's own (see
+    /// [`crate::dsl::MAX_OUTPUT_RECORDS`]).
+    pub fn run(&self, input: &str) -> Result<String, JsValue> {
+        naive_pipe::execute_parsed_rat(&self.commands, input, &ui_options())
+            .map(|(output, _, _)| output)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Number of stages in the parsed pipeline.
+    #[wasm_bindgen(getter)]
+    pub fn stage_count(&self) -> usize {
+        self.commands.len()
+    }
+}
@@ -1,7 +1,9 @@
 //! CLI tool to run pipeline (.pipe) files against input data (batched executor).
 
 use clap::Parser;
-use pipelines_rs::execute_pipeline;
+use pipelines_rs::{
+    ExecuteOptions, execute_pipeline_with_options, split_header, validate_pipeline_text,
+};
 use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
@@ -15,7 +17,8 @@ struct Cli {
     pipeline: String,
 
     /// Input data file (80-byte fixed-width records, or /dev/stdin)
-    input: String,
+    #[arg(required_unless_present = "dry_run")]
+    input: Option<String>,
 
     /// Write output to file instead of stdout
     #[arg(short, long)]
@@ -24,6 +27,30 @@ struct Cli {
     /// Show paths, executor, and record counts on stderr
     #[arg(short, long)]
     verbose: bool,
+
+    /// Parse and validate the pipeline, list its stages, and exit
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Exit with an error if input records produce no output records
+    #[arg(long)]
+    fail_on_empty: bool,
+
+    /// Expand tabs in input to spaces with tab stops every N columns
+    #[arg(long, value_name = "N")]
+    expand_tabs: Option<usize>,
+
+    /// Exit with an error unless the last stage is CONSOLE or HOLE
+    #[arg(long)]
+    require_sink: bool,
+
+    /// Treat the first input line as a header: skip it (and show it with --verbose)
+    #[arg(long)]
+    skip_header: bool,
+
+    /// Join an input line ending in C with the next line into one record
+    #[arg(long, value_name = "C")]
+    continuation: Option<char>,
 }
 
 fn main() {
@@ -37,23 +64,51 @@ fn main() {
         }
     };
 
-    let input_text = match fs::read_to_string(&cli.input) {
+    if cli.dry_run {
+        match validate_pipeline_text(&pipeline_text) {
+            Ok(stages) => {
+                for (i, stage) in stages.iter().enumerate() {
+                    println!("{i:>3}  {:<10} {}", stage.name, stage.description);
+                }
+                return;
+            }
+            Err(e) => {
+                eprintln!("Pipeline error: {e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    // clap guarantees an input path unless --dry-run was given
+    let input = cli.input.unwrap_or_default();
+    let input_text = match fs::read_to_string(&input) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("Error reading input file '{}': {e}", cli.input);
+            eprintln!("Error reading input file '{input}': {e}");
             process::exit(1);
         }
     };
 
     if cli.verbose {
         eprintln!("Pipeline: {}", cli.pipeline);
-        eprintln!("Input:    {}", cli.input);
+        eprintln!("Input:    {input}");
         eprintln!("Output:   {}", cli.output.as_deref().unwrap_or("(stdout)"));
+        if cli.skip_header {
+            eprintln!("Header:   {}", split_header(&input_text).0.unwrap_or(""));
+        }
         eprintln!("Executor: batched");
     }
 
-    match execute_pipeline(&input_text, &pipeline_text) {
-        Ok((output, input_count, output_count)) => {
+    let options = ExecuteOptions {
+        fail_on_empty: cli.fail_on_empty,
+        expand_tabs: cli.expand_tabs,
+        require_explicit_sink: cli.require_sink,
+        skip_header: cli.skip_header,
+        continuation: cli.continuation,
+        ..ExecuteOptions::default()
+    };
+    match execute_pipeline_with_options(&input_text, &pipeline_text, &options) {
+        Ok((output, input_count, output_count, _)) => {
             if let Some(out_path) = &cli.output {
                 if let Some(parent) = Path::new(out_path.as_str()).parent()
                     && !parent.as_os_str().is_empty()
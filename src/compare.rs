@@ -0,0 +1,182 @@
+//! Compare two record streams and report their differences.
+//!
+//! Pipelines read a single input, so comparing two datasets (say, a
+//! transform's output before and after a change) lives here as plain
+//! functions over [`Record`]s rather than as a stage.
+//!
+//! Differences are reported `diff`-style: records from the left stream are
+//! prefixed with [`LEFT_MARKER`], records from the right with
+//! [`RIGHT_MARKER`]. Like [`crate::dsl::flag_record`], the prefix pushes the
+//! record right and its last columns are truncated at the record width.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::Record;
+
+/// Prefix for a record that appears only (or differently) in the left stream.
+pub const LEFT_MARKER: &str = "< ";
+
+/// Prefix for a record that appears only (or differently) in the right stream.
+pub const RIGHT_MARKER: &str = "> ";
+
+/// Compare two streams record by record, aligned by position.
+///
+/// Wherever the records at the same position differ, emits the left record
+/// marked `< ` followed by the right one marked `> `. Extra records at the
+/// end of the longer stream are emitted with that stream's marker. Identical
+/// streams produce no records.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{Record, execute_diff};
+///
+/// let left = vec![Record::from_str("A 1"), Record::from_str("B 2")];
+/// let right = vec![Record::from_str("A 1"), Record::from_str("B 3")];
+/// let diff: Vec<String> = execute_diff(left, right)
+///     .iter()
+///     .map(|r| r.as_str().trim_end().to_string())
+///     .collect();
+/// assert_eq!(diff, vec!["< B 2", "> B 3"]);
+/// ```
+pub fn execute_diff(left: Vec<Record>, right: Vec<Record>) -> Vec<Record> {
+    let mut out = Vec::new();
+    for index in 0..left.len().max(right.len()) {
+        match (left.get(index), right.get(index)) {
+            (Some(l), Some(r)) if l == r => {}
+            (l, r) => {
+                out.extend(l.map(|l| marked(LEFT_MARKER, l)));
+                out.extend(r.map(|r| marked(RIGHT_MARKER, r)));
+            }
+        }
+    }
+    out
+}
+
+/// Compare two streams, pairing records by the key in field `pos,len`.
+///
+/// Keys are compared trimmed; the nth left record with a key pairs with the
+/// nth right record with that key. Unlike [`execute_diff`], an inserted or
+/// deleted record is reported once instead of shifting every record after
+/// it. Changed pairs and left-only records are emitted in left order, then
+/// right-only records in right order.
+pub fn execute_diff_keyed(
+    left: Vec<Record>,
+    right: Vec<Record>,
+    pos: usize,
+    len: usize,
+) -> Vec<Record> {
+    let mut by_key: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (index, record) in right.iter().enumerate() {
+        by_key
+            .entry(record.field(pos, len).trim())
+            .or_default()
+            .push_back(index);
+    }
+
+    let mut paired = vec![false; right.len()];
+    let mut out = Vec::new();
+    for l in &left {
+        match by_key
+            .get_mut(l.field(pos, len).trim())
+            .and_then(VecDeque::pop_front)
+        {
+            Some(index) => {
+                paired[index] = true;
+                if *l != right[index] {
+                    out.push(marked(LEFT_MARKER, l));
+                    out.push(marked(RIGHT_MARKER, &right[index]));
+                }
+            }
+            None => out.push(marked(LEFT_MARKER, l)),
+        }
+    }
+    out.extend(
+        right
+            .iter()
+            .zip(&paired)
+            .filter(|(_, paired)| !**paired)
+            .map(|(r, _)| marked(RIGHT_MARKER, r)),
+    );
+    out
+}
+
+/// Prefix a record with a diff marker.
+fn marked(marker: &str, record: &Record) -> Record {
+    Record::from_str(&format!("{marker}{}", record.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::read_input_records;
+
+    const EMPLOYEES: &str = include_str!("../specs/input-fixed-80.data");
+
+    fn texts(records: &[Record]) -> Vec<String> {
+        records
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_reports_only_changed_lines() {
+        let left = read_input_records(EMPLOYEES, None);
+        let changed = EMPLOYEES.replace(
+            "CHEN    LISA      ENGINEER  00080000",
+            "CHEN    LISA      ENGINEER  00085000",
+        );
+        let right = read_input_records(&changed, None);
+        assert_eq!(
+            texts(&execute_diff(left, right)),
+            vec![
+                "< CHEN    LISA      ENGINEER  00080000",
+                "> CHEN    LISA      ENGINEER  00085000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_identical_and_uneven_streams() {
+        let records = read_input_records(EMPLOYEES, None);
+        assert!(execute_diff(records.clone(), records.clone()).is_empty());
+
+        let shorter = records[..6].to_vec();
+        let diff = execute_diff(records.clone(), shorter.clone());
+        assert_eq!(diff.len(), 2);
+        assert!(texts(&diff).iter().all(|t| t.starts_with("< ")));
+        let diff = execute_diff(shorter, records);
+        assert_eq!(texts(&diff)[0], "> TAYLOR  SUSAN     MARKETING 00065000");
+    }
+
+    #[test]
+    fn test_diff_keyed_survives_insertion() {
+        let left = read_input_records(EMPLOYEES, None);
+        let mut right = left.clone();
+        right.insert(1, Record::from_str("NEWMAN  ALFRED    SALES     00040000"));
+        right[5] = Record::from_str("CHEN    LISA      ENGINEER  00085000");
+
+        // Positional alignment reports every record after the insertion
+        assert_eq!(execute_diff(left.clone(), right.clone()).len(), 15);
+
+        assert_eq!(
+            texts(&execute_diff_keyed(left, right, 0, 8)),
+            vec![
+                "< CHEN    LISA      ENGINEER  00080000",
+                "> CHEN    LISA      ENGINEER  00085000",
+                "> NEWMAN  ALFRED    SALES     00040000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_keyed_duplicate_keys_pair_in_order() {
+        let left = vec![Record::from_str("K 1"), Record::from_str("K 2")];
+        let right = vec![Record::from_str("K 1"), Record::from_str("K 3")];
+        assert_eq!(
+            texts(&execute_diff_keyed(left, right, 0, 1)),
+            vec!["< K 2", "> K 3"]
+        );
+    }
+}
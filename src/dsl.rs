@@ -19,27 +19,87 @@
 //! - Any stage can be in the middle (CONSOLE passes through while printing)
 //! - Any stage can be last (output discarded if not a sink like CONSOLE)
 //!
+//! Field positions are 0-based columns. A negative position counts back from
+//! the end of the 80-column record: `-8,8` is the last 8 columns.
+//!
 //! Supported stages:
 //! - `CONSOLE` - Read from input (first), pass through (middle), or write to output (last)
 //! - `FILTER pos,len = "value"` - Keep records where field equals value
 //! - `FILTER pos,len != "value"` - Omit records where field equals value
+//! - `FILTER pos,len BETWEEN "low" AND "high"` - Keep records where field is in the inclusive range
+//! - `FILTER pos,len op FIELD pos,len` - Keep records where one field compares to another (`=`, `!=`, `<`, `<=`, `>`, `>=`)
+//! - `NORM pos,len` - Rewrite a numeric field as a canonical, right-justified integer
+//! - `OMIT ...` - Drop the records a FILTER with the same condition would keep
+//! - `ROUTE ... TO name [ELSE name]` - Send the records a FILTER would keep to a named output channel
+//! - `PIVOT key=pos,len value=pos,len [group=pos,len] KEY:dest ...` - Turn each group of key/value records into one wide record
 //! - `HOLE` - Discard all input, output nothing (like /dev/null)
-//! - `SELECT p1,l1,d1; p2,l2,d2; ...` - Select and reposition fields
+//! - `HOLE COUNT` - Discard all input, emit `DROPPED=n` summary record
+//! - `SELECT p1,l1,d1; p2,l2,d2; ...` - Select and reposition fields (later fields win overlaps)
+//! - `SELECT ... STRICT` - As SELECT, but overlapping destinations are an error
+//! - `SELECT ... SEP /s/` - As SELECT, with `s` written between fields in destination order
+//! - `FIELD pos,len [PAD]` - Emit just one field, trimmed (or with its padding), at column 0
 //! - `TAKE n` - Keep first n records
 //! - `SKIP n` - Skip first n records
+//! - `PAGE n,size` - Keep page n (1-based) of size records, like `SKIP | TAKE`
+//! - `ONCHANGE pos,len` - Keep a record only when a field differs from the previous record's
+//! - `RESEQ pos,len start,step [FILL c]` - Overwrite a field with a zero- (or `c`-) padded sequence number
+//! - `TOJSON name=pos,len ...` - Rewrite each record as a JSON object of trimmed fields
+//! - `SORT [pos,len [ASC|DESC] [NUM]; ...]` - Sort records by one or more keys (stable)
+//! - `SHUFFLE seed` - Randomize record order, reproducibly for a given seed
+//! - `TOPN n pos,len` - Keep the n records with the largest numeric field, largest first
 //! - `LOCATE "pattern"` - Keep records containing pattern (grep-like)
-//! - `LOCATE pos,len "pattern"` - Keep records where field contains pattern
+//! - `LOCATE pos,len [TRIM] "pattern"` - Keep records where field (padding included, or with TRIM excluded) contains pattern
+//! - `LOCATE [pos,len] ANY|ALL /p1/ /p2/ ...` - Keep records containing any (or all) of the patterns
 //! - `NLOCATE "pattern"` - Keep records NOT containing pattern
+//! - `CONTEXT /pattern/ n` or `before,after` - Keep records containing pattern plus neighboring records (like `grep -C`)
 //! - `COUNT` - Count records and emit count as a single record
+//! - `HASH` - Emit `HASH=<hex>`, an order-sensitive FNV-1a hash of all records
+//! - `STATS [PASSTHROUGH]` - Emit record count and min/max/average length summary records
 //! - `CHANGE "old" "new"` - Replace occurrences of old with new (sed-like)
+//! - `CHANGE /old/new/ FIRST|n` - Replace only the first (or first n) occurrences per record
+//! - `CHANGE /old/new/ REPEAT` - Reapply the replacement until the record stops changing
+//! - `CHANGE /old/new/ WHERE condition` - Replace only in records matching a FILTER condition
 //! - `LITERAL "text"` - Append a literal record to the stream
+//! - `GENERATE n /template/ [SEED s]` - Emit n records from a template with `{SEQ:w}` and `{RAND:w}` fields
+//! - `HEADER text` - Emit `text` before all records, expanding `{COUNT}` and `{DATE}`
 //! - `UPPER` - Convert records to uppercase
 //! - `LOWER` - Convert records to lowercase
 //! - `REVERSE` - Reverse characters in each record
+//! - `REVERSE FULL` - Reverse all 80 columns, padding included
 //! - `DUPLICATE n` - Repeat each record n times
+//! - `EXPAND pos,len [CLEAR]` - Repeat each record as many times as its count field says
+//! - `BLOCK n` - Emit a blank separator record after every n records
+//! - `SORTITEMS pos,len,width` - Sort the fixed-width items within a field
+//! - `SEGMENT n` - Emit a `--- SEGMENT k ---` header before each group of n records
+//! - `CHOP width` - Split each record's content into width-column records
+//! - `WRAP width [CONT col]` - Word-wrap each record's content into width-column records, marking continued pieces with `+` in column col
+//! - `JUSTIFY pos,len RIGHT|LEFT [FILL c]` - Re-justify a field within its columns, padding with `c`
+//! - `ASSERT pos,len NUMERIC|ALPHA|ALNUM|BLANK [STRICT]` - Flag (or with STRICT, drop) records whose field has the wrong type
+//! - `VERIFY pos,len TYPE; ...` - Replace records whose fields have the wrong types with diagnostics
+//! - `DATEFMT pos,len FROM fmt TO fmt [FLAG]` - Rewrite a date between `ymd`, `mdy`, `dmy` and `julian` layouts
+//! - `DECODE pos,len /code=text;.../ [DEFAULT /text/]` - Replace a field's code with its text from a lookup table
+//! - `DEFAULT pos,len /value/ [LEFT|RIGHT]` - Fill a blank field with a default value
+//! - `MASK pos,len; pos,len ... [FILL c]` - Overwrite fields with `*` (or `c`), e.g. to redact them
+//! - `EXTRACT /marker/ [DROP]` - Keep only the text after a marker
+//! - `LENGTH min,max` - Keep records whose trimmed length is in range
+//! - `FREQ pos,len` - Emit `<value> <count>` per distinct field value, most frequent first
+//! - `CROSSTAB pos,len BY pos,len` - Emit a table of counts, one row per value of the first field and one column per value of the second
+//! - `BUILD pos,len /lit/ pos,len ...` - Concatenate trimmed fields and literals
+//! - `ZIP` - Combine records pairwise, side by side (two-up layout)
+//! - `APPLY name` - Rewrite each record with a named transform (see [`crate::transform`])
+//! - `INSERT EVERY n /text/ [TRAILING]` - Emit a text record after every nth record
+//! - `INCLUDE name` - Splice in the stages of a named sub-pipeline (see [`crate::include`])
 //! - Lines starting with `#` are comments
+//! - A line starting with `#!` is a disabled stage: it keeps its place in the
+//!   pipeline as a pass-through (`Command::Disabled`) so stage indices don't shift
+//! - `# LAYOUT name pos,len; ...` declares the record field layout (see [`parse_layout`])
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::{Pipeline, Record};
+use crate::include::{MAX_INCLUDE_DEPTH, PipelineRegistry};
+use crate::transform::{TransformFn, TransformRegistry};
+use crate::{Pipeline, PipelineError, RECORD_WIDTH, Record};
 
 /// Callback type for stage start events: `(stage_index, stage_name)`.
 type StageStartCallback = Box<dyn Fn(usize, &str) + 'static>;
@@ -97,53 +157,650 @@ impl DebugInfo {
     }
 }
 
-/// Execute a pipeline defined by DSL text on input records.
+/// Options controlling pipeline execution.
+#[derive(Debug, Clone, Default)]
+pub struct ExecuteOptions {
+    /// Collect a `TruncationWarning` for each input line longer than the
+    /// record width. Off by default (lines are silently truncated).
+    pub warn_truncation: bool,
+    /// Fail with `PipelineError::EmptyOutput` when the source produced
+    /// records but the pipeline output none, e.g. because of a misconfigured
+    /// filter. Off by default (empty output is a normal result).
+    pub fail_on_empty: bool,
+    /// Transforms available to `APPLY`. Defaults to the built-ins.
+    pub transforms: TransformRegistry,
+    /// Expand tab characters in input lines to spaces, with tab stops
+    /// every `n` columns, before they become records. `None` (the default)
+    /// keeps each tab as a single raw byte, which shifts later columns.
+    pub expand_tabs: Option<usize>,
+    /// Fail with `PipelineError::NoSink` unless the last stage is `CONSOLE`
+    /// or `HOLE`. Off by default (any stage can be last).
+    pub require_explicit_sink: bool,
+    /// Fail with `PipelineError::OutputLimitExceeded` once any stage
+    /// outputs more than this many records, even if a later stage would
+    /// drop some. Both executors count records as they are made and stop
+    /// at the first one past the limit, so a runaway `DUPLICATE` or
+    /// `EXPAND` can't hang a browser tab. `None` (the default) is
+    /// unlimited.
+    pub max_output_records: Option<usize>,
+    /// Drop the first input record (a line of column names, say) before a
+    /// `CONSOLE` source reads the rest. The header is not processed and is
+    /// not counted in `input_count`; [`split_header`] recovers it. Off by
+    /// default.
+    pub skip_header: bool,
+    /// Sub-pipelines available to `INCLUDE`. Empty by default.
+    pub pipelines: PipelineRegistry,
+    /// Fail with `PipelineError::FieldOutOfBounds`, before any input is
+    /// read, when a stage's `pos,len` field (see [`Command::field_specs`])
+    /// runs past the record width. Off by default (fields are clamped).
+    pub strict_bounds: bool,
+    /// Join an input line that ends with this character to the line after
+    /// it, so one record can span several lines (see
+    /// [`join_continuations`]). `None` (the default) reads one record per
+    /// line.
+    pub continuation: Option<char>,
+    /// Fail with `PipelineError::ExpansionLimitExceeded` once any stage has
+    /// output more than this many records per input record it received
+    /// (see [`check_expansion`]), catching runaway `DUPLICATE` or `EXPAND`
+    /// stages. The RAT executor checks as records flow; the batch executor
+    /// checks each stage's finished output. `None` (the default) is
+    /// unlimited.
+    pub expansion_factor_limit: Option<usize>,
+}
+
+impl ExecuteOptions {
+    /// Apply the `require_explicit_sink` check to a parsed pipeline.
+    pub fn check_sink(&self, commands: &[Command]) -> Result<(), String> {
+        if self.require_explicit_sink
+            && let Some(last) = commands.last()
+            && !matches!(last, Command::Console | Command::Hole { .. })
+        {
+            let last = last.name().to_string();
+            return Err(PipelineError::NoSink { last }.to_string());
+        }
+        Ok(())
+    }
+
+    /// Apply the `strict_bounds` check to a parsed pipeline.
+    pub fn check_bounds(&self, commands: &[Command]) -> Result<(), String> {
+        if !self.strict_bounds {
+            return Ok(());
+        }
+        for cmd in commands {
+            if let Some((pos, len)) = cmd
+                .field_specs()
+                .into_iter()
+                .find(|(pos, len)| pos + len > RECORD_WIDTH)
+            {
+                let stage = cmd.name().to_string();
+                return Err(PipelineError::FieldOutOfBounds { stage, pos, len }.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Read `CONSOLE` input into records, honoring `skip_header`,
+    /// `continuation` and `expand_tabs`.
+    pub fn console_records(&self, input_text: &str) -> Vec<Record> {
+        self.console_lines(input_text)
+            .into_iter()
+            .map(|(_, line)| Record::from_str(&line))
+            .collect()
+    }
+
+    /// Find the `CONSOLE` records that were truncated to fit the record
+    /// width.
+    ///
+    /// Lines are measured as [`console_records`](Self::console_records)
+    /// reads them: after the header is skipped, continuations are joined
+    /// and tabs are expanded. A joined record reports the source line it
+    /// starts on, counted from 1 including blank and header lines.
+    pub fn truncation_warnings(&self, input_text: &str) -> Vec<TruncationWarning> {
+        self.console_lines(input_text)
+            .into_iter()
+            .map(|(line_number, line)| (line_number, line.chars().count()))
+            .filter(|&(_, len)| len > RECORD_WIDTH)
+            .map(|(line_number, original_len)| TruncationWarning {
+                line_number,
+                original_len,
+            })
+            .collect()
+    }
+
+    /// The lines that become `CONSOLE` records, each with the 1-based
+    /// source line it starts on. Matches [`split_header`],
+    /// [`join_continuations`] and [`read_input_records`].
+    fn console_lines(&self, input_text: &str) -> Vec<(usize, String)> {
+        let mut lines = input_lines(input_text)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line))
+            .peekable();
+        if self.skip_header {
+            // The header is the first non-empty line
+            lines.find(|(_, line)| !line.is_empty());
+        }
+        let mut out = Vec::new();
+        while let Some((line_number, line)) = lines.next() {
+            let mut text = line.to_string();
+            if let Some(marker) = self.continuation {
+                while lines.peek().is_some()
+                    && let Some(start) = text.strip_suffix(marker)
+                {
+                    text.truncate(start.len());
+                    text.push_str(lines.next().unwrap().1);
+                }
+            }
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(width) = self.expand_tabs {
+                text = expand_tabs(&text, width);
+            }
+            out.push((line_number, text));
+        }
+        out
+    }
+
+    /// Apply the `max_output_records` check to an output record count.
+    pub fn check_limit(&self, output_count: usize) -> Result<(), String> {
+        match self.max_output_records {
+            Some(limit) if output_count > limit => {
+                Err(PipelineError::OutputLimitExceeded { limit }.to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the `fail_on_empty` check to a finished run's record counts.
+    pub fn check_output(&self, input_count: usize, output_count: usize) -> Result<(), String> {
+        if self.fail_on_empty && input_count > 0 && output_count == 0 {
+            return Err(PipelineError::EmptyOutput { input_count }.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An input line that was truncated to fit the record width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncationWarning {
+    /// 1-based line number in the input text.
+    pub line_number: usize,
+    /// Length of the line in characters before truncation.
+    pub original_len: usize,
+}
+
+/// Split input text into lines, accepting any line-ending style.
 ///
-/// Returns (output_text, input_count, output_count) on success.
-pub fn execute_pipeline(
-    input_text: &str,
-    pipeline_text: &str,
-) -> Result<(String, usize, usize), String> {
-    // Parse pipeline commands
-    let commands = parse_commands(pipeline_text)?;
+/// `\n`, `\r\n` and a lone `\r` each end a line, and a final line without
+/// a terminator is kept. A terminator at the very end does not start an
+/// extra empty line. Every reader of input text goes through this, so the
+/// batch and RAT executors always see the same lines.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::input_lines;
+///
+/// assert_eq!(input_lines("A\r\nB\rC\nD"), vec!["A", "B", "C", "D"]);
+/// assert_eq!(input_lines("A\n\nB\n"), vec!["A", "", "B"]);
+/// ```
+pub fn input_lines(input_text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut rest = input_text;
+    while let Some((line, after)) = split_first_line(rest) {
+        lines.push(line);
+        rest = after;
+    }
+    lines
+}
 
-    // Validate pipeline structure
-    if commands.is_empty() {
-        return Err("Pipeline is empty".to_string());
+/// Split off the first line of `text` and the text after its terminator,
+/// using the line endings of [`input_lines`]. Returns `None` for empty text.
+fn split_first_line(text: &str) -> Option<(&str, &str)> {
+    if text.is_empty() {
+        return None;
+    }
+    let Some(end) = text.find(['\n', '\r']) else {
+        return Some((text, ""));
+    };
+    let terminator = if text[end..].starts_with("\r\n") {
+        2
+    } else {
+        1
+    };
+    Some((&text[..end], &text[end + terminator..]))
+}
+
+/// Split input text into its header line and the text after it.
+///
+/// The header is the first non-empty line, i.e. the record that
+/// `ExecuteOptions::skip_header` drops. Returns `None` and empty text when
+/// the input has no records.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::split_header;
+///
+/// let (header, body) = split_header("NAME    DEPT\nSMITH   SALES\n");
+/// assert_eq!(header, Some("NAME    DEPT"));
+/// assert_eq!(body, "SMITH   SALES\n");
+/// ```
+pub fn split_header(input_text: &str) -> (Option<&str>, &str) {
+    let mut rest = input_text;
+    while let Some((line, after)) = split_first_line(rest) {
+        if !line.is_empty() {
+            return (Some(line), after);
+        }
+        rest = after;
+    }
+    (None, rest)
+}
+
+/// Read `CONSOLE` input text into records, one per non-empty line.
+///
+/// Lines are split by [`input_lines`]. With `expand_tabs` set, tabs are
+/// first expanded to the given tab stops (see [`expand_tabs`]).
+pub fn read_input_records(input_text: &str, expand_tabs_width: Option<usize>) -> Vec<Record> {
+    input_lines(input_text)
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| match expand_tabs_width {
+            Some(width) => Record::from_str(&expand_tabs(line, width)),
+            None => Record::from_str(line),
+        })
+        .collect()
+}
+
+/// Join each line of `input_text` that ends with `marker` to the line after
+/// it, dropping the marker, so one logical record can span several lines.
+///
+/// Lines are split by [`input_lines`] and joined with `\n`. A marker on the
+/// last line has nothing to join and is kept.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::join_continuations;
+///
+/// assert_eq!(join_continuations("A \\\nB\nC\\", '\\'), "A B\nC\\\n");
+/// ```
+pub fn join_continuations(input_text: &str, marker: char) -> String {
+    let mut out = String::with_capacity(input_text.len());
+    let mut lines = input_lines(input_text).into_iter().peekable();
+    while let Some(line) = lines.next() {
+        match line.strip_suffix(marker) {
+            Some(start) if lines.peek().is_some() => out.push_str(start),
+            _ => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Replace each tab with spaces up to the next multiple of `tab_width`.
+///
+/// Columns are counted in characters from the start of the line. A
+/// `tab_width` of 0 removes tabs.
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            if tab_width > 0 {
+                let spaces = tab_width - column % tab_width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+        } else {
+            out.push(c);
+            column += 1;
+        }
     }
+    out
+}
+
+/// Find input lines longer than the record width.
+///
+/// These lines are truncated when read by a `CONSOLE` source with default
+/// options; see [`ExecuteOptions::truncation_warnings`] for the others.
+pub fn truncation_warnings(input_text: &str) -> Vec<TruncationWarning> {
+    ExecuteOptions::default().truncation_warnings(input_text)
+}
+
+/// Apply the `expansion_factor_limit` check to one stage's record counts.
+///
+/// `stage_index` counts the stages after the source from 0. A stage that
+/// has seen no input may still output `limit` records, so a COUNT or
+/// LITERAL on empty input passes any limit of 1 or more.
+pub fn check_expansion(
+    stage_index: usize,
+    stage: &str,
+    inputs: usize,
+    outputs: usize,
+    limit: usize,
+) -> Result<(), PipelineError> {
+    if outputs > limit.saturating_mul(inputs.max(1)) {
+        return Err(PipelineError::ExpansionLimitExceeded {
+            stage_index,
+            stage: stage.to_string(),
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Check pipeline structure: at least two stages, and a source stage first.
+///
+/// Any stage can be last - if not a sink, output is simply discarded.
+/// Any stage can be in the middle - CONSOLE passes through while printing.
+fn validate_commands(commands: &[Command]) -> Result<(), String> {
+    let Some(first) = commands.first() else {
+        return Err("Pipeline is empty".to_string());
+    };
 
     // Need at least 2 stages (source and something to receive output)
     if commands.len() < 2 {
         return Err("Pipeline must have at least 2 stages".to_string());
     }
 
-    // Check first stage can be first (source)
-    let first = commands.first().unwrap();
     if !first.can_be_first() {
         return Err(format!(
             "{} cannot be the first stage (try CONSOLE, LITERAL, or HOLE)",
             first.name()
         ));
     }
+    check_includes_expanded(commands)
+}
+
+/// Reject commands that still hold an `INCLUDE` stage.
+///
+/// Parsing a pipeline expands every `INCLUDE`; only the stage lists built by
+/// [`parse_snippet`] keep them, and those are meant for a
+/// [`PipelineRegistry`], not for running directly.
+pub fn check_includes_expanded(commands: &[Command]) -> Result<(), String> {
+    match commands.iter().find_map(|c| match c {
+        Command::Include { name } => Some(name),
+        _ => None,
+    }) {
+        Some(name) => Err(format!(
+            "INCLUDE {name} was not expanded (parse the pipeline with a PipelineRegistry)"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Summary of one parsed stage, produced by [`validate_pipeline_text`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageSummary {
+    /// Stage command name, e.g. `FILTER`.
+    pub name: &'static str,
+    /// One-sentence description of the stage (see [`Command::describe`]).
+    pub description: String,
+}
+
+/// Parse and validate a pipeline without executing it.
+///
+/// Runs the same parsing and structural checks as [`execute_pipeline`] but
+/// reads no input, so editors and linters can show pipeline structure and
+/// report errors before a run.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::validate_pipeline_text;
+///
+/// let stages = validate_pipeline_text("PIPE CONSOLE | TAKE 3 | CONSOLE").unwrap();
+/// assert_eq!(stages.len(), 3);
+/// assert_eq!(stages[1].name, "TAKE");
+/// assert_eq!(stages[1].description, "Keep only the first 3 records");
+/// assert!(validate_pipeline_text("PIPE TAKE 3 | CONSOLE").is_err());
+/// ```
+pub fn validate_pipeline_text(pipeline_text: &str) -> crate::error::Result<Vec<StageSummary>> {
+    let commands = parse_commands(pipeline_text).map_err(PipelineError::Parse)?;
+    validate_commands(&commands).map_err(PipelineError::Parse)?;
+    Ok(commands
+        .iter()
+        .map(|cmd| StageSummary {
+            name: cmd.name(),
+            description: cmd.describe(),
+        })
+        .collect())
+}
+
+/// Explain a pipeline in plain English, one sentence per stage.
+///
+/// Each sentence is the stage's [`Command::describe`] text, with columns
+/// 1-based as they appear on a ruler (field `18,10` is "columns 19–28"),
+/// and CONSOLE described by where it sits in the pipeline.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::explain;
+///
+/// let lines = explain("PIPE CONSOLE | FILTER 18,10 = \"SALES\" | CONSOLE").unwrap();
+/// assert_eq!(lines[1], "Keep records where columns 19–28 equal 'SALES'.");
+/// ```
+pub fn explain(pipeline_text: &str) -> crate::error::Result<Vec<String>> {
+    let commands = parse_commands(pipeline_text).map_err(PipelineError::Parse)?;
+    validate_commands(&commands).map_err(PipelineError::Parse)?;
+    let last = commands.len() - 1;
+    Ok(commands
+        .iter()
+        .enumerate()
+        .map(|(i, cmd)| explain_command(cmd, i, last))
+        .collect())
+}
+
+/// 1-based column range of the field at `pos,len`, e.g. "columns 19–28".
+fn columns(pos: usize, len: usize) -> String {
+    match len {
+        0 => format!("no columns (at column {})", pos + 1),
+        1 => format!("column {}", pos + 1),
+        _ => format!("columns {}–{}", pos + 1, pos + len),
+    }
+}
+
+/// One sentence describing `cmd` as stage `index` of a pipeline whose last
+/// stage is `last`: [`Command::describe`], with CONSOLE's role spelled out.
+fn explain_command(cmd: &Command, index: usize, last: usize) -> String {
+    let sentence = match cmd {
+        Command::Console if index == 0 => "Read records from the input".to_string(),
+        Command::Console if index == last => "Write records to the output".to_string(),
+        Command::Console => "Pass records through, showing them on the console".to_string(),
+        _ => cmd.describe(),
+    };
+    format!("{sentence}.")
+}
+
+/// Execute a pipeline defined by DSL text on input records.
+///
+/// Returns (output_text, input_count, output_count) on success.
+pub fn execute_pipeline(
+    input_text: &str,
+    pipeline_text: &str,
+) -> Result<(String, usize, usize), String> {
+    let (output_text, input_count, output_count, _) =
+        execute_pipeline_with_options(input_text, pipeline_text, &ExecuteOptions::default())?;
+    Ok((output_text, input_count, output_count))
+}
+
+/// Line separating the pipeline from its input in a combined document
+/// (see [`parse_document`]).
+pub const DOCUMENT_INPUT_MARKER: &str = "=== INPUT ===";
+
+/// Split a combined document into its pipeline text and input text.
+///
+/// The first line that is exactly [`DOCUMENT_INPUT_MARKER`], starting in
+/// column 0 (trailing spaces and `\r` are ignored), ends the pipeline.
+/// Everything after that line is the input, unchanged, so later marker lines
+/// are input records. A document without a marker line is all pipeline,
+/// with empty input. Fails if there is no pipeline text before the marker.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::parse_document;
+///
+/// let doc = "PIPE CONSOLE | TAKE 1 | CONSOLE\n=== INPUT ===\nA\nB\n";
+/// let (pipeline, input) = parse_document(doc).unwrap();
+/// assert_eq!(pipeline, "PIPE CONSOLE | TAKE 1 | CONSOLE\n");
+/// assert_eq!(input, "A\nB\n");
+/// ```
+pub fn parse_document(text: &str) -> Result<(String, String), PipelineError> {
+    let mut offset = 0;
+    let mut split = None;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == DOCUMENT_INPUT_MARKER {
+            split = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let (pipeline, input) = match split {
+        Some((start, end)) => (&text[..start], &text[end..]),
+        None => (text, ""),
+    };
+    if pipeline.trim().is_empty() {
+        return Err(PipelineError::Parse(format!(
+            "document has no pipeline before '{DOCUMENT_INPUT_MARKER}'"
+        )));
+    }
+    Ok((pipeline.to_string(), input.to_string()))
+}
+
+/// Execute a combined document (see [`parse_document`]): its pipeline on
+/// its input.
+///
+/// Returns (output_text, input_count, output_count) on success.
+pub fn execute_document(text: &str) -> Result<(String, usize, usize), String> {
+    let (pipeline, input) = parse_document(text).map_err(|e| e.to_string())?;
+    execute_pipeline(&input, &pipeline)
+}
+
+/// Execute a pipeline with explicit execution options.
+///
+/// Returns (output_text, input_count, output_count, truncation_warnings)
+/// on success. Warnings are only collected when
+/// `options.warn_truncation` is set and the source stage is `CONSOLE`.
+pub fn execute_pipeline_with_options(
+    input_text: &str,
+    pipeline_text: &str,
+    options: &ExecuteOptions,
+) -> Result<(String, usize, usize, Vec<TruncationWarning>), String> {
+    let commands = parse_commands_with_options(pipeline_text, options)?;
+    execute_parsed_with_options(&commands, input_text, options)
+}
+
+/// Execute a pipeline with explicit options, handing each output record to
+/// `on_record` as it becomes available.
+///
+/// Returns the same as [`execute_pipeline_with_options`]. The batch
+/// executor finishes every stage before the last one produces anything, so
+/// `on_record` is called for all output records, in order, once the run has
+/// succeeded; it is not called when the run fails. A UI that renders from
+/// the callback will keep working if records later start arriving
+/// incrementally.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{ExecuteOptions, execute_pipeline_with_sink};
+///
+/// let mut seen = Vec::new();
+/// let (output, _, _, _) = execute_pipeline_with_sink(
+///     "A1\nB2\nA3",
+///     "PIPE CONSOLE | LOCATE /A/ | CONSOLE",
+///     &ExecuteOptions::default(),
+///     |record| seen.push(record.as_str().trim_end().to_string()),
+/// )
+/// .unwrap();
+/// assert_eq!(seen, ["A1", "A3"]);
+/// assert_eq!(output, "A1\nA3");
+/// ```
+pub fn execute_pipeline_with_sink(
+    input_text: &str,
+    pipeline_text: &str,
+    options: &ExecuteOptions,
+    on_record: impl FnMut(&Record),
+) -> Result<(String, usize, usize, Vec<TruncationWarning>), String> {
+    let commands = parse_commands_with_options(pipeline_text, options)?;
+    execute_parsed_with_sink(&commands, input_text, options, on_record)
+}
+
+/// Execute an already-parsed pipeline on input records.
+///
+/// Callers that run the same pipeline many times (editors, watch loops)
+/// can parse once with [`parse_commands`] and skip reparsing on every run.
+/// Returns (output_text, input_count, output_count) on success.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{execute_parsed, parse_commands};
+///
+/// let commands = parse_commands("PIPE CONSOLE | TAKE 1 | CONSOLE").unwrap();
+/// let (output, _, _) = execute_parsed(&commands, "A\nB").unwrap();
+/// assert_eq!(output, "A");
+/// let (output, _, _) = execute_parsed(&commands, "C\nD").unwrap();
+/// assert_eq!(output, "C");
+/// ```
+pub fn execute_parsed(
+    commands: &[Command],
+    input_text: &str,
+) -> Result<(String, usize, usize), String> {
+    let (output_text, input_count, output_count, _) =
+        execute_parsed_with_options(commands, input_text, &ExecuteOptions::default())?;
+    Ok((output_text, input_count, output_count))
+}
+
+/// Execute an already-parsed pipeline with explicit execution options.
+///
+/// Same as [`execute_pipeline_with_options`] minus the parse step, so
+/// `options.transforms` and `options.pipelines` are not consulted here:
+/// transforms and includes are resolved when the commands are parsed.
+pub fn execute_parsed_with_options(
+    commands: &[Command],
+    input_text: &str,
+    options: &ExecuteOptions,
+) -> Result<(String, usize, usize, Vec<TruncationWarning>), String> {
+    execute_parsed_with_sink(commands, input_text, options, |_| {})
+}
 
-    // Any stage can be last - if not a sink, output is simply discarded
-    // Any stage can be in the middle - CONSOLE passes through while printing
+/// Execute an already-parsed pipeline like [`execute_parsed_with_options`],
+/// handing each output record to `on_record` as in
+/// [`execute_pipeline_with_sink`].
+pub fn execute_parsed_with_sink(
+    commands: &[Command],
+    input_text: &str,
+    options: &ExecuteOptions,
+    mut on_record: impl FnMut(&Record),
+) -> Result<(String, usize, usize, Vec<TruncationWarning>), String> {
+    validate_commands(commands)?;
+    options.check_sink(commands)?;
+    options.check_bounds(commands)?;
+    let first = commands.first().unwrap();
 
     // Get initial records based on first stage type
     let input_records: Vec<Record> = match first {
         Command::Console => {
             // CONSOLE reads from input text
-            input_text
-                .lines()
-                .filter(|line| !line.is_empty())
-                .map(Record::from_str)
-                .collect()
+            options.console_records(input_text)
         }
         Command::Literal { text } => {
             // LITERAL generates a single record
             vec![Record::from_str(text)]
         }
-        Command::Hole => {
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => generate_records(*count, template, *seed),
+        Command::Hole { .. } => {
             // HOLE generates an empty stream
             vec![]
         }
@@ -154,13 +811,21 @@ pub fn execute_pipeline(
     };
 
     let input_count = input_records.len();
+    let warnings = if options.warn_truncation && matches!(first, Command::Console) {
+        options.truncation_warnings(input_text)
+    } else {
+        vec![]
+    };
 
     // Apply all commands after the first (source)
     // Any stage can be last - it transforms and the result is output
     let remaining_commands = &commands[1..];
-    let output_records = apply_commands(input_records, remaining_commands)?;
+    let output_records = apply_commands(input_records, remaining_commands, options)?;
 
     let output_count = output_records.len();
+    options.check_limit(output_count)?;
+    options.check_output(input_count, output_count)?;
+    output_records.iter().for_each(&mut on_record);
 
     // Format output (CONSOLE writes to output)
     let output_text = output_records
@@ -169,7 +834,7 @@ pub fn execute_pipeline(
         .collect::<Vec<_>>()
         .join("\n");
 
-    Ok((output_text, input_count, output_count))
+    Ok((output_text, input_count, output_count, warnings))
 }
 
 /// Execute a pipeline with debug callbacks for stage-by-stage inspection.
@@ -181,33 +846,20 @@ pub fn execute_pipeline_debug(
     debug: &Option<DebugCallbacks>,
 ) -> Result<(String, usize, usize, Vec<DebugInfo>), String> {
     let commands = parse_commands(pipeline_text)?;
-
-    if commands.is_empty() {
-        return Err("Pipeline is empty".to_string());
-    }
-
-    if commands.len() < 2 {
-        return Err("Pipeline must have at least 2 stages".to_string());
-    }
-
+    validate_commands(&commands)?;
     let first = commands.first().unwrap();
-    if !first.can_be_first() {
-        return Err(format!(
-            "{} cannot be first stage (try CONSOLE, LITERAL, or HOLE)",
-            first.name()
-        ));
-    }
 
     let input_records: Vec<Record> = match first {
-        Command::Console => input_text
-            .lines()
-            .filter(|line| !line.is_empty())
-            .map(Record::from_str)
-            .collect(),
+        Command::Console => read_input_records(input_text, None),
         Command::Literal { text } => {
             vec![Record::from_str(text)]
         }
-        Command::Hole => {
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => generate_records(*count, template, *seed),
+        Command::Hole { .. } => {
             vec![]
         }
         _ => {
@@ -264,7 +916,7 @@ pub fn execute_pipeline_debug(
         let input_count_stage = current_records.len();
         let input_records_clone = debug.as_ref().map(|_| current_records.clone());
 
-        current_records = apply_command(current_records, cmd)?;
+        current_records = apply_command(current_records, cmd, usize::MAX)?;
 
         let output_count_stage = current_records.len();
         let output_records_clone = debug.as_ref().map(|_| current_records.clone());
@@ -317,8 +969,37 @@ pub enum Command {
         len: usize,
         value: String,
     },
+    /// FILTER pos,len BETWEEN "low" AND "high" - inclusive, numeric when possible
+    FilterRange {
+        pos: usize,
+        len: usize,
+        low: String,
+        high: String,
+    },
+    /// FILTER pos,len op FIELD pos,len - compare two fields, numeric when possible
+    FilterFieldCmp {
+        left: (usize, usize),
+        right: (usize, usize),
+        op: CompareOp,
+    },
     /// SELECT p1,l1,d1; p2,l2,d2; ...
-    Select { fields: Vec<(usize, usize, usize)> },
+    ///
+    /// Fields are written in order, so where destinations overlap the later
+    /// field wins. With a `SEP /s/` clause, fields are laid out in
+    /// destination order with `s` between them (see [`select_separated`]).
+    Select {
+        fields: Vec<(usize, usize, usize)>,
+        separator: Option<String>,
+    },
+    /// FIELD pos,len [PAD] - emit only one field, moved to column 0
+    ///
+    /// The field is trimmed unless `trim` is false (`PAD`).
+    Field { pos: usize, len: usize, trim: bool },
+    /// TOJSON name=pos,len ... - rewrite each record as a JSON object
+    ///
+    /// Each `(name, pos, len)` becomes a string member holding the trimmed
+    /// field (see [`json_record`]).
+    ToJson { fields: Vec<(String, usize, usize)> },
     /// TAKE n
     Take { n: usize },
     /// SKIP n
@@ -328,6 +1009,23 @@ pub enum Command {
         pattern: String,
         /// Optional field restriction (pos, len)
         field: Option<(usize, usize)>,
+        /// Search the field without its padding (`LOCATE pos,len TRIM`)
+        trim: bool,
+    },
+    /// LOCATE ANY|ALL /p1/ /p2/ ... - keep records containing any (or all)
+    /// of several patterns
+    LocateMulti {
+        patterns: Vec<String>,
+        mode: MatchMode,
+        /// Optional field restriction (pos, len)
+        field: Option<(usize, usize)>,
+    },
+    /// CONTEXT /pattern/ n|before,after - records containing pattern plus
+    /// up to `before` records ahead of and `after` records behind each one
+    Context {
+        pattern: String,
+        before: usize,
+        after: usize,
     },
     /// NLOCATE "pattern" - keep records NOT containing pattern
     Nlocate {
@@ -337,841 +1035,8839 @@ pub enum Command {
     },
     /// COUNT - count records and emit summary
     Count,
+    /// HASH - emit one `HASH=<hex>` record hashing every record seen
+    /// (see [`RecordHasher`])
+    Hash,
+    /// STATS [PASSTHROUGH] - emit record count and length summary records
+    /// (see [`LengthStats`]), after the records themselves with PASSTHROUGH
+    Stats { passthrough: bool },
     /// CHANGE "old" "new" - replace occurrences
-    Change { old: String, new: String },
+    ///
+    /// With a `limit`, only the first `limit` occurrences in each record
+    /// are replaced (see [`change_text`]). With `repeat`, the substitution
+    /// is re-applied until the record stops changing (see
+    /// [`change_until_stable`]).
+    Change {
+        old: String,
+        new: String,
+        limit: Option<usize>,
+        repeat: bool,
+    },
+    /// CHANGE "old" "new" WHERE condition - replace every occurrence, but
+    /// only in records a FILTER `condition` would keep; every record is
+    /// passed on
+    ChangeWhere {
+        condition: Box<Command>,
+        old: String,
+        new: String,
+    },
     /// LITERAL "text" - append a literal record
     Literal { text: String },
+    /// GENERATE n /template/ [SEED s] - emit n records expanded from a
+    /// template, see [`generate_records`]
+    Generate {
+        count: usize,
+        template: String,
+        seed: u64,
+    },
     /// UPPER - convert to uppercase
     Upper,
     /// LOWER - convert to lowercase
     Lower,
-    /// REVERSE - reverse characters in record
-    Reverse,
+    /// REVERSE - reverse characters in record, ignoring trailing padding
+    /// REVERSE FULL - reverse the whole 80-column record, padding included
+    Reverse { full: bool },
     /// DUPLICATE n - repeat each record n times
     Duplicate { n: usize },
+    /// A stage line commented out with `#!`; passes records through unchanged.
+    ///
+    /// `original` is the stage text after the prefix, kept unparsed.
+    Disabled { original: String },
+    /// BLOCK n - emit a blank separator record after every n records
+    Block { n: usize },
+    /// SEGMENT n - emit a numbered header (see [`segment_header`]) before
+    /// each group of n records
+    Segment { n: usize },
+    /// HEADER text - emit `text` before all records, with `{COUNT}` and
+    /// `{DATE}` expanded (see [`expand_header`])
+    Header { template: String },
+    /// APPLY name - rewrite each record with a named transform, resolved
+    /// from a [`TransformRegistry`] at parse time
+    Apply {
+        name: String,
+        transform: TransformFn,
+    },
+    /// CHOP width - split each record's content into width-column records
+    Chop { width: usize },
     /// HOLE - discard all input, output nothing (like /dev/null)
-    Hole,
+    /// HOLE COUNT - discard all input, emit `DROPPED=n` on completion
+    Hole { count: bool },
+    /// JUSTIFY pos,len RIGHT|LEFT [FILL c] - re-justify a field within its columns
+    Justify {
+        pos: usize,
+        len: usize,
+        side: JustifySide,
+        /// Padding character, space unless `FILL` is given
+        fill: char,
+    },
+    /// ASSERT pos,len NUMERIC|ALPHA|ALNUM|BLANK [STRICT] - check a field's type
+    ///
+    /// Conforming records pass unchanged. Failing records are prefixed with
+    /// [`ASSERT_MARKER`], or dropped when `strict` is set.
+    Assert {
+        pos: usize,
+        len: usize,
+        kind: FieldKind,
+        strict: bool,
+    },
+    /// VERIFY pos,len TYPE; ... - check several fields' types
+    ///
+    /// Conforming records pass unchanged; a failing record is replaced by
+    /// one diagnostic per failed check (see [`verify_record`]).
+    Verify {
+        checks: Vec<(usize, usize, FieldKind)>,
+    },
+    /// EXTRACT /marker/ [DROP] - keep only the text following a marker
+    Extract {
+        marker: String,
+        /// Emit an empty record (true) or drop the record (false) when the
+        /// marker is not found
+        keep_unmatched: bool,
+    },
+    /// LENGTH min,max - keep records whose trimmed character count is in range
+    Length {
+        min: usize,
+        /// Upper bound, or `None` for `LENGTH min,`
+        max: Option<usize>,
+    },
+    /// FREQ pos,len - count distinct field values, emit `<value> <count>`
+    Freq { pos: usize, len: usize },
+    /// CROSSTAB pos,len BY pos,len - a table of counts by two fields
+    /// (see [`crosstab_records`])
+    Crosstab {
+        row_field: (usize, usize),
+        col_field: (usize, usize),
+    },
+    /// BUILD pos,len /lit/ pos,len ... - concatenate fields and literals
+    Build { items: Vec<BuildItem> },
+    /// ZIP - pair records 1+2, 3+4, ... side by side
+    Zip,
+    /// PAGE n,size - keep page n (1-based) of `size` records
+    Page { page: usize, size: usize },
+    /// RESEQ pos,len start,step [FILL c] - renumber a field with an incrementing sequence
+    Reseq {
+        pos: usize,
+        len: usize,
+        start: i64,
+        step: i64,
+        /// Padding character, `'0'` unless `FILL` is given
+        fill: char,
+    },
+    /// TOPN n pos,len - keep the n records with the largest numeric field
+    TopN { n: usize, pos: usize, len: usize },
+    /// SORT pos,len [ASC|DESC] [NUM]; ... - sort records by several keys
+    ///
+    /// Keys apply in order; records equal on every key keep their input
+    /// order (see [`compare_by_keys`]).
+    Sort { keys: Vec<SortKey> },
+    /// EXPAND pos,len [CLEAR] - repeat each record by its own count field
+    ///
+    /// See [`expand_record`] for zero, non-numeric and oversized counts.
+    Expand { pos: usize, len: usize, clear: bool },
+    /// DATEFMT pos,len FROM fmt TO fmt [FLAG] - rewrite a date field's layout
+    ///
+    /// See [`reformat_date`] for width changes and invalid dates.
+    DateFmt {
+        pos: usize,
+        len: usize,
+        from: DateFormat,
+        to: DateFormat,
+        flag: bool,
+    },
+    /// DECODE pos,len /code=text;.../ [DEFAULT /text/] - look up a field's code
+    ///
+    /// See [`decode_field`] for how the field is matched and rewritten.
+    Decode {
+        pos: usize,
+        len: usize,
+        /// Replacement text for each code
+        map: BTreeMap<String, String>,
+        /// Text for codes missing from `map`; `None` leaves them unchanged
+        default: Option<String>,
+    },
+    /// DEFAULT pos,len /value/ [LEFT|RIGHT] - fill a blank field with a value
+    ///
+    /// See [`default_field`] for alignment and overlong values.
+    Default {
+        pos: usize,
+        len: usize,
+        value: String,
+        /// Side to align `value` to, left unless `RIGHT` is given
+        justify: JustifySide,
+    },
+    /// MASK pos,len; pos,len ... [FILL c] - overwrite fields with a fill character
+    Mask {
+        fields: Vec<(usize, usize)>,
+        fill: char,
+    },
+    /// WRAP width [CONT col] - word-wrap content into width-column records
+    Wrap {
+        width: usize,
+        cont_col: Option<usize>,
+    },
+    /// SHUFFLE seed - randomize record order with a seeded generator
+    Shuffle { seed: u64 },
+    /// INCLUDE name - a sub-pipeline reference, only found in the stage
+    /// lists of a [`PipelineRegistry`]; parsing a pipeline replaces it with
+    /// the named stages
+    Include { name: String },
+    /// OMIT ... - drop the records `filter` (one of the FILTER variants)
+    /// would keep
+    Omit { filter: Box<Command> },
+    /// ROUTE ... TO name [ELSE name] - send the records `filter` (one of
+    /// the FILTER variants) would keep to channel `to`, and the rest to
+    /// `otherwise` or, without ELSE, on down the pipeline
+    Route {
+        filter: Box<Command>,
+        to: String,
+        otherwise: Option<String>,
+    },
+    /// ONCHANGE pos,len - the first record, then each record whose field
+    /// differs from the previous record's
+    OnChange { pos: usize, len: usize },
+    /// SORTITEMS pos,len,width - sort the `item_width`-character items in
+    /// a field of each record (see [`sort_items`])
+    SortItems {
+        pos: usize,
+        len: usize,
+        item_width: usize,
+    },
+    /// INSERT EVERY n /text/ [TRAILING] - a text record after every nth
+    /// record; after the last one only with TRAILING
+    InsertEvery {
+        n: usize,
+        text: String,
+        trailing: bool,
+    },
+    /// NORM pos,len - canonical integer, right-justified (see [`normalize_number`])
+    Norm { pos: usize, len: usize },
+    /// PIVOT key=pos,len value=pos,len [group=pos,len] KEY:dest ... -
+    /// one wide record per group, each key's value at its destination
+    /// column (see [`pivot_group`])
+    Pivot {
+        key: (usize, usize),
+        value: (usize, usize),
+        group: Option<(usize, usize)>,
+        columns: Vec<(String, usize)>,
+    },
 }
 
-impl Command {
-    /// Can this stage be the first stage in a pipeline (source)?
-    /// Sources generate or read records without needing upstream input.
-    pub fn can_be_first(&self) -> bool {
-        // CONSOLE reads from input, LITERAL generates a record, HOLE generates empty stream
-        matches!(
-            self,
-            Command::Console | Command::Literal { .. } | Command::Hole
-        )
+/// What a stage does to the record stream, for tools that render or check
+/// pipelines (see [`Command::kind`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    /// Supplies records: CONSOLE (which, placed last, also writes the
+    /// output) and LITERAL.
+    Source,
+    /// Passes some records through unchanged and drops the rest.
+    Filter,
+    /// Rewrites records one at a time, or adds records around them.
+    Transform,
+    /// Discards its input (HOLE).
+    Sink,
+    /// Needs many records to produce its output: counts, hashes, reorders
+    /// or combines them.
+    Aggregate,
+}
+
+/// One piece of a BUILD output record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildItem {
+    /// Input field at `pos,len`, trimmed of surrounding spaces.
+    Field { pos: usize, len: usize },
+    /// Literal text, copied as-is.
+    Literal(String),
+}
+
+/// Columns given to each record of a ZIP pair.
+pub const ZIP_HALF_WIDTH: usize = RECORD_WIDTH / 2;
+
+/// Place two records side by side: `left` in columns 0-39, `right` in 40-79.
+///
+/// Each side keeps only its first 40 columns. A missing `right` leaves the
+/// second half blank.
+pub fn zip_pair(left: &Record, right: Option<&Record>) -> Record {
+    let mut out = Record::new();
+    out.set_field(0, ZIP_HALF_WIDTH, left.field(0, ZIP_HALF_WIDTH));
+    if let Some(right) = right {
+        out.set_field(
+            ZIP_HALF_WIDTH,
+            ZIP_HALF_WIDTH,
+            right.field(0, ZIP_HALF_WIDTH),
+        );
     }
+    out
+}
 
-    /// Get the stage name for error messages.
-    pub fn name(&self) -> &'static str {
-        match self {
-            Command::Console => "CONSOLE",
-            Command::FilterEq { .. } | Command::FilterNe { .. } => "FILTER",
-            Command::Select { .. } => "SELECT",
-            Command::Take { .. } => "TAKE",
-            Command::Skip { .. } => "SKIP",
-            Command::Locate { .. } => "LOCATE",
-            Command::Nlocate { .. } => "NLOCATE",
-            Command::Count => "COUNT",
-            Command::Change { .. } => "CHANGE",
-            Command::Literal { .. } => "LITERAL",
-            Command::Upper => "UPPER",
-            Command::Lower => "LOWER",
-            Command::Reverse => "REVERSE",
-            Command::Duplicate { .. } => "DUPLICATE",
-            Command::Hole => "HOLE",
+/// Lay out SELECT fields in destination order with `separator` between them.
+///
+/// Each separator is written directly after the previous field, and a field
+/// whose destination falls inside the previous field or separator is moved
+/// right to the first free column, so fields never overlap. Fields with the
+/// same destination keep their written order.
+pub fn select_separated(
+    record: &Record,
+    fields: &[(usize, usize, usize)],
+    separator: &str,
+) -> Record {
+    let mut ordered: Vec<&(usize, usize, usize)> = fields.iter().collect();
+    ordered.sort_by_key(|&&(_, _, dest)| dest);
+
+    let mut out = Record::new();
+    let mut cursor = 0;
+    for (i, &&(src, len, dest)) in ordered.iter().enumerate() {
+        if i > 0 {
+            out.set_field(cursor, separator.len(), separator);
+            cursor += separator.len();
         }
+        let dest = dest.max(cursor);
+        out.set_field(dest, len, record.field(src, len));
+        cursor = dest + len;
     }
+    out
 }
 
-/// Parse DSL text into commands.
-pub fn parse_commands(text: &str) -> Result<Vec<Command>, String> {
-    let mut commands = Vec::new();
+/// Expand the tokens in a HEADER template.
+///
+/// - `{COUNT}` - the number of records the HEADER stage received
+/// - `{DATE}` - today's date in UTC as `YYYY-MM-DD`; not available in the
+///   browser (wasm32), where the token is left as-is
+pub fn expand_header(template: &str, count: usize) -> String {
+    let text = template.replace("{COUNT}", &count.to_string());
+    #[cfg(not(target_arch = "wasm32"))]
+    let text = text.replace("{DATE}", &utc_date_today());
+    text
+}
 
-    // Normalize: split each source line on '|' so that both multi-line and
-    // single-line pipeline definitions work.  We keep track of the original
-    // line number for error messages.
-    let mut segments: Vec<(usize, &str)> = Vec::new();
-    for (line_num, line) in text.lines().enumerate() {
-        for part in line.split('|') {
-            segments.push((line_num, part));
-        }
-    }
+/// Today's UTC date as `YYYY-MM-DD`, from the system clock.
+#[cfg(not(target_arch = "wasm32"))]
+fn utc_date_today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
 
-    for (line_num, segment) in segments {
-        let segment = segment.trim();
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+///
+/// Howard Hinnant's `civil_from_days` algorithm (proleptic Gregorian).
+#[cfg(not(target_arch = "wasm32"))]
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
 
-        // Skip empty segments and comments
-        if segment.is_empty() || segment.starts_with('#') {
-            continue;
-        }
+/// Split a record's content into successive `width`-column records.
+///
+/// Records are fixed at 80 columns, so CHOP works on the logical content:
+/// everything up to the last non-space column. Each piece is moved to
+/// column 0 and the final piece may be shorter than `width`. A blank record
+/// yields a single blank record, so no record disappears.
+pub fn chop_record(record: &Record, width: usize) -> Vec<Record> {
+    let used = record.as_str().trim_end().len();
+    if used == 0 {
+        return vec![record.clone()];
+    }
+    (0..used)
+        .step_by(width)
+        .map(|pos| record.slice(pos, width.min(used - pos)))
+        .collect()
+}
 
-        // Handle "PIPE COMMAND" - extract command after PIPE
-        let segment = if segment.to_uppercase().starts_with("PIPE ") {
-            segment[5..].trim()
-        } else if segment.eq_ignore_ascii_case("PIPE") {
-            // Skip standalone PIPE declaration
-            continue;
-        } else {
-            segment
-        };
+/// Sort the `item_width`-character items packed into field `pos,len`.
+///
+/// The field is split into items from its left edge and the items are
+/// sorted bytewise. When `len` is not a multiple of `item_width`, the
+/// leftover columns at the end of the field are not an item and stay where
+/// they are. Like [`Record::field`], the field is clipped at column 80.
+pub fn sort_items(record: &Record, pos: usize, len: usize, item_width: usize) -> Record {
+    let region = record.field(pos, len);
+    let whole = region.len() - region.len() % item_width;
+    let (items, remainder) = region.split_at(whole);
+    let mut items: Vec<&str> = (0..whole)
+        .step_by(item_width)
+        .map(|start| &items[start..start + item_width])
+        .collect();
+    items.sort_unstable();
+    let sorted = items.concat() + remainder;
+    let mut result = record.clone();
+    result.set_field(pos, len, &sorted);
+    result
+}
 
-        // Remove trailing ? (explicit end of pipeline)
-        let segment = segment.trim_end_matches('?').trim();
+/// The `--- SEGMENT k ---` record SEGMENT emits before group `k` (from 1).
+pub fn segment_header(k: usize) -> Record {
+    Record::from_str(&format!("--- SEGMENT {k} ---"))
+}
 
-        // Skip if segment is now empty
-        if segment.is_empty() {
-            continue;
-        }
+/// Character WRAP places in the continuation column of non-final pieces.
+pub const WRAP_MARKER: char = '+';
 
-        let cmd = parse_command(segment).map_err(|e| format!("Line {}: {}", line_num + 1, e))?;
-        commands.push(cmd);
+/// Word-wrap a record's content into successive records of at most `width`
+/// columns.
+///
+/// The content is the record trimmed of leading and trailing spaces. Lines
+/// break at the last space that fits, and the spaces at a break are
+/// dropped; a word longer than `width` is hard-broken. With `cont_col`,
+/// every piece but the last gets [`WRAP_MARKER`] in that column. A blank
+/// record yields a single blank record, like [`chop_record`].
+pub fn wrap_record(record: &Record, width: usize, cont_col: Option<usize>) -> Vec<Record> {
+    let mut rest = record.as_str().trim();
+    if rest.is_empty() {
+        return vec![record.clone()];
     }
-
-    Ok(commands)
+    let mut pieces = Vec::new();
+    while rest.len() > width {
+        let (piece, tail) = match rest[..=width].rfind(' ') {
+            Some(at) => (rest[..at].trim_end(), &rest[at..]),
+            None => rest.split_at(width),
+        };
+        pieces.push(piece);
+        rest = tail.trim_start();
+    }
+    pieces.push(rest);
+
+    let last = pieces.len() - 1;
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, piece)| {
+            let mut out = Record::from_str(piece);
+            if let Some(col) = cont_col.filter(|_| index < last) {
+                out.set_field(col, 1, &WRAP_MARKER.to_string());
+            }
+            out
+        })
+        .collect()
 }
 
-/// Parse a single command line.
-fn parse_command(line: &str) -> Result<Command, String> {
-    let upper = line.to_uppercase();
-
-    if upper == "CONSOLE" || upper.starts_with("CONSOLE ") {
-        Ok(Command::Console)
-    } else if upper.starts_with("FILTER") {
-        parse_filter(line)
-    } else if upper.starts_with("SELECT") {
-        parse_select(line)
-    } else if upper.starts_with("TAKE") {
-        parse_take(line)
-    } else if upper.starts_with("SKIP") {
-        parse_skip(line)
-    } else if upper.starts_with("NLOCATE") {
-        parse_nlocate(line)
-    } else if upper.starts_with("LOCATE") {
-        parse_locate(line)
-    } else if upper == "COUNT" || upper.starts_with("COUNT ") {
-        Ok(Command::Count)
-    } else if upper.starts_with("CHANGE") {
-        parse_change(line)
-    } else if upper.starts_with("LITERAL") {
-        parse_literal(line)
-    } else if upper == "UPPER" || upper.starts_with("UPPER ") {
-        Ok(Command::Upper)
-    } else if upper == "LOWER" || upper.starts_with("LOWER ") {
-        Ok(Command::Lower)
-    } else if upper == "REVERSE" || upper.starts_with("REVERSE ") {
-        Ok(Command::Reverse)
-    } else if upper.starts_with("DUPLICATE") {
-        parse_duplicate(line)
-    } else if upper == "HOLE" || upper.starts_with("HOLE ") {
-        Ok(Command::Hole)
-    } else {
-        Err(format!(
-            "Unknown command: {}",
-            line.split_whitespace().next().unwrap_or(line)
-        ))
+/// Largest count [`expand_record`] honours; see there.
+pub const EXPAND_MAX_COUNT: usize = 1_000_000;
+
+/// Repeat a record as many times as the integer in its `pos,len` field.
+///
+/// A count of 0 or less yields no records. A blank or non-numeric count, or
+/// one above [`EXPAND_MAX_COUNT`], is not an error: the record passes
+/// through once, unchanged, so bad counts stay visible in the output. With
+/// `clear`, the count field of each copy is blanked.
+///
+/// Copies are made as the iterator is consumed, so an executor enforcing
+/// `ExecuteOptions::max_output_records` can stop part way through.
+pub fn expand_record(
+    record: &Record,
+    pos: usize,
+    len: usize,
+    clear: bool,
+) -> impl Iterator<Item = Record> + use<> {
+    let count = record
+        .field_i64(pos, len)
+        .map(|count| usize::try_from(count).unwrap_or(0))
+        .filter(|&count| count <= EXPAND_MAX_COUNT);
+    let Some(count) = count else {
+        return std::iter::repeat_n(record.clone(), 1);
+    };
+    let mut copy = record.clone();
+    if clear {
+        copy.set_field(pos, len, "");
     }
+    std::iter::repeat_n(copy, count)
 }
 
-/// Parse FILTER command.
-fn parse_filter(line: &str) -> Result<Command, String> {
-    // FILTER pos,len = "value" or FILTER pos,len != "value"
-    let rest = line[6..].trim(); // Skip "FILTER"
+/// Concatenate BUILD items left to right into a fresh record.
+pub fn build_record(record: &Record, items: &[BuildItem]) -> Record {
+    let mut out = String::new();
+    for item in items {
+        match item {
+            BuildItem::Field { pos, len } => out.push_str(record.field(*pos, *len).trim()),
+            BuildItem::Literal(text) => out.push_str(text),
+        }
+    }
+    Record::from_str(&out)
+}
 
-    // Find the operator
-    let (field_part, op, value) = if let Some(idx) = rest.find("!=") {
-        let field_part = rest[..idx].trim();
-        let value_part = rest[idx + 2..].trim();
-        (field_part, "!=", value_part)
-    } else if let Some(idx) = rest.find('=') {
-        let field_part = rest[..idx].trim();
-        let value_part = rest[idx + 1..].trim();
-        (field_part, "=", value_part)
-    } else {
-        return Err("FILTER requires = or != operator".to_string());
-    };
+/// Comparison operator of a field-to-field FILTER.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
 
-    // Parse pos,len
-    let parts: Vec<&str> = field_part.split(',').collect();
-    if parts.len() != 2 {
-        return Err("FILTER requires pos,len before operator".to_string());
+impl CompareOp {
+    /// Parse an operator symbol such as `>=`.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "=" => Some(CompareOp::Eq),
+            "!=" => Some(CompareOp::Ne),
+            "<" => Some(CompareOp::Lt),
+            "<=" => Some(CompareOp::Le),
+            ">" => Some(CompareOp::Gt),
+            ">=" => Some(CompareOp::Ge),
+            _ => None,
+        }
     }
 
-    let pos: usize = parts[0]
-        .trim()
-        .parse()
-        .map_err(|_| "Invalid position number")?;
-    let len: usize = parts[1]
-        .trim()
-        .parse()
-        .map_err(|_| "Invalid length number")?;
+    /// The operator as written in a pipeline.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
 
-    // Parse quoted value
-    let value = parse_quoted_string(value)?;
+    /// Whether a comparison with this `ordering` satisfies the operator.
+    pub fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+        }
+    }
 
-    if op == "!=" {
-        Ok(Command::FilterNe { pos, len, value })
-    } else {
-        Ok(Command::FilterEq { pos, len, value })
+    /// The operator in words, for [`explain`].
+    fn verb(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "equal",
+            CompareOp::Ne => "differ from",
+            CompareOp::Lt => "are less than",
+            CompareOp::Le => "are at most",
+            CompareOp::Gt => "are greater than",
+            CompareOp::Ge => "are at least",
+        }
     }
 }
 
-/// Parse SELECT command.
-fn parse_select(line: &str) -> Result<Command, String> {
-    // SELECT p1,l1,d1; p2,l2,d2; ...
-    let rest = line[6..].trim(); // Skip "SELECT"
-
-    let mut fields = Vec::new();
+/// Which side of its columns a JUSTIFY stage aligns a field to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JustifySide {
+    Left,
+    Right,
+}
 
-    for field_spec in rest.split(';') {
-        let field_spec = field_spec.trim();
-        if field_spec.is_empty() {
-            continue;
+impl JustifySide {
+    /// Justify the trimmed `value` within `width` columns, padding with `fill`.
+    pub fn justify(self, value: &str, width: usize, fill: char) -> String {
+        let value = value.trim();
+        let padding: String =
+            std::iter::repeat_n(fill, width.saturating_sub(value.chars().count())).collect();
+        match self {
+            JustifySide::Left => format!("{value}{padding}"),
+            JustifySide::Right => format!("{padding}{value}"),
         }
+    }
+}
 
-        let parts: Vec<&str> = field_spec.split(',').collect();
-        if parts.len() != 3 {
-            return Err(format!(
-                "SELECT field '{}' requires src_pos,len,dest_pos",
-                field_spec
-            ));
-        }
+/// Direction of one SORT key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
 
-        let src_pos: usize = parts[0]
-            .trim()
-            .parse()
-            .map_err(|_| format!("Invalid source position in '{}'", field_spec))?;
-        let len: usize = parts[1]
-            .trim()
-            .parse()
-            .map_err(|_| format!("Invalid length in '{}'", field_spec))?;
-        let dest_pos: usize = parts[2]
-            .trim()
-            .parse()
-            .map_err(|_| format!("Invalid destination position in '{}'", field_spec))?;
+/// One SORT key: a field, its direction, and whether it compares as a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub pos: usize,
+    pub len: usize,
+    pub dir: SortDir,
+    /// Compare as signed integers; non-numeric fields sort below every number.
+    /// Otherwise the raw field text is compared.
+    pub numeric: bool,
+}
 
-        fields.push((src_pos, len, dest_pos));
+impl SortKey {
+    /// Compare two records on this key alone.
+    pub fn compare(&self, a: &Record, b: &Record) -> Ordering {
+        let ord = if self.numeric {
+            a.field_i64(self.pos, self.len)
+                .cmp(&b.field_i64(self.pos, self.len))
+        } else {
+            a.field(self.pos, self.len).cmp(b.field(self.pos, self.len))
+        };
+        match self.dir {
+            SortDir::Asc => ord,
+            SortDir::Desc => ord.reverse(),
+        }
     }
+}
 
-    if fields.is_empty() {
-        return Err("SELECT requires at least one field specification".to_string());
-    }
+/// Compare two records key by key; the first key that differs decides.
+pub fn compare_by_keys(a: &Record, b: &Record, keys: &[SortKey]) -> Ordering {
+    keys.iter()
+        .map(|key| key.compare(a, b))
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
 
-    Ok(Command::Select { fields })
+/// How a multi-pattern LOCATE combines its patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// At least one pattern must occur.
+    Any,
+    /// Every pattern must occur.
+    All,
 }
 
-/// Parse TAKE command.
-fn parse_take(line: &str) -> Result<Command, String> {
-    let rest = line[4..].trim(); // Skip "TAKE"
-    let n: usize = rest.parse().map_err(|_| "TAKE requires a number")?;
-    Ok(Command::Take { n })
+impl MatchMode {
+    /// Does `record` (or its `pos,len` field) contain the patterns this mode requires?
+    pub fn matches(
+        self,
+        record: &Record,
+        patterns: &[String],
+        field: Option<(usize, usize)>,
+    ) -> bool {
+        let text = match field {
+            Some((pos, len)) => record.field(pos, len),
+            None => record.as_str(),
+        };
+        match self {
+            MatchMode::Any => patterns.iter().any(|p| text.contains(p.as_str())),
+            MatchMode::All => patterns.iter().all(|p| text.contains(p.as_str())),
+        }
+    }
 }
 
-/// Parse SKIP command.
-fn parse_skip(line: &str) -> Result<Command, String> {
-    let rest = line[4..].trim(); // Skip "SKIP"
-    let n: usize = rest.parse().map_err(|_| "SKIP requires a number")?;
-    Ok(Command::Skip { n })
+/// The type an ASSERT or VERIFY stage requires of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// A signed integer (see [`Record::field_is_numeric`]).
+    Numeric,
+    /// ASCII letters and embedded spaces (see [`Record::field_is_alpha`]).
+    Alpha,
+    /// ASCII letters, digits and embedded spaces, ignoring surrounding
+    /// spaces; a blank field is not alphanumeric.
+    Alnum,
+    /// Nothing but spaces.
+    Blank,
 }
 
-/// Parse a delimited string using CMS Pipelines convention.
-/// The first non-blank character is the delimiter, and the string
-/// continues until the next occurrence of that delimiter.
-/// Returns (extracted_string, rest_of_input).
-fn parse_delimited_string(s: &str) -> Result<(String, &str), String> {
-    let s = s.trim_start();
-    if s.is_empty() {
-        return Err("Expected delimited string".to_string());
+impl FieldKind {
+    /// Does the field at `pos,len` of `record` have this type?
+    pub fn check(self, record: &Record, pos: usize, len: usize) -> bool {
+        match self {
+            FieldKind::Numeric => record.field_is_numeric(pos, len),
+            FieldKind::Alpha => record.field_is_alpha(pos, len),
+            FieldKind::Alnum => {
+                let value = record.field(pos, len).trim();
+                !value.is_empty()
+                    && value
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b' ')
+            }
+            FieldKind::Blank => record.field(pos, len).trim().is_empty(),
+        }
     }
 
-    // First character is the delimiter
-    let delim = s.chars().next().unwrap();
-    let after_delim = &s[delim.len_utf8()..];
+    /// The type for a DSL keyword (`NUMERIC`, `ALPHA`, `ALNUM` or `BLANK`),
+    /// in any case.
+    pub fn from_keyword(word: &str) -> Option<Self> {
+        [
+            FieldKind::Numeric,
+            FieldKind::Alpha,
+            FieldKind::Alnum,
+            FieldKind::Blank,
+        ]
+        .into_iter()
+        .find(|kind| word.eq_ignore_ascii_case(kind.keyword()))
+    }
 
-    // Find the closing delimiter
-    if let Some(end) = after_delim.find(delim) {
-        let extracted = after_delim[..end].to_string();
-        let rest = &after_delim[end + delim.len_utf8()..];
-        Ok((extracted, rest))
-    } else {
-        Err(format!("Unclosed delimiter '{}'", delim))
+    /// The DSL keyword for this type.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            FieldKind::Numeric => "NUMERIC",
+            FieldKind::Alpha => "ALPHA",
+            FieldKind::Alnum => "ALNUM",
+            FieldKind::Blank => "BLANK",
+        }
+    }
+
+    /// The type in plain words, for `explain`.
+    fn adjective(self) -> &'static str {
+        match self {
+            FieldKind::Numeric => "numeric",
+            FieldKind::Alpha => "alphabetic",
+            FieldKind::Alnum => "alphanumeric",
+            FieldKind::Blank => "blank",
+        }
     }
 }
 
-/// Parse a quoted string value (legacy helper, delegates to parse_delimited_string).
-fn parse_quoted_string(s: &str) -> Result<String, String> {
-    let (result, _) = parse_delimited_string(s)?;
-    Ok(result)
+/// Running 64-bit FNV-1a hash over whole records, for the HASH stage.
+///
+/// All 80 bytes of each record are hashed, padding included, so the result
+/// depends on record order and content and is stable across runs and
+/// platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordHasher {
+    state: u64,
 }
 
-/// Parse LOCATE command.
-/// CMS Pipelines: Uses first non-blank char as delimiter.
-/// Formats:
-///   LOCATE /pattern/       - search entire record (/ is delimiter)
-///   LOCATE "pattern"       - search entire record (" is delimiter)
-///   LOCATE .pattern.       - search entire record (. is delimiter)
-///   LOCATE pos,len /pattern/ - search specific field
-fn parse_locate(line: &str) -> Result<Command, String> {
-    let rest = line[6..].trim(); // Skip "LOCATE"
+impl RecordHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
 
-    if rest.is_empty() {
-        return Err("LOCATE requires a pattern".to_string());
+    /// A hasher that has seen no records.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: Self::OFFSET_BASIS,
+        }
     }
 
-    // If first char is a digit, parse field spec first
-    if rest.chars().next().unwrap().is_ascii_digit() {
-        // Find where the field spec ends (after the comma and second number)
-        // Format: pos,len <delimited-pattern>
-        let mut parts = rest.splitn(2, |c: char| !c.is_ascii_digit() && c != ',');
-        let field_spec = parts.next().unwrap_or("");
-        let pattern_part = parts.next().unwrap_or("").trim_start();
-
-        let field_parts: Vec<&str> = field_spec.split(',').collect();
-        if field_parts.len() != 2 {
-            return Err("LOCATE field spec requires pos,len".to_string());
+    /// Fold one record into the hash.
+    pub fn update(&mut self, record: &Record) {
+        for &byte in record.as_bytes() {
+            self.state ^= u64::from(byte);
+            self.state = self.state.wrapping_mul(Self::PRIME);
         }
+    }
 
-        let pos: usize = field_parts[0]
-            .trim()
-            .parse()
-            .map_err(|_| "Invalid position number")?;
-        let len: usize = field_parts[1]
-            .trim()
-            .parse()
-            .map_err(|_| "Invalid length number")?;
+    /// The hash of the records seen so far.
+    #[must_use]
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
 
-        let (pattern, _) = parse_delimited_string(pattern_part)?;
-        Ok(Command::Locate {
-            pattern,
-            field: Some((pos, len)),
-        })
-    } else {
-        // No field spec, just the delimited pattern
-        let (pattern, _) = parse_delimited_string(rest)?;
-        Ok(Command::Locate {
-            pattern,
-            field: None,
-        })
+    /// The `HASH=<hex>` summary record.
+    #[must_use]
+    pub fn to_record(&self) -> Record {
+        Record::from_str(&format!("HASH={:016x}", self.state))
     }
 }
 
-/// Parse NLOCATE command.
-/// CMS Pipelines: Uses first non-blank char as delimiter (same as LOCATE).
-fn parse_nlocate(line: &str) -> Result<Command, String> {
-    let rest = line[7..].trim(); // Skip "NLOCATE"
+impl Default for RecordHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    if rest.is_empty() {
-        return Err("NLOCATE requires a pattern".to_string());
+/// Running record count and length range, for the STATS stage.
+///
+/// Lengths are measured like LENGTH does: in characters, ignoring trailing
+/// spaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LengthStats {
+    count: usize,
+    min: usize,
+    max: usize,
+    total: usize,
+}
+
+impl LengthStats {
+    /// Fold one record into the statistics.
+    pub fn update(&mut self, record: &Record) {
+        let len = record.as_str().trim_end().chars().count();
+        self.min = if self.count == 0 {
+            len
+        } else {
+            self.min.min(len)
+        };
+        self.max = self.max.max(len);
+        self.total += len;
+        self.count += 1;
     }
 
-    // If first char is a digit, parse field spec first
-    if rest.chars().next().unwrap().is_ascii_digit() {
-        let mut parts = rest.splitn(2, |c: char| !c.is_ascii_digit() && c != ',');
-        let field_spec = parts.next().unwrap_or("");
-        let pattern_part = parts.next().unwrap_or("").trim_start();
+    /// Records seen so far.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.count
+    }
 
-        let field_parts: Vec<&str> = field_spec.split(',').collect();
-        if field_parts.len() != 2 {
-            return Err("NLOCATE field spec requires pos,len".to_string());
-        }
+    /// Shortest record length, or 0 before any record.
+    #[must_use]
+    pub fn min(&self) -> usize {
+        self.min
+    }
 
-        let pos: usize = field_parts[0]
-            .trim()
-            .parse()
-            .map_err(|_| "Invalid position number")?;
-        let len: usize = field_parts[1]
-            .trim()
-            .parse()
-            .map_err(|_| "Invalid length number")?;
+    /// Longest record length, or 0 before any record.
+    #[must_use]
+    pub fn max(&self) -> usize {
+        self.max
+    }
 
-        let (pattern, _) = parse_delimited_string(pattern_part)?;
-        Ok(Command::Nlocate {
-            pattern,
-            field: Some((pos, len)),
-        })
-    } else {
-        let (pattern, _) = parse_delimited_string(rest)?;
-        Ok(Command::Nlocate {
-            pattern,
-            field: None,
-        })
+    /// Mean record length, or 0 before any record.
+    #[must_use]
+    pub fn average(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total as f64 / self.count as f64
+        }
     }
-}
 
-/// Parse CHANGE command.
-/// CMS Pipelines: Uses first non-blank char as delimiter.
-/// Both strings must use the SAME delimiter.
-/// Format: CHANGE /old/ /new/ or CHANGE "old" "new"
-fn parse_change(line: &str) -> Result<Command, String> {
-    let rest = line[6..].trim(); // Skip "CHANGE"
+    /// The `RECORDS=`, `MINLEN=`, `MAXLEN=` and `AVGLEN=` summary records;
+    /// the average has two decimals.
+    #[must_use]
+    pub fn to_records(&self) -> Vec<Record> {
+        [
+            format!("RECORDS={}", self.count),
+            format!("MINLEN={}", self.min),
+            format!("MAXLEN={}", self.max),
+            format!("AVGLEN={:.2}", self.average()),
+        ]
+        .iter()
+        .map(|text| Record::from_str(text))
+        .collect()
+    }
+}
 
-    if rest.is_empty() {
-        return Err("CHANGE requires two delimited strings".to_string());
+/// Shuffle `records` in place, in an order determined only by `seed`.
+///
+/// A Fisher-Yates shuffle driven by an xorshift64 generator, so the same
+/// seed gives the same permutation on every run and platform.
+pub fn shuffle_records(records: &mut [Record], seed: u64) {
+    let mut state = xorshift_start(seed);
+    for i in (1..records.len()).rev() {
+        let j = (xorshift_next(&mut state) % (i as u64 + 1)) as usize;
+        records.swap(i, j);
     }
+}
 
-    // Parse first delimited string
-    let (old, after_first) = parse_delimited_string(rest)?;
+/// The initial xorshift64 state for `seed`.
+fn xorshift_start(seed: u64) -> u64 {
+    const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+    // xorshift must never hold 0, and a small seed needs mixing to start well
+    match seed ^ GOLDEN {
+        0 => GOLDEN,
+        mixed => mixed,
+    }
+}
 
-    // Parse second delimited string (must use same delimiter as first)
-    let (new, _) = parse_delimited_string(after_first)?;
+/// Advance an xorshift64 `state` and return the new value.
+fn xorshift_next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
 
-    Ok(Command::Change { old, new })
+/// A placeholder in a GENERATE template.
+enum TemplateField {
+    /// `{SEQ:width}`
+    Seq(usize),
+    /// `{RAND:width}`
+    Rand(usize),
 }
 
-/// Parse LITERAL command.
-/// CMS Pipelines: LITERAL does NOT use delimiters.
-/// Everything after "LITERAL " is the literal text.
-/// LITERAL FOO    -> "FOO"
-/// LITERAL "FOO"  -> "\"FOO\"" (quotes are part of text)
-/// LITERAL /FOO/  -> "/FOO/"  (slashes are part of text)
-fn parse_literal(line: &str) -> Result<Command, String> {
-    let rest = line[7..].trim_start(); // Skip "LITERAL", keep leading spaces in text
-    if rest.is_empty() {
-        return Err("LITERAL requires text".to_string());
-    }
-    // Trim trailing whitespace from the text
-    let text = rest.trim_end().to_string();
-    Ok(Command::Literal { text })
+/// The placeholder `text` starts with, and its length in bytes.
+fn template_field(text: &str) -> Option<(TemplateField, usize)> {
+    let end = text.find('}')?;
+    let (name, width) = text[1..end].split_once(':')?;
+    let width: usize = width.parse().ok()?;
+    let field = match name {
+        "SEQ" => TemplateField::Seq(width),
+        "RAND" => TemplateField::Rand(width),
+        _ => return None,
+    };
+    Some((field, end + 1))
 }
 
-/// Parse DUPLICATE command.
-/// Format: DUPLICATE n
-fn parse_duplicate(line: &str) -> Result<Command, String> {
-    let rest = line[9..].trim(); // Skip "DUPLICATE"
-    let n: usize = rest.parse().map_err(|_| "DUPLICATE requires a number")?;
-    if n == 0 {
-        return Err("DUPLICATE count must be at least 1".to_string());
-    }
-    Ok(Command::Duplicate { n })
+/// Build `count` records from a GENERATE template.
+///
+/// - `{SEQ:width}` - the record number, from 1, zero-padded to `width`
+/// - `{RAND:width}` - `width` random digits from an xorshift64 generator
+///   seeded with `seed`, so a seed always gives the same records
+///
+/// Any other text, including unrecognized `{...}`, is copied as-is.
+pub fn generate_records(count: usize, template: &str, seed: u64) -> Vec<Record> {
+    let mut state = xorshift_start(seed);
+    (1..=count)
+        .map(|seq| {
+            let mut text = String::new();
+            let mut rest = template;
+            while let Some(start) = rest.find('{') {
+                text.push_str(&rest[..start]);
+                rest = &rest[start..];
+                match template_field(rest) {
+                    Some((TemplateField::Seq(width), used)) => {
+                        text.push_str(&format!("{seq:0width$}"));
+                        rest = &rest[used..];
+                    }
+                    Some((TemplateField::Rand(width), used)) => {
+                        text.extend(
+                            (0..width)
+                                .map(|_| char::from(b'0' + (xorshift_next(&mut state) % 10) as u8)),
+                        );
+                        rest = &rest[used..];
+                    }
+                    None => {
+                        text.push('{');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            text.push_str(rest);
+            Record::from_str(&text)
+        })
+        .collect()
 }
 
-/// Apply commands to records.
-fn apply_commands(records: Vec<Record>, commands: &[Command]) -> Result<Vec<Record>, String> {
-    // We need to collect and re-create pipeline for each command
-    // because the Pipeline type changes with each operation
-    let mut current: Vec<Record> = records;
+/// Prefix an ASSERT stage puts on records that fail its check.
+pub const ASSERT_MARKER: &str = "*ERR* ";
 
-    for cmd in commands {
-        current = apply_command(current, cmd)?;
+/// Check `record` against VERIFY's `checks`.
+///
+/// A conforming record is returned alone. Otherwise each failed check gives
+/// a diagnostic record: [`ASSERT_MARKER`], the check (`28,8 NUMERIC`) and
+/// the offending record, truncated at the record width.
+pub fn verify_record(record: &Record, checks: &[(usize, usize, FieldKind)]) -> Vec<Record> {
+    let diagnostics: Vec<Record> = checks
+        .iter()
+        .filter(|(pos, len, kind)| !kind.check(record, *pos, *len))
+        .map(|(pos, len, kind)| {
+            Record::from_str(&format!(
+                "{ASSERT_MARKER}{pos},{len} {} {}",
+                kind.keyword(),
+                record.as_str()
+            ))
+        })
+        .collect();
+    if diagnostics.is_empty() {
+        vec![record.clone()]
+    } else {
+        diagnostics
     }
+}
 
-    Ok(current)
+/// Prefix `record` with [`ASSERT_MARKER`], truncating at the record width.
+pub fn flag_record(record: &Record) -> Record {
+    Record::from_str(&format!("{ASSERT_MARKER}{}", record.as_str()))
 }
 
-/// Apply a single command to records.
-fn apply_command(records: Vec<Record>, cmd: &Command) -> Result<Vec<Record>, String> {
-    match cmd {
-        Command::Console => {
-            // Console in the middle of pipeline just passes through
-            Ok(records)
+/// A date layout read and written by DATEFMT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `YYYYMMDD`
+    Ymd,
+    /// `MM/DD/YYYY`
+    Mdy,
+    /// `DD/MM/YYYY`
+    Dmy,
+    /// `YYYYDDD`, where `DDD` is the day of the year
+    Julian,
+}
+
+impl DateFormat {
+    /// Look up a format by its DATEFMT keyword, ignoring case.
+    pub fn from_keyword(word: &str) -> Option<DateFormat> {
+        match word.to_ascii_lowercase().as_str() {
+            "ymd" => Some(DateFormat::Ymd),
+            "mdy" => Some(DateFormat::Mdy),
+            "dmy" => Some(DateFormat::Dmy),
+            "julian" => Some(DateFormat::Julian),
+            _ => None,
         }
-        Command::FilterEq { pos, len, value } => {
-            let pos = *pos;
-            let len = *len;
-            let value = value.clone();
-            Ok(Pipeline::new(records.into_iter())
-                .filter(move |r| r.field_eq(pos, len, &value))
-                .collect())
+    }
+
+    /// The DATEFMT keyword for this format.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            DateFormat::Ymd => "ymd",
+            DateFormat::Mdy => "mdy",
+            DateFormat::Dmy => "dmy",
+            DateFormat::Julian => "julian",
         }
-        Command::FilterNe { pos, len, value } => {
-            let pos = *pos;
-            let len = *len;
-            let value = value.clone();
-            Ok(Pipeline::new(records.into_iter())
-                .filter(move |r| !r.field_eq(pos, len, &value))
-                .collect())
+    }
+
+    /// The layout as a picture, e.g. `MM/DD/YYYY`.
+    pub fn pattern(self) -> &'static str {
+        match self {
+            DateFormat::Ymd => "YYYYMMDD",
+            DateFormat::Mdy => "MM/DD/YYYY",
+            DateFormat::Dmy => "DD/MM/YYYY",
+            DateFormat::Julian => "YYYYDDD",
         }
-        Command::Select { fields } => {
-            let fields = fields.clone();
-            Ok(Pipeline::new(records.into_iter()).select(fields).collect())
+    }
+
+    /// Width in columns of a date written in this format.
+    pub fn width(self) -> usize {
+        self.pattern().len()
+    }
+
+    /// Read a trimmed date in this layout as (year, month, day).
+    ///
+    /// Returns `None` unless the text matches the layout exactly and names
+    /// a real calendar date (leap years included).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::DateFormat;
+    ///
+    /// assert_eq!(DateFormat::Ymd.read("20240115"), Some((2024, 1, 15)));
+    /// assert_eq!(DateFormat::Julian.read("2024060"), Some((2024, 2, 29)));
+    /// assert_eq!(DateFormat::Mdy.read("02/30/2024"), None);
+    /// ```
+    pub fn read(self, text: &str) -> Option<(u32, u32, u32)> {
+        let text = text.trim();
+        if text.len() != self.width() {
+            return None;
         }
-        Command::Take { n } => Ok(Pipeline::new(records.into_iter()).take(*n).collect()),
-        Command::Skip { n } => Ok(Pipeline::new(records.into_iter()).skip(*n).collect()),
-        Command::Locate { pattern, field } => {
-            let pattern = pattern.clone();
-            match field {
-                Some((pos, len)) => {
-                    let pos = *pos;
-                    let len = *len;
-                    Ok(Pipeline::new(records.into_iter())
-                        .filter(move |r| r.field_contains(pos, len, &pattern))
-                        .collect())
-                }
-                None => {
-                    // Search entire record
-                    Ok(Pipeline::new(records.into_iter())
-                        .filter(move |r| r.as_str().contains(pattern.as_str()))
-                        .collect())
-                }
+        let number = |from: usize, to: usize| -> Option<u32> {
+            let digits = text.get(from..to)?;
+            if digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse().ok()
+            } else {
+                None
             }
-        }
-        Command::Nlocate { pattern, field } => {
-            let pattern = pattern.clone();
-            match field {
-                Some((pos, len)) => {
-                    let pos = *pos;
-                    let len = *len;
-                    Ok(Pipeline::new(records.into_iter())
-                        .filter(move |r| !r.field_contains(pos, len, &pattern))
-                        .collect())
-                }
-                None => Ok(Pipeline::new(records.into_iter())
-                    .filter(move |r| !r.as_str().contains(pattern.as_str()))
-                    .collect()),
+        };
+        let slashes = |at: [usize; 2]| at.iter().all(|&i| text.as_bytes()[i] == b'/');
+        let (year, month, day) = match self {
+            DateFormat::Ymd => (number(0, 4)?, number(4, 6)?, number(6, 8)?),
+            DateFormat::Mdy if slashes([2, 5]) => (number(6, 10)?, number(0, 2)?, number(3, 5)?),
+            DateFormat::Dmy if slashes([2, 5]) => (number(6, 10)?, number(3, 5)?, number(0, 2)?),
+            DateFormat::Julian => {
+                let year = number(0, 4)?;
+                let (month, day) = month_day_from_ordinal(year, number(4, 7)?)?;
+                (year, month, day)
+            }
+            DateFormat::Mdy | DateFormat::Dmy => return None,
+        };
+        let valid = (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day);
+        valid.then_some((year, month, day))
+    }
+
+    /// Write a date in this layout. The date must be valid (see [`Self::read`]).
+    pub fn write(self, year: u32, month: u32, day: u32) -> String {
+        match self {
+            DateFormat::Ymd => format!("{year:04}{month:02}{day:02}"),
+            DateFormat::Mdy => format!("{month:02}/{day:02}/{year:04}"),
+            DateFormat::Dmy => format!("{day:02}/{month:02}/{year:04}"),
+            DateFormat::Julian => {
+                let ordinal: u32 = (1..month).map(|m| days_in_month(year, m)).sum::<u32>() + day;
+                format!("{year:04}{ordinal:03}")
             }
         }
-        Command::Count => {
-            // Count records and emit a single summary record
-            let count = records.len();
-            Ok(vec![Record::from_str(&count.to_string())])
-        }
-        Command::Change { old, new } => {
-            // Replace all occurrences of old with new in each record
-            let old = old.clone();
-            let new = new.clone();
-            Ok(Pipeline::new(records.into_iter())
-                .map(move |r| {
-                    let content = r.as_str().replace(&old, &new);
-                    Record::from_str(&content)
-                })
-                .collect())
-        }
-        Command::Literal { text } => {
-            // CMS Pipelines: LITERAL is a "prefix" filter.
-            // It outputs its literal text FIRST, then passes through all input records.
-            let mut result = vec![Record::from_str(text)];
-            result.extend(records);
-            Ok(result)
-        }
-        Command::Upper => {
-            // Convert all records to uppercase
-            Ok(Pipeline::new(records.into_iter())
-                .map(|r| Record::from_str(&r.as_str().to_uppercase()))
-                .collect())
-        }
-        Command::Lower => {
-            // Convert all records to lowercase
-            Ok(Pipeline::new(records.into_iter())
-                .map(|r| Record::from_str(&r.as_str().to_lowercase()))
-                .collect())
-        }
-        Command::Reverse => {
-            // Reverse characters in each record (trim first to avoid reversing trailing spaces)
-            Ok(Pipeline::new(records.into_iter())
-                .map(|r| {
-                    let reversed: String = r.as_str().trim_end().chars().rev().collect();
-                    Record::from_str(&reversed)
-                })
-                .collect())
+    }
+}
+
+/// Days in `month` (1-12) of `year`, in the proleptic Gregorian calendar.
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
         }
-        Command::Duplicate { n } => {
-            // Repeat each record n times
-            let n = *n;
-            Ok(records
-                .into_iter()
-                .flat_map(|r| std::iter::repeat_n(r, n))
-                .collect())
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Convert a 1-based day of the year to (month, day), if it exists.
+fn month_day_from_ordinal(year: u32, ordinal: u32) -> Option<(u32, u32)> {
+    let mut remaining = ordinal;
+    for month in 1..=12 {
+        let days = days_in_month(year, month);
+        if (1..=days).contains(&remaining) {
+            return Some((month, remaining));
         }
-        Command::Hole => {
-            // Discard all input records, output nothing (like /dev/null)
-            // Consume records but produce empty output
-            drop(records);
-            Ok(vec![])
+        remaining = remaining.checked_sub(days)?;
+    }
+    None
+}
+
+/// Rewrite the date at `pos,len` from one layout to another.
+///
+/// The old field is blanked and the new date is written at `pos` in the
+/// target layout's width, which may be narrower or wider than `len`. A date
+/// that doesn't read as `from` leaves the record unchanged, or with `flag`
+/// prefixes it with [`ASSERT_MARKER`].
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{DateFormat, Record, reformat_date};
+///
+/// let record = Record::from_str("SMITH   20240115");
+/// let out = reformat_date(&record, 8, 8, DateFormat::Ymd, DateFormat::Mdy, false);
+/// assert_eq!(out.as_str().trim_end(), "SMITH   01/15/2024");
+/// ```
+pub fn reformat_date(
+    record: &Record,
+    pos: usize,
+    len: usize,
+    from: DateFormat,
+    to: DateFormat,
+    flag: bool,
+) -> Record {
+    match from.read(record.field(pos, len)) {
+        Some((year, month, day)) => {
+            let mut out = record.clone();
+            out.set_field(pos, len, "");
+            out.set_field(pos, to.width(), &to.write(year, month, day));
+            out
         }
+        None if flag => flag_record(record),
+        None => record.clone(),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Command {
+    /// Can this stage be the first stage in a pipeline (source)?
+    /// Sources generate or read records without needing upstream input.
+    pub fn can_be_first(&self) -> bool {
+        // CONSOLE reads from input, LITERAL generates a record, HOLE generates empty stream
+        matches!(
+            self,
+            Command::Console
+                | Command::Literal { .. }
+                | Command::Generate { .. }
+                | Command::Hole { .. }
+        )
+    }
 
-    #[test]
-    fn test_parse_filter_eq() {
-        let cmd = parse_command(r#"FILTER 18,10 = "SALES""#).unwrap();
-        match cmd {
-            Command::FilterEq { pos, len, value } => {
-                assert_eq!(pos, 18);
-                assert_eq!(len, 10);
-                assert_eq!(value, "SALES");
+    /// Classify the stage by what it does to the record stream.
+    ///
+    /// The kind doesn't depend on where the stage sits, so a trailing
+    /// CONSOLE is still a `Source`.
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            Command::Console | Command::Literal { .. } | Command::Generate { .. } => {
+                CommandKind::Source
             }
-            _ => panic!("Expected FilterEq"),
+            Command::Hole { .. } => CommandKind::Sink,
+            Command::FilterEq { .. }
+            | Command::FilterNe { .. }
+            | Command::FilterRange { .. }
+            | Command::FilterFieldCmp { .. }
+            | Command::Omit { .. }
+            | Command::Route { .. }
+            | Command::OnChange { .. }
+            | Command::Take { .. }
+            | Command::Skip { .. }
+            | Command::Page { .. }
+            | Command::Locate { .. }
+            | Command::LocateMulti { .. }
+            | Command::Nlocate { .. }
+            | Command::Context { .. }
+            | Command::Length { .. }
+            | Command::Assert { strict: true, .. } => CommandKind::Filter,
+            Command::Count
+            | Command::Hash
+            | Command::Stats { passthrough: false }
+            | Command::Freq { .. }
+            | Command::Crosstab { .. }
+            | Command::TopN { .. }
+            | Command::Sort { .. }
+            | Command::Shuffle { .. }
+            | Command::Zip
+            | Command::Pivot { .. } => CommandKind::Aggregate,
+            Command::Select { .. }
+            | Command::Field { .. }
+            | Command::ToJson { .. }
+            | Command::Change { .. }
+            | Command::ChangeWhere { .. }
+            | Command::Upper
+            | Command::Lower
+            | Command::Reverse { .. }
+            | Command::Duplicate { .. }
+            | Command::Disabled { .. }
+            | Command::Block { .. }
+            | Command::Segment { .. }
+            | Command::SortItems { .. }
+            | Command::Header { .. }
+            | Command::Apply { .. }
+            | Command::Chop { .. }
+            | Command::Justify { .. }
+            | Command::Assert { strict: false, .. }
+            | Command::Verify { .. }
+            | Command::Extract { .. }
+            | Command::Build { .. }
+            | Command::Reseq { .. }
+            | Command::Expand { .. }
+            | Command::DateFmt { .. }
+            | Command::Decode { .. }
+            | Command::Default { .. }
+            | Command::Mask { .. }
+            | Command::Wrap { .. }
+            | Command::Include { .. }
+            | Command::InsertEvery { .. }
+            | Command::Norm { .. }
+            | Command::Stats { passthrough: true } => CommandKind::Transform,
         }
     }
 
-    #[test]
-    fn test_parse_filter_ne() {
-        let cmd = parse_command(r#"FILTER 18,10 != "SALES""#).unwrap();
-        match cmd {
+    /// Get the stage name for error messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Console => "CONSOLE",
+            Command::FilterEq { .. }
+            | Command::FilterNe { .. }
+            | Command::FilterRange { .. }
+            | Command::FilterFieldCmp { .. } => "FILTER",
+            Command::Select { .. } => "SELECT",
+            Command::Field { .. } => "FIELD",
+            Command::ToJson { .. } => "TOJSON",
+            Command::Take { .. } => "TAKE",
+            Command::Skip { .. } => "SKIP",
+            Command::Locate { .. } | Command::LocateMulti { .. } => "LOCATE",
+            Command::Nlocate { .. } => "NLOCATE",
+            Command::Context { .. } => "CONTEXT",
+            Command::Count => "COUNT",
+            Command::Hash => "HASH",
+            Command::Stats { .. } => "STATS",
+            Command::Change { .. } | Command::ChangeWhere { .. } => "CHANGE",
+            Command::Literal { .. } => "LITERAL",
+            Command::Generate { .. } => "GENERATE",
+            Command::Upper => "UPPER",
+            Command::Lower => "LOWER",
+            Command::Reverse { .. } => "REVERSE",
+            Command::Duplicate { .. } => "DUPLICATE",
+            Command::Block { .. } => "BLOCK",
+            Command::Segment { .. } => "SEGMENT",
+            Command::Chop { .. } => "CHOP",
+            Command::Apply { .. } => "APPLY",
+            Command::Header { .. } => "HEADER",
+            Command::Disabled { .. } => "DISABLED",
+            Command::Hole { .. } => "HOLE",
+            Command::Justify { .. } => "JUSTIFY",
+            Command::Assert { .. } => "ASSERT",
+            Command::Verify { .. } => "VERIFY",
+            Command::Extract { .. } => "EXTRACT",
+            Command::Length { .. } => "LENGTH",
+            Command::Freq { .. } => "FREQ",
+            Command::Crosstab { .. } => "CROSSTAB",
+            Command::Build { .. } => "BUILD",
+            Command::Zip => "ZIP",
+            Command::Page { .. } => "PAGE",
+            Command::Reseq { .. } => "RESEQ",
+            Command::TopN { .. } => "TOPN",
+            Command::Sort { .. } => "SORT",
+            Command::Expand { .. } => "EXPAND",
+            Command::DateFmt { .. } => "DATEFMT",
+            Command::Decode { .. } => "DECODE",
+            Command::Default { .. } => "DEFAULT",
+            Command::Mask { .. } => "MASK",
+            Command::Wrap { .. } => "WRAP",
+            Command::Shuffle { .. } => "SHUFFLE",
+            Command::Include { .. } => "INCLUDE",
+            Command::Omit { .. } => "OMIT",
+            Command::Route { .. } => "ROUTE",
+            Command::OnChange { .. } => "ONCHANGE",
+            Command::SortItems { .. } => "SORTITEMS",
+            Command::Pivot { .. } => "PIVOT",
+            Command::Norm { .. } => "NORM",
+            Command::InsertEvery { .. } => "INSERT",
+        }
+    }
+
+    /// The `pos,len` column ranges the stage reads or writes.
+    ///
+    /// SELECT contributes each destination (`dest,len`) as well as each
+    /// source field. Used by the `ExecuteOptions::strict_bounds` check.
+    pub fn field_specs(&self) -> Vec<(usize, usize)> {
+        match self {
+            Command::FilterEq { pos, len, .. }
+            | Command::FilterNe { pos, len, .. }
+            | Command::FilterRange { pos, len, .. }
+            | Command::Field { pos, len, .. }
+            | Command::Justify { pos, len, .. }
+            | Command::Assert { pos, len, .. }
+            | Command::Freq { pos, len }
+            | Command::Reseq { pos, len, .. }
+            | Command::TopN { pos, len, .. }
+            | Command::Expand { pos, len, .. }
+            | Command::DateFmt { pos, len, .. }
+            | Command::Decode { pos, len, .. }
+            | Command::Default { pos, len, .. }
+            | Command::Norm { pos, len }
+            | Command::OnChange { pos, len }
+            | Command::SortItems { pos, len, .. } => vec![(*pos, *len)],
+            Command::FilterFieldCmp { left, right, .. } => vec![*left, *right],
+            Command::Crosstab {
+                row_field,
+                col_field,
+            } => vec![*row_field, *col_field],
+            Command::Select {
+                fields,
+                separator: None,
+            } => fields
+                .iter()
+                .flat_map(|&(pos, len, dest)| [(pos, len), (dest, len)])
+                .collect(),
+            // With a separator, destinations only order the fields
+            Command::Select {
+                fields,
+                separator: Some(_),
+            } => fields.iter().map(|&(pos, len, _)| (pos, len)).collect(),
+            Command::ToJson { fields } => fields.iter().map(|(_, pos, len)| (*pos, *len)).collect(),
+            Command::Locate { field, .. }
+            | Command::LocateMulti { field, .. }
+            | Command::Nlocate { field, .. } => field.iter().copied().collect(),
+            Command::Build { items } => items
+                .iter()
+                .filter_map(|item| match item {
+                    BuildItem::Field { pos, len } => Some((*pos, *len)),
+                    BuildItem::Literal(_) => None,
+                })
+                .collect(),
+            Command::Sort { keys } => keys.iter().map(|key| (key.pos, key.len)).collect(),
+            Command::Mask { fields, .. } => fields.clone(),
+            Command::Omit { filter }
+            | Command::Route { filter, .. }
+            | Command::ChangeWhere {
+                condition: filter, ..
+            } => filter.field_specs(),
+            Command::Pivot {
+                key, value, group, ..
+            } => [*key, *value].into_iter().chain(*group).collect(),
+            Command::Verify { checks } => checks.iter().map(|(pos, len, _)| (*pos, *len)).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Describe the stage in one plain-English sentence, without a final
+    /// period. Columns are 1-based, as on a ruler: field `18,10` is
+    /// "columns 19–28". [`explain`] builds on this.
+    pub fn describe(&self) -> String {
+        let within = |field: &Option<(usize, usize)>| match field {
+            Some((pos, len)) => format!(" in {}", columns(*pos, *len)),
+            None => String::new(),
+        };
+        let quoted = |items: &[String]| {
+            items
+                .iter()
+                .map(|s| format!("'{s}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Command::Console => "Read input or write output".to_string(),
+            Command::FilterEq { pos, len, value } => {
+                format!("Keep records where {} equal '{value}'", columns(*pos, *len))
+            }
             Command::FilterNe { pos, len, value } => {
-                assert_eq!(pos, 18);
-                assert_eq!(len, 10);
+                format!("Drop records where {} equal '{value}'", columns(*pos, *len))
+            }
+            Command::FilterRange {
+                pos,
+                len,
+                low,
+                high,
+            } => format!(
+                "Keep records where {} are between '{low}' and '{high}'",
+                columns(*pos, *len)
+            ),
+            Command::FilterFieldCmp { left, right, op } => format!(
+                "Keep records where {} {} {}",
+                columns(left.0, left.1),
+                op.verb(),
+                columns(right.0, right.1)
+            ),
+            Command::Select { fields, separator } => {
+                let moves: Vec<String> = fields
+                    .iter()
+                    .map(|(pos, len, dest)| {
+                        format!("{} to column {}", columns(*pos, *len), dest + 1)
+                    })
+                    .collect();
+                match separator {
+                    Some(sep) => format!(
+                        "Build new records from {}, with '{sep}' between fields",
+                        moves.join(", ")
+                    ),
+                    None => format!("Build new records from {}", moves.join(", ")),
+                }
+            }
+            Command::Field { pos, len, trim } => {
+                let how = if *trim { "trimmed" } else { "with its padding" };
+                format!("Replace each record with {}, {how}", columns(*pos, *len))
+            }
+            Command::ToJson { fields } => {
+                let members: Vec<String> = fields
+                    .iter()
+                    .map(|(name, pos, len)| format!("'{name}' from {}", columns(*pos, *len)))
+                    .collect();
+                format!(
+                    "Rewrite each record as a JSON object of {}",
+                    members.join(", ")
+                )
+            }
+            Command::Take { n } => format!("Keep only the first {n} records"),
+            Command::Skip { n } => format!("Skip the first {n} records"),
+            Command::Locate {
+                pattern,
+                field,
+                trim,
+            } => {
+                let trimmed = if *trim { ", ignoring padding" } else { "" };
+                format!(
+                    "Keep records containing '{pattern}'{}{trimmed}",
+                    within(field)
+                )
+            }
+            Command::LocateMulti {
+                patterns,
+                mode,
+                field,
+            } => {
+                let which = match mode {
+                    MatchMode::Any => "any",
+                    MatchMode::All => "all",
+                };
+                format!(
+                    "Keep records containing {which} of {}{}",
+                    quoted(patterns),
+                    within(field)
+                )
+            }
+            Command::Nlocate { pattern, field } => {
+                format!("Drop records containing '{pattern}'{}", within(field))
+            }
+            Command::Count => "Replace all records with a count of them".to_string(),
+            Command::Hash => "Replace all records with a hash of them".to_string(),
+            Command::Stats { passthrough: false } => {
+                "Replace all records with their count and min/max/average length".to_string()
+            }
+            Command::Stats { passthrough: true } => {
+                "Pass records through, then add their count and min/max/average length".to_string()
+            }
+            Command::Change {
+                old,
+                new,
+                limit,
+                repeat,
+            } => {
+                let base = match limit {
+                    None => format!("Replace every '{old}' with '{new}'"),
+                    Some(1) => format!("Replace the first '{old}' in each record with '{new}'"),
+                    Some(n) => format!("Replace the first {n} '{old}' in each record with '{new}'"),
+                };
+                if *repeat {
+                    format!("{base}, repeating until the record stops changing")
+                } else {
+                    base
+                }
+            }
+            Command::ChangeWhere {
+                condition,
+                old,
+                new,
+            } => {
+                let inner = condition.describe();
+                let which = inner.strip_prefix("Keep ").unwrap_or(&inner);
+                format!("Replace every '{old}' with '{new}' in {which}, passing all records on")
+            }
+            Command::Literal { text } => format!("Emit the record '{text}' before the input"),
+            Command::Generate {
+                count,
+                template,
+                seed,
+            } => format!(
+                "Emit {count} records from the template '{template}' (seed {seed}) before the input"
+            ),
+            Command::Upper => "Convert records to uppercase".to_string(),
+            Command::Lower => "Convert records to lowercase".to_string(),
+            Command::Reverse { full: false } => "Reverse the text of each record".to_string(),
+            Command::Reverse { full: true } => "Reverse all 80 columns of each record".to_string(),
+            Command::Duplicate { n } => format!("Repeat each record {n} times"),
+            Command::Block { n } => format!("Insert a blank record after every {n} records"),
+            Command::Segment { n } => {
+                format!("Insert a numbered segment header before each group of {n} records")
+            }
+            Command::Chop { width } => format!("Split each record into {width}-column pieces"),
+            Command::Apply { name, .. } => {
+                format!("Rewrite each record with the '{name}' transform")
+            }
+            Command::Header { template } => {
+                format!("Emit the header '{template}' before all records")
+            }
+            Command::Disabled { original } => {
+                format!("Pass records through ('{original}' is disabled)")
+            }
+            Command::Hole { count: false } => "Discard all records".to_string(),
+            Command::Hole { count: true } => {
+                "Discard all records, emitting how many were dropped".to_string()
+            }
+            Command::Justify {
+                pos,
+                len,
+                side,
+                fill,
+            } => {
+                let side = match side {
+                    JustifySide::Left => "left",
+                    JustifySide::Right => "right",
+                };
+                let padding = if *fill == ' ' {
+                    String::new()
+                } else {
+                    format!(", padding with '{fill}'")
+                };
+                format!("Align {} to the {side}{padding}", columns(*pos, *len))
+            }
+            Command::Assert {
+                pos,
+                len,
+                kind,
+                strict,
+            } => {
+                let action = if *strict { "Drop" } else { "Flag" };
+                format!(
+                    "{action} records where {} is not {}",
+                    columns(*pos, *len),
+                    kind.adjective()
+                )
+            }
+            Command::Verify { checks } => {
+                let checks: Vec<String> = checks
+                    .iter()
+                    .map(|(pos, len, kind)| format!("{} {}", columns(*pos, *len), kind.adjective()))
+                    .collect();
+                format!(
+                    "Check that {}, replacing failing records with one diagnostic per failed check",
+                    checks.join(" and ")
+                )
+            }
+            Command::Extract {
+                marker,
+                keep_unmatched,
+            } => {
+                let unmatched = if *keep_unmatched {
+                    "blank records"
+                } else {
+                    "dropped"
+                };
+                format!(
+                    "Keep only the text after '{marker}' (records without it become {unmatched})"
+                )
+            }
+            Command::Length {
+                min,
+                max: Some(max),
+            } => format!("Keep records between {min} and {max} characters long"),
+            Command::Length { min, max: None } => {
+                format!("Keep records at least {min} characters long")
+            }
+            Command::Freq { pos, len } => format!(
+                "Count how often each value of {} occurs, most frequent first",
+                columns(*pos, *len)
+            ),
+            Command::Crosstab {
+                row_field,
+                col_field,
+            } => format!(
+                "Count records by {} (one row per value) and {} (one column per value)",
+                columns(row_field.0, row_field.1),
+                columns(col_field.0, col_field.1)
+            ),
+            Command::Build { items } => {
+                let parts: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        BuildItem::Field { pos, len } => columns(*pos, *len),
+                        BuildItem::Literal(text) => format!("'{text}'"),
+                    })
+                    .collect();
+                format!("Build each record from {}", parts.join(", "))
+            }
+            Command::Zip => "Join records in pairs, side by side".to_string(),
+            Command::Page { page, size } => {
+                format!("Keep page {page} of the records, {size} records per page")
+            }
+            Command::Reseq {
+                pos,
+                len,
+                start,
+                step,
+                fill,
+            } => format!(
+                "Number records in {}, starting at {start} and counting by {step}, padded with '{fill}'",
+                columns(*pos, *len)
+            ),
+            Command::TopN { n, pos, len } => format!(
+                "Keep the {n} records with the largest number in {}",
+                columns(*pos, *len)
+            ),
+            Command::Sort { keys } => {
+                let parts: Vec<String> = keys
+                    .iter()
+                    .map(|k| {
+                        let dir = match k.dir {
+                            SortDir::Asc => "ascending",
+                            SortDir::Desc => "descending",
+                        };
+                        let num = if k.numeric { " numerically" } else { "" };
+                        format!("{} {dir}{num}", columns(k.pos, k.len))
+                    })
+                    .collect();
+                format!("Sort records by {}", parts.join(", then "))
+            }
+            Command::Expand { pos, len, clear } => {
+                let cleared = if *clear { ", then blank the count" } else { "" };
+                format!(
+                    "Repeat each record as many times as the number in {}{cleared}",
+                    columns(*pos, *len)
+                )
+            }
+            Command::DateFmt {
+                pos,
+                len,
+                from,
+                to,
+                flag,
+            } => {
+                let invalid = if *flag {
+                    "flagging invalid dates"
+                } else {
+                    "leaving invalid dates unchanged"
+                };
+                format!(
+                    "Rewrite the date in {} from {} to {}, {invalid}",
+                    columns(*pos, *len),
+                    from.pattern(),
+                    to.pattern()
+                )
+            }
+            Command::Decode {
+                pos,
+                len,
+                map,
+                default,
+            } => {
+                let unmatched = match default {
+                    Some(text) => format!("others become '{text}'"),
+                    None => "others are left unchanged".to_string(),
+                };
+                format!(
+                    "Replace the code in {} using a table of {} entries; {unmatched}",
+                    columns(*pos, *len),
+                    map.len()
+                )
+            }
+            Command::Default {
+                pos,
+                len,
+                value,
+                justify,
+            } => {
+                let side = match justify {
+                    JustifySide::Left => "left",
+                    JustifySide::Right => "right",
+                };
+                format!(
+                    "Write '{value}' into {} when it is blank, aligned {side}",
+                    columns(*pos, *len)
+                )
+            }
+            Command::Mask { fields, fill } => {
+                let ranges: Vec<String> = fields
+                    .iter()
+                    .map(|(pos, len)| columns(*pos, *len))
+                    .collect();
+                format!("Overwrite {} with '{fill}'", ranges.join(" and "))
+            }
+            Command::Shuffle { seed } => format!("Shuffle the records (seed {seed})"),
+            Command::Include { name } => format!("Run the stages of the '{name}' sub-pipeline"),
+            Command::Pivot {
+                key,
+                value,
+                group,
+                columns: cols,
+            } => {
+                let boundary = match group {
+                    Some((pos, len)) => {
+                        format!("a blank record or a change in {}", columns(*pos, *len))
+                    }
+                    None => "a blank record".to_string(),
+                };
+                let keys: Vec<String> = cols.iter().map(|(name, _)| name.clone()).collect();
+                format!(
+                    "Combine each group of records (ended by {boundary}) into one record, placing the value in {} for keys {} from {}",
+                    columns(value.0, value.1),
+                    quoted(&keys),
+                    columns(key.0, key.1)
+                )
+            }
+            Command::Context {
+                pattern,
+                before,
+                after,
+            } => format!(
+                "Keep records containing '{pattern}', with up to {before} records before and {after} after each"
+            ),
+            Command::InsertEvery { n, text, trailing } => {
+                let end = if *trailing {
+                    ""
+                } else {
+                    ", except after the last record"
+                };
+                format!("Insert the record '{text}' after every {n} records{end}")
+            }
+            Command::Norm { pos, len } => format!(
+                "Rewrite the number in {} without leading zeros, right-justified",
+                columns(*pos, *len)
+            ),
+            Command::Omit { filter } => {
+                let inner = filter.describe();
+                match inner.strip_prefix("Keep ") {
+                    Some(rest) => format!("Drop {rest}"),
+                    None => inner.replacen("Drop ", "Keep ", 1),
+                }
+            }
+            Command::Route {
+                filter,
+                to,
+                otherwise,
+            } => {
+                let inner = filter.describe();
+                let which = inner.strip_prefix("Keep ").unwrap_or(&inner);
+                match otherwise {
+                    Some(other) => format!("Send {which} to channel '{to}', the rest to '{other}'"),
+                    None => format!("Send {which} to channel '{to}', passing the rest on"),
+                }
+            }
+            Command::SortItems {
+                pos,
+                len,
+                item_width,
+            } => format!(
+                "Sort the {item_width}-character items within {} of each record",
+                columns(*pos, *len)
+            ),
+            Command::OnChange { pos, len } => format!(
+                "Keep the first record and each record whose {} differs from the previous record's",
+                columns(*pos, *len)
+            ),
+            Command::Wrap { width, cont_col } => match cont_col {
+                Some(col) => format!(
+                    "Word-wrap each record into {width}-column pieces, marking continued pieces with '{WRAP_MARKER}' in column {col}"
+                ),
+                None => format!("Word-wrap each record into {width}-column pieces"),
+            },
+        }
+    }
+
+    /// Write the stage back as DSL text that parses to the same command.
+    ///
+    /// Keywords are uppercase, options that hold their default are left
+    /// out, and strings are delimited with `"` unless they contain one.
+    /// Positions are written as parsed, so a negative position comes back
+    /// as the column it counted to.
+    pub fn to_dsl_string(&self) -> String {
+        let field = |field: &Option<(usize, usize)>| match field {
+            Some((pos, len)) => format!(" {pos},{len}"),
+            None => String::new(),
+        };
+        let fill = |fill: char, default: char| match fill {
+            _ if fill == default => String::new(),
+            ' ' => format!(" FILL {}", delimited(" ")),
+            _ => format!(" FILL {fill}"),
+        };
+        let flag = |set: bool, word: &str| {
+            if set {
+                format!(" {word}")
+            } else {
+                String::new()
+            }
+        };
+        match self {
+            Command::Console => "CONSOLE".to_string(),
+            Command::FilterEq { pos, len, value } => {
+                format!("FILTER {pos},{len} = {}", delimited(value))
+            }
+            Command::FilterNe { pos, len, value } => {
+                format!("FILTER {pos},{len} != {}", delimited(value))
+            }
+            Command::FilterRange {
+                pos,
+                len,
+                low,
+                high,
+            } => format!(
+                "FILTER {pos},{len} BETWEEN {} AND {}",
+                delimited(low),
+                delimited(high)
+            ),
+            Command::FilterFieldCmp { left, right, op } => format!(
+                "FILTER {},{} {} FIELD {},{}",
+                left.0,
+                left.1,
+                op.symbol(),
+                right.0,
+                right.1
+            ),
+            Command::Select { fields, separator } => {
+                let specs: Vec<String> = fields
+                    .iter()
+                    .map(|(pos, len, dest)| format!("{pos},{len},{dest}"))
+                    .collect();
+                match separator {
+                    Some(sep) => format!("SELECT {} SEP {}", specs.join("; "), delimited(sep)),
+                    None => format!("SELECT {}", specs.join("; ")),
+                }
+            }
+            Command::Field { pos, len, trim } => format!("FIELD {pos},{len}{}", flag(!trim, "PAD")),
+            Command::ToJson { fields } => {
+                let specs: Vec<String> = fields
+                    .iter()
+                    .map(|(name, pos, len)| format!("{name}={pos},{len}"))
+                    .collect();
+                format!("TOJSON {}", specs.join(" "))
+            }
+            Command::Take { n } => format!("TAKE {n}"),
+            Command::Skip { n } => format!("SKIP {n}"),
+            Command::Locate {
+                pattern,
+                field: at,
+                trim,
+            } => format!(
+                "LOCATE{}{} {}",
+                field(at),
+                flag(*trim, "TRIM"),
+                delimited(pattern)
+            ),
+            Command::LocateMulti {
+                patterns,
+                mode,
+                field: at,
+            } => {
+                let mode = match mode {
+                    MatchMode::Any => "ANY",
+                    MatchMode::All => "ALL",
+                };
+                let patterns: Vec<String> = patterns.iter().map(|p| delimited(p)).collect();
+                format!("LOCATE{} {mode} {}", field(at), patterns.join(" "))
+            }
+            Command::Context {
+                pattern,
+                before,
+                after,
+            } => format!("CONTEXT {} {before},{after}", delimited(pattern)),
+            Command::Nlocate { pattern, field: at } => {
+                format!("NLOCATE{} {}", field(at), delimited(pattern))
+            }
+            Command::Count => "COUNT".to_string(),
+            Command::Hash => "HASH".to_string(),
+            Command::Stats { passthrough } => format!("STATS{}", flag(*passthrough, "PASSTHROUGH")),
+            Command::Change {
+                old,
+                new,
+                limit,
+                repeat,
+            } => {
+                let limit = limit.map(|n| format!(" {n}")).unwrap_or_default();
+                format!(
+                    "CHANGE {} {}{limit}{}",
+                    delimited(old),
+                    delimited(new),
+                    flag(*repeat, "REPEAT")
+                )
+            }
+            Command::ChangeWhere {
+                condition,
+                old,
+                new,
+            } => {
+                let condition = condition.to_dsl_string();
+                let condition = condition.trim_start_matches("FILTER");
+                format!(
+                    "CHANGE {} {} WHERE{condition}",
+                    delimited(old),
+                    delimited(new)
+                )
+            }
+            Command::Literal { text } => format!("LITERAL {text}"),
+            Command::Generate {
+                count,
+                template,
+                seed: 0,
+            } => format!("GENERATE {count} {}", delimited(template)),
+            Command::Generate {
+                count,
+                template,
+                seed,
+            } => format!("GENERATE {count} {} SEED {seed}", delimited(template)),
+            Command::Upper => "UPPER".to_string(),
+            Command::Lower => "LOWER".to_string(),
+            Command::Reverse { full } => format!("REVERSE{}", flag(*full, "FULL")),
+            Command::Duplicate { n } => format!("DUPLICATE {n}"),
+            Command::Disabled { original } => format!("{DISABLED_PREFIX} | {original}"),
+            Command::Block { n } => format!("BLOCK {n}"),
+            Command::Segment { n } => format!("SEGMENT {n}"),
+            Command::Header { template } => format!("HEADER {template}"),
+            Command::Apply { name, .. } => format!("APPLY {name}"),
+            Command::Chop { width } => format!("CHOP {width}"),
+            Command::Hole { count } => format!("HOLE{}", flag(*count, "COUNT")),
+            Command::Justify {
+                pos,
+                len,
+                side,
+                fill: with,
+            } => {
+                let side = match side {
+                    JustifySide::Left => "LEFT",
+                    JustifySide::Right => "RIGHT",
+                };
+                format!("JUSTIFY {pos},{len} {side}{}", fill(*with, ' '))
+            }
+            Command::Assert {
+                pos,
+                len,
+                kind,
+                strict,
+            } => {
+                format!(
+                    "ASSERT {pos},{len} {}{}",
+                    kind.keyword(),
+                    flag(*strict, "STRICT")
+                )
+            }
+            Command::Verify { checks } => {
+                let checks: Vec<String> = checks
+                    .iter()
+                    .map(|(pos, len, kind)| format!("{pos},{len} {}", kind.keyword()))
+                    .collect();
+                format!("VERIFY {}", checks.join("; "))
+            }
+            Command::Extract {
+                marker,
+                keep_unmatched,
+            } => format!(
+                "EXTRACT {}{}",
+                delimited(marker),
+                flag(!keep_unmatched, "DROP")
+            ),
+            Command::Length { min, max } => match max {
+                Some(max) => format!("LENGTH {min},{max}"),
+                None => format!("LENGTH {min},"),
+            },
+            Command::Freq { pos, len } => format!("FREQ {pos},{len}"),
+            Command::Crosstab {
+                row_field,
+                col_field,
+            } => format!(
+                "CROSSTAB {},{} BY {},{}",
+                row_field.0, row_field.1, col_field.0, col_field.1
+            ),
+            Command::Build { items } => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        BuildItem::Field { pos, len } => format!("{pos},{len}"),
+                        BuildItem::Literal(text) => delimited(text),
+                    })
+                    .collect();
+                format!("BUILD {}", items.join(" "))
+            }
+            Command::Zip => "ZIP".to_string(),
+            Command::Page { page, size } => format!("PAGE {page},{size}"),
+            Command::Reseq {
+                pos,
+                len,
+                start,
+                step,
+                fill: with,
+            } => format!("RESEQ {pos},{len} {start},{step}{}", fill(*with, '0')),
+            Command::TopN { n, pos, len } => format!("TOPN {n} {pos},{len}"),
+            Command::Sort { keys } => {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|key| {
+                        let dir = match key.dir {
+                            SortDir::Asc => "",
+                            SortDir::Desc => " DESC",
+                        };
+                        format!("{},{}{dir}{}", key.pos, key.len, flag(key.numeric, "NUM"))
+                    })
+                    .collect();
+                format!("SORT {}", keys.join("; "))
+            }
+            Command::Expand { pos, len, clear } => {
+                format!("EXPAND {pos},{len}{}", flag(*clear, "CLEAR"))
+            }
+            Command::DateFmt {
+                pos,
+                len,
+                from,
+                to,
+                flag: flagged,
+            } => format!(
+                "DATEFMT {pos},{len} FROM {} TO {}{}",
+                from.keyword(),
+                to.keyword(),
+                flag(*flagged, "FLAG")
+            ),
+            Command::Decode {
+                pos,
+                len,
+                map,
+                default,
+            } => {
+                let pairs: Vec<String> = map
+                    .iter()
+                    .map(|(code, text)| format!("{code}={text}"))
+                    .collect();
+                let default = default
+                    .as_ref()
+                    .map(|text| format!(" DEFAULT {}", delimited(text)))
+                    .unwrap_or_default();
+                format!(
+                    "DECODE {pos},{len} {}{default}",
+                    delimited(&pairs.join(";"))
+                )
+            }
+            Command::Default {
+                pos,
+                len,
+                value,
+                justify,
+            } => match justify {
+                JustifySide::Left => format!("DEFAULT {pos},{len} {}", delimited(value)),
+                JustifySide::Right => format!("DEFAULT {pos},{len} {} RIGHT", delimited(value)),
+            },
+            Command::Mask { fields, fill: with } => {
+                let specs: Vec<String> = fields
+                    .iter()
+                    .map(|(pos, len)| format!("{pos},{len}"))
+                    .collect();
+                format!("MASK {}{}", specs.join("; "), fill(*with, '*'))
+            }
+            Command::Wrap { width, cont_col } => match cont_col {
+                Some(col) => format!("WRAP {width} CONT {col}"),
+                None => format!("WRAP {width}"),
+            },
+            Command::Shuffle { seed } => format!("SHUFFLE {seed}"),
+            Command::Include { name } => format!("INCLUDE {name}"),
+            Command::Omit { filter } => {
+                let condition = filter.to_dsl_string();
+                format!("OMIT{}", condition.trim_start_matches("FILTER"))
+            }
+            Command::Route {
+                filter,
+                to,
+                otherwise,
+            } => {
+                let condition = filter.to_dsl_string();
+                let condition = condition.trim_start_matches("FILTER");
+                match otherwise {
+                    Some(other) => format!("ROUTE{condition} TO {to} ELSE {other}"),
+                    None => format!("ROUTE{condition} TO {to}"),
+                }
+            }
+            Command::OnChange { pos, len } => format!("ONCHANGE {pos},{len}"),
+            Command::SortItems {
+                pos,
+                len,
+                item_width,
+            } => format!("SORTITEMS {pos},{len},{item_width}"),
+            Command::InsertEvery { n, text, trailing } => format!(
+                "INSERT EVERY {n} {}{}",
+                delimited(text),
+                flag(*trailing, "TRAILING")
+            ),
+            Command::Norm { pos, len } => format!("NORM {pos},{len}"),
+            Command::Pivot {
+                key,
+                value,
+                group,
+                columns,
+            } => {
+                let group = group
+                    .map(|(pos, len)| format!(" group={pos},{len}"))
+                    .unwrap_or_default();
+                let columns: Vec<String> = columns
+                    .iter()
+                    .map(|(name, dest)| format!("{name}:{dest}"))
+                    .collect();
+                format!(
+                    "PIVOT key={},{} value={},{}{group} {}",
+                    key.0,
+                    key.1,
+                    value.0,
+                    value.1,
+                    columns.join(" ")
+                )
+            }
+        }
+    }
+}
+
+/// Line prefix that disables a stage while keeping its place in the pipeline.
+pub const DISABLED_PREFIX: &str = "#!";
+
+/// Return the stage text of a `#!` disabled-stage line, if `line` is one.
+///
+/// A leading `|` after the prefix is dropped, so `#! | UPPER` and `#! UPPER`
+/// both disable `UPPER`. A bare `#!` is an ordinary comment.
+pub fn disabled_stage(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(DISABLED_PREFIX)?.trim();
+    let rest = rest.strip_prefix('|').unwrap_or(rest).trim();
+    (!rest.is_empty()).then_some(rest)
+}
+
+/// Comment prefix that introduces a record layout directive.
+const LAYOUT_DIRECTIVE: &str = "LAYOUT";
+
+/// Intended field layout of the records flowing through a pipeline.
+///
+/// Declared with a comment directive such as `# LAYOUT Last 0,8; First 8,10`.
+/// Each field is `(name, pos, len)` using the same 0-based positions as
+/// stage field specs. The layout does not affect execution; it is carried
+/// along so debuggers can label columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordLayout {
+    pub fields: Vec<(String, usize, usize)>,
+}
+
+/// Extract the `# LAYOUT` directive from pipeline text, if present.
+///
+/// Returns an error if the directive is malformed or appears more than once.
+pub fn parse_layout(text: &str) -> Result<Option<RecordLayout>, String> {
+    let mut layout = None;
+    for (line_num, line) in text.lines().enumerate() {
+        let Some(spec) = layout_directive(line) else {
+            continue;
+        };
+        if layout.is_some() {
+            return Err(format!("Line {}: duplicate LAYOUT directive", line_num + 1));
+        }
+        let parsed =
+            parse_layout_fields(spec).map_err(|e| format!("Line {}: {e}", line_num + 1))?;
+        layout = Some(parsed);
+    }
+    Ok(layout)
+}
+
+/// Return the field list if `line` is a `# LAYOUT` directive.
+fn layout_directive(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix('#')?.trim_start();
+    let keyword = rest.get(..LAYOUT_DIRECTIVE.len())?;
+    if !keyword.eq_ignore_ascii_case(LAYOUT_DIRECTIVE) {
+        return None;
+    }
+    let spec = &rest[LAYOUT_DIRECTIVE.len()..];
+    if spec.is_empty() || spec.starts_with(char::is_whitespace) {
+        Some(spec.trim())
+    } else {
+        None
+    }
+}
+
+/// Parse the `name pos,len; name pos,len` field list of a LAYOUT directive.
+fn parse_layout_fields(spec: &str) -> Result<RecordLayout, String> {
+    let mut fields = Vec::new();
+    for part in spec.split(';') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let (name, pos_len) = part
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("LAYOUT field '{part}' must be 'name pos,len'"))?;
+        let (pos, len) = parse_pos_len(pos_len.trim(), "LAYOUT")?;
+        fields.push((name.to_string(), pos, len));
+    }
+    if fields.is_empty() {
+        return Err("LAYOUT requires at least one field".to_string());
+    }
+    Ok(RecordLayout { fields })
+}
+
+/// Parse DSL text into commands.
+///
+/// `APPLY` names are resolved against the built-in transforms. No
+/// sub-pipelines are registered, so any `INCLUDE` is an error.
+pub fn parse_commands(text: &str) -> Result<Vec<Command>, String> {
+    parse_commands_with_transforms(text, &TransformRegistry::default())
+}
+
+/// Parse DSL text into commands, resolving `APPLY` names against `transforms`.
+pub fn parse_commands_with_transforms(
+    text: &str,
+    transforms: &TransformRegistry,
+) -> Result<Vec<Command>, String> {
+    parse_text(text, transforms, Some(&PipelineRegistry::default()))
+}
+
+/// Parse DSL text into commands, resolving `APPLY` names against
+/// `options.transforms` and expanding `INCLUDE` from `options.pipelines`.
+pub fn parse_commands_with_options(
+    text: &str,
+    options: &ExecuteOptions,
+) -> Result<Vec<Command>, String> {
+    parse_text(text, &options.transforms, Some(&options.pipelines))
+}
+
+/// Rewrite pipeline text in a normalized form, for storing and diffing.
+///
+/// The pipeline is parsed and each stage written back with
+/// [`Command::to_dsl_string`], one per line: `PIPE` before the first
+/// stage, `| ` before the rest, and `?` on a line of its own at the end.
+/// Disabled stages are kept as `#! | stage` lines and a `# LAYOUT`
+/// directive is kept at the top; other comments are dropped. Two
+/// pipelines that parse to the same stages canonicalize to the same text.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::canonicalize;
+///
+/// let text = canonicalize("pipe console|  filter 18,10 = \"SALES\" | console ?").unwrap();
+/// assert_eq!(text, "PIPE CONSOLE\n| FILTER 18,10 = \"SALES\"\n| CONSOLE\n?");
+/// ```
+pub fn canonicalize(pipeline: &str) -> Result<String, PipelineError> {
+    let layout = parse_layout(pipeline).map_err(PipelineError::Parse)?;
+    let commands = parse_commands(pipeline).map_err(PipelineError::Parse)?;
+
+    let mut lines = Vec::new();
+    if let Some(layout) = layout {
+        let fields: Vec<String> = layout
+            .fields
+            .iter()
+            .map(|(name, pos, len)| format!("{name} {pos},{len}"))
+            .collect();
+        lines.push(format!("# {LAYOUT_DIRECTIVE} {}", fields.join("; ")));
+    }
+    let mut prefix = "PIPE ";
+    for command in &commands {
+        if matches!(command, Command::Disabled { .. }) {
+            lines.push(command.to_dsl_string());
+        } else {
+            lines.push(format!("{prefix}{}", command.to_dsl_string()));
+            prefix = "| ";
+        }
+    }
+    lines.push("?".to_string());
+    Ok(lines.join("\n"))
+}
+
+/// Parse the stages of a sub-pipeline for a [`PipelineRegistry`].
+///
+/// Unlike [`parse_commands`], any `INCLUDE` is kept as a
+/// `Command::Include` and only expanded when a pipeline including this one
+/// is parsed, so snippets can be registered in any order.
+pub fn parse_snippet(text: &str) -> Result<Vec<Command>, String> {
+    parse_text(text, &TransformRegistry::default(), None)
+}
+
+/// Parse DSL text, expanding `INCLUDE` from `pipelines` unless it is `None`.
+fn parse_text(
+    text: &str,
+    transforms: &TransformRegistry,
+    pipelines: Option<&PipelineRegistry>,
+) -> Result<Vec<Command>, String> {
+    let mut commands = Vec::new();
+
+    // Normalize: split each source line on '|' so that both multi-line and
+    // single-line pipeline definitions work.  We keep track of the original
+    // line number for error messages.
+    // A disabled stage line is kept whole, as its text may contain '|'.
+    let mut segments: Vec<(usize, &str)> = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with(DISABLED_PREFIX) {
+            segments.push((line_num, line));
+            continue;
+        }
+        for part in line.split('|') {
+            segments.push((line_num, part));
+        }
+    }
+
+    for (line_num, segment) in segments {
+        let segment = segment.trim();
+
+        // Layout directives are comments, but must still be well-formed
+        if let Some(spec) = layout_directive(segment) {
+            parse_layout_fields(spec).map_err(|e| format!("Line {}: {e}", line_num + 1))?;
+            continue;
+        }
+
+        if let Some(original) = disabled_stage(segment) {
+            commands.push(Command::Disabled {
+                original: original.to_string(),
+            });
+            continue;
+        }
+
+        // Skip empty segments and comments
+        if segment.is_empty() || segment.starts_with('#') {
+            continue;
+        }
+
+        // Handle "PIPE COMMAND" - extract command after PIPE
+        let segment = if segment.to_uppercase().starts_with("PIPE ") {
+            segment[5..].trim()
+        } else if segment.eq_ignore_ascii_case("PIPE") {
+            // Skip standalone PIPE declaration
+            continue;
+        } else {
+            segment
+        };
+
+        // Remove trailing ? (explicit end of pipeline)
+        let segment = segment.trim_end_matches('?').trim();
+
+        // Skip if segment is now empty
+        if segment.is_empty() {
+            continue;
+        }
+
+        let cmd = parse_command_with(segment, transforms)
+            .map_err(|e| format!("Line {}: {}", line_num + 1, e))?;
+        match (cmd, pipelines) {
+            (Command::Include { name }, Some(pipelines)) => {
+                expand_include(&name, pipelines, &mut Vec::new(), &mut commands)
+                    .map_err(|e| format!("Line {}: {}", line_num + 1, e))?;
+            }
+            (cmd, _) => commands.push(cmd),
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Append the stages of sub-pipeline `name` to `out`, expanding nested
+/// `INCLUDE`s. `chain` holds the names being expanded, outermost first.
+fn expand_include(
+    name: &str,
+    pipelines: &PipelineRegistry,
+    chain: &mut Vec<String>,
+    out: &mut Vec<Command>,
+) -> Result<(), String> {
+    let repeated = chain.iter().any(|n| n.eq_ignore_ascii_case(name));
+    chain.push(name.to_string());
+    if repeated || chain.len() > MAX_INCLUDE_DEPTH {
+        return Err(PipelineError::IncludeCycle {
+            chain: chain.clone(),
+        }
+        .to_string());
+    }
+    let stages = pipelines.get(name).ok_or_else(|| {
+        let names = pipelines.names();
+        let available = if names.is_empty() {
+            "none registered".to_string()
+        } else {
+            format!("available: {}", names.join(", "))
+        };
+        format!("Unknown pipeline '{name}' ({available})")
+    })?;
+    for stage in stages {
+        match stage {
+            Command::Include { name } => expand_include(name, pipelines, chain, out)?,
+            stage => out.push(stage.clone()),
+        }
+    }
+    chain.pop();
+    Ok(())
+}
+
+/// Parser for one catalog entry. Receives the whole stage text, with any
+/// alias already rewritten to the command's canonical name.
+type ParseFn = fn(&str, &TransformRegistry) -> Result<Command, String>;
+
+/// One supported command: its keyword, syntax, and a short description.
+///
+/// [`command_catalog`] lists every command the parser accepts; the parser
+/// dispatches from the same table, so the two cannot drift apart.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    /// Canonical keyword, as reported by [`Command::name`].
+    pub name: &'static str,
+    /// Other keywords accepted for this command.
+    pub aliases: &'static [&'static str],
+    /// Syntax summary, e.g. `TAKE n`.
+    pub syntax: &'static str,
+    /// One-line description.
+    pub summary: &'static str,
+    /// A stage that parses as this command.
+    pub example: &'static str,
+    parse: ParseFn,
+}
+
+impl CommandSpec {
+    /// The keyword (name or alias) that `line` starts with, if any.
+    ///
+    /// Matching is case-insensitive, and the keyword must not run on into
+    /// more letters: `TAKE 3` and `LOCATE/x/` match, `TAKEN` does not.
+    fn matched_keyword(&self, line: &str) -> Option<&'static str> {
+        std::iter::once(&self.name)
+            .chain(self.aliases)
+            .copied()
+            .find(|keyword| {
+                line.get(..keyword.len())
+                    .is_some_and(|head| head.eq_ignore_ascii_case(keyword))
+                    && !line[keyword.len()..].starts_with(|c: char| c.is_ascii_alphabetic())
+            })
+    }
+}
+
+/// Every supported command, in alphabetical order.
+static CATALOG: &[CommandSpec] = &[
+    CommandSpec {
+        name: "APPLY",
+        aliases: &[],
+        syntax: "APPLY name",
+        summary: "Rewrite each record with a named transform",
+        example: "APPLY trim",
+        parse: parse_apply,
+    },
+    CommandSpec {
+        name: "ASSERT",
+        aliases: &[],
+        syntax: "ASSERT pos,len NUMERIC|ALPHA [STRICT]",
+        summary: "Flag (or with STRICT, drop) records whose field has the wrong type",
+        example: "ASSERT 28,8 NUMERIC",
+        parse: |line, _| parse_assert(line),
+    },
+    CommandSpec {
+        name: "BLOCK",
+        aliases: &[],
+        syntax: "BLOCK n",
+        summary: "Emit a blank separator record after every n records",
+        example: "BLOCK 3",
+        parse: |line, _| parse_block(line),
+    },
+    CommandSpec {
+        name: "BUILD",
+        aliases: &[],
+        syntax: "BUILD pos,len /lit/ pos,len ...",
+        summary: "Concatenate trimmed fields and literals",
+        example: "BUILD 8,10 / / 0,8",
+        parse: |line, _| parse_build(line),
+    },
+    CommandSpec {
+        name: "CHANGE",
+        aliases: &[],
+        syntax: "CHANGE /old/new/ [FIRST|n] [REPEAT] | CHANGE /old/new/ WHERE condition",
+        summary: "Replace occurrences of old with new (all, or the first n)",
+        example: "CHANGE \"SALES\" \"MKTG\"",
+        parse: |line, _| parse_change(line),
+    },
+    CommandSpec {
+        name: "CHOP",
+        aliases: &[],
+        syntax: "CHOP width",
+        summary: "Split each record's content into width-column records",
+        example: "CHOP 20",
+        parse: |line, _| parse_chop(line),
+    },
+    CommandSpec {
+        name: "CONSOLE",
+        aliases: &["CONS"],
+        syntax: "CONSOLE",
+        summary: "Read input (first), pass through (middle), or write output (last)",
+        example: "CONSOLE",
+        parse: |_, _| Ok(Command::Console),
+    },
+    CommandSpec {
+        name: "CONTEXT",
+        aliases: &[],
+        syntax: "CONTEXT /pattern/ n | CONTEXT /pattern/ before,after",
+        summary: "Keep records containing pattern plus n (or before and after) neighboring records, like grep -C",
+        example: "CONTEXT /ERROR/ 1",
+        parse: |line, _| parse_context(line),
+    },
+    CommandSpec {
+        name: "COUNT",
+        aliases: &[],
+        syntax: "COUNT",
+        summary: "Count records and emit the count as a single record",
+        example: "COUNT",
+        parse: |_, _| Ok(Command::Count),
+    },
+    CommandSpec {
+        name: "CROSSTAB",
+        aliases: &[],
+        syntax: "CROSSTAB pos,len BY pos,len",
+        summary: "Emit a table of record counts, one row per value of the first field and one column per value of the second",
+        example: "CROSSTAB 18,10 BY 0,1",
+        parse: |line, _| parse_crosstab(line),
+    },
+    CommandSpec {
+        name: "DATEFMT",
+        aliases: &[],
+        syntax: "DATEFMT pos,len FROM ymd|mdy|dmy|julian TO ymd|mdy|dmy|julian [FLAG]",
+        summary: "Rewrite a date field in another layout, optionally flagging invalid dates",
+        example: "DATEFMT 28,8 FROM ymd TO mdy",
+        parse: |line, _| parse_datefmt(line),
+    },
+    CommandSpec {
+        name: "DECODE",
+        aliases: &[],
+        syntax: "DECODE pos,len /code=text;code=text.../ [DEFAULT /text/]",
+        summary: "Replace a field's code with its text from an inline lookup table",
+        example: "DECODE 18,10 /SLS=SALES;ENG=ENGINEER/",
+        parse: |line, _| parse_decode(line),
+    },
+    CommandSpec {
+        name: "DEFAULT",
+        aliases: &[],
+        syntax: "DEFAULT pos,len /value/ [LEFT|RIGHT]",
+        summary: "Fill a blank field with a default value",
+        example: "DEFAULT 18,10 /UNKNOWN/",
+        parse: |line, _| parse_default(line),
+    },
+    CommandSpec {
+        name: "DUPLICATE",
+        aliases: &["DUP"],
+        syntax: "DUPLICATE n",
+        summary: "Repeat each record n times",
+        example: "DUPLICATE 2",
+        parse: |line, _| parse_duplicate(line),
+    },
+    CommandSpec {
+        name: "EXPAND",
+        aliases: &[],
+        syntax: "EXPAND pos,len [CLEAR]",
+        summary: "Repeat each record as many times as its numeric count field says",
+        example: "EXPAND 28,8 CLEAR",
+        parse: |line, _| parse_expand(line),
+    },
+    CommandSpec {
+        name: "EXTRACT",
+        aliases: &[],
+        syntax: "EXTRACT /marker/ [DROP]",
+        summary: "Keep only the text after a marker",
+        example: "EXTRACT /=/",
+        parse: |line, _| parse_extract(line),
+    },
+    CommandSpec {
+        name: "FIELD",
+        aliases: &[],
+        syntax: "FIELD pos,len [PAD]",
+        summary: "Emit just one field, trimmed (or with its padding), at column 0",
+        example: "FIELD 18,10",
+        parse: |line, _| parse_field(line),
+    },
+    CommandSpec {
+        name: "FILTER",
+        aliases: &[],
+        syntax: "FILTER pos,len = \"value\" | FILTER pos,len != \"value\" | FILTER pos,len BETWEEN \"low\" AND \"high\" | FILTER pos,len op FIELD pos,len",
+        summary: "Keep (=) or omit (!=) records where a field equals value, keep a range, or compare two fields",
+        example: "FILTER 18,10 = \"SALES\"",
+        parse: |line, _| parse_filter(line),
+    },
+    CommandSpec {
+        name: "FREQ",
+        aliases: &[],
+        syntax: "FREQ pos,len",
+        summary: "Emit <value> <count> per distinct field value, most frequent first",
+        example: "FREQ 18,10",
+        parse: |line, _| parse_freq(line),
+    },
+    CommandSpec {
+        name: "GENERATE",
+        aliases: &[],
+        syntax: "GENERATE n /template/ [SEED s]",
+        summary: "Emit n records from a template, expanding {SEQ:width} and {RAND:width}",
+        example: "GENERATE 3 /REC{SEQ:3} {RAND:4}/ SEED 7",
+        parse: |line, _| parse_generate(line),
+    },
+    CommandSpec {
+        name: "HASH",
+        aliases: &[],
+        syntax: "HASH",
+        summary: "Emit HASH=<hex>, an order-sensitive FNV-1a hash of all records",
+        example: "HASH",
+        parse: |_, _| Ok(Command::Hash),
+    },
+    CommandSpec {
+        name: "HEADER",
+        aliases: &[],
+        syntax: "HEADER text",
+        summary: "Emit text before all records, expanding {COUNT} and {DATE}",
+        example: "HEADER {COUNT} RECORDS",
+        parse: |line, _| parse_header(line),
+    },
+    CommandSpec {
+        name: "HOLE",
+        aliases: &[],
+        syntax: "HOLE [COUNT]",
+        summary: "Discard all input (with COUNT, emit a DROPPED=n summary)",
+        example: "HOLE COUNT",
+        parse: |line, _| parse_hole(line),
+    },
+    CommandSpec {
+        name: "INCLUDE",
+        aliases: &[],
+        syntax: "INCLUDE name",
+        summary: "Splice in the stages of a registered sub-pipeline",
+        example: "INCLUDE normalize",
+        parse: |line, _| parse_include(line),
+    },
+    CommandSpec {
+        name: "INSERT",
+        aliases: &[],
+        syntax: "INSERT EVERY n /text/ [TRAILING]",
+        summary: "Emit a text record after every nth record (after the last only with TRAILING)",
+        example: "INSERT EVERY 5 /-----/",
+        parse: |line, _| parse_insert(line),
+    },
+    CommandSpec {
+        name: "JUSTIFY",
+        aliases: &[],
+        syntax: "JUSTIFY pos,len RIGHT|LEFT [FILL c]",
+        summary: "Re-justify a field within its columns",
+        example: "JUSTIFY 28,8 RIGHT",
+        parse: |line, _| parse_justify(line),
+    },
+    CommandSpec {
+        name: "LENGTH",
+        aliases: &[],
+        syntax: "LENGTH min,max",
+        summary: "Keep records whose trimmed length is in range",
+        example: "LENGTH 1,40",
+        parse: |line, _| parse_length(line),
+    },
+    CommandSpec {
+        name: "LITERAL",
+        aliases: &[],
+        syntax: "LITERAL \"text\"",
+        summary: "Append a literal record to the stream",
+        example: "LITERAL \"END OF REPORT\"",
+        parse: |line, _| parse_literal(line),
+    },
+    CommandSpec {
+        name: "LOCATE",
+        aliases: &[],
+        syntax: "LOCATE [pos,len] [ANY|ALL] \"pattern\" ... | LOCATE pos,len TRIM \"pattern\"",
+        summary: "Keep records (or fields) containing pattern (any or all of several)",
+        example: "LOCATE \"SALES\"",
+        parse: |line, _| parse_locate(line),
+    },
+    CommandSpec {
+        name: "LOWER",
+        aliases: &[],
+        syntax: "LOWER",
+        summary: "Convert records to lowercase",
+        example: "LOWER",
+        parse: |_, _| Ok(Command::Lower),
+    },
+    CommandSpec {
+        name: "MASK",
+        aliases: &[],
+        syntax: "MASK pos,len; pos,len ... [FILL c]",
+        summary: "Overwrite fields with * (or another fill character) to redact them",
+        example: "MASK 28,8 FILL /#/",
+        parse: |line, _| parse_mask(line),
+    },
+    CommandSpec {
+        name: "NLOCATE",
+        aliases: &[],
+        syntax: "NLOCATE [pos,len] \"pattern\"",
+        summary: "Keep records (or fields) NOT containing pattern",
+        example: "NLOCATE \"SALES\"",
+        parse: |line, _| parse_nlocate(line),
+    },
+    CommandSpec {
+        name: "NORM",
+        aliases: &[],
+        syntax: "NORM pos,len",
+        summary: "Rewrite a numeric field as a canonical, right-justified integer",
+        example: "NORM 28,8",
+        parse: |line, _| parse_norm(line),
+    },
+    CommandSpec {
+        name: "OMIT",
+        aliases: &[],
+        syntax: "OMIT pos,len = \"value\" | OMIT pos,len != \"value\" | OMIT pos,len BETWEEN \"low\" AND \"high\" | OMIT pos,len op FIELD pos,len",
+        summary: "Drop the records a FILTER with the same condition would keep",
+        example: "OMIT 18,10 = \"SALES\"",
+        parse: |line, _| parse_omit(line),
+    },
+    CommandSpec {
+        name: "ONCHANGE",
+        aliases: &[],
+        syntax: "ONCHANGE pos,len",
+        summary: "Keep the first record and each record whose field differs from the previous record's",
+        example: "ONCHANGE 18,10",
+        parse: |line, _| parse_onchange(line),
+    },
+    CommandSpec {
+        name: "PAGE",
+        aliases: &[],
+        syntax: "PAGE n,size",
+        summary: "Keep page n (1-based) of size records",
+        example: "PAGE 2,10",
+        parse: |line, _| parse_page(line),
+    },
+    CommandSpec {
+        name: "PIVOT",
+        aliases: &[],
+        syntax: "PIVOT key=pos,len value=pos,len [group=pos,len] KEY:dest ...",
+        summary: "Turn each group of key/value records into one wide record",
+        example: "PIVOT key=0,8 value=10,20 NAME:0 DEPT:10 SALARY:20",
+        parse: |line, _| parse_pivot(line),
+    },
+    CommandSpec {
+        name: "RESEQ",
+        aliases: &[],
+        syntax: "RESEQ pos,len start,step [FILL c]",
+        summary: "Overwrite a field with a zero-padded sequence number",
+        example: "RESEQ 72,8 10,10",
+        parse: |line, _| parse_reseq(line),
+    },
+    CommandSpec {
+        name: "REVERSE",
+        aliases: &[],
+        syntax: "REVERSE [FULL]",
+        summary: "Reverse characters in each record (FULL: all 80 columns)",
+        example: "REVERSE",
+        parse: |line, _| parse_reverse(line),
+    },
+    CommandSpec {
+        name: "ROUTE",
+        aliases: &[],
+        syntax: "ROUTE <FILTER condition> TO name [ELSE name]",
+        summary: "Send records matching a FILTER condition to a named output channel",
+        example: "ROUTE 18,10 = \"SALES\" TO sales ELSE other",
+        parse: |line, _| parse_route(line),
+    },
+    CommandSpec {
+        name: "SEGMENT",
+        aliases: &[],
+        syntax: "SEGMENT n",
+        summary: "Emit a --- SEGMENT k --- header before each group of n records",
+        example: "SEGMENT 3",
+        parse: |line, _| parse_segment(line),
+    },
+    CommandSpec {
+        name: "SELECT",
+        aliases: &[],
+        syntax: "SELECT p1,l1,d1; p2,l2,d2; ... [SEP /s/] [STRICT]",
+        summary: "Select and reposition fields",
+        example: "SELECT 0,8,0; 28,8,8",
+        parse: |line, _| parse_select(line),
+    },
+    CommandSpec {
+        name: "SHUFFLE",
+        aliases: &[],
+        syntax: "SHUFFLE seed",
+        summary: "Randomize record order (the same seed gives the same order)",
+        example: "SHUFFLE 42",
+        parse: |line, _| parse_shuffle(line),
+    },
+    CommandSpec {
+        name: "SKIP",
+        aliases: &[],
+        syntax: "SKIP n",
+        summary: "Skip the first n records",
+        example: "SKIP 1",
+        parse: |line, _| parse_skip(line),
+    },
+    CommandSpec {
+        name: "SORT",
+        aliases: &[],
+        syntax: "SORT [pos,len [ASC|DESC] [NUM]; ...]",
+        summary: "Sort records by one or more keys, keeping input order for ties",
+        example: "SORT 18,10 ASC; 28,8 DESC NUM",
+        parse: |line, _| parse_sort(line),
+    },
+    CommandSpec {
+        name: "SORTITEMS",
+        aliases: &[],
+        syntax: "SORTITEMS pos,len,width",
+        summary: "Sort the fixed-width items packed into a field of each record",
+        example: "SORTITEMS 0,12,4",
+        parse: |line, _| parse_sortitems(line),
+    },
+    CommandSpec {
+        name: "STATS",
+        aliases: &[],
+        syntax: "STATS [PASSTHROUGH]",
+        summary: "Emit RECORDS=, MINLEN=, MAXLEN= and AVGLEN= summary records",
+        example: "STATS PASSTHROUGH",
+        parse: |line, _| parse_stats(line),
+    },
+    CommandSpec {
+        name: "TAKE",
+        aliases: &[],
+        syntax: "TAKE n",
+        summary: "Keep the first n records",
+        example: "TAKE 5",
+        parse: |line, _| parse_take(line),
+    },
+    CommandSpec {
+        name: "TOJSON",
+        aliases: &[],
+        syntax: "TOJSON name=pos,len ...",
+        summary: "Rewrite each record as a JSON object of trimmed fields",
+        example: "TOJSON Last=0,8 First=8,10",
+        parse: |line, _| parse_tojson(line),
+    },
+    CommandSpec {
+        name: "TOPN",
+        aliases: &[],
+        syntax: "TOPN n pos,len",
+        summary: "Keep the n records with the largest numeric field, largest first",
+        example: "TOPN 3 28,8",
+        parse: |line, _| parse_topn(line),
+    },
+    CommandSpec {
+        name: "UPPER",
+        aliases: &[],
+        syntax: "UPPER",
+        summary: "Convert records to uppercase",
+        example: "UPPER",
+        parse: |_, _| Ok(Command::Upper),
+    },
+    CommandSpec {
+        name: "VERIFY",
+        aliases: &[],
+        syntax: "VERIFY pos,len NUMERIC|ALPHA|ALNUM|BLANK; ...",
+        summary: "Pass records whose fields have the given types; replace others with diagnostics",
+        example: "VERIFY 18,10 ALPHA; 28,8 NUMERIC",
+        parse: |line, _| parse_verify(line),
+    },
+    CommandSpec {
+        name: "WRAP",
+        aliases: &[],
+        syntax: "WRAP width [CONT col]",
+        summary: "Word-wrap each record's content into width-column records",
+        example: "WRAP 40 CONT 79",
+        parse: |line, _| parse_wrap(line),
+    },
+    CommandSpec {
+        name: "ZIP",
+        aliases: &[],
+        syntax: "ZIP",
+        summary: "Combine records pairwise, side by side",
+        example: "ZIP",
+        parse: |_, _| Ok(Command::Zip),
+    },
+];
+
+/// List every supported command with its syntax and a short description.
+///
+/// This is the table the parser dispatches from, so help text and tooling
+/// built on it always match what [`parse_commands`] accepts.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::command_catalog;
+///
+/// let take = command_catalog().into_iter().find(|c| c.name == "TAKE").unwrap();
+/// assert_eq!(take.syntax, "TAKE n");
+/// ```
+#[must_use]
+pub fn command_catalog() -> Vec<CommandSpec> {
+    CATALOG.to_vec()
+}
+
+/// Parse a single command line.
+fn parse_command_with(line: &str, transforms: &TransformRegistry) -> Result<Command, String> {
+    for spec in CATALOG {
+        if let Some(keyword) = spec.matched_keyword(line) {
+            if keyword == spec.name {
+                return (spec.parse)(line, transforms);
+            }
+            // Parsers slice off the canonical name, so rewrite the alias.
+            let line = format!("{}{}", spec.name, &line[keyword.len()..]);
+            return (spec.parse)(&line, transforms);
+        }
+    }
+    Err(format!(
+        "Unknown command: {}",
+        line.split_whitespace().next().unwrap_or(line)
+    ))
+}
+
+/// Parse FILTER command.
+fn parse_filter(line: &str) -> Result<Command, String> {
+    // FILTER pos,len = "value" or FILTER pos,len != "value"
+    // or FILTER pos,len BETWEEN "low" AND "high"
+    let rest = line[6..].trim(); // Skip "FILTER"
+
+    // BETWEEN must come before the first quoted value, which may contain '='
+    let head = &rest[..rest.find('"').unwrap_or(rest.len())];
+    if let Some(idx) = head.to_uppercase().find("BETWEEN") {
+        let (pos, len) = parse_filter_field(&rest[..idx])?;
+        return parse_filter_range(pos, len, &rest[idx + 7..]);
+    }
+    if head.to_uppercase().contains("FIELD") {
+        return parse_filter_field_cmp(rest);
+    }
+
+    // Find the operator
+    let (field_part, op, value) = if let Some(idx) = rest.find("!=") {
+        let field_part = rest[..idx].trim();
+        let value_part = rest[idx + 2..].trim();
+        (field_part, "!=", value_part)
+    } else if let Some(idx) = rest.find('=') {
+        let field_part = rest[..idx].trim();
+        let value_part = rest[idx + 1..].trim();
+        (field_part, "=", value_part)
+    } else {
+        return Err("FILTER requires = or != operator".to_string());
+    };
+
+    let (pos, len) = parse_filter_field(field_part)?;
+
+    // Parse quoted value
+    let value = parse_quoted_string(value)?;
+
+    if op == "!=" {
+        Ok(Command::FilterNe { pos, len, value })
+    } else {
+        Ok(Command::FilterEq { pos, len, value })
+    }
+}
+
+/// Parse OMIT command: any FILTER condition, inverted.
+fn parse_omit(line: &str) -> Result<Command, String> {
+    let filter = parse_filter(&format!("FILTER{}", &line[4..])) // Skip "OMIT"
+        .map_err(|e| e.replacen("FILTER", "OMIT", 1))?;
+    Ok(Command::Omit {
+        filter: Box::new(filter),
+    })
+}
+
+/// Output channel of the records that reach the end of a pipeline, as
+/// opposed to those a ROUTE stage sent to a named channel. ROUTE cannot
+/// use this name.
+pub const MAIN_CHANNEL: &str = "main";
+
+/// Parse ROUTE command: any FILTER condition, then the channels.
+/// Format: ROUTE <condition> TO name [ELSE name]
+fn parse_route(line: &str) -> Result<Command, String> {
+    let usage = || "ROUTE requires a condition and TO name [ELSE name]".to_string();
+    let last_word = |text: &str| {
+        text.trim_end()
+            .rsplit_once(char::is_whitespace)
+            .map(|(rest, word)| (rest.len(), word.to_string()))
+            .ok_or_else(usage)
+    };
+    let rest = &line[5..]; // Skip "ROUTE"
+    let (end, last) = last_word(rest)?;
+    let rest = &rest[..end];
+    let (end, keyword) = last_word(rest)?;
+    let rest = &rest[..end];
+    let (condition, to, otherwise) = if keyword.eq_ignore_ascii_case("ELSE") {
+        let (end, to) = last_word(rest)?;
+        let rest = &rest[..end];
+        let (end, keyword) = last_word(rest)?;
+        if !keyword.eq_ignore_ascii_case("TO") {
+            return Err(usage());
+        }
+        (&rest[..end], to, Some(last))
+    } else if keyword.eq_ignore_ascii_case("TO") {
+        (rest, last, None)
+    } else {
+        return Err(usage());
+    };
+    if let Some(name) = std::iter::once(&to)
+        .chain(&otherwise)
+        .find(|name| name.eq_ignore_ascii_case(MAIN_CHANNEL))
+    {
+        return Err(format!("ROUTE channel name '{name}' is reserved"));
+    }
+    let filter = parse_filter(&format!("FILTER{condition}"))
+        .map_err(|e| e.replacen("FILTER", "ROUTE", 1))?;
+    Ok(Command::Route {
+        filter: Box::new(filter),
+        to,
+        otherwise,
+    })
+}
+
+/// Whether a FILTER command keeps `record`.
+///
+/// OMIT keeps exactly the records this rejects. Commands other than the
+/// FILTER variants keep every record.
+pub fn filter_keeps(filter: &Command, record: &Record) -> bool {
+    match filter {
+        Command::FilterEq { pos, len, value } => record.field_eq(*pos, *len, value),
+        Command::FilterNe { pos, len, value } => !record.field_eq(*pos, *len, value),
+        Command::FilterRange {
+            pos,
+            len,
+            low,
+            high,
+        } => record.field_in_range(*pos, *len, low, high),
+        Command::FilterFieldCmp { left, right, op } => {
+            op.holds(record.compare_fields(left.0, left.1, right.0, right.1))
+        }
+        _ => true,
+    }
+}
+
+/// Parse NORM command.
+/// Format: NORM pos,len
+fn parse_norm(line: &str) -> Result<Command, String> {
+    let (pos, len) = parse_pos_len(line[4..].trim(), "NORM")?; // Skip "NORM"
+    Ok(Command::Norm { pos, len })
+}
+
+/// Canonical integer text for a numeric field, or `None` if it isn't one.
+///
+/// Surrounding spaces are ignored. The sign may lead or trail (`-42`,
+/// `42-`, `+42`), or be overpunched on the last digit as zoned decimal
+/// text writes it: `{` and `A`-`I` are +0 to +9, `}` and `J`-`R` are -0 to
+/// -9. Leading zeros are dropped, and zero is never negative.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::normalize_number;
+///
+/// assert_eq!(normalize_number("00050000").as_deref(), Some("50000"));
+/// assert_eq!(normalize_number("0001234J").as_deref(), Some("-12341"));
+/// assert_eq!(normalize_number("  007- ").as_deref(), Some("-7"));
+/// assert_eq!(normalize_number("12A4"), None);
+/// ```
+pub fn normalize_number(text: &str) -> Option<String> {
+    let text = text.trim();
+    let (negative, digits) = if let Some(rest) = text.strip_prefix('-') {
+        (true, rest.to_string())
+    } else if let Some(rest) = text.strip_prefix('+') {
+        (false, rest.to_string())
+    } else if let Some(rest) = text.strip_suffix('-') {
+        (true, rest.to_string())
+    } else if let Some(rest) = text.strip_suffix('+') {
+        (false, rest.to_string())
+    } else {
+        let mut chars = text.chars();
+        match chars.next_back()? {
+            last @ '0'..='9' => (false, format!("{}{last}", chars.as_str())),
+            '{' => (false, format!("{}0", chars.as_str())),
+            '}' => (true, format!("{}0", chars.as_str())),
+            last @ 'A'..='I' => (
+                false,
+                format!("{}{}", chars.as_str(), last as u8 - b'A' + 1),
+            ),
+            last @ 'J'..='R' => (true, format!("{}{}", chars.as_str(), last as u8 - b'J' + 1)),
+            _ => return None,
+        }
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let magnitude = digits.trim_start_matches('0');
+    Some(match magnitude {
+        "" => "0".to_string(),
+        _ if negative => format!("-{magnitude}"),
+        _ => magnitude.to_string(),
+    })
+}
+
+/// Rewrite the number in a `pos,len` field of a copy of `record` with
+/// [`normalize_number`], right-justified in the field.
+///
+/// A field that isn't a number, or whose canonical form doesn't fit (a
+/// one-column `J` is `-1`), is left as it was.
+pub fn norm_record(record: &Record, pos: usize, len: usize) -> Record {
+    let mut out = record.clone();
+    let field = record.field(pos, len);
+    if let Some(number) = normalize_number(field)
+        && number.len() <= field.len()
+    {
+        out.set_field(pos, len, &format!("{number:>width$}", width = field.len()));
+    }
+    out
+}
+
+/// Parse the `pos,len` field spec in front of a FILTER operator.
+fn parse_filter_field(field_part: &str) -> Result<(usize, usize), String> {
+    let parts: Vec<&str> = field_part.trim().split(',').collect();
+    if parts.len() != 2 {
+        return Err("FILTER requires pos,len before operator".to_string());
+    }
+
+    let pos = parse_position(parts[0])?;
+    let len: usize = parts[1]
+        .trim()
+        .parse()
+        .map_err(|_| "Invalid length number")?;
+    Ok((pos, len))
+}
+
+/// Parse FILTER pos,len op FIELD pos,len.
+fn parse_filter_field_cmp(rest: &str) -> Result<Command, String> {
+    const OPERATOR_CHARS: &[char] = &['=', '!', '<', '>'];
+    let op_start = rest
+        .find(OPERATOR_CHARS)
+        .ok_or("FILTER ... FIELD requires an operator (=, !=, <, <=, >, >=)")?;
+    let op_end = rest[op_start..]
+        .find(|c| !OPERATOR_CHARS.contains(&c))
+        .map_or(rest.len(), |n| op_start + n);
+    let symbol = &rest[op_start..op_end];
+    let op = CompareOp::from_symbol(symbol)
+        .ok_or_else(|| format!("Unknown FILTER operator '{symbol}'"))?;
+    let left = parse_filter_field(&rest[..op_start])?;
+    let right_part = rest[op_end..].trim_start();
+    let Some(right_spec) = right_part
+        .get(..5)
+        .filter(|word| word.eq_ignore_ascii_case("FIELD"))
+        .map(|_| &right_part[5..])
+    else {
+        return Err(format!("FILTER {symbol} FIELD requires FIELD pos,len"));
+    };
+    let right = parse_pos_len(right_spec.trim(), "FILTER ... FIELD")?;
+    Ok(Command::FilterFieldCmp { left, right, op })
+}
+
+/// Parse the `"low" AND "high"` bounds of FILTER ... BETWEEN.
+///
+/// Bounds compare numerically when both are integers, otherwise as text;
+/// either way `low` must not exceed `high`.
+fn parse_filter_range(pos: usize, len: usize, bounds: &str) -> Result<Command, String> {
+    let (low, rest) = parse_delimited_string(bounds)
+        .map_err(|_| "FILTER BETWEEN requires \"low\" AND \"high\"".to_string())?;
+    let rest = rest.trim_start();
+    let Some(high_part) = rest
+        .get(..3)
+        .filter(|word| word.eq_ignore_ascii_case("AND"))
+        .map(|_| &rest[3..])
+    else {
+        return Err("FILTER BETWEEN requires AND between the bounds".to_string());
+    };
+    let (high, trailing) = parse_delimited_string(high_part)?;
+    if !trailing.trim().is_empty() {
+        return Err(format!(
+            "Unexpected text after FILTER BETWEEN: '{}'",
+            trailing.trim()
+        ));
+    }
+    let inverted = match (low.trim().parse::<i64>(), high.trim().parse::<i64>()) {
+        (Ok(lo), Ok(hi)) => lo > hi,
+        _ => low.trim() > high.trim(),
+    };
+    if inverted {
+        return Err(format!(
+            "FILTER BETWEEN low bound \"{low}\" is greater than high bound \"{high}\""
+        ));
+    }
+    Ok(Command::FilterRange {
+        pos,
+        len,
+        low,
+        high,
+    })
+}
+
+/// Parse SELECT command.
+fn parse_select(line: &str) -> Result<Command, String> {
+    // SELECT p1,l1,d1; p2,l2,d2; ... [SEP /s/] [STRICT]
+    let rest = line[6..].trim(); // Skip "SELECT"
+    let (rest, strict) = match rest.rsplit_once(char::is_whitespace) {
+        Some((specs, word)) if word.eq_ignore_ascii_case("STRICT") => (specs, true),
+        _ => (rest, false),
+    };
+    // Field specs are digits and punctuation only, so the first "SEP" starts the clause
+    let (rest, separator) = match rest.to_uppercase().find("SEP") {
+        Some(at) => {
+            let (sep, trailing) = parse_delimited_string(&rest[at + 3..])?;
+            if !trailing.trim().is_empty() {
+                return Err(format!(
+                    "Unexpected text after SELECT SEP: '{}'",
+                    trailing.trim()
+                ));
+            }
+            if sep.is_empty() {
+                return Err("SELECT SEP requires a non-empty separator".to_string());
+            }
+            (&rest[..at], Some(sep))
+        }
+        None => (rest, None),
+    };
+
+    let mut fields = Vec::new();
+
+    for field_spec in rest.split(';') {
+        let field_spec = field_spec.trim();
+        if field_spec.is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = field_spec.split(',').collect();
+        if parts.len() != 3 {
+            return Err(format!(
+                "SELECT field '{}' requires src_pos,len,dest_pos",
+                field_spec
+            ));
+        }
+
+        let src_pos = parse_position(parts[0])
+            .map_err(|_| format!("Invalid source position in '{}'", field_spec))?;
+        let len: usize = parts[1]
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid length in '{}'", field_spec))?;
+        let dest_pos = parse_position(parts[2])
+            .map_err(|_| format!("Invalid destination position in '{}'", field_spec))?;
+
+        fields.push((src_pos, len, dest_pos));
+    }
+
+    if fields.is_empty() {
+        return Err("SELECT requires at least one field specification".to_string());
+    }
+
+    if strict && let Some((a, b)) = overlapping_destinations(&fields) {
+        let (_, a_len, a_dest) = fields[a];
+        let (_, b_len, b_dest) = fields[b];
+        return Err(format!(
+            "SELECT STRICT: destination {a_dest},{a_len} overlaps {b_dest},{b_len}"
+        ));
+    }
+
+    Ok(Command::Select { fields, separator })
+}
+
+/// Parse FIELD command.
+/// Format: FIELD pos,len or FIELD pos,len PAD
+fn parse_field(line: &str) -> Result<Command, String> {
+    let rest = line[5..].trim(); // Skip "FIELD"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "FIELD")?;
+    let trim = match parts.next() {
+        None => true,
+        Some(word) if word.eq_ignore_ascii_case("PAD") => false,
+        Some(word) => return Err(format!("FIELD accepts only PAD, got '{word}'")),
+    };
+    if parts.next().is_some() {
+        return Err("FIELD has too many arguments".to_string());
+    }
+    Ok(Command::Field { pos, len, trim })
+}
+
+/// Extract one field into a fresh record at column 0, trimmed if `trim`.
+pub fn field_record(record: &Record, pos: usize, len: usize, trim: bool) -> Record {
+    if trim {
+        Record::from_str(record.field(pos, len).trim())
+    } else {
+        record.slice(pos, len)
+    }
+}
+
+/// Parse TOJSON command.
+/// Format: TOJSON name=pos,len name=pos,len ...
+fn parse_tojson(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "TOJSON"
+    let mut fields: Vec<(String, usize, usize)> = Vec::new();
+    for item in rest.split_whitespace() {
+        let Some((name, spec)) = item.split_once('=') else {
+            return Err(format!("TOJSON expects name=pos,len, got '{item}'"));
+        };
+        if name.is_empty() {
+            return Err(format!("TOJSON field name missing in '{item}'"));
+        }
+        if fields.iter().any(|(seen, _, _)| seen == name) {
+            return Err(format!("TOJSON field '{name}' given twice"));
+        }
+        let (pos, len) = parse_pos_len(spec, "TOJSON")?;
+        fields.push((name.to_string(), pos, len));
+    }
+    if fields.is_empty() {
+        return Err("TOJSON requires at least one name=pos,len field".to_string());
+    }
+    Ok(Command::ToJson { fields })
+}
+
+/// Render the named fields of a record as a one-line JSON object.
+///
+/// Values are the trimmed field text, as JSON strings. The result is
+/// stored in a record, so JSON longer than the record width is truncated;
+/// keep the field list short enough to fit.
+pub fn json_record(record: &Record, fields: &[(String, usize, usize)]) -> Record {
+    let members: Vec<String> = record
+        .named_fields(fields)
+        .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value.trim())))
+        .collect();
+    Record::from_str(&format!("{{{}}}", members.join(",")))
+}
+
+/// Quote a string for JSON, escaping quotes, backslashes and control characters.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Find the first pair of SELECT fields whose destination ranges overlap.
+///
+/// Returns the indices of the two fields, earlier first.
+fn overlapping_destinations(fields: &[(usize, usize, usize)]) -> Option<(usize, usize)> {
+    for (j, &(_, b_len, b_dest)) in fields.iter().enumerate() {
+        for (i, &(_, a_len, a_dest)) in fields[..j].iter().enumerate() {
+            if a_dest < b_dest + b_len && b_dest < a_dest + a_len {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+/// Parse TAKE command.
+fn parse_take(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "TAKE"
+    let n: usize = rest.parse().map_err(|_| "TAKE requires a number")?;
+    Ok(Command::Take { n })
+}
+
+/// Parse SKIP command.
+fn parse_skip(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "SKIP"
+    let n: usize = rest.parse().map_err(|_| "SKIP requires a number")?;
+    Ok(Command::Skip { n })
+}
+
+/// Parse a delimited string using CMS Pipelines convention.
+/// The first non-blank character is the delimiter, and the string
+/// continues until the next occurrence of that delimiter.
+/// Returns (extracted_string, rest_of_input).
+fn parse_delimited_string(s: &str) -> Result<(String, &str), String> {
+    let s = s.trim_start();
+    if s.is_empty() {
+        return Err("Expected delimited string".to_string());
+    }
+
+    // First character is the delimiter
+    let delim = s.chars().next().unwrap();
+    let after_delim = &s[delim.len_utf8()..];
+
+    // Find the closing delimiter
+    if let Some(end) = after_delim.find(delim) {
+        let extracted = after_delim[..end].to_string();
+        let rest = &after_delim[end + delim.len_utf8()..];
+        Ok((extracted, rest))
+    } else {
+        Err(format!("Unclosed delimiter '{}'", delim))
+    }
+}
+
+/// Delimiters tried, in order, when writing a string back as DSL text.
+const DELIMITERS: &[char] = &['"', '/', '\'', '.', ':', '!', '+', '%', '@', '~', '^', '&'];
+
+/// Wrap `text` in the first delimiter from [`DELIMITERS`] it doesn't contain.
+fn delimited(text: &str) -> String {
+    let delim = DELIMITERS
+        .iter()
+        .find(|d| !text.contains(**d))
+        .unwrap_or(&DELIMITERS[0]);
+    format!("{delim}{text}{delim}")
+}
+
+/// Parse a quoted string value (legacy helper, delegates to parse_delimited_string).
+fn parse_quoted_string(s: &str) -> Result<String, String> {
+    let (result, _) = parse_delimited_string(s)?;
+    Ok(result)
+}
+
+/// Parse LOCATE command.
+/// CMS Pipelines: Uses first non-blank char as delimiter.
+/// Formats:
+///   LOCATE /pattern/       - search entire record (/ is delimiter)
+///   LOCATE "pattern"       - search entire record (" is delimiter)
+///   LOCATE .pattern.       - search entire record (. is delimiter)
+///   LOCATE pos,len /pattern/ - search specific field, padding included
+///   LOCATE pos,len TRIM /pattern/ - search specific field, padding ignored
+fn parse_locate(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "LOCATE"
+
+    if rest.is_empty() {
+        return Err("LOCATE requires a pattern".to_string());
+    }
+
+    // If the pattern starts with a field spec, parse it first
+    // Format: pos,len <delimited-pattern>
+    let (field, pattern_part) = match split_field_spec(rest) {
+        Some((field_spec, pattern_part)) => {
+            let field_parts: Vec<&str> = field_spec.split(',').collect();
+            if field_parts.len() != 2 {
+                return Err("LOCATE field spec requires pos,len".to_string());
+            }
+
+            let pos = parse_position(field_parts[0])?;
+            let len: usize = field_parts[1]
+                .trim()
+                .parse()
+                .map_err(|_| "Invalid length number")?;
+            (Some((pos, len)), pattern_part)
+        }
+        None => (None, rest),
+    };
+
+    // ANY or ALL followed by a list of delimited patterns
+    let mode_word = pattern_part.split_whitespace().next().unwrap_or("");
+    let mode = if mode_word.eq_ignore_ascii_case("ANY") {
+        Some(MatchMode::Any)
+    } else if mode_word.eq_ignore_ascii_case("ALL") {
+        Some(MatchMode::All)
+    } else {
+        None
+    };
+    if let Some(mode) = mode {
+        let mut list = pattern_part[mode_word.len()..].trim_start();
+        let mut patterns = Vec::new();
+        while !list.is_empty() {
+            let (pattern, after) = parse_delimited_string(list)?;
+            patterns.push(pattern);
+            list = after.trim_start();
+        }
+        if patterns.is_empty() {
+            return Err(format!("LOCATE {mode_word} requires at least one pattern"));
+        }
+        return Ok(Command::LocateMulti {
+            patterns,
+            mode,
+            field,
+        });
+    }
+
+    // TRIM, before the pattern like ANY/ALL, searches the trimmed field
+    let trim = mode_word.eq_ignore_ascii_case("TRIM");
+    let pattern_part = if trim {
+        if field.is_none() {
+            return Err("LOCATE TRIM requires a field pos,len".to_string());
+        }
+        pattern_part[mode_word.len()..].trim_start()
+    } else {
+        pattern_part
+    };
+
+    let (pattern, _) = parse_delimited_string(pattern_part)?;
+    Ok(Command::Locate {
+        pattern,
+        field,
+        trim,
+    })
+}
+
+/// Parse NLOCATE command.
+/// CMS Pipelines: Uses first non-blank char as delimiter (same as LOCATE).
+fn parse_nlocate(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "NLOCATE"
+
+    if rest.is_empty() {
+        return Err("NLOCATE requires a pattern".to_string());
+    }
+
+    // If the pattern starts with a field spec, parse it first
+    if let Some((field_spec, pattern_part)) = split_field_spec(rest) {
+        let field_parts: Vec<&str> = field_spec.split(',').collect();
+        if field_parts.len() != 2 {
+            return Err("NLOCATE field spec requires pos,len".to_string());
+        }
+
+        let pos = parse_position(field_parts[0])?;
+        let len: usize = field_parts[1]
+            .trim()
+            .parse()
+            .map_err(|_| "Invalid length number")?;
+
+        let (pattern, _) = parse_delimited_string(pattern_part)?;
+        Ok(Command::Nlocate {
+            pattern,
+            field: Some((pos, len)),
+        })
+    } else {
+        let (pattern, _) = parse_delimited_string(rest)?;
+        Ok(Command::Nlocate {
+            pattern,
+            field: None,
+        })
+    }
+}
+
+/// Parse CHANGE command.
+/// CMS Pipelines: Uses first non-blank char as delimiter.
+/// Both strings must use the SAME delimiter.
+/// Format: CHANGE /old/new/ or CHANGE /old/ /new/ or CHANGE "old" "new",
+/// optionally followed by FIRST or a replacement count n, then REPEAT;
+/// or followed by WHERE and a FILTER condition
+fn parse_change(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "CHANGE"
+
+    if rest.is_empty() {
+        return Err("CHANGE requires two delimited strings".to_string());
+    }
+
+    // Parse first delimited string
+    let (old, after_first) = parse_delimited_string(rest)?;
+
+    // Parse second delimited string: either its own delimited string after a
+    // blank (/old/ /new/), or the text up to the shared delimiter (/old/new/)
+    let (new, after_second) = if after_first.starts_with(char::is_whitespace) {
+        parse_delimited_string(after_first)?
+    } else {
+        let delim = rest.chars().next().unwrap();
+        let end = after_first
+            .find(delim)
+            .ok_or_else(|| format!("Unclosed delimiter '{delim}'"))?;
+        (
+            after_first[..end].to_string(),
+            &after_first[end + delim.len_utf8()..],
+        )
+    };
+
+    if let Some((word, condition)) = after_second.trim_start().split_once(char::is_whitespace)
+        && word.eq_ignore_ascii_case("WHERE")
+    {
+        let condition = parse_filter(&format!("FILTER {condition}"))
+            .map_err(|e| e.replacen("FILTER", "CHANGE ... WHERE", 1))?;
+        return Ok(Command::ChangeWhere {
+            condition: Box::new(condition),
+            old,
+            new,
+        });
+    }
+
+    let mut words: Vec<&str> = after_second.split_whitespace().collect();
+    let repeat = words
+        .last()
+        .is_some_and(|word| word.eq_ignore_ascii_case("REPEAT"));
+    if repeat {
+        words.pop();
+    }
+    let limit = match words.as_slice() {
+        [] => None,
+        [word] if word.eq_ignore_ascii_case("FIRST") => Some(1),
+        [word] => match word.parse::<usize>() {
+            Ok(0) => return Err("CHANGE count must be at least 1".to_string()),
+            Ok(n) => Some(n),
+            Err(_) => {
+                return Err(format!(
+                    "CHANGE accepts FIRST, a count or REPEAT, got '{word}'"
+                ));
+            }
+        },
+        _ => {
+            return Err(format!(
+                "CHANGE accepts FIRST or a count, then REPEAT, got '{}'",
+                after_second.trim()
+            ));
+        }
+    };
+
+    Ok(Command::Change {
+        old,
+        new,
+        limit,
+        repeat,
+    })
+}
+
+/// Replace occurrences of `old` with `new`, at most `limit` of them
+/// (all when `limit` is `None`), scanning left to right.
+pub fn change_text(text: &str, old: &str, new: &str, limit: Option<usize>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, _) in text.match_indices(old).take(limit.unwrap_or(usize::MAX)) {
+        out.push_str(&text[copied..start]);
+        out.push_str(new);
+        copied = start + old.len();
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Most substitution passes `CHANGE ... REPEAT` makes on one record before
+/// giving up with `PipelineError::ChangeDidNotConverge`.
+///
+/// A record that revisits an earlier state fails as soon as it does, so
+/// this only bounds records that keep reaching new states. A growing
+/// replacement needs about one pass per column and one that shuffles text
+/// along the record a few, so the limit leaves plenty of room.
+pub const CHANGE_REPEAT_LIMIT: usize = RECORD_WIDTH * RECORD_WIDTH;
+
+/// Apply [`change_text`] to a record until it stops changing.
+///
+/// The record is compared after each pass, so a replacement that grows past
+/// column 80 still converges once the truncated record is stable. A record
+/// that comes back to a state it had before would cycle forever, so it fails
+/// at once, as does one still changing after [`CHANGE_REPEAT_LIMIT`] passes.
+pub fn change_until_stable(
+    record: &Record,
+    old: &str,
+    new: &str,
+    limit: Option<usize>,
+) -> Result<Record, PipelineError> {
+    until_stable(record, |current| {
+        Record::from_str(&change_text(current.as_str(), old, new, limit))
+    })
+    .map_err(|passes| PipelineError::ChangeDidNotConverge {
+        old: old.to_string(),
+        new: new.to_string(),
+        passes,
+    })
+}
+
+/// Apply `step` to a record until it returns the record unchanged.
+///
+/// Fails with the number of passes made once a pass returns a record seen
+/// before (a cycle) or after [`CHANGE_REPEAT_LIMIT`] passes.
+fn until_stable(record: &Record, mut step: impl FnMut(&Record) -> Record) -> Result<Record, usize> {
+    let mut current = record.clone();
+    let mut seen = HashSet::from([current.clone()]);
+    for pass in 1..=CHANGE_REPEAT_LIMIT {
+        let next = step(&current);
+        if next == current {
+            return Ok(current);
+        }
+        if !seen.insert(next.clone()) {
+            return Err(pass);
+        }
+        current = next;
+    }
+    Err(CHANGE_REPEAT_LIMIT)
+}
+
+/// Parse REVERSE command.
+/// Format: REVERSE or REVERSE FULL
+fn parse_reverse(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "REVERSE"
+    match rest {
+        "" => Ok(Command::Reverse { full: false }),
+        opt if opt.eq_ignore_ascii_case("FULL") => Ok(Command::Reverse { full: true }),
+        opt => Err(format!("REVERSE accepts only FULL, got '{opt}'")),
+    }
+}
+
+/// Parse an optional trailing `FILL c` clause: a single ASCII padding
+/// character, or `default` when the clause is absent.
+fn parse_fill<'a>(
+    parts: &mut impl Iterator<Item = &'a str>,
+    cmd: &str,
+    default: char,
+) -> Result<char, String> {
+    let fill = match parts.next() {
+        None => return Ok(default),
+        Some(word) if word.eq_ignore_ascii_case("FILL") => {
+            fill_char(parts.next().unwrap_or(""), cmd)?
+        }
+        Some(word) => return Err(format!("Unexpected {cmd} argument '{word}'")),
+    };
+    if let Some(extra) = parts.next() {
+        return Err(format!("Unexpected {cmd} argument '{extra}'"));
+    }
+    Ok(fill)
+}
+
+/// Read a FILL character written bare (`*`) or delimited (`/*/`).
+///
+/// The delimited form is the only way to write a space: `FILL / /`.
+fn fill_char(text: &str, cmd: &str) -> Result<char, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let fill = match chars.as_slice() {
+        [c] => *c,
+        [open, c, close] if open == close => *c,
+        _ => return Err(format!("{cmd} FILL requires a single ASCII character")),
+    };
+    if !fill.is_ascii() {
+        return Err(format!("{cmd} FILL requires a single ASCII character"));
+    }
+    Ok(fill)
+}
+
+/// Parse MASK command.
+/// Format: MASK pos,len; pos,len ... [FILL c]
+fn parse_mask(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "MASK"
+    // Field specs are digits and punctuation only, so the first "FILL" starts the clause
+    let (specs, fill) = match rest.to_uppercase().find("FILL") {
+        Some(at) => (&rest[..at], fill_char(rest[at + 4..].trim(), "MASK")?),
+        None => (rest, '*'),
+    };
+    if specs.trim().is_empty() {
+        return Err("MASK requires at least one pos,len field".to_string());
+    }
+    let fields = specs
+        .split(';')
+        .map(|spec| parse_pos_len(spec.trim(), "MASK"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Command::Mask { fields, fill })
+}
+
+/// Parse PIVOT command.
+/// Format: PIVOT key=pos,len value=pos,len [group=pos,len] KEY:dest ...
+fn parse_pivot(line: &str) -> Result<Command, String> {
+    let rest = line[5..].trim(); // Skip "PIVOT"
+    let (mut key, mut value, mut group) = (None, None, None);
+    let mut columns: Vec<(String, usize)> = Vec::new();
+    for word in rest.split_whitespace() {
+        if let Some((option, spec)) = word.split_once('=') {
+            let slot = match option.to_lowercase().as_str() {
+                "key" => &mut key,
+                "value" => &mut value,
+                "group" => &mut group,
+                _ => return Err(format!("Unknown PIVOT option '{option}'")),
+            };
+            *slot = Some(parse_pos_len(spec, "PIVOT")?);
+        } else if let Some((name, dest)) = word.rsplit_once(':') {
+            let dest: usize = dest
+                .parse()
+                .map_err(|_| format!("Invalid PIVOT destination column '{dest}'"))?;
+            if name.is_empty() || dest >= RECORD_WIDTH {
+                return Err(format!(
+                    "PIVOT column '{word}' needs a key and a destination below {RECORD_WIDTH}"
+                ));
+            }
+            columns.push((name.to_string(), dest));
+        } else {
+            return Err(format!("Unexpected PIVOT argument '{word}'"));
+        }
+    }
+    let (Some(key), Some(value)) = (key, value) else {
+        return Err("PIVOT requires key=pos,len and value=pos,len".to_string());
+    };
+    if columns.is_empty() {
+        return Err("PIVOT requires at least one KEY:dest column".to_string());
+    }
+    Ok(Command::Pivot {
+        key,
+        value,
+        group,
+        columns,
+    })
+}
+
+/// Whether `record` ends the PIVOT group that started with `first`.
+///
+/// A blank record always ends a group. With a `group` field, so does a
+/// record whose (trimmed) group field differs from the first record's.
+pub fn pivot_group_ends(first: &Record, record: &Record, group: Option<(usize, usize)>) -> bool {
+    record.is_blank()
+        || group
+            .is_some_and(|(pos, len)| first.field(pos, len).trim() != record.field(pos, len).trim())
+}
+
+/// Build PIVOT's wide record from one group of key/value records.
+///
+/// Each record's trimmed `value` field is written at the destination
+/// column of its trimmed `key` field. Keys without a column are ignored,
+/// and a key repeated within the group keeps its last value.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{Record, pivot_group};
+///
+/// let group = [Record::from_str("NAME SMITH"), Record::from_str("DEPT SALES")];
+/// let columns = [("NAME".to_string(), 0), ("DEPT".to_string(), 8)];
+/// let wide = pivot_group(&group, (0, 4), (5, 10), &columns);
+/// assert_eq!(wide.as_str().trim_end(), "SMITH   SALES");
+/// ```
+pub fn pivot_group(
+    group: &[Record],
+    key: (usize, usize),
+    value: (usize, usize),
+    columns: &[(String, usize)],
+) -> Record {
+    let mut values: Vec<Option<&str>> = vec![None; columns.len()];
+    for record in group {
+        let name = record.field(key.0, key.1).trim();
+        if let Some(slot) = columns.iter().position(|(col, _)| col == name) {
+            values[slot] = Some(record.field(value.0, value.1).trim());
+        }
+    }
+    let mut out = Record::new();
+    for ((_, dest), value) in columns.iter().zip(values) {
+        if let Some(value) = value {
+            out.set_field(*dest, value.len(), value);
+        }
+    }
+    out
+}
+
+/// Overwrite each `pos,len` field of a copy of `record` with `fill`.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{Record, mask_record};
+///
+/// let record = Record::from_str("SMITH   00050000");
+/// let masked = mask_record(&record, &[(8, 8)], '*');
+/// assert_eq!(masked.as_str().trim_end(), "SMITH   ********");
+/// ```
+pub fn mask_record(record: &Record, fields: &[(usize, usize)], fill: char) -> Record {
+    let mut out = record.clone();
+    for &(pos, len) in fields {
+        out.set_field_padded(pos, len, "", fill);
+    }
+    out
+}
+
+/// Replace the code in the `pos,len` field of a copy of `record` with its
+/// text from `map`.
+///
+/// The field is trimmed before it is looked up. A code missing from `map`
+/// is replaced with `default`, or left unchanged when that is `None`. The
+/// text is written left-justified and cut to the field's width.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use pipelines_rs::{Record, decode_field};
+///
+/// let map = HashMap::from([("ENG".to_string(), "ENGINEER".to_string())]);
+/// let record = Record::from_str("JONES   MARY      ENG       00075000");
+/// let decoded = decode_field(&record, 18, 10, &map, None);
+/// assert_eq!(decoded.field(18, 10), "ENGINEER  ");
+/// ```
+pub fn decode_field(
+    record: &Record,
+    pos: usize,
+    len: usize,
+    map: &HashMap<String, String>,
+    default: Option<&str>,
+) -> Record {
+    let code = record.field(pos, len).trim();
+    match map.get(code).map(String::as_str).or(default) {
+        Some(text) => {
+            let mut out = record.clone();
+            out.set_field(pos, len, text);
+            out
+        }
+        None => record.clone(),
+    }
+}
+
+/// Fill the `pos,len` field of a copy of `record` with `value` if it is blank.
+///
+/// A field holding anything but spaces is left alone. `value` is trimmed
+/// and aligned to `justify` within the field; a value longer than the
+/// field keeps its leading characters.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{JustifySide, Record, default_field};
+///
+/// let record = Record::from_str("SMITH             ");
+/// let filled = default_field(&record, 8, 10, "N/A", JustifySide::Right);
+/// assert_eq!(filled.as_str().trim_end(), "SMITH          N/A");
+/// ```
+pub fn default_field(
+    record: &Record,
+    pos: usize,
+    len: usize,
+    value: &str,
+    justify: JustifySide,
+) -> Record {
+    let field = record.field(pos, len);
+    if !field.trim().is_empty() {
+        return record.clone();
+    }
+    let mut out = record.clone();
+    out.set_field(pos, len, &justify.justify(value, field.len(), ' '));
+    out
+}
+
+/// Parse RESEQ command.
+/// Format: RESEQ pos,len start,step [FILL c]
+fn parse_reseq(line: &str) -> Result<Command, String> {
+    let rest = line[5..].trim(); // Skip "RESEQ"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "RESEQ")?;
+    let (start, step) = parts
+        .next()
+        .and_then(|s| s.split_once(','))
+        .ok_or("RESEQ requires start,step after the field")?;
+    let start: i64 = start.trim().parse().map_err(|_| "Invalid RESEQ start")?;
+    let step: i64 = step.trim().parse().map_err(|_| "Invalid RESEQ step")?;
+    if step == 0 {
+        return Err("RESEQ step cannot be zero".to_string());
+    }
+    let fill = parse_fill(&mut parts, "RESEQ", '0')?;
+    Ok(Command::Reseq {
+        pos,
+        len,
+        start,
+        step,
+        fill,
+    })
+}
+
+/// Parse SORT command.
+/// Format: SORT, or SORT pos,len [ASC|DESC] [NUM]; pos,len ...
+fn parse_sort(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "SORT"
+    if rest.is_empty() {
+        // Whole record, ascending
+        return Ok(Command::Sort {
+            keys: vec![SortKey {
+                pos: 0,
+                len: RECORD_WIDTH,
+                dir: SortDir::Asc,
+                numeric: false,
+            }],
+        });
+    }
+
+    let mut keys = Vec::new();
+    for spec in rest.split(';') {
+        let mut parts = spec.split_whitespace();
+        let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "SORT")?;
+        let mut dir = None;
+        let mut numeric = false;
+        for word in parts {
+            match word.to_uppercase().as_str() {
+                "ASC" | "A" if dir.is_none() => dir = Some(SortDir::Asc),
+                "DESC" | "D" if dir.is_none() => dir = Some(SortDir::Desc),
+                "NUM" if !numeric => numeric = true,
+                _ => return Err(format!("Unexpected SORT key option '{word}'")),
+            }
+        }
+        keys.push(SortKey {
+            pos,
+            len,
+            dir: dir.unwrap_or(SortDir::Asc),
+            numeric,
+        });
+    }
+    Ok(Command::Sort { keys })
+}
+
+/// Parse TOPN command.
+/// Format: TOPN n pos,len
+fn parse_topn(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "TOPN"
+    let (n, field) = rest
+        .split_once(char::is_whitespace)
+        .ok_or("TOPN requires n pos,len")?;
+    let n: usize = n.parse().map_err(|_| "TOPN requires a number")?;
+    if n == 0 {
+        return Err("TOPN n must be at least 1".to_string());
+    }
+    let (pos, len) = parse_pos_len(field.trim(), "TOPN")?;
+    Ok(Command::TopN { n, pos, len })
+}
+
+/// Parse INCLUDE command.
+/// Format: INCLUDE name
+fn parse_include(line: &str) -> Result<Command, String> {
+    let name = line[7..].trim(); // Skip "INCLUDE"
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err("INCLUDE requires a single pipeline name".to_string());
+    }
+    Ok(Command::Include {
+        name: name.to_string(),
+    })
+}
+
+/// Parse SHUFFLE command.
+/// Format: SHUFFLE seed
+fn parse_shuffle(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "SHUFFLE"
+    let seed: u64 = rest
+        .parse()
+        .map_err(|_| "SHUFFLE requires a numeric seed")?;
+    Ok(Command::Shuffle { seed })
+}
+
+/// Parse PAGE command.
+/// Format: PAGE n,size - page numbers start at 1
+fn parse_page(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "PAGE"
+    let (page, size) = rest.split_once(',').ok_or("PAGE requires n,size")?;
+    let page: usize = page.trim().parse().map_err(|_| "Invalid PAGE number")?;
+    let size: usize = size.trim().parse().map_err(|_| "Invalid PAGE size")?;
+    if page == 0 {
+        return Err("PAGE numbers start at 1".to_string());
+    }
+    if size == 0 {
+        return Err("PAGE size must be at least 1".to_string());
+    }
+    Ok(Command::Page { page, size })
+}
+
+/// Parse LITERAL command.
+/// CMS Pipelines: LITERAL does NOT use delimiters.
+/// Everything after "LITERAL " is the literal text.
+/// LITERAL FOO    -> "FOO"
+/// LITERAL "FOO"  -> "\"FOO\"" (quotes are part of text)
+/// LITERAL /FOO/  -> "/FOO/"  (slashes are part of text)
+fn parse_literal(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim_start(); // Skip "LITERAL", keep leading spaces in text
+    if rest.is_empty() {
+        return Err("LITERAL requires text".to_string());
+    }
+    // Trim trailing whitespace from the text
+    let text = rest.trim_end().to_string();
+    Ok(Command::Literal { text })
+}
+
+/// Parse GENERATE command.
+/// Format: GENERATE n /template/ [SEED s]
+fn parse_generate(line: &str) -> Result<Command, String> {
+    let rest = line[8..].trim(); // Skip "GENERATE"
+    let (count, template_part) = rest
+        .split_once(char::is_whitespace)
+        .ok_or("GENERATE requires a count and /template/")?;
+    let count: usize = count
+        .parse()
+        .map_err(|_| "GENERATE requires a numeric count")?;
+    if count == 0 {
+        return Err("GENERATE count must be at least 1".to_string());
+    }
+    let (template, after) = parse_delimited_string(template_part)?;
+    if template.is_empty() {
+        return Err("GENERATE template cannot be empty".to_string());
+    }
+    let seed = match after.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => 0,
+        [keyword, seed] if keyword.eq_ignore_ascii_case("SEED") => seed
+            .parse()
+            .map_err(|_| "GENERATE SEED requires a number")?,
+        _ => {
+            return Err(format!(
+                "GENERATE accepts only SEED s, got '{}'",
+                after.trim()
+            ));
+        }
+    };
+    Ok(Command::Generate {
+        count,
+        template,
+        seed,
+    })
+}
+
+/// Parse HEADER command.
+/// Format: HEADER text (tokens `{COUNT}` and `{DATE}` expand at run time)
+fn parse_header(line: &str) -> Result<Command, String> {
+    let template = line[6..].trim(); // Skip "HEADER"
+    if template.is_empty() {
+        return Err("HEADER requires text".to_string());
+    }
+    Ok(Command::Header {
+        template: template.to_string(),
+    })
+}
+
+/// Parse EXPAND command.
+/// Format: EXPAND pos,len [CLEAR]
+fn parse_expand(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "EXPAND"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "EXPAND")?;
+    let clear = match parts.next() {
+        None => false,
+        Some(word) if word.eq_ignore_ascii_case("CLEAR") => true,
+        Some(word) => return Err(format!("EXPAND accepts only CLEAR, got '{word}'")),
+    };
+    if parts.next().is_some() {
+        return Err("EXPAND has too many arguments".to_string());
+    }
+    Ok(Command::Expand { pos, len, clear })
+}
+
+/// Parse DECODE command.
+/// Format: DECODE pos,len /code=text;code=text.../ [DEFAULT /text/]
+fn parse_decode(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "DECODE"
+    let (spec, table) = rest
+        .split_once(char::is_whitespace)
+        .ok_or("DECODE requires pos,len /code=text;.../")?;
+    let (pos, len) = parse_pos_len(spec, "DECODE")?;
+    let (table, after) = parse_delimited_string(table)?;
+
+    let mut map = BTreeMap::new();
+    for pair in table.split(';') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        let Some((code, text)) = pair.split_once('=') else {
+            return Err(format!("DECODE expects code=text, got '{pair}'"));
+        };
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(format!("DECODE code missing in '{pair}'"));
+        }
+        if map
+            .insert(code.to_string(), text.trim().to_string())
+            .is_some()
+        {
+            return Err(format!("DECODE code '{code}' given twice"));
+        }
+    }
+    if map.is_empty() {
+        return Err("DECODE requires at least one code=text pair".to_string());
+    }
+
+    let after = after.trim();
+    let default = if after.is_empty() {
+        None
+    } else {
+        let Some(text) = after
+            .get(..7)
+            .filter(|word| word.eq_ignore_ascii_case("DEFAULT"))
+            .map(|_| &after[7..])
+        else {
+            return Err(format!("DECODE accepts only DEFAULT /text/, got '{after}'"));
+        };
+        let (text, trailing) = parse_delimited_string(text)?;
+        if !trailing.trim().is_empty() {
+            return Err(format!(
+                "Unexpected text after DECODE DEFAULT: '{}'",
+                trailing.trim()
+            ));
+        }
+        Some(text)
+    };
+
+    Ok(Command::Decode {
+        pos,
+        len,
+        map,
+        default,
+    })
+}
+
+/// Parse DEFAULT command.
+/// Format: DEFAULT pos,len /value/ [LEFT|RIGHT]
+fn parse_default(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "DEFAULT"
+    let (spec, value_part) = rest
+        .split_once(char::is_whitespace)
+        .ok_or("DEFAULT requires pos,len /value/")?;
+    let (pos, len) = parse_pos_len(spec, "DEFAULT")?;
+    let (value, after) = parse_delimited_string(value_part)?;
+    if value.trim().is_empty() {
+        return Err("DEFAULT value cannot be blank".to_string());
+    }
+    let justify = match after.trim() {
+        "" => JustifySide::Left,
+        word if word.eq_ignore_ascii_case("LEFT") => JustifySide::Left,
+        word if word.eq_ignore_ascii_case("RIGHT") => JustifySide::Right,
+        word => return Err(format!("DEFAULT accepts only LEFT or RIGHT, got '{word}'")),
+    };
+    Ok(Command::Default {
+        pos,
+        len,
+        value,
+        justify,
+    })
+}
+
+/// Parse DATEFMT command.
+/// Format: DATEFMT pos,len FROM fmt TO fmt [FLAG]
+fn parse_datefmt(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "DATEFMT"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "DATEFMT")?;
+    let mut format_after = |keyword: &str| -> Result<DateFormat, String> {
+        match parts.next() {
+            Some(word) if word.eq_ignore_ascii_case(keyword) => {}
+            _ => return Err(format!("DATEFMT requires {keyword} <format>")),
+        }
+        let word = parts.next().unwrap_or("");
+        DateFormat::from_keyword(word)
+            .ok_or_else(|| format!("DATEFMT format must be ymd, mdy, dmy or julian, got '{word}'"))
+    };
+    let from = format_after("FROM")?;
+    let to = format_after("TO")?;
+    let flag = match parts.next() {
+        None => false,
+        Some(word) if word.eq_ignore_ascii_case("FLAG") => true,
+        Some(word) => return Err(format!("DATEFMT accepts only FLAG, got '{word}'")),
+    };
+    if parts.next().is_some() {
+        return Err("DATEFMT has too many arguments".to_string());
+    }
+    Ok(Command::DateFmt {
+        pos,
+        len,
+        from,
+        to,
+        flag,
+    })
+}
+
+/// Parse DUPLICATE command.
+/// Format: DUPLICATE n
+fn parse_duplicate(line: &str) -> Result<Command, String> {
+    let rest = line[9..].trim(); // Skip "DUPLICATE"
+    let n: usize = rest.parse().map_err(|_| "DUPLICATE requires a number")?;
+    if n == 0 {
+        return Err("DUPLICATE count must be at least 1".to_string());
+    }
+    Ok(Command::Duplicate { n })
+}
+
+/// Parse APPLY command.
+/// Format: APPLY name
+fn parse_apply(line: &str, transforms: &TransformRegistry) -> Result<Command, String> {
+    let name = line[5..].trim(); // Skip "APPLY"
+    if name.is_empty() {
+        return Err("APPLY requires a transform name".to_string());
+    }
+    let transform = transforms.get(name).ok_or_else(|| {
+        format!(
+            "Unknown transform '{name}' (available: {})",
+            transforms.names().join(", ")
+        )
+    })?;
+    Ok(Command::Apply {
+        name: name.to_lowercase(),
+        transform,
+    })
+}
+
+/// Parse CHOP command.
+/// Format: CHOP width
+fn parse_chop(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "CHOP"
+    let width: usize = rest.parse().map_err(|_| "CHOP requires a width")?;
+    if width == 0 || width > RECORD_WIDTH {
+        return Err(format!("CHOP width must be 1-{RECORD_WIDTH}"));
+    }
+    Ok(Command::Chop { width })
+}
+
+/// Parse WRAP command.
+/// Format: WRAP width [CONT col]
+fn parse_wrap(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "WRAP"
+    let mut parts = rest.split_whitespace();
+    let width: usize = parts
+        .next()
+        .and_then(|w| w.parse().ok())
+        .ok_or("WRAP requires a width")?;
+    if width == 0 || width > RECORD_WIDTH {
+        return Err(format!("WRAP width must be 1-{RECORD_WIDTH}"));
+    }
+    let cont_col = match parts.next() {
+        None => None,
+        Some(kw) if kw.eq_ignore_ascii_case("CONT") => {
+            let col: usize = parts
+                .next()
+                .and_then(|c| c.parse().ok())
+                .ok_or("WRAP CONT requires a column")?;
+            if col < width || col >= RECORD_WIDTH {
+                return Err(format!(
+                    "WRAP CONT column must be {width}-{} (past the wrapped text)",
+                    RECORD_WIDTH - 1
+                ));
+            }
+            Some(col)
+        }
+        Some(other) => return Err(format!("WRAP: unexpected '{other}' (expected CONT col)")),
+    };
+    if let Some(extra) = parts.next() {
+        return Err(format!("WRAP: unexpected '{extra}'"));
+    }
+    Ok(Command::Wrap { width, cont_col })
+}
+
+/// Parse BLOCK command.
+/// Format: BLOCK n
+fn parse_block(line: &str) -> Result<Command, String> {
+    let rest = line[5..].trim(); // Skip "BLOCK"
+    let n: usize = rest.parse().map_err(|_| "BLOCK requires a number")?;
+    if n == 0 {
+        return Err("BLOCK size must be at least 1".to_string());
+    }
+    Ok(Command::Block { n })
+}
+
+/// Parse SEGMENT command.
+/// Format: SEGMENT n
+fn parse_segment(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "SEGMENT"
+    let n: usize = rest.parse().map_err(|_| "SEGMENT requires a number")?;
+    if n == 0 {
+        return Err("SEGMENT size must be at least 1".to_string());
+    }
+    Ok(Command::Segment { n })
+}
+
+/// Parse INSERT command.
+/// Format: INSERT EVERY n /text/ [TRAILING]
+fn parse_insert(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "INSERT"
+    let usage = "INSERT requires EVERY n /text/";
+    let Some(rest) = rest
+        .get(..5)
+        .filter(|word| word.eq_ignore_ascii_case("EVERY"))
+        .map(|_| rest[5..].trim_start())
+    else {
+        return Err(usage.to_string());
+    };
+    let (count, text) = rest.split_once(char::is_whitespace).ok_or(usage)?;
+    let n: usize = count
+        .parse()
+        .map_err(|_| format!("INSERT EVERY requires a number, got '{count}'"))?;
+    if n == 0 {
+        return Err("INSERT EVERY count must be at least 1".to_string());
+    }
+    let (text, trailing) = parse_delimited_string(text)?;
+    let trailing = match trailing.trim() {
+        "" => false,
+        word if word.eq_ignore_ascii_case("TRAILING") => true,
+        word => return Err(format!("INSERT accepts only TRAILING, got '{word}'")),
+    };
+    Ok(Command::InsertEvery { n, text, trailing })
+}
+
+/// Parse CONTEXT command.
+/// Format: CONTEXT /pattern/ n or CONTEXT /pattern/ before,after
+fn parse_context(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "CONTEXT"
+    if rest.is_empty() {
+        return Err("CONTEXT requires a pattern and a record count".to_string());
+    }
+    let (pattern, count) = parse_delimited_string(rest)?;
+    let count = count.trim();
+    let number = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("CONTEXT requires n or before,after counts, got '{count}'"))
+    };
+    let (before, after) = match count.split_once(',') {
+        Some((before, after)) => (number(before)?, number(after)?),
+        None => {
+            let n = number(count)?;
+            (n, n)
+        }
+    };
+    Ok(Command::Context {
+        pattern,
+        before,
+        after,
+    })
+}
+
+/// Parse HOLE command.
+/// Format: HOLE or HOLE COUNT
+fn parse_hole(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "HOLE"
+    if rest.is_empty() {
+        Ok(Command::Hole { count: false })
+    } else if rest.eq_ignore_ascii_case("COUNT") {
+        Ok(Command::Hole { count: true })
+    } else {
+        Err(format!("HOLE accepts only COUNT, got '{rest}'"))
+    }
+}
+
+/// Parse STATS command.
+/// Format: STATS or STATS PASSTHROUGH
+fn parse_stats(line: &str) -> Result<Command, String> {
+    let rest = line[5..].trim(); // Skip "STATS"
+    if rest.is_empty() {
+        Ok(Command::Stats { passthrough: false })
+    } else if rest.eq_ignore_ascii_case("PASSTHROUGH") {
+        Ok(Command::Stats { passthrough: true })
+    } else {
+        Err(format!("STATS accepts only PASSTHROUGH, got '{rest}'"))
+    }
+}
+
+/// Parse a column position.
+///
+/// A negative position counts back from the end of the record, so `-8` is
+/// column 72 of 80. A negative position larger than the record width
+/// clamps to column 0.
+fn parse_position(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let invalid = || format!("Invalid position number '{text}'");
+    match text.strip_prefix('-') {
+        Some(from_end) => {
+            let from_end: usize = from_end.parse().map_err(|_| invalid())?;
+            Ok(RECORD_WIDTH.saturating_sub(from_end))
+        }
+        None => text.parse().map_err(|_| invalid()),
+    }
+}
+
+/// Split a leading `pos,len` field spec from a LOCATE/NLOCATE argument.
+///
+/// Returns the spec and the rest (the delimited pattern). A leading `-`
+/// starts a negative position only when a comma follows, so `-abc-` and
+/// `-1-` remain patterns delimited by `-`.
+fn split_field_spec(rest: &str) -> Option<(&str, &str)> {
+    let body = rest.strip_prefix('-').unwrap_or(rest);
+    if !body.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let body_end = body
+        .find(|c: char| !c.is_ascii_digit() && c != ',')
+        .unwrap_or(body.len());
+    let end = rest.len() - body.len() + body_end;
+    let spec = &rest[..end];
+    if rest.starts_with('-') && !spec.contains(',') {
+        return None;
+    }
+    Some((spec, rest[end..].trim_start()))
+}
+
+/// Parse a `pos,len` field specification.
+///
+/// `pos` may be negative to count from the end of the record (see
+/// [`parse_position`]).
+fn parse_pos_len(spec: &str, cmd: &str) -> Result<(usize, usize), String> {
+    let parts: Vec<&str> = spec.split(',').collect();
+    if parts.len() != 2 {
+        return Err(format!("{cmd} requires pos,len"));
+    }
+    let pos = parse_position(parts[0])?;
+    let len: usize = parts[1]
+        .trim()
+        .parse()
+        .map_err(|_| "Invalid length number")?;
+    Ok((pos, len))
+}
+
+/// Parse JUSTIFY command.
+/// Format: JUSTIFY pos,len RIGHT|LEFT [FILL c]
+fn parse_justify(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "JUSTIFY"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "JUSTIFY")?;
+
+    let side = match parts.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("RIGHT") => JustifySide::Right,
+        Some("LEFT") => JustifySide::Left,
+        _ => return Err("JUSTIFY requires RIGHT or LEFT".to_string()),
+    };
+    let fill = parse_fill(&mut parts, "JUSTIFY", ' ')?;
+
+    Ok(Command::Justify {
+        pos,
+        len,
+        side,
+        fill,
+    })
+}
+
+/// Parse ASSERT command.
+/// Format: ASSERT pos,len NUMERIC|ALPHA|ALNUM|BLANK [STRICT]
+fn parse_assert(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "ASSERT"
+    let mut parts = rest.split_whitespace();
+    let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "ASSERT")?;
+
+    let kind = parts
+        .next()
+        .and_then(FieldKind::from_keyword)
+        .ok_or("ASSERT requires NUMERIC, ALPHA, ALNUM or BLANK")?;
+
+    let strict = match parts.next() {
+        None => false,
+        Some(word) if word.eq_ignore_ascii_case("STRICT") => true,
+        Some(word) => return Err(format!("ASSERT accepts only STRICT, got '{word}'")),
+    };
+    if parts.next().is_some() {
+        return Err("ASSERT has too many arguments".to_string());
+    }
+
+    Ok(Command::Assert {
+        pos,
+        len,
+        kind,
+        strict,
+    })
+}
+
+/// Parse VERIFY command.
+/// Format: VERIFY pos,len TYPE; pos,len TYPE; ...
+fn parse_verify(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "VERIFY"
+    if rest.is_empty() {
+        return Err("VERIFY requires at least one pos,len TYPE check".to_string());
+    }
+    let checks = rest
+        .split(';')
+        .map(|check| {
+            let mut parts = check.split_whitespace();
+            let (pos, len) = parse_pos_len(parts.next().unwrap_or(""), "VERIFY")?;
+            let kind = parts
+                .next()
+                .and_then(FieldKind::from_keyword)
+                .ok_or("VERIFY requires NUMERIC, ALPHA, ALNUM or BLANK after pos,len")?;
+            if let Some(extra) = parts.next() {
+                return Err(format!("VERIFY expected ';' before '{extra}'"));
+            }
+            Ok((pos, len, kind))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Command::Verify { checks })
+}
+
+/// Parse EXTRACT command.
+/// Format: EXTRACT /marker/ or EXTRACT /marker/ DROP
+fn parse_extract(line: &str) -> Result<Command, String> {
+    let rest = line[7..].trim(); // Skip "EXTRACT"
+    if rest.is_empty() {
+        return Err("EXTRACT requires a delimited marker".to_string());
+    }
+
+    let (marker, after) = parse_delimited_string(rest)?;
+    if marker.is_empty() {
+        return Err("EXTRACT marker cannot be empty".to_string());
+    }
+
+    let keep_unmatched = match after.trim() {
+        "" => true,
+        opt if opt.eq_ignore_ascii_case("DROP") => false,
+        opt => return Err(format!("EXTRACT accepts only DROP, got '{opt}'")),
+    };
+
+    Ok(Command::Extract {
+        marker,
+        keep_unmatched,
+    })
+}
+
+/// Parse LENGTH command.
+/// Format: LENGTH min,max or LENGTH min, (no max) or LENGTH ,max (no min)
+fn parse_length(line: &str) -> Result<Command, String> {
+    let rest = line[6..].trim(); // Skip "LENGTH"
+    let (min, max) = rest
+        .split_once(',')
+        .ok_or("LENGTH requires min,max (either may be omitted)")?;
+    let (min, max) = (min.trim(), max.trim());
+    if min.is_empty() && max.is_empty() {
+        return Err("LENGTH requires at least one of min or max".to_string());
+    }
+
+    let min: usize = if min.is_empty() {
+        0
+    } else {
+        min.parse().map_err(|_| "Invalid LENGTH minimum")?
+    };
+    let max: Option<usize> = if max.is_empty() {
+        None
+    } else {
+        Some(max.parse().map_err(|_| "Invalid LENGTH maximum")?)
+    };
+    if max.is_some_and(|max| max < min) {
+        return Err(format!("LENGTH minimum {min} exceeds maximum"));
+    }
+
+    Ok(Command::Length { min, max })
+}
+
+/// Parse FREQ command.
+/// Format: FREQ pos,len
+fn parse_freq(line: &str) -> Result<Command, String> {
+    let rest = line[4..].trim(); // Skip "FREQ"
+    let (pos, len) = parse_pos_len(rest, "FREQ")?;
+    Ok(Command::Freq { pos, len })
+}
+
+/// Parse ONCHANGE command.
+/// Format: ONCHANGE pos,len
+fn parse_onchange(line: &str) -> Result<Command, String> {
+    let rest = line[8..].trim(); // Skip "ONCHANGE"
+    let (pos, len) = parse_pos_len(rest, "ONCHANGE")?;
+    Ok(Command::OnChange { pos, len })
+}
+
+/// Parse SORTITEMS command.
+/// Format: SORTITEMS pos,len,width
+fn parse_sortitems(line: &str) -> Result<Command, String> {
+    let rest = line[9..].trim(); // Skip "SORTITEMS"
+    let (spec, width) = rest
+        .rsplit_once(',')
+        .ok_or("SORTITEMS requires pos,len,width")?;
+    let (pos, len) = parse_pos_len(spec, "SORTITEMS")?;
+    let item_width: usize = width
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid SORTITEMS item width '{}'", width.trim()))?;
+    if item_width == 0 {
+        return Err("SORTITEMS item width must be at least 1".to_string());
+    }
+    Ok(Command::SortItems {
+        pos,
+        len,
+        item_width,
+    })
+}
+
+/// Parse CROSSTAB command.
+/// Format: CROSSTAB pos,len BY pos,len
+fn parse_crosstab(line: &str) -> Result<Command, String> {
+    let rest = line[8..].trim(); // Skip "CROSSTAB"
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let [row, by, col] = words[..] else {
+        return Err("CROSSTAB requires pos,len BY pos,len".to_string());
+    };
+    if !by.eq_ignore_ascii_case("BY") {
+        return Err(format!("CROSSTAB expected BY, got '{by}'"));
+    }
+    Ok(Command::Crosstab {
+        row_field: parse_pos_len(row, "CROSSTAB")?,
+        col_field: parse_pos_len(col, "CROSSTAB")?,
+    })
+}
+
+/// Parse BUILD command.
+/// Format: BUILD 0,8 /,/ 28,8 - field specs and delimited literals, in order
+fn parse_build(line: &str) -> Result<Command, String> {
+    let mut rest = line[5..].trim(); // Skip "BUILD"
+    let mut items = Vec::new();
+
+    while !rest.is_empty() {
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (pos, len) = parse_pos_len(&rest[..end], "BUILD field")?;
+            items.push(BuildItem::Field { pos, len });
+            rest = rest[end..].trim_start();
+        } else {
+            let (text, after) = parse_delimited_string(rest)?;
+            items.push(BuildItem::Literal(text));
+            rest = after.trim_start();
+        }
+    }
+
+    if items.is_empty() {
+        return Err("BUILD requires at least one field or literal".to_string());
+    }
+    Ok(Command::Build { items })
+}
+
+/// Format value counts as `<value> <count>` records.
+///
+/// Sorted by descending count, with ties broken by value ascending.
+pub fn frequency_records(counts: HashMap<String, usize>) -> Vec<Record> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(va, ca), (vb, cb)| cb.cmp(ca).then_with(|| va.cmp(vb)));
+    counts
+        .into_iter()
+        .map(|(value, count)| Record::from_str(&format!("{value} {count}")))
+        .collect()
+}
+
+/// Format CROSSTAB counts, keyed by row value then column value, as a table.
+///
+/// Row 0 is the header: blank under the row labels, then each column value.
+/// Each following record is one row value, left-justified and padded to
+/// the longest row value, then its count in each column (0 where there
+/// were no records). Rows and columns are sorted by value. Every column
+/// is right-justified in one width: the longest column value or count,
+/// plus one space of separation. Empty counts produce no records.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use pipelines_rs::crosstab_records;
+///
+/// let mut counts: HashMap<String, HashMap<String, usize>> = HashMap::new();
+/// *counts.entry("SALES".into()).or_default().entry("S".into()).or_default() += 2;
+/// *counts.entry("IT".into()).or_default().entry("C".into()).or_default() += 1;
+/// let table: Vec<String> = crosstab_records(counts)
+///     .iter()
+///     .map(|r| r.as_str().trim_end().to_string())
+///     .collect();
+/// assert_eq!(table, ["      C S", "IT    1 0", "SALES 0 2"]);
+/// ```
+pub fn crosstab_records(counts: HashMap<String, HashMap<String, usize>>) -> Vec<Record> {
+    if counts.is_empty() {
+        return Vec::new();
+    }
+    let mut rows: Vec<(String, HashMap<String, usize>)> = counts.into_iter().collect();
+    rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut cols: Vec<&String> = rows.iter().flat_map(|(_, cells)| cells.keys()).collect();
+    cols.sort();
+    cols.dedup();
+
+    let label_width = rows.iter().map(|(row, _)| row.len()).max().unwrap_or(0);
+    let count_width = rows
+        .iter()
+        .flat_map(|(_, cells)| cells.values())
+        .map(|count| count.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let width = cols
+        .iter()
+        .map(|col| col.len())
+        .fold(count_width, usize::max)
+        + 1;
+
+    let mut header = format!("{:label_width$}", "");
+    for col in &cols {
+        header.push_str(&format!("{col:>width$}"));
+    }
+    let mut out = vec![Record::from_str(&header)];
+    for (row, cells) in &rows {
+        let mut line = format!("{row:<label_width$}");
+        for col in &cols {
+            let count = cells.get(*col).copied().unwrap_or(0);
+            line.push_str(&format!("{count:>width$}"));
+        }
+        out.push(Record::from_str(&line));
+    }
+    out
+}
+
+/// Apply commands to records.
+///
+/// Each stage's output is checked as soon as the stage finishes: against
+/// its input with [`check_expansion`] when `options.expansion_factor_limit`
+/// is set, and against `options.max_output_records`, so a runaway stage
+/// fails before any later stage runs. DUPLICATE and EXPAND are capped as
+/// they make copies (see [`apply_command`]), so they fail without building
+/// all of their output.
+fn apply_commands(
+    records: Vec<Record>,
+    commands: &[Command],
+    options: &ExecuteOptions,
+) -> Result<Vec<Record>, String> {
+    // We need to collect and re-create pipeline for each command
+    // because the Pipeline type changes with each operation
+    let mut current: Vec<Record> = records;
+
+    for (stage_index, cmd) in commands.iter().enumerate() {
+        let inputs = current.len();
+        let mut cap = options.max_output_records.unwrap_or(usize::MAX);
+        if let Some(limit) = options.expansion_factor_limit {
+            cap = cap.min(limit.saturating_mul(inputs.max(1)));
+        }
+        current = apply_command(current, cmd, cap)?;
+        if let Some(limit) = options.expansion_factor_limit {
+            check_expansion(stage_index, cmd.name(), inputs, current.len(), limit)
+                .map_err(|e| e.to_string())?;
+        }
+        options.check_limit(current.len())?;
+    }
+
+    Ok(current)
+}
+
+/// Apply a single command to records.
+///
+/// DUPLICATE and EXPAND stop making copies once they have output one record
+/// more than `cap`, so a runaway fan-out never builds all of its output;
+/// the caller's limit checks then reject the stage. Pass `usize::MAX` for
+/// no cap.
+fn apply_command(records: Vec<Record>, cmd: &Command, cap: usize) -> Result<Vec<Record>, String> {
+    match cmd {
+        Command::Console | Command::Disabled { .. } => {
+            // Console in the middle of pipeline just passes through
+            Ok(records)
+        }
+        Command::FilterEq { pos, len, value } => {
+            let pos = *pos;
+            let len = *len;
+            let value = value.clone();
+            Ok(Pipeline::new(records.into_iter())
+                .filter(move |r| r.field_eq(pos, len, &value))
+                .collect())
+        }
+        Command::FilterNe { pos, len, value } => {
+            let pos = *pos;
+            let len = *len;
+            let value = value.clone();
+            Ok(Pipeline::new(records.into_iter())
+                .filter(move |r| !r.field_eq(pos, len, &value))
+                .collect())
+        }
+        Command::FilterRange {
+            pos,
+            len,
+            low,
+            high,
+        } => Ok(records
+            .into_iter()
+            .filter(|r| r.field_in_range(*pos, *len, low, high))
+            .collect()),
+        Command::FilterFieldCmp { left, right, op } => Ok(records
+            .into_iter()
+            .filter(|r| op.holds(r.compare_fields(left.0, left.1, right.0, right.1)))
+            .collect()),
+        // Routed records leave the pipeline; only the main channel flows on
+        Command::Route {
+            otherwise: Some(_), ..
+        } => Ok(vec![]),
+        Command::Route {
+            filter,
+            otherwise: None,
+            ..
+        } => Ok(records
+            .into_iter()
+            .filter(|r| !filter_keeps(filter, r))
+            .collect()),
+        Command::Omit { filter } => Ok(records
+            .into_iter()
+            .filter(|r| !filter_keeps(filter, r))
+            .collect()),
+        Command::OnChange { pos, len } => {
+            let mut last: Option<String> = None;
+            Ok(records
+                .into_iter()
+                .filter(|r| {
+                    let key = r.field(*pos, *len);
+                    let changed = last.as_deref() != Some(key);
+                    if changed {
+                        last = Some(key.to_string());
+                    }
+                    changed
+                })
+                .collect())
+        }
+        Command::Norm { pos, len } => {
+            Ok(records.iter().map(|r| norm_record(r, *pos, *len)).collect())
+        }
+        Command::Pivot {
+            key,
+            value,
+            group,
+            columns,
+        } => {
+            let mut output = Vec::new();
+            let mut current: Vec<Record> = Vec::new();
+            for record in records {
+                if current
+                    .first()
+                    .is_some_and(|first| pivot_group_ends(first, &record, *group))
+                {
+                    output.push(pivot_group(&current, *key, *value, columns));
+                    current.clear();
+                }
+                if !record.is_blank() {
+                    current.push(record);
+                }
+            }
+            if !current.is_empty() {
+                output.push(pivot_group(&current, *key, *value, columns));
+            }
+            Ok(output)
+        }
+        Command::Select {
+            fields,
+            separator: None,
+        } => {
+            let fields = fields.clone();
+            Ok(Pipeline::new(records.into_iter()).select(fields).collect())
+        }
+        Command::Select {
+            fields,
+            separator: Some(sep),
+        } => Ok(Pipeline::new(records.into_iter())
+            .map(|r| select_separated(&r, fields, sep))
+            .collect()),
+        Command::Field { pos, len, trim } => Ok(Pipeline::new(records.into_iter())
+            .map(|r| field_record(&r, *pos, *len, *trim))
+            .collect()),
+        Command::ToJson { fields } => Ok(Pipeline::new(records.into_iter())
+            .map(|r| json_record(&r, fields))
+            .collect()),
+        Command::Take { n } => Ok(Pipeline::new(records.into_iter()).take(*n).collect()),
+        Command::Skip { n } => Ok(Pipeline::new(records.into_iter()).skip(*n).collect()),
+        Command::Reseq {
+            pos,
+            len,
+            start,
+            step,
+            fill,
+        } => {
+            // Overwrite the field with start, start+step, ...
+            let (pos, len, step, fill) = (*pos, *len, *step, *fill);
+            let mut next = *start;
+            Ok(Pipeline::new(records.into_iter())
+                .map(move |mut r| {
+                    r.set_field_i64_padded(pos, len, next, fill);
+                    next = next.wrapping_add(step);
+                    r
+                })
+                .collect())
+        }
+        Command::Sort { keys } => {
+            // Stable sort keeps input order among records equal on every key
+            let mut records = records;
+            records.sort_by(|a, b| compare_by_keys(a, b, keys));
+            Ok(records)
+        }
+        Command::Shuffle { seed } => {
+            let mut records = records;
+            shuffle_records(&mut records, *seed);
+            Ok(records)
+        }
+        Command::Include { .. } => {
+            // Parsing expands INCLUDE, so only hand-built command lists get here
+            check_includes_expanded(std::slice::from_ref(cmd)).map(|()| records)
+        }
+        Command::Expand { pos, len, clear } => Ok(records
+            .iter()
+            .flat_map(|r| expand_record(r, *pos, *len, *clear))
+            .take(cap.saturating_add(1))
+            .collect()),
+        Command::DateFmt {
+            pos,
+            len,
+            from,
+            to,
+            flag,
+        } => Ok(records
+            .iter()
+            .map(|r| reformat_date(r, *pos, *len, *from, *to, *flag))
+            .collect()),
+        Command::Decode {
+            pos,
+            len,
+            map,
+            default,
+        } => {
+            let map: HashMap<String, String> = map.clone().into_iter().collect();
+            Ok(records
+                .iter()
+                .map(|r| decode_field(r, *pos, *len, &map, default.as_deref()))
+                .collect())
+        }
+        Command::Default {
+            pos,
+            len,
+            value,
+            justify,
+        } => Ok(records
+            .iter()
+            .map(|r| default_field(r, *pos, *len, value, *justify))
+            .collect()),
+        Command::Mask { fields, fill } => Ok(records
+            .iter()
+            .map(|r| mask_record(r, fields, *fill))
+            .collect()),
+        Command::Wrap { width, cont_col } => Ok(records
+            .iter()
+            .flat_map(|r| wrap_record(r, *width, *cont_col))
+            .collect()),
+        Command::TopN { n, pos, len } => {
+            // Stable sort keeps input order among equal values;
+            // non-numeric fields (None) sort below every number
+            let mut records = records;
+            records.sort_by_key(|r| std::cmp::Reverse(r.field_i64(*pos, *len)));
+            records.truncate(*n);
+            Ok(records)
+        }
+        Command::Page { page, size } => Ok(Pipeline::new(records.into_iter())
+            .skip((page - 1).saturating_mul(*size))
+            .take(*size)
+            .collect()),
+        Command::Locate {
+            pattern,
+            field,
+            trim,
+        } => {
+            let pattern = pattern.clone();
+            match field {
+                Some((pos, len)) => {
+                    let pos = *pos;
+                    let len = *len;
+                    let contains = if *trim {
+                        Record::field_contains_trimmed
+                    } else {
+                        Record::field_contains
+                    };
+                    Ok(Pipeline::new(records.into_iter())
+                        .filter(move |r| contains(r, pos, len, &pattern))
+                        .collect())
+                }
+                None => {
+                    // Search entire record
+                    Ok(Pipeline::new(records.into_iter())
+                        .filter(move |r| r.as_str().contains(pattern.as_str()))
+                        .collect())
+                }
+            }
+        }
+        Command::LocateMulti {
+            patterns,
+            mode,
+            field,
+        } => Ok(Pipeline::new(records.into_iter())
+            .filter(|r| mode.matches(r, patterns, *field))
+            .collect()),
+        Command::Nlocate { pattern, field } => {
+            let pattern = pattern.clone();
+            match field {
+                Some((pos, len)) => {
+                    let pos = *pos;
+                    let len = *len;
+                    Ok(Pipeline::new(records.into_iter())
+                        .filter(move |r| !r.field_contains(pos, len, &pattern))
+                        .collect())
+                }
+                None => Ok(Pipeline::new(records.into_iter())
+                    .filter(move |r| !r.as_str().contains(pattern.as_str()))
+                    .collect()),
+            }
+        }
+        Command::Count => {
+            // Count records and emit a single summary record
+            let count = records.len();
+            Ok(vec![Record::from_str(&count.to_string())])
+        }
+        Command::Hash => {
+            let mut hasher = RecordHasher::new();
+            for record in &records {
+                hasher.update(record);
+            }
+            Ok(vec![hasher.to_record()])
+        }
+        Command::Stats { passthrough } => {
+            let mut stats = LengthStats::default();
+            for record in &records {
+                stats.update(record);
+            }
+            let mut result = if *passthrough { records } else { vec![] };
+            result.extend(stats.to_records());
+            Ok(result)
+        }
+        Command::Change {
+            old,
+            new,
+            limit,
+            repeat: true,
+        } => records
+            .iter()
+            .map(|r| change_until_stable(r, old, new, *limit).map_err(|e| e.to_string()))
+            .collect(),
+        Command::Change {
+            old,
+            new,
+            limit,
+            repeat: false,
+        } => {
+            // Replace occurrences of old with new in each record, up to the limit
+            let old = old.clone();
+            let new = new.clone();
+            let limit = *limit;
+            Ok(Pipeline::new(records.into_iter())
+                .map(move |r| {
+                    let content = change_text(r.as_str(), &old, &new, limit);
+                    Record::from_str(&content)
+                })
+                .collect())
+        }
+        Command::ChangeWhere {
+            condition,
+            old,
+            new,
+        } => Ok(records
+            .into_iter()
+            .map(|r| {
+                if filter_keeps(condition, &r) {
+                    Record::from_str(&change_text(r.as_str(), old, new, None))
+                } else {
+                    r
+                }
+            })
+            .collect()),
+        Command::Literal { text } => {
+            // CMS Pipelines: LITERAL is a "prefix" filter.
+            // It outputs its literal text FIRST, then passes through all input records.
+            let mut result = vec![Record::from_str(text)];
+            result.extend(records);
+            Ok(result)
+        }
+        Command::Generate {
+            count,
+            template,
+            seed,
+        } => {
+            // Like LITERAL, the generated records come before the input
+            let mut result = generate_records(*count, template, *seed);
+            result.extend(records);
+            Ok(result)
+        }
+        Command::Upper => {
+            // Convert all records to uppercase
+            Ok(Pipeline::new(records.into_iter())
+                .map(|r| r.map_chars(|c| c.to_ascii_uppercase()))
+                .collect())
+        }
+        Command::Lower => {
+            // Convert all records to lowercase
+            Ok(Pipeline::new(records.into_iter())
+                .map(|r| r.map_chars(|c| c.to_ascii_lowercase()))
+                .collect())
+        }
+        Command::Reverse { full: false } => {
+            // Reverse characters in each record (trim first to avoid reversing trailing spaces)
+            Ok(Pipeline::new(records.into_iter())
+                .map(|r| {
+                    let reversed: String = r.as_str().trim_end().chars().rev().collect();
+                    Record::from_str(&reversed)
+                })
+                .collect())
+        }
+        Command::Reverse { full: true } => {
+            // Reverse all 80 bytes, so leading and trailing padding swap places
+            Ok(Pipeline::new(records.into_iter())
+                .map(|r| {
+                    let mut bytes = *r.as_bytes();
+                    bytes.reverse();
+                    Record::from_bytes(&bytes)
+                })
+                .collect())
+        }
+        Command::Duplicate { n } => {
+            // Repeat each record n times
+            let n = *n;
+            Ok(records
+                .into_iter()
+                .flat_map(|r| std::iter::repeat_n(r, n))
+                .take(cap.saturating_add(1))
+                .collect())
+        }
+        Command::Header { template } => {
+            // The header goes first, so its count covers every record
+            let header = Record::from_str(&expand_header(template, records.len()));
+            Ok(std::iter::once(header).chain(records).collect())
+        }
+        Command::Apply { transform, .. } => Ok(Pipeline::new(records.into_iter())
+            .map(|r| Record::from_str(&transform(r.as_str().trim_end())))
+            .collect()),
+        Command::Chop { width } => Ok(records
+            .iter()
+            .flat_map(|r| chop_record(r, *width))
+            .collect()),
+        Command::Context {
+            pattern,
+            before,
+            after,
+        } => {
+            // Mark each match's window; overlapping windows share records
+            let mut keep = vec![false; records.len()];
+            for (i, r) in records.iter().enumerate() {
+                if r.as_str().contains(pattern.as_str()) {
+                    let end = (i + after + 1).min(records.len());
+                    keep[i.saturating_sub(*before)..end].fill(true);
+                }
+            }
+            Ok(records
+                .into_iter()
+                .zip(keep)
+                .filter_map(|(r, keep)| keep.then_some(r))
+                .collect())
+        }
+        Command::InsertEvery { n, text, trailing } => {
+            let total = records.len();
+            let mut output = Vec::with_capacity(total + total / n);
+            for (i, r) in records.into_iter().enumerate() {
+                output.push(r);
+                if (i + 1).is_multiple_of(*n) && (i + 1 < total || *trailing) {
+                    output.push(Record::from_str(text));
+                }
+            }
+            Ok(output)
+        }
+        Command::SortItems {
+            pos,
+            len,
+            item_width,
+        } => Ok(records
+            .iter()
+            .map(|r| sort_items(r, *pos, *len, *item_width))
+            .collect()),
+        Command::Segment { n } => {
+            let n = *n;
+            Ok(records
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, r)| {
+                    if i.is_multiple_of(n) {
+                        vec![segment_header(i / n + 1), r]
+                    } else {
+                        vec![r]
+                    }
+                })
+                .collect())
+        }
+        Command::Block { n } => {
+            // Follow every nth record with a blank one; a final partial group gets none
+            let n = *n;
+            Ok(records
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, r)| {
+                    if (i + 1).is_multiple_of(n) {
+                        vec![r, Record::new()]
+                    } else {
+                        vec![r]
+                    }
+                })
+                .collect())
+        }
+        Command::Hole { count } => {
+            // Discard all input records, output nothing (like /dev/null)
+            // HOLE COUNT reports how many records were discarded
+            if *count {
+                Ok(vec![Record::from_str(&format!(
+                    "DROPPED={}",
+                    records.len()
+                ))])
+            } else {
+                drop(records);
+                Ok(vec![])
+            }
+        }
+        Command::Verify { checks } => Ok(records
+            .iter()
+            .flat_map(|r| verify_record(r, checks))
+            .collect()),
+        Command::Assert {
+            pos,
+            len,
+            kind,
+            strict,
+        } => {
+            // Pass conforming records; flag or drop the rest
+            let (pos, len, kind, strict) = (*pos, *len, *kind, *strict);
+            Ok(records
+                .into_iter()
+                .filter_map(|r| {
+                    if kind.check(&r, pos, len) {
+                        Some(r)
+                    } else if strict {
+                        None
+                    } else {
+                        Some(flag_record(&r))
+                    }
+                })
+                .collect())
+        }
+        Command::Justify {
+            pos,
+            len,
+            side,
+            fill,
+        } => {
+            // Re-justify the trimmed field content within its own columns
+            let (pos, len, side, fill) = (*pos, *len, *side, *fill);
+            Ok(Pipeline::new(records.into_iter())
+                .map(move |mut r| {
+                    let field = r.field(pos, len);
+                    let justified = side.justify(field, field.len(), fill);
+                    r.set_field(pos, len, &justified);
+                    r
+                })
+                .collect())
+        }
+        Command::Extract {
+            marker,
+            keep_unmatched,
+        } => {
+            // Keep the text after the first occurrence of the marker
+            let marker = marker.clone();
+            let keep_unmatched = *keep_unmatched;
+            Ok(Pipeline::new(records.into_iter())
+                .filter_map(move |r| match r.as_str().find(marker.as_str()) {
+                    Some(idx) => Some(Record::from_str(&r.as_str()[idx + marker.len()..])),
+                    None if keep_unmatched => Some(Record::new()),
+                    None => None,
+                })
+                .collect())
+        }
+        Command::Length { min, max } => {
+            // Count characters, not bytes, ignoring the record's space padding
+            let (min, max) = (*min, *max);
+            Ok(Pipeline::new(records.into_iter())
+                .filter(move |r| {
+                    let n = r.as_str().trim_end().chars().count();
+                    n >= min && max.is_none_or(|max| n <= max)
+                })
+                .collect())
+        }
+        Command::Freq { pos, len } => {
+            // Tally trimmed field values, like `sort | uniq -c | sort -rn`
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for r in &records {
+                *counts
+                    .entry(r.field(*pos, *len).trim().to_string())
+                    .or_default() += 1;
+            }
+            Ok(frequency_records(counts))
+        }
+        Command::Crosstab {
+            row_field,
+            col_field,
+        } => {
+            let mut counts: HashMap<String, HashMap<String, usize>> = HashMap::new();
+            for r in &records {
+                let row = r.field(row_field.0, row_field.1).trim().to_string();
+                let col = r.field(col_field.0, col_field.1).trim().to_string();
+                *counts.entry(row).or_default().entry(col).or_default() += 1;
+            }
+            Ok(crosstab_records(counts))
+        }
+        Command::Build { items } => {
+            // Assemble a new record from fields and literals
+            let items = items.clone();
+            Ok(Pipeline::new(records.into_iter())
+                .map(move |r| build_record(&r, &items))
+                .collect())
+        }
+        Command::Zip => {
+            // Pair consecutive records; an odd last record is emitted alone
+            Ok(records
+                .chunks(2)
+                .map(|pair| zip_pair(&pair[0], pair.get(1)))
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a single command with the built-in transforms.
+    fn parse_command(line: &str) -> Result<Command, String> {
+        parse_command_with(line, &TransformRegistry::default())
+    }
+
+    #[test]
+    fn test_parse_filter_eq() {
+        let cmd = parse_command(r#"FILTER 18,10 = "SALES""#).unwrap();
+        match cmd {
+            Command::FilterEq { pos, len, value } => {
+                assert_eq!(pos, 18);
+                assert_eq!(len, 10);
+                assert_eq!(value, "SALES");
+            }
+            _ => panic!("Expected FilterEq"),
+        }
+    }
+
+    #[test]
+    fn test_parse_filter_ne() {
+        let cmd = parse_command(r#"FILTER 18,10 != "SALES""#).unwrap();
+        match cmd {
+            Command::FilterNe { pos, len, value } => {
+                assert_eq!(pos, 18);
+                assert_eq!(len, 10);
                 assert_eq!(value, "SALES");
             }
-            _ => panic!("Expected FilterNe"),
+            _ => panic!("Expected FilterNe"),
+        }
+    }
+
+    #[test]
+    fn test_parse_filter_between() {
+        match parse_command(r#"FILTER 28,8 between "00050000" and "00070000""#).unwrap() {
+            Command::FilterRange {
+                pos,
+                len,
+                low,
+                high,
+            } => {
+                assert_eq!((pos, len), (28, 8));
+                assert_eq!((low.as_str(), high.as_str()), ("00050000", "00070000"));
+            }
+            other => panic!("Expected FilterRange, got {other:?}"),
+        }
+        // A '=' inside a bound is not mistaken for the equality operator
+        assert!(matches!(
+            parse_command(r#"FILTER 0,3 BETWEEN "A=B" AND "Z""#).unwrap(),
+            Command::FilterRange { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_between_errors() {
+        for bad in [
+            r#"FILTER 28,8 BETWEEN "70000" AND "50000""#,
+            r#"FILTER 0,5 BETWEEN "M" AND "C""#,
+            r#"FILTER 28,8 BETWEEN "1" "2""#,
+            r#"FILTER 28,8 BETWEEN "1" AND"#,
+            r#"FILTER 28,8 BETWEEN "1" AND "2" EXTRA"#,
+            r#"FILTER 28 BETWEEN "1" AND "2""#,
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+        // Numeric bounds compare as numbers, not text
+        assert!(parse_command(r#"FILTER 28,8 BETWEEN "900" AND "1000""#).is_ok());
+    }
+
+    #[test]
+    fn test_execute_filter_between_salary_band() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE\n| FILTER 28,8 BETWEEN \"00055000\" AND \"00075000\"\n| SELECT 0,8,0\n| CONSOLE\n?";
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "JONES\nDOE\nWILSON\nTAYLOR");
+        assert_eq!((input_count, output_count), (8, 4));
+        // Blank fields are excluded even from a text range covering everything
+        let (output, _, _) = execute_pipeline(
+            "A 5\nB\nC 7",
+            "PIPE CONSOLE | FILTER 2,1 BETWEEN \"\" AND \"9\" | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "A 5\nC 7");
+    }
+
+    #[test]
+    fn test_parse_filter_field_cmp() {
+        for (line, expected) in [
+            ("FILTER 0,8 = FIELD 10,8", CompareOp::Eq),
+            ("FILTER 0,8 != field 10,8", CompareOp::Ne),
+            ("FILTER 0,8<FIELD 10,8", CompareOp::Lt),
+            ("FILTER 0,8 <= FIELD 10,8", CompareOp::Le),
+            ("FILTER 0,8 > FIELD 10,8", CompareOp::Gt),
+            ("FILTER 0,8 >= FIELD 10,8", CompareOp::Ge),
+        ] {
+            match parse_command(line).unwrap() {
+                Command::FilterFieldCmp { left, right, op } => {
+                    assert_eq!((left, right, op), ((0, 8), (10, 8), expected), "{line}");
+                }
+                other => panic!("Expected FilterFieldCmp, got {other:?}"),
+            }
+        }
+        for bad in [
+            "FILTER 0,8 FIELD 10,8",
+            "FILTER 0,8 =< FIELD 10,8",
+            "FILTER 0,8 = FIELD",
+            "FILTER 0,8 = FIELD 10",
+            "FILTER 0 = FIELD 10,8",
+            "FILTER 0,8 > 10,8 FIELD",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+        // A quoted "FIELD" is still a plain value
+        assert!(matches!(
+            parse_command(r#"FILTER 0,5 = "FIELD""#).unwrap(),
+            Command::FilterEq { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_onchange() {
+        assert!(matches!(
+            parse_command("ONCHANGE 18,10").unwrap(),
+            Command::OnChange { pos: 18, len: 10 }
+        ));
+        assert!(parse_command("ONCHANGE").is_err());
+        assert!(parse_command("ONCHANGE 18").is_err());
+    }
+
+    #[test]
+    fn test_execute_onchange_department_boundaries() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | SORT 18,10 | ONCHANGE 18,10 | FIELD 0,8 | CONSOLE",
+        )
+        .unwrap();
+        // The first record of each department in sorted (stable) order
+        assert_eq!(output, "JONES\nWILSON\nSMITH");
+
+        // Unsorted, every record starts a new run of its department
+        let (_, _, count) =
+            execute_pipeline(input, "PIPE CONSOLE | ONCHANGE 18,10 | CONSOLE").unwrap();
+        assert_eq!(count, 8);
+    }
+
+    #[test]
+    fn test_execute_onchange_first_record_passes() {
+        let (output, _, _) =
+            execute_pipeline("A 1\nA 2\nA 3", "PIPE CONSOLE | ONCHANGE 0,1 | CONSOLE").unwrap();
+        assert_eq!(output, "A 1");
+        let (output, _, _) = execute_pipeline(
+            "A 1\nB 2\nB 3\nA 4",
+            "PIPE CONSOLE | ONCHANGE 0,1 | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "A 1\nB 2\nA 4");
+    }
+
+    #[test]
+    fn test_parse_omit() {
+        match parse_command(r#"OMIT 18,10 = "SALES""#).unwrap() {
+            Command::Omit { filter } => {
+                assert!(matches!(
+                    *filter,
+                    Command::FilterEq {
+                        pos: 18,
+                        len: 10,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("Expected Omit, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command(r#"omit 0,8 between "A" and "M""#).unwrap(),
+            Command::Omit { filter } if matches!(*filter, Command::FilterRange { .. })
+        ));
+        let err = parse_command("OMIT 18,10 \"SALES\"").unwrap_err();
+        assert_eq!(err, "OMIT requires = or != operator");
+    }
+
+    #[test]
+    fn test_parse_route() {
+        match parse_command(r#"ROUTE 18,10 = "SALES TO X" TO sales ELSE other"#).unwrap() {
+            Command::Route {
+                filter,
+                to,
+                otherwise,
+            } => {
+                assert!(matches!(
+                    *filter,
+                    Command::FilterEq { pos: 18, len: 10, ref value } if value == "SALES TO X"
+                ));
+                assert_eq!(
+                    (to.as_str(), otherwise.as_deref()),
+                    ("sales", Some("other"))
+                );
+            }
+            other => panic!("Expected Route, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("route 0,4 < field 4,4 to low").unwrap(),
+            Command::Route { filter, otherwise: None, .. }
+                if matches!(*filter, Command::FilterFieldCmp { .. })
+        ));
+        for bad in [
+            "ROUTE",
+            "ROUTE 18,10 = \"SALES\"",
+            "ROUTE 18,10 = \"SALES\" TO",
+            "ROUTE 18,10 = \"SALES\" INTO sales",
+            "ROUTE 18,10 = \"SALES\" sales ELSE other",
+            "ROUTE TO sales",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+        assert_eq!(
+            parse_command("ROUTE 0,1 = \"A\" TO a ELSE Main").unwrap_err(),
+            "ROUTE channel name 'Main' is reserved"
+        );
+    }
+
+    #[test]
+    fn test_execute_route_keeps_main_channel() {
+        let input = "A1\nB2\nA3\nC4";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | ROUTE 0,1 = \"A\" TO a | CONSOLE").unwrap();
+        assert_eq!(output, "B2\nC4");
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | ROUTE 0,1 = \"A\" TO a ELSE rest | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_parse_insert() {
+        match parse_command("INSERT EVERY 2 /---/").unwrap() {
+            Command::InsertEvery { n, text, trailing } => {
+                assert_eq!((n, text.as_str(), trailing), (2, "---", false));
+            }
+            other => panic!("Expected InsertEvery, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("insert every 3 \"a b\" trailing").unwrap(),
+            Command::InsertEvery {
+                n: 3,
+                trailing: true,
+                ..
+            }
+        ));
+        for bad in [
+            "INSERT 2 /---/",
+            "INSERT EVERY /---/",
+            "INSERT EVERY 0 /---/",
+            "INSERT EVERY 2",
+            "INSERT EVERY 2 /---",
+            "INSERT EVERY 2 /---/ AFTER",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_execute_insert_every() {
+        let input = "1\n2\n3\n4\n5";
+        let run = |stage: &str| {
+            let pipeline = format!("PIPE CONSOLE | {stage} | CONSOLE");
+            execute_pipeline(input, &pipeline).unwrap().0
+        };
+        assert_eq!(run("INSERT EVERY 2 /---/"), "1\n2\n---\n3\n4\n---\n5");
+        // A count landing on the last record adds nothing unless TRAILING
+        assert_eq!(run("INSERT EVERY 5 /---/"), "1\n2\n3\n4\n5");
+        assert_eq!(run("INSERT EVERY 5 /---/ TRAILING"), "1\n2\n3\n4\n5\n---");
+        assert_eq!(run("INSERT EVERY 6 /---/ TRAILING"), "1\n2\n3\n4\n5");
+    }
+
+    #[test]
+    fn test_normalize_number() {
+        for (text, expected) in [
+            ("00050000", Some("50000")),
+            ("  +0042 ", Some("42")),
+            ("-0042", Some("-42")),
+            ("0042-", Some("-42")),
+            ("0000", Some("0")),
+            ("-000", Some("0")),
+            ("0005000}", Some("-50000")),
+            ("0005000{", Some("50000")),
+            ("0000012J", Some("-121")),
+            ("0000012R", Some("-129")),
+            ("0000012A", Some("121")),
+            ("", None),
+            ("-", None),
+            ("--5", None),
+            ("12 3", None),
+            ("ABC", None),
+            ("0000012S", None),
+        ] {
+            assert_eq!(normalize_number(text).as_deref(), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_execute_norm() {
+        let input = "SMITH   00050000\nREFUND  0007500}\nBAD     12X45678\nONE     J";
+        let pipeline = "PIPE CONSOLE | NORM 8,8 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            "SMITH      50000\nREFUND    -75000\nBAD     12X45678\nONE           -1"
+        );
+        // -1 doesn't fit a one-column field
+        let (output, _, _) = execute_pipeline("J", "PIPE CONSOLE | NORM 0,1 | CONSOLE").unwrap();
+        assert_eq!(output, "J");
+        assert!(parse_command("NORM 8").is_err());
+        assert!(matches!(
+            parse_command("NORM -8,8").unwrap(),
+            Command::Norm { pos: 72, len: 8 }
+        ));
+    }
+
+    #[test]
+    fn test_execute_omit_inverts_filter() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let run = |pipeline: &str| execute_pipeline(input, pipeline).unwrap().0;
+        let omitted = run(r#"PIPE CONSOLE | OMIT 18,10 = "SALES" | CONSOLE"#);
+        assert_eq!(
+            omitted,
+            run(r#"PIPE CONSOLE | FILTER 18,10 != "SALES" | CONSOLE"#)
+        );
+        assert_eq!(omitted.lines().count(), 5);
+        assert!(omitted.lines().all(|l| !l.contains("SALES")));
+        // Every record lands on exactly one side of a condition
+        for condition in [
+            r#"28,8 BETWEEN "50000" AND "70000""#,
+            "0,8 < FIELD 8,10",
+            r#"0,8 != "SMITH""#,
+        ] {
+            let kept = run(&format!("PIPE CONSOLE | FILTER {condition} | CONSOLE"));
+            let dropped = run(&format!("PIPE CONSOLE | OMIT {condition} | CONSOLE"));
+            assert_eq!(
+                kept.lines().count() + dropped.lines().count(),
+                8,
+                "{condition}"
+            );
+            assert!(dropped.lines().all(|l| !kept.contains(l)), "{condition}");
+        }
+    }
+
+    #[test]
+    fn test_execute_filter_field_cmp() {
+        // Planned vs actual amount in columns 0-5 and 6-11
+        let input = "00100 00100 A\n00200 00150 B\n  300 00300 C\n00050 00075 D";
+        let run = |op: &str| {
+            let pipeline = format!("PIPE CONSOLE | FILTER 0,5 {op} FIELD 6,5 | CONSOLE");
+            let (output, _, _) = execute_pipeline(input, &pipeline).unwrap();
+            output
+                .lines()
+                .map(|l| &l[12..])
+                .collect::<Vec<_>>()
+                .join("")
+        };
+        assert_eq!(run("="), "AC");
+        assert_eq!(run("!="), "BD");
+        assert_eq!(run(">"), "B");
+        assert_eq!(run("<="), "ACD");
+        // Text fields compare trimmed
+        let (output, _, _) = execute_pipeline(
+            "SMITH   SMITH\nJONES   SMITH",
+            "PIPE CONSOLE | FILTER 0,8 = FIELD 8,8 | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "SMITH   SMITH");
+    }
+
+    #[test]
+    fn test_parse_select() {
+        let cmd = parse_command("SELECT 0,8,0; 28,8,8").unwrap();
+        match cmd {
+            Command::Select { fields, .. } => {
+                assert_eq!(fields.len(), 2);
+                assert_eq!(fields[0], (0, 8, 0));
+                assert_eq!(fields[1], (28, 8, 8));
+            }
+            _ => panic!("Expected Select"),
+        }
+    }
+
+    #[test]
+    fn test_parse_take() {
+        let cmd = parse_command("TAKE 5").unwrap();
+        match cmd {
+            Command::Take { n } => assert_eq!(n, 5),
+            _ => panic!("Expected Take"),
+        }
+    }
+
+    #[test]
+    fn test_parse_console() {
+        let cmd = parse_command("CONSOLE").unwrap();
+        assert!(matches!(cmd, Command::Console));
+    }
+
+    #[test]
+    fn test_execute_pipeline() {
+        let input = "SMITH   JOHN      SALES     00050000\nJONES   MARY      ENGINEER  00075000";
+        let pipeline = r#"PIPE CONSOLE
+| FILTER 18,10 = "SALES"
+| CONSOLE
+?"#;
+
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+
+        assert_eq!(input_count, 2);
+        assert_eq!(output_count, 1);
+        assert!(output.contains("SMITH"));
+        assert!(!output.contains("JONES"));
+    }
+
+    #[test]
+    fn test_execute_parsed_runs_repeatedly() {
+        let pipeline = "PIPE CONSOLE | FILTER 18,10 = \"SALES\" | CONSOLE";
+        let commands = parse_commands(pipeline).unwrap();
+        let input = "SMITH   JOHN      SALES     00050000\nJONES   MARY      ENGINEER  00075000";
+        assert_eq!(
+            execute_parsed(&commands, input).unwrap(),
+            execute_pipeline(input, pipeline).unwrap()
+        );
+        let (output, input_count, output_count) =
+            execute_parsed(&commands, "DOE     JANE      SALES     00060000").unwrap();
+        assert_eq!((input_count, output_count), (1, 1));
+        assert!(output.starts_with("DOE"));
+    }
+
+    #[test]
+    fn test_execute_parsed_validates_structure() {
+        let commands = parse_commands("PIPE TAKE 1 | CONSOLE").unwrap();
+        let err = execute_parsed(&commands, "A").unwrap_err();
+        assert!(err.contains("TAKE cannot be the first stage"), "{err}");
+        assert!(execute_parsed(&[], "A").is_err());
+    }
+
+    #[test]
+    fn test_parse_document_splits_at_first_marker() {
+        let doc = "# Sales only\nPIPE CONSOLE\n| LOCATE /SALES/\n| CONSOLE\n?\n\
+                   === INPUT ===\r\n\
+                   SMITH   SALES\n=== INPUT ===\n  JONES ENGINEER\n";
+        let (pipeline, input) = parse_document(doc).unwrap();
+        assert_eq!(
+            pipeline,
+            "# Sales only\nPIPE CONSOLE\n| LOCATE /SALES/\n| CONSOLE\n?\n"
+        );
+        // Later markers and leading spaces belong to the input
+        assert_eq!(input, "SMITH   SALES\n=== INPUT ===\n  JONES ENGINEER\n");
+
+        // An indented marker is not the marker
+        let (pipeline, input) = parse_document("PIPE CONSOLE | CONSOLE\n === INPUT ===").unwrap();
+        assert_eq!(pipeline, "PIPE CONSOLE | CONSOLE\n === INPUT ===");
+        assert_eq!(input, "");
+    }
+
+    #[test]
+    fn test_parse_document_without_input_or_pipeline() {
+        let (pipeline, input) = parse_document("PIPE LITERAL X | CONSOLE").unwrap();
+        assert_eq!(
+            (pipeline.as_str(), input.as_str()),
+            ("PIPE LITERAL X | CONSOLE", "")
+        );
+        let (_, input) = parse_document("PIPE CONSOLE | CONSOLE\n=== INPUT ===").unwrap();
+        assert_eq!(input, "");
+        for doc in ["", "=== INPUT ===\nA", "\n  \n=== INPUT ===\nA"] {
+            assert!(
+                matches!(parse_document(doc), Err(PipelineError::Parse(_))),
+                "{doc:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_execute_document_end_to_end() {
+        let doc = "PIPE CONSOLE\n| FILTER 18,10 = \"SALES\"\n| CONSOLE\n?\n\
+                   === INPUT ===\n\
+                   SMITH   JOHN      SALES     00050000\n\
+                   JONES   MARY      ENGINEER  00075000\n\
+                   DOE     JANE      SALES     00060000\n";
+        let (output, input_count, output_count) = execute_document(doc).unwrap();
+        assert_eq!((input_count, output_count), (3, 2));
+        assert_eq!(
+            output,
+            "SMITH   JOHN      SALES     00050000\nDOE     JANE      SALES     00060000"
+        );
+        let (output, input_count, _) = execute_document("PIPE CONSOLE | COUNT | CONSOLE").unwrap();
+        assert_eq!((output.as_str(), input_count), ("0", 0));
+        assert!(execute_document("=== INPUT ===\nA").is_err());
+    }
+
+    #[test]
+    fn test_sink_receives_every_output_record_in_order() {
+        let input = "C\nA\nB\nA";
+        let pipeline = "PIPE CONSOLE | SORT | DUPLICATE 2 | CONSOLE";
+        let mut seen = Vec::new();
+        let (output, _, output_count, _) =
+            execute_pipeline_with_sink(input, pipeline, &ExecuteOptions::default(), |r| {
+                seen.push(r.clone())
+            })
+            .unwrap();
+        assert_eq!(seen.len(), output_count);
+        let texts: Vec<&str> = seen.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(texts, ["A", "A", "A", "A", "B", "B", "C", "C"]);
+        assert_eq!(texts.join("\n"), output);
+
+        // A failed run delivers nothing
+        let options = ExecuteOptions {
+            max_output_records: Some(3),
+            ..ExecuteOptions::default()
+        };
+        let mut calls = 0;
+        assert!(execute_pipeline_with_sink(input, pipeline, &options, |_| calls += 1).is_err());
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn test_pipeline_requires_source_first() {
+        let input = "SMITH   JOHN      SALES     00050000";
+
+        // FILTER cannot be first stage
+        let pipeline = r#"PIPE FILTER 18,10 = "SALES"
+| CONSOLE"#;
+        let result = execute_pipeline(input, pipeline);
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .contains("FILTER cannot be the first stage")
+        );
+    }
+
+    #[test]
+    fn test_any_stage_can_be_last_filter() {
+        // FILTER as last stage - output is discarded
+        let pipeline = r#"PIPE CONSOLE
+| FILTER 18,10 = "SALES""#;
+        let result = execute_pipeline("SMITH   JOHN      SALES     00050000", pipeline);
+        // Should succeed - output just discarded
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_execute_locate() {
+        let input = "SMITH   JOHN      SALES     00050000
+JONES   MARY      ENGINEER  00075000
+DOE     JANE      SALES     00060000";
+        let pipeline = r#"PIPE CONSOLE
+| LOCATE "SALES"
+| CONSOLE
+?"#;
+
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+
+        assert_eq!(input_count, 3);
+        assert_eq!(output_count, 2);
+        assert!(output.contains("SMITH"));
+        assert!(output.contains("DOE"));
+        assert!(!output.contains("JONES"));
+    }
+
+    #[test]
+    fn test_parse_locate_trim() {
+        assert!(matches!(
+            parse_command("LOCATE 18,10 TRIM /SALES /").unwrap(),
+            Command::Locate { ref pattern, field: Some((18, 10)), trim: true } if pattern == "SALES "
+        ));
+        assert!(matches!(
+            parse_command("LOCATE 18,10 /SALES /").unwrap(),
+            Command::Locate { trim: false, .. }
+        ));
+        assert_eq!(
+            parse_command("LOCATE trim /SALES/").unwrap_err(),
+            "LOCATE TRIM requires a field pos,len"
+        );
+    }
+
+    #[test]
+    fn test_execute_locate_field_trailing_space() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        // The field is searched with its padding, so "SALES " matches "SALES     "
+        let (_, _, count) =
+            execute_pipeline(input, "PIPE CONSOLE | LOCATE 18,10 /SALES / | CONSOLE").unwrap();
+        assert_eq!(count, 3);
+        let (_, _, count) =
+            execute_pipeline(input, "PIPE CONSOLE | LOCATE 18,10 TRIM /SALES / | CONSOLE").unwrap();
+        assert_eq!(count, 0);
+        // MARKETING fills 9 of 10 columns: one space of padding follows it
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | LOCATE 18,10 /ING / | FIELD 0,8 | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "WILSON\nTAYLOR");
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | LOCATE 18,10 TRIM /ING/ | FIELD 0,8 | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "WILSON\nTAYLOR");
+    }
+
+    #[test]
+    fn test_execute_count() {
+        let input = "SMITH   JOHN      SALES     00050000
+JONES   MARY      ENGINEER  00075000
+DOE     JANE      SALES     00060000";
+        let pipeline = r#"PIPE CONSOLE
+| COUNT
+| CONSOLE
+?"#;
+
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+
+        assert_eq!(input_count, 3);
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "3");
+    }
+
+    #[test]
+    fn test_literal_as_first_stage() {
+        // LITERAL can be the first stage, generating a single record
+        // CMS-style: no delimiters, everything after LITERAL is the text
+        let pipeline = r#"PIPE LITERAL HELLO, WORLD
+| CONSOLE
+?"#;
+
+        let (output, input_count, output_count) = execute_pipeline("", pipeline).unwrap();
+
+        assert_eq!(input_count, 1); // LITERAL generates 1 record
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "HELLO, WORLD");
+    }
+
+    #[test]
+    fn test_literal_first_with_middle_stages() {
+        // CMS-style: no delimiters
+        let pipeline = r#"PIPE LITERAL hello world
+| UPPER
+| CONSOLE
+?"#;
+
+        let (output, _input_count, output_count) = execute_pipeline("", pipeline).unwrap();
+
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "HELLO WORLD");
+    }
+
+    #[test]
+    fn test_filter_cannot_be_first() {
+        let pipeline = r#"PIPE FILTER 0,5 = "TEST"
+| CONSOLE"#;
+        let result = execute_pipeline("", pipeline);
+        assert!(result.is_err(), "Expected error but got: {:?}", result);
+        let err = result.unwrap_err();
+        assert!(
+            err.contains("FILTER cannot be the first stage"),
+            "Got: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_any_stage_can_be_last() {
+        // Output is discarded if last stage isn't a sink
+        // CMS-style: LITERAL without delimiters
+        let pipeline = r#"PIPE CONSOLE
+| LITERAL END"#;
+        let result = execute_pipeline("test", pipeline);
+        // Should succeed - LITERAL as last just means output is discarded
+        assert!(result.is_ok(), "Expected success but got: {:?}", result);
+    }
+
+    #[test]
+    fn test_console_in_middle() {
+        // CONSOLE in middle passes through (useful for debugging)
+        let pipeline = r#"PIPE CONSOLE
+| CONSOLE
+| CONSOLE"#;
+        let (output, input_count, output_count) = execute_pipeline("test", pipeline).unwrap();
+        assert_eq!(input_count, 1);
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "test");
+    }
+
+    #[test]
+    fn test_hole_as_first() {
+        // HOLE as first generates empty stream
+        // CMS-style: LITERAL without delimiters
+        let pipeline = r#"PIPE HOLE
+| LITERAL No input
+| CONSOLE"#;
+        let (output, input_count, output_count) = execute_pipeline("ignored", pipeline).unwrap();
+        assert_eq!(input_count, 0); // HOLE generates 0 records
+        assert_eq!(output_count, 1); // LITERAL adds 1
+        assert_eq!(output, "No input");
+    }
+
+    #[test]
+    fn test_hole_in_middle() {
+        // HOLE discards upstream, downstream gets nothing (before LITERAL)
+        // CMS-style: LITERAL without delimiters
+        let pipeline = r#"PIPE CONSOLE
+| COUNT
+| HOLE
+| LITERAL Discarded
+| CONSOLE"#;
+        let (output, input_count, output_count) = execute_pipeline("A\nB\nC", pipeline).unwrap();
+        assert_eq!(input_count, 3);
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "Discarded");
+    }
+
+    #[test]
+    fn test_hole_as_last() {
+        // HOLE as last discards all output
+        let pipeline = r#"PIPE CONSOLE
+| HOLE"#;
+        let (output, input_count, output_count) = execute_pipeline("test", pipeline).unwrap();
+        assert_eq!(input_count, 1); // CONSOLE read 1 record
+        assert_eq!(output_count, 0); // HOLE discarded it
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_debug_info_new() {
+        let info = DebugInfo::new("CONSOLE".to_string(), 3, 2);
+        assert_eq!(info.stage_name, "CONSOLE");
+        assert_eq!(info.input_count, 3);
+        assert_eq!(info.output_count, 2);
+        assert!(info.input_records.is_none());
+        assert!(info.output_records.is_none());
+    }
+
+    #[test]
+    fn test_debug_info_with_records() {
+        let input_records = vec![
+            Record::from_str("ABC"),
+            Record::from_str("DEF"),
+            Record::from_str("GHI"),
+        ];
+        let output_records = vec![Record::from_str("ABC"), Record::from_str("DEF")];
+        let info = DebugInfo::with_records(
+            "FILTER".to_string(),
+            3,
+            2,
+            input_records.clone(),
+            output_records.clone(),
+        );
+        assert_eq!(info.stage_name, "FILTER");
+        assert_eq!(info.input_count, 3);
+        assert_eq!(info.output_count, 2);
+        assert!(info.input_records.is_some());
+        assert!(info.output_records.is_some());
+        assert_eq!(info.input_records.unwrap(), input_records);
+        assert_eq!(info.output_records.unwrap(), output_records);
+    }
+
+    #[test]
+    fn test_execute_pipeline_debug_without_callbacks() {
+        let pipeline = r#"PIPE CONSOLE
+| CONSOLE"#;
+        let input = "A\nB\nC";
+        let (output, input_count, output_count, debug_info) =
+            execute_pipeline_debug(input, pipeline, &None).unwrap();
+        assert_eq!(input_count, 3);
+        assert_eq!(output_count, 3);
+        assert_eq!(output, "A\nB\nC");
+        assert_eq!(debug_info.len(), 2);
+        assert_eq!(debug_info[0].stage_name, "CONSOLE");
+        assert_eq!(debug_info[1].stage_name, "CONSOLE");
+        assert!(debug_info[0].input_records.is_none());
+        assert!(debug_info[0].output_records.is_none());
+    }
+
+    #[test]
+    fn test_truncation_warnings_opt_in() {
+        let long = "X".repeat(95);
+        let input = format!("SHORT\n{long}\nOK");
+        let pipeline = "PIPE CONSOLE | CONSOLE";
+
+        let (_, _, _, warnings) =
+            execute_pipeline_with_options(&input, pipeline, &ExecuteOptions::default()).unwrap();
+        assert!(warnings.is_empty());
+
+        let options = ExecuteOptions {
+            warn_truncation: true,
+            ..ExecuteOptions::default()
+        };
+        let (output, _, _, warnings) =
+            execute_pipeline_with_options(&input, pipeline, &options).unwrap();
+        assert_eq!(
+            warnings,
+            vec![TruncationWarning {
+                line_number: 2,
+                original_len: 95,
+            }]
+        );
+        // Default behavior is still silent truncation
+        assert_eq!(output.lines().nth(1).unwrap(), "X".repeat(80));
+    }
+
+    #[test]
+    fn test_truncation_warnings_line_numbers_count_blank_lines() {
+        let input = format!("A\n\n{}", "Y".repeat(81));
+        assert_eq!(
+            truncation_warnings(&input),
+            vec![TruncationWarning {
+                line_number: 3,
+                original_len: 81,
+            }]
+        );
+        assert!(truncation_warnings(&"Z".repeat(80)).is_empty());
+    }
+
+    #[test]
+    fn test_truncation_warnings_follow_input_options() {
+        let half = "H".repeat(50);
+        let input = format!("NAME\n\n{half}\\\n{half}\nA\t{}", "T".repeat(75));
+
+        // Raw lines are all short enough
+        assert!(truncation_warnings(&input).is_empty());
+
+        let options = ExecuteOptions {
+            skip_header: true,
+            continuation: Some('\\'),
+            expand_tabs: Some(8),
+            ..ExecuteOptions::default()
+        };
+        assert_eq!(
+            options.truncation_warnings(&input),
+            vec![
+                // The joined record starts on source line 3
+                TruncationWarning {
+                    line_number: 3,
+                    original_len: 100,
+                },
+                // The tab expands to 7 spaces
+                TruncationWarning {
+                    line_number: 5,
+                    original_len: 83,
+                },
+            ]
+        );
+        // A header that is too long is skipped, not reported
+        let input = format!("{}\nA", "N".repeat(90));
+        assert!(options.truncation_warnings(&input).is_empty());
+        assert_eq!(options.console_records(&input).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_justify() {
+        let cmd = parse_command("JUSTIFY 28,8 RIGHT").unwrap();
+        match cmd {
+            Command::Justify {
+                pos,
+                len,
+                side,
+                fill,
+            } => {
+                assert_eq!((pos, len), (28, 8));
+                assert_eq!(side, JustifySide::Right);
+                assert_eq!(fill, ' ');
+            }
+            _ => panic!("Expected Justify"),
+        }
+        let cmd = parse_command("justify 0,5 left").unwrap();
+        assert!(matches!(
+            cmd,
+            Command::Justify {
+                side: JustifySide::Left,
+                ..
+            }
+        ));
+        assert!(parse_command("JUSTIFY 0,5").is_err());
+        assert!(parse_command("JUSTIFY 0 RIGHT").is_err());
+        assert!(matches!(
+            parse_command("JUSTIFY 0,5 RIGHT fill 0").unwrap(),
+            Command::Justify { fill: '0', .. }
+        ));
+        assert!(parse_command("JUSTIFY 0,5 RIGHT FILL").is_err());
+        assert!(parse_command("JUSTIFY 0,5 RIGHT FILL 00").is_err());
+        assert!(parse_command("JUSTIFY 0,5 RIGHT PAD 0").is_err());
+    }
+
+    #[test]
+    fn test_execute_justify_fill() {
+        let pipeline = "PIPE CONSOLE | JUSTIFY 5,8 RIGHT FILL 0 | CONSOLE";
+        let (output, _, _) = execute_pipeline("ABCDE42      X", pipeline).unwrap();
+        assert_eq!(output, "ABCDE00000042X");
+
+        let pipeline = "PIPE CONSOLE | JUSTIFY 5,8 LEFT FILL . | CONSOLE";
+        let (output, _, _) = execute_pipeline("ABCDE   42   X", pipeline).unwrap();
+        assert_eq!(output, "ABCDE42......X");
+    }
+
+    #[test]
+    fn test_execute_justify() {
+        // Right-justify pads with spaces on the left (not zeros)
+        let pipeline = "PIPE CONSOLE | JUSTIFY 5,8 RIGHT | CONSOLE";
+        let (output, _, _) = execute_pipeline("ABCDE42      X", pipeline).unwrap();
+        assert_eq!(output, "ABCDE      42X");
+
+        // Left-justify trims leading spaces and pads on the right
+        let pipeline = "PIPE CONSOLE | JUSTIFY 5,8 LEFT | CONSOLE";
+        let (output, _, _) = execute_pipeline("ABCDE   42   X", pipeline).unwrap();
+        assert_eq!(output, "ABCDE42      X");
+    }
+
+    #[test]
+    fn test_justify_field_past_record_end() {
+        // Field is clipped to the record width before justifying
+        let pipeline = "PIPE CONSOLE | JUSTIFY 76,10 RIGHT | CONSOLE";
+        let input = format!("{}AB", " ".repeat(76));
+        let (output, _, _) = execute_pipeline(&input, pipeline).unwrap();
+        assert_eq!(output, format!("{}AB", " ".repeat(78)));
+    }
+
+    #[test]
+    fn test_parse_hole() {
+        assert!(matches!(
+            parse_command("HOLE").unwrap(),
+            Command::Hole { count: false }
+        ));
+        assert!(matches!(
+            parse_command("hole count").unwrap(),
+            Command::Hole { count: true }
+        ));
+        assert!(parse_command("HOLE BOGUS").is_err());
+    }
+
+    #[test]
+    fn test_hole_count_reports_dropped() {
+        let pipeline = r#"PIPE CONSOLE
+| LOCATE /BAD/
+| HOLE COUNT
+| CONSOLE"#;
+        let (output, _, output_count) = execute_pipeline("BAD 1\nGOOD\nBAD 2", pipeline).unwrap();
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "DROPPED=2");
+    }
+
+    #[test]
+    fn test_hole_count_empty_input() {
+        let (output, _, _) = execute_pipeline("", "PIPE CONSOLE | HOLE COUNT").unwrap();
+        assert_eq!(output, "DROPPED=0");
+    }
+
+    #[test]
+    fn test_parse_extract() {
+        match parse_command("EXTRACT /user=/").unwrap() {
+            Command::Extract {
+                marker,
+                keep_unmatched,
+            } => {
+                assert_eq!(marker, "user=");
+                assert!(keep_unmatched);
+            }
+            _ => panic!("Expected Extract"),
+        }
+        assert!(matches!(
+            parse_command("EXTRACT /x/ drop").unwrap(),
+            Command::Extract {
+                keep_unmatched: false,
+                ..
+            }
+        ));
+        assert!(parse_command("EXTRACT").is_err());
+        assert!(parse_command("EXTRACT //").is_err());
+        assert!(parse_command("EXTRACT /x/ BOGUS").is_err());
+    }
+
+    #[test]
+    fn test_execute_extract() {
+        let input = "user=alice level=1\nts=9 user=bob\nno marker here";
+        let pipeline = "PIPE CONSOLE | EXTRACT /user=/ | CONSOLE";
+        let (output, _, output_count) = execute_pipeline(input, pipeline).unwrap();
+        // Marker at start, marker in middle, marker absent (empty record)
+        assert_eq!(output_count, 3);
+        assert_eq!(output, "alice level=1\nbob\n");
+    }
+
+    #[test]
+    fn test_execute_extract_drop_unmatched() {
+        let input = "user=alice\nno marker here";
+        let pipeline = "PIPE CONSOLE | EXTRACT /user=/ DROP | CONSOLE";
+        let (output, _, output_count) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output_count, 1);
+        assert_eq!(output, "alice");
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let pipeline = "# LAYOUT Last 0,8; First 8,10\nPIPE CONSOLE\n| CONSOLE\n?";
+        let layout = parse_layout(pipeline).unwrap().unwrap();
+        assert_eq!(
+            layout.fields,
+            vec![("Last".to_string(), 0, 8), ("First".to_string(), 8, 10)]
+        );
+        // The directive is a comment as far as commands are concerned
+        assert_eq!(parse_commands(pipeline).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_layout_absent() {
+        let pipeline = "# LAYOUTS are not directives\nPIPE CONSOLE | CONSOLE";
+        assert_eq!(parse_layout(pipeline).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_layout_errors() {
+        assert!(parse_commands("# LAYOUT Last\nPIPE CONSOLE | CONSOLE").is_err());
+        assert!(parse_commands("# layout Last 0,x\nPIPE CONSOLE | CONSOLE").is_err());
+        assert!(parse_layout("# LAYOUT\nPIPE CONSOLE | CONSOLE").is_err());
+        assert!(parse_layout("# LAYOUT A 0,1\n# LAYOUT B 1,1").is_err());
+    }
+
+    #[test]
+    fn test_parse_length() {
+        assert!(matches!(
+            parse_command("LENGTH 1,40").unwrap(),
+            Command::Length {
+                min: 1,
+                max: Some(40)
+            }
+        ));
+        assert!(matches!(
+            parse_command("LENGTH 10,").unwrap(),
+            Command::Length { min: 10, max: None }
+        ));
+        assert!(matches!(
+            parse_command("LENGTH ,5").unwrap(),
+            Command::Length {
+                min: 0,
+                max: Some(5)
+            }
+        ));
+        assert!(parse_command("LENGTH").is_err());
+        assert!(parse_command("LENGTH ,").is_err());
+        assert!(parse_command("LENGTH 5").is_err());
+        assert!(parse_command("LENGTH 5,4").is_err());
+        assert!(parse_command("LENGTH x,4").is_err());
+    }
+
+    #[test]
+    fn test_execute_length_boundaries() {
+        let input = "abc\nabcd\nabcde\nabcdef";
+        let pipeline = "PIPE CONSOLE | LENGTH 4,5 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "abcd\nabcde");
+
+        let (output, _, _) = execute_pipeline(input, "PIPE CONSOLE | LENGTH 5, | CONSOLE").unwrap();
+        assert_eq!(output, "abcde\nabcdef");
+
+        let (output, _, _) = execute_pipeline(input, "PIPE CONSOLE | LENGTH ,3 | CONSOLE").unwrap();
+        assert_eq!(output, "abc");
+    }
+
+    #[test]
+    fn test_execute_length_measures_stored_record() {
+        // Non-ASCII characters are stored as '?', one column per character
+        let input = "\u{e9}\u{e9}";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | LENGTH 2,2 | CONSOLE").unwrap();
+        assert_eq!(output, "??");
+
+        // Leading spaces count, trailing padding does not
+        let (_, _, output_count) =
+            execute_pipeline("  ab  ", "PIPE CONSOLE | LENGTH 4,4 | CONSOLE").unwrap();
+        assert_eq!(output_count, 1);
+    }
+
+    #[test]
+    fn test_parse_freq() {
+        assert!(matches!(
+            parse_command("FREQ 18,10").unwrap(),
+            Command::Freq { pos: 18, len: 10 }
+        ));
+        assert!(parse_command("FREQ").is_err());
+        assert!(parse_command("FREQ 18").is_err());
+    }
+
+    #[test]
+    fn test_execute_freq_departments() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let (output, input_count, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | FREQ 18,10 | CONSOLE").unwrap();
+        assert_eq!(input_count, 8);
+        assert_eq!(output_count, 3);
+        // ENGINEER and SALES tie at 3, broken alphabetically
+        assert_eq!(output, "ENGINEER 3\nSALES 3\nMARKETING 2");
+    }
+
+    #[test]
+    fn test_parse_crosstab() {
+        assert!(matches!(
+            parse_command("CROSSTAB 18,10 by 0,1").unwrap(),
+            Command::Crosstab {
+                row_field: (18, 10),
+                col_field: (0, 1),
+            }
+        ));
+        assert!(parse_command("CROSSTAB 18,10").is_err());
+        assert!(parse_command("CROSSTAB 18,10 AND 0,1").is_err());
+        assert!(parse_command("CROSSTAB 18,10 BY 0").is_err());
+    }
+
+    #[test]
+    fn test_execute_crosstab_department_by_initial() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let (output, input_count, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | CROSSTAB 18,10 BY 0,1 | CONSOLE").unwrap();
+        assert_eq!(input_count, 8);
+        assert_eq!(output_count, 4);
+        let expected = [
+            "          B C D G J S T W",
+            "ENGINEER  1 1 0 0 1 0 0 0",
+            "MARKETING 0 0 0 0 0 0 1 1",
+            "SALES     0 0 1 1 0 1 0 0",
+        ];
+        assert_eq!(output, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_crosstab_widens_columns_to_fit() {
+        let input = "A XYZ\nA XYZ\nB Q\n";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | CROSSTAB 0,1 BY 2,3 | CONSOLE").unwrap();
+        assert_eq!(output, "    Q XYZ\nA   0   2\nB   1   0");
+        let (output, _, output_count) =
+            execute_pipeline("", "PIPE CONSOLE | CROSSTAB 0,1 BY 2,3 | CONSOLE").unwrap();
+        assert_eq!((output.as_str(), output_count), ("", 0));
+    }
+
+    #[test]
+    fn test_parse_build() {
+        match parse_command("BUILD 0,8 /, / 28,8").unwrap() {
+            Command::Build { items } => assert_eq!(
+                items,
+                vec![
+                    BuildItem::Field { pos: 0, len: 8 },
+                    BuildItem::Literal(", ".to_string()),
+                    BuildItem::Field { pos: 28, len: 8 },
+                ]
+            ),
+            _ => panic!("Expected Build"),
+        }
+        assert!(parse_command("BUILD").is_err());
+        assert!(parse_command("BUILD 0,8 /,").is_err());
+        assert!(parse_command("BUILD 0,x").is_err());
+    }
+
+    #[test]
+    fn test_execute_build_csv() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE | TAKE 2 | BUILD 0,8 /,/ 28,8 /,/ 18,10 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "SMITH,00050000,SALES\nJONES,00075000,ENGINEER");
+    }
+
+    #[test]
+    fn test_execute_build_literals_only() {
+        let (output, _, _) =
+            execute_pipeline("x", "PIPE CONSOLE | BUILD /a/ \"b\" | CONSOLE").unwrap();
+        assert_eq!(output, "ab");
+    }
+
+    #[test]
+    fn test_validate_pipeline_text_summaries() {
+        let stages = validate_pipeline_text(
+            "PIPE CONSOLE\n| FILTER 18,10 = \"SALES\"\n| LENGTH 1,\n| HOLE COUNT\n?",
+        )
+        .unwrap();
+        let summary: Vec<(&str, &str)> = stages
+            .iter()
+            .map(|s| (s.name, s.description.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("CONSOLE", "Read input or write output"),
+                ("FILTER", "Keep records where columns 19–28 equal 'SALES'"),
+                ("LENGTH", "Keep records at least 1 characters long"),
+                (
+                    "HOLE",
+                    "Discard all records, emitting how many were dropped"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_pipeline_text_errors() {
+        let err = validate_pipeline_text("PIPE FILTER 0,1 = \"A\" | CONSOLE").unwrap_err();
+        assert!(
+            matches!(&err, PipelineError::Parse(msg) if msg.contains("cannot be the first stage"))
+        );
+        assert!(matches!(
+            validate_pipeline_text("PIPE CONSOLE | BOGUS"),
+            Err(PipelineError::Parse(_))
+        ));
+        assert!(validate_pipeline_text("").is_err());
+        assert!(validate_pipeline_text("PIPE CONSOLE").is_err());
+    }
+
+    #[test]
+    fn test_execute_zip_pairs_records() {
+        let input = "LEFT\nRIGHT\nL2\nR2";
+        let (output, _, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | ZIP | CONSOLE").unwrap();
+        assert_eq!(output_count, 2);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], format!("{:<40}RIGHT", "LEFT"));
+        assert_eq!(lines[1], format!("{:<40}R2", "L2"));
+    }
+
+    #[test]
+    fn test_execute_zip_odd_count() {
+        let input = "A\nB\nC";
+        let (output, _, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | ZIP | CONSOLE").unwrap();
+        assert_eq!(output_count, 2);
+        // The lone trailing record stays in columns 0-39
+        assert_eq!(output.lines().last(), Some("C"));
+    }
+
+    #[test]
+    fn test_zip_pair_truncates_each_half() {
+        let long = Record::from_str(&"x".repeat(60));
+        let zipped = zip_pair(&long, Some(&Record::from_str("y")));
+        assert_eq!(zipped.field(0, 40), "x".repeat(40));
+        assert_eq!(zipped.field(40, 40).trim_end(), "y");
+    }
+
+    #[test]
+    fn test_select_overlap_last_writer_wins() {
+        // Second field overwrites columns 2-5 written by the first
+        let pipeline = "PIPE CONSOLE | SELECT 0,6,0; 6,4,2 | CONSOLE";
+        let (output, _, _) = execute_pipeline("AAAAAABBBB", pipeline).unwrap();
+        assert_eq!(output, "AABBBB");
+
+        // Reordering the specs reverses the outcome
+        let pipeline = "PIPE CONSOLE | SELECT 6,4,2; 0,6,0 | CONSOLE";
+        let (output, _, _) = execute_pipeline("AAAAAABBBB", pipeline).unwrap();
+        assert_eq!(output, "AAAAAA");
+    }
+
+    #[test]
+    fn test_select_strict_rejects_overlap() {
+        let err = parse_command("SELECT 0,6,0; 6,4,2 STRICT").unwrap_err();
+        assert!(err.contains("overlaps"), "unexpected error: {err}");
+
+        // Adjacent destinations do not overlap
+        match parse_command("SELECT 0,6,0; 6,4,6 strict").unwrap() {
+            Command::Select { fields, .. } => assert_eq!(fields, vec![(0, 6, 0), (6, 4, 6)]),
+            _ => panic!("Expected Select"),
+        }
+    }
+
+    #[test]
+    fn test_parse_page() {
+        assert!(matches!(
+            parse_command("PAGE 2,3").unwrap(),
+            Command::Page { page: 2, size: 3 }
+        ));
+        assert!(parse_command("PAGE 0,3").is_err());
+        assert!(parse_command("PAGE 1,0").is_err());
+        assert!(parse_command("PAGE 2").is_err());
+    }
+
+    #[test]
+    fn test_execute_page() {
+        let input = "1\n2\n3\n4\n5\n6\n7\n8";
+        let run = |page: &str| {
+            execute_pipeline(input, &format!("PIPE CONSOLE | PAGE {page} | CONSOLE"))
+                .unwrap()
+                .0
+        };
+        assert_eq!(run("2,3"), "4\n5\n6");
+        // Final short page, and a page beyond the data
+        assert_eq!(run("3,3"), "7\n8");
+        assert_eq!(run("4,3"), "");
+    }
+
+    #[test]
+    fn test_parse_reverse() {
+        assert!(matches!(
+            parse_command("REVERSE").unwrap(),
+            Command::Reverse { full: false }
+        ));
+        assert!(matches!(
+            parse_command("reverse full").unwrap(),
+            Command::Reverse { full: true }
+        ));
+        assert!(parse_command("REVERSE HALF").is_err());
+    }
+
+    #[test]
+    fn test_reverse_full_keeps_padding() {
+        let (output, _, _) =
+            execute_pipeline("  AB", "PIPE CONSOLE | REVERSE FULL | CONSOLE").unwrap();
+        // 76 spaces of padding move to the front; the two leading spaces trail
+        assert_eq!(output, format!("{}BA", " ".repeat(76)));
+    }
+
+    #[test]
+    fn test_reverse_full_twice_is_identity() {
+        let input = vec![
+            Record::from_str("  padded  record"),
+            Record::from_str(&"x".repeat(80)),
+        ];
+        let twice = [
+            Command::Reverse { full: true },
+            Command::Reverse { full: true },
+        ];
+        let output = apply_commands(input.clone(), &twice, &ExecuteOptions::default()).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_parse_reseq() {
+        assert!(matches!(
+            parse_command("RESEQ 0,4 10,10").unwrap(),
+            Command::Reseq {
+                pos: 0,
+                len: 4,
+                start: 10,
+                step: 10,
+                fill: '0'
+            }
+        ));
+        assert!(matches!(
+            parse_command("RESEQ 0,4 1,1 FILL *").unwrap(),
+            Command::Reseq { fill: '*', .. }
+        ));
+        assert!(parse_command("RESEQ 0,4 10,0").is_err());
+        assert!(parse_command("RESEQ 0,4").is_err());
+        assert!(parse_command("RESEQ 0,4 1,1 X").is_err());
+    }
+
+    #[test]
+    fn test_execute_reseq() {
+        let input = "0000 A\n0000 B\n0000 C";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | RESEQ 0,4 100,5 | CONSOLE").unwrap();
+        assert_eq!(output, "0100 A\n0105 B\n0110 C");
+    }
+
+    #[test]
+    fn test_execute_reseq_overflow_keeps_low_digits() {
+        let input = "00 A\n00 B";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | RESEQ 0,2 99,1 | CONSOLE").unwrap();
+        assert_eq!(output, "99 A\n00 B");
+    }
+
+    #[test]
+    fn test_parse_topn() {
+        assert!(matches!(
+            parse_command("TOPN 5 28,8").unwrap(),
+            Command::TopN {
+                n: 5,
+                pos: 28,
+                len: 8
+            }
+        ));
+        assert!(parse_command("TOPN 0 28,8").is_err());
+        assert!(parse_command("TOPN 5").is_err());
+        assert!(parse_command("TOPN x 28,8").is_err());
+    }
+
+    #[test]
+    fn test_execute_topn_salaries() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE | TOPN 3 28,8 | SELECT 0,8,0 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "BROWN\nCHEN\nJONES");
+    }
+
+    #[test]
+    fn test_execute_topn_more_than_input() {
+        let input = "5\nx\n9\n1";
+        let (output, _, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | TOPN 10 0,3 | CONSOLE").unwrap();
+        // All records, largest first; the non-numeric record sorts last
+        assert_eq!(output_count, 4);
+        assert_eq!(output, "9\n5\n1\nx");
+    }
+
+    #[test]
+    fn test_parse_select_sep() {
+        match parse_command("SELECT 0,8,0; 28,8,9 SEP /,/ STRICT").unwrap() {
+            Command::Select { fields, separator } => {
+                assert_eq!(fields, vec![(0, 8, 0), (28, 8, 9)]);
+                assert_eq!(separator.as_deref(), Some(","));
+            }
+            _ => panic!("Expected Select"),
+        }
+        assert!(parse_command("SELECT 0,8,0 SEP //").is_err());
+        assert!(parse_command("SELECT 0,8,0 SEP /,").is_err());
+        assert!(parse_command("SELECT 0,8,0 SEP /,/ extra").is_err());
+    }
+
+    #[test]
+    fn test_execute_select_sep_comma_delimited() {
+        let input = "SMITH   JOHN      SALES     00050000";
+        let pipeline = "PIPE CONSOLE | SELECT 28,8,9; 0,8,0 SEP /,/ | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "SMITH   ,00050000");
+    }
+
+    #[test]
+    fn test_select_separated_shifts_to_avoid_overlap() {
+        let record = Record::from_str("AAAABBBB");
+        // Second destination 4 would collide with the separator; it moves to 6
+        let out = select_separated(&record, &[(0, 4, 0), (4, 4, 4)], "--");
+        assert_eq!(out.as_str().trim_end(), "AAAA--BBBB");
+    }
+
+    #[test]
+    fn test_parse_block() {
+        assert!(matches!(
+            parse_command("BLOCK 3").unwrap(),
+            Command::Block { n: 3 }
+        ));
+        assert!(parse_command("BLOCK 0").is_err());
+        assert!(parse_command("BLOCK").is_err());
+    }
+
+    #[test]
+    fn test_execute_block_separators() {
+        let input = "1\n2\n3\n4\n5\n6\n7";
+        let (output, _, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | BLOCK 3 | CONSOLE").unwrap();
+        assert_eq!(output_count, 9);
+        assert_eq!(output, "1\n2\n3\n\n4\n5\n6\n\n7");
+    }
+
+    #[test]
+    fn test_execute_sortitems() {
+        let input = "DDDDBBBBCCCC XY\nAAAACCCCBBBB";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | SORTITEMS 0,12,4 | CONSOLE").unwrap();
+        assert_eq!(output, "BBBBCCCCDDDD XY\nAAAABBBBCCCC");
+        match parse_command("sortitems 18,10,3").unwrap() {
+            Command::SortItems {
+                pos,
+                len,
+                item_width,
+            } => assert_eq!((pos, len, item_width), (18, 10, 3)),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(parse_command("SORTITEMS 0,12,0").is_err());
+        assert!(parse_command("SORTITEMS 0,12").is_err());
+    }
+
+    #[test]
+    fn test_sort_items_keeps_remainder_in_place() {
+        // 14 columns hold three 4-character items and a 2-column remainder
+        let record = Record::from_str("CC33BB22AA11zz");
+        let sorted = sort_items(&record, 0, 14, 4);
+        assert_eq!(sorted.field(0, 14), "AA11BB22CC33zz");
+        // Columns outside the field are untouched; a field past column 80 is clipped
+        let record = Record::from_str(&format!("{:>76}ZZYY", "x"));
+        assert_eq!(sort_items(&record, 76, 10, 2).field(74, 6), " xYYZZ");
+        assert_eq!(sort_items(&record, 76, 10, 2).field(0, 75).trim(), "");
+    }
+
+    #[test]
+    fn test_execute_segment_headers() {
+        let input = "1\n2\n3\n4\n5\n6\n7";
+        let (output, _, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | SEGMENT 3 | CONSOLE").unwrap();
+        assert_eq!(output_count, 10);
+        assert_eq!(
+            output,
+            "--- SEGMENT 1 ---\n1\n2\n3\n--- SEGMENT 2 ---\n4\n5\n6\n--- SEGMENT 3 ---\n7"
+        );
+        let (output, _, _) = execute_pipeline("", "PIPE CONSOLE | SEGMENT 3 | CONSOLE").unwrap();
+        assert_eq!(output, "");
+        assert!(parse_command("SEGMENT 0").is_err());
+        assert!(parse_command("SEGMENT").is_err());
+    }
+
+    #[test]
+    fn test_parse_assert() {
+        assert!(matches!(
+            parse_command("ASSERT 28,8 numeric").unwrap(),
+            Command::Assert {
+                pos: 28,
+                len: 8,
+                kind: FieldKind::Numeric,
+                strict: false
+            }
+        ));
+        assert!(matches!(
+            parse_command("ASSERT 0,8 ALPHA STRICT").unwrap(),
+            Command::Assert {
+                kind: FieldKind::Alpha,
+                strict: true,
+                ..
+            }
+        ));
+        assert!(parse_command("ASSERT 0,8").is_err());
+        assert!(parse_command("ASSERT 0,8 DATE").is_err());
+        assert!(matches!(
+            parse_command("ASSERT 0,8 alnum").unwrap(),
+            Command::Assert {
+                kind: FieldKind::Alnum,
+                ..
+            }
+        ));
+        assert!(parse_command("ASSERT 0,8 ALPHA LOOSE").is_err());
+    }
+
+    #[test]
+    fn test_field_kind_alnum_and_blank() {
+        let record = Record::from_str("AB12 C   X9?");
+        assert!(FieldKind::Alnum.check(&record, 0, 6));
+        assert!(!FieldKind::Alnum.check(&record, 9, 3));
+        assert!(!FieldKind::Alnum.check(&record, 6, 3));
+        assert!(FieldKind::Blank.check(&record, 6, 3));
+        assert!(FieldKind::Blank.check(&record, 70, 20));
+        assert!(!FieldKind::Blank.check(&record, 5, 2));
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        match parse_command("verify 18,10 alpha; 28,8 NUMERIC;36,4 blank").unwrap() {
+            Command::Verify { checks } => assert_eq!(
+                checks,
+                [
+                    (18, 10, FieldKind::Alpha),
+                    (28, 8, FieldKind::Numeric),
+                    (36, 4, FieldKind::Blank),
+                ]
+            ),
+            other => panic!("Expected Verify, got {other:?}"),
+        }
+        assert!(parse_command("VERIFY").is_err());
+        assert!(parse_command("VERIFY 0,4").is_err());
+        assert!(parse_command("VERIFY 0,4 DATE").is_err());
+        assert!(parse_command("VERIFY 0,4 ALPHA 4,4 NUMERIC").is_err());
+        assert!(parse_command("VERIFY 0,4 ALPHA;").is_err());
+    }
+
+    #[test]
+    fn test_execute_verify_diagnoses_bad_salary() {
+        let input = "SMITH   JOHN      SALES     00050000\n\
+                     JONES   MARY      ENGINEER  000X5000\n\
+                     DOE     JANE      SALES     00060000";
+        let (output, input_count, output_count) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | VERIFY 18,10 ALPHA; 28,8 NUMERIC | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!((input_count, output_count), (3, 3));
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "SMITH   JOHN      SALES     00050000");
+        assert_eq!(
+            lines[1],
+            "*ERR* 28,8 NUMERIC JONES   MARY      ENGINEER  000X5000"
+        );
+        assert_eq!(lines[2], "DOE     JANE      SALES     00060000");
+
+        // Each failed check gets its own diagnostic
+        let record = Record::from_str("12345678  ");
+        let diagnostics = verify_record(
+            &record,
+            &[(0, 8, FieldKind::Alpha), (8, 2, FieldKind::Numeric)],
+        );
+        let texts: Vec<&str> = diagnostics.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(
+            texts,
+            ["*ERR* 0,8 ALPHA 12345678", "*ERR* 8,2 NUMERIC 12345678"]
+        );
+    }
+
+    #[test]
+    fn test_execute_assert_flags_and_drops() {
+        let input = "A 00100\nB 00X00\nC 00300\nD      ";
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | ASSERT 2,5 NUMERIC | CONSOLE").unwrap();
+        assert_eq!(output, "A 00100\n*ERR* B 00X00\nC 00300\n*ERR* D");
+
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | ASSERT 2,5 NUMERIC STRICT | CONSOLE").unwrap();
+        assert_eq!(output, "A 00100\nC 00300");
+    }
+
+    #[test]
+    fn test_parse_disabled_stage_keeps_position() {
+        let text = "PIPE CONSOLE\n#! | LOCATE /A|B/\n| UPPER\n| #! TAKE 2\n# comment\n| CONSOLE\n?";
+        let commands = parse_commands(text).unwrap();
+        let names: Vec<&str> = commands.iter().map(Command::name).collect();
+        assert_eq!(
+            names,
+            vec!["CONSOLE", "DISABLED", "UPPER", "DISABLED", "CONSOLE"]
+        );
+        assert!(matches!(
+            &commands[1],
+            Command::Disabled { original } if original == "LOCATE /A|B/"
+        ));
+        assert!(matches!(
+            &commands[3],
+            Command::Disabled { original } if original == "TAKE 2"
+        ));
+        // A bare "#!" is just a comment
+        assert_eq!(
+            parse_commands("PIPE CONSOLE\n#!\n| CONSOLE").unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_execute_disabled_stage_passes_through() {
+        let pipeline = "PIPE CONSOLE\n#! | TAKE 1\n| CONSOLE\n?";
+        let (output, _, _) = execute_pipeline("a\nb", pipeline).unwrap();
+        assert_eq!(output, "a\nb");
+    }
+
+    #[test]
+    fn test_parse_chop() {
+        assert!(matches!(
+            parse_command("CHOP 30").unwrap(),
+            Command::Chop { width: 30 }
+        ));
+        assert!(parse_command("CHOP 0").is_err());
+        assert!(parse_command("CHOP 81").is_err());
+        assert!(parse_command("CHOP").is_err());
+    }
+
+    #[test]
+    fn test_chop_record_final_short_chunk() {
+        let text: String = ('A'..='Z').cycle().take(RECORD_WIDTH).collect();
+        let pieces = chop_record(&Record::from_str(&text), 30);
+        let lens: Vec<usize> = pieces.iter().map(|r| r.as_str().trim_end().len()).collect();
+        assert_eq!(lens, vec![30, 30, 20]);
+        assert_eq!(pieces[1].as_str().trim_end(), &text[30..60]);
+        assert_eq!(chop_record(&Record::new(), 30), vec![Record::new()]);
+    }
+
+    #[test]
+    fn test_execute_chop() {
+        let (output, _, output_count) =
+            execute_pipeline("ABCDEFG\n\nXY", "PIPE CONSOLE | CHOP 3 | CONSOLE").unwrap();
+        assert_eq!(output_count, 4);
+        assert_eq!(output, "ABC\nDEF\nG\nXY");
+    }
+
+    #[test]
+    fn test_execute_header_count() {
+        let pipeline = "PIPE CONSOLE | LOCATE /A/ | HEADER Report: {COUNT} records | CONSOLE";
+        let (output, _, output_count) = execute_pipeline("A1\nB2\nA3", pipeline).unwrap();
+        assert_eq!(output_count, 3);
+        assert_eq!(output, "Report: 2 records\nA1\nA3");
+
+        // With no input the header still appears
+        let (output, _, _) = execute_pipeline("B", pipeline).unwrap();
+        assert_eq!(output, "Report: 0 records");
+    }
+
+    #[test]
+    fn test_expand_header_date() {
+        let text = expand_header("{DATE} {COUNT} {OTHER}", 7);
+        let (date, rest) = text.split_once(' ').unwrap();
+        assert_eq!(rest, "7 {OTHER}");
+        assert_eq!(date.len(), 10);
+        assert_eq!(&date[4..5], "-");
+        assert_eq!(&date[7..8], "-");
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_722), (2023, 12, 31));
+    }
+
+    #[test]
+    fn test_fail_on_empty_output() {
+        let pipeline = "PIPE CONSOLE | LOCATE /ZZZ/ | CONSOLE";
+        let options = ExecuteOptions {
+            fail_on_empty: true,
+            ..ExecuteOptions::default()
+        };
+
+        // Off by default: empty output is fine
+        let (output, _, output_count) = execute_pipeline("A\nB", pipeline).unwrap();
+        assert_eq!((output.as_str(), output_count), ("", 0));
+
+        let err = execute_pipeline_with_options("A\nB", pipeline, &options).unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::EmptyOutput { input_count: 2 }.to_string()
+        );
+
+        // Empty input is not an error, nor is non-empty output
+        assert!(execute_pipeline_with_options("", pipeline, &options).is_ok());
+        assert!(execute_pipeline_with_options("ZZZ", pipeline, &options).is_ok());
+    }
+
+    #[test]
+    fn test_max_output_records_boundary() {
+        let pipeline = "PIPE CONSOLE | DUPLICATE 5 | CONSOLE";
+        let limited = |limit| ExecuteOptions {
+            max_output_records: Some(limit),
+            ..ExecuteOptions::default()
+        };
+
+        // 2 records x 5 = 10 output records: exactly at the limit is fine
+        let (_, _, output_count, _) =
+            execute_pipeline_with_options("A\nB", pipeline, &limited(10)).unwrap();
+        assert_eq!(output_count, 10);
+
+        let err = execute_pipeline_with_options("A\nB", pipeline, &limited(9)).unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 9 }.to_string()
+        );
+        assert!(execute_pipeline("A\nB", pipeline).is_ok());
+
+        // A runaway stage fails at its own boundary, before later stages run
+        let err = execute_pipeline_with_options(
+            "A\nB",
+            "PIPE CONSOLE | DUPLICATE 5 | TAKE 1 | CONSOLE",
+            &limited(9),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 9 }.to_string()
+        );
+
+        // Copies stop at the limit instead of all 10 billion being made
+        let err = execute_pipeline_with_options(
+            "A",
+            "PIPE CONSOLE | DUPLICATE 100000 | DUPLICATE 100000 | CONSOLE",
+            &limited(10),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 10 }.to_string()
+        );
+        let err = execute_pipeline_with_options(
+            "A 999999",
+            "PIPE CONSOLE | EXPAND 2,6 | EXPAND 2,6 | CONSOLE",
+            &limited(10),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::OutputLimitExceeded { limit: 10 }.to_string()
+        );
+    }
+
+    #[test]
+    fn test_upper_lower_keep_all_80_columns() {
+        let mut record = Record::from_str("Smith");
+        record.set_field(79, 1, "x");
+        let upper = apply_command(vec![record.clone()], &Command::Upper, usize::MAX).unwrap();
+        assert_eq!(upper[0].as_bytes().len(), RECORD_WIDTH);
+        assert_eq!(upper[0].field(0, 5), "SMITH");
+        assert_eq!(upper[0].field(5, 74), " ".repeat(74));
+        assert_eq!(upper[0].field(79, 1), "X");
+        let lower = apply_command(upper, &Command::Lower, usize::MAX).unwrap();
+        assert_eq!(
+            lower[0].field(0, 80),
+            record.map_chars(|c| c.to_ascii_lowercase()).field(0, 80)
+        );
+        assert_eq!(lower[0].field(79, 1), "x");
+    }
+
+    #[test]
+    fn test_expansion_factor_limit() {
+        let limited = ExecuteOptions {
+            expansion_factor_limit: Some(10),
+            ..ExecuteOptions::default()
+        };
+        let pipeline = "PIPE CONSOLE | UPPER | DUPLICATE 1000 | CONSOLE";
+        let err = execute_pipeline_with_options("A\nB", pipeline, &limited).unwrap_err();
+        let expected = PipelineError::ExpansionLimitExceeded {
+            stage_index: 1,
+            stage: "DUPLICATE".to_string(),
+            limit: 10,
+        };
+        assert_eq!(err, expected.to_string());
+        assert!(execute_pipeline("A\nB", pipeline).is_ok());
+
+        // Exactly the limit, and summaries of empty input, are fine
+        for pipeline in [
+            "PIPE CONSOLE | DUPLICATE 10 | CONSOLE",
+            "PIPE CONSOLE | COUNT | LITERAL X | CONSOLE",
+        ] {
+            assert!(
+                execute_pipeline_with_options("", pipeline, &limited).is_ok(),
+                "{pipeline}"
+            );
+            assert!(
+                execute_pipeline_with_options("A\nB", pipeline, &limited).is_ok(),
+                "{pipeline}"
+            );
+        }
+        assert!(check_expansion(0, "X", 0, 11, 10).is_err());
+        assert!(check_expansion(0, "X", 3, 31, 10).is_err());
+        assert!(check_expansion(0, "X", 3, 30, 10).is_ok());
+    }
+
+    #[test]
+    fn test_skip_header_option() {
+        let input = "LAST    FIRST     DEPT      SALARY\nSMITH   JOHN      SALES     00050000\nDOE     JANE      SALES     00060000";
+        let pipeline = "PIPE CONSOLE | SELECT 0,8,0 | CONSOLE";
+        let options = ExecuteOptions {
+            skip_header: true,
+            ..ExecuteOptions::default()
+        };
+
+        let (output, input_count, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "LAST\nSMITH\nDOE");
+        assert_eq!(input_count, 3);
+
+        // The header is neither processed nor counted
+        let (output, input_count, output_count, _) =
+            execute_pipeline_with_options(input, pipeline, &options).unwrap();
+        assert_eq!(output, "SMITH\nDOE");
+        assert_eq!((input_count, output_count), (2, 2));
+        assert_eq!(
+            split_header(input).0,
+            Some("LAST    FIRST     DEPT      SALARY")
+        );
+    }
+
+    #[test]
+    fn test_continuation_option_joins_lines() {
+        let options = ExecuteOptions {
+            continuation: Some('\\'),
+            ..ExecuteOptions::default()
+        };
+        let input = "SMITH   JOHN      \\\nSALES     00050000\nDOE\nA\\\nB\\\r\nC\nEND\\";
+        let pipeline = "PIPE CONSOLE | CONSOLE";
+        let (output, input_count, _, _) =
+            execute_pipeline_with_options(input, pipeline, &options).unwrap();
+        assert_eq!(
+            output,
+            "SMITH   JOHN      SALES     00050000\nDOE\nABC\nEND\\"
+        );
+        assert_eq!(input_count, 4);
+
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_join_continuations_edge_cases() {
+        assert_eq!(join_continuations("", '\\'), "");
+        assert_eq!(join_continuations("\\\n\\\nA", '\\'), "A\n");
+        // A continued line followed by an empty line ends there
+        assert_eq!(join_continuations("A+\n\nB", '+'), "A\nB\n");
+        assert_eq!(join_continuations("A+", '+'), "A+\n");
+        // Only a trailing marker continues a line
+        assert_eq!(join_continuations("A+B\nC", '+'), "A+B\nC\n");
+    }
+
+    #[test]
+    fn test_split_header_edge_cases() {
+        assert_eq!(split_header(""), (None, ""));
+        assert_eq!(split_header("\n\r\n"), (None, ""));
+        assert_eq!(split_header("\nHDR\r\nA"), (Some("HDR"), "A"));
+        assert_eq!(split_header("HDR"), (Some("HDR"), ""));
+        // A header-only input leaves no records
+        let options = ExecuteOptions {
+            skip_header: true,
+            ..ExecuteOptions::default()
+        };
+        assert!(options.console_records("HDR\n").is_empty());
+    }
+
+    #[test]
+    fn test_require_explicit_sink() {
+        let pipeline = "PIPE CONSOLE | FILTER 0,1 = \"A\"";
+        let options = ExecuteOptions {
+            require_explicit_sink: true,
+            ..ExecuteOptions::default()
+        };
+
+        // Off by default: any stage can be last
+        let (output, _, _) = execute_pipeline("A\nB", pipeline).unwrap();
+        assert_eq!(output, "A");
+
+        let err = execute_pipeline_with_options("A\nB", pipeline, &options).unwrap_err();
+        assert_eq!(
+            err,
+            PipelineError::NoSink {
+                last: "FILTER".to_string()
+            }
+            .to_string()
+        );
+
+        for pipeline in [
+            "PIPE CONSOLE | FILTER 0,1 = \"A\" | CONSOLE",
+            "PIPE CONSOLE | HOLE",
+        ] {
+            assert!(execute_pipeline_with_options("A\nB", pipeline, &options).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_strict_bounds() {
+        let options = ExecuteOptions {
+            strict_bounds: true,
+            ..ExecuteOptions::default()
+        };
+        let run = |pipeline: &str| execute_pipeline_with_options("A\nB", pipeline, &options);
+
+        // Off by default: the destination is clamped away
+        let (output, _, _) =
+            execute_pipeline("A\nB", "PIPE CONSOLE | SELECT 0,8,200 | CONSOLE").unwrap();
+        assert_eq!(output, "\n");
+
+        for (pipeline, stage, pos, len) in [
+            ("PIPE CONSOLE | SELECT 0,8,200 | CONSOLE", "SELECT", 200, 8),
+            (
+                "PIPE CONSOLE | FILTER 75,10 = \"A\" | CONSOLE",
+                "FILTER",
+                75,
+                10,
+            ),
+            ("PIPE CONSOLE | LOCATE 79,2 /A/ | CONSOLE", "LOCATE", 79, 2),
+            ("PIPE CONSOLE | SORT 0,8; 80,1 | CONSOLE", "SORT", 80, 1),
+            (
+                "PIPE CONSOLE | OMIT 0,1 = FIELD 90,1 | CONSOLE",
+                "OMIT",
+                90,
+                1,
+            ),
+        ] {
+            let err = run(pipeline).unwrap_err();
+            let stage = stage.to_string();
+            assert_eq!(
+                err,
+                PipelineError::FieldOutOfBounds { stage, pos, len }.to_string(),
+                "{pipeline}"
+            );
+        }
+        for pipeline in [
+            "PIPE CONSOLE | SELECT 0,8,72 | CONSOLE",
+            "PIPE CONSOLE | FILTER -8,8 = \"A\" | CONSOLE",
+            "PIPE CONSOLE | SELECT 0,1,200; 2,1,5 SEP /,/ | CONSOLE",
+        ] {
+            assert!(run(pipeline).is_ok(), "{pipeline}");
+        }
+    }
+
+    #[test]
+    fn test_parse_position_negative() {
+        assert_eq!(parse_position("12"), Ok(12));
+        assert_eq!(parse_position("-8"), Ok(72));
+        assert_eq!(parse_position("-80"), Ok(0));
+        // Magnitude beyond the record width clamps to column 0
+        assert_eq!(parse_position("-100"), Ok(0));
+        assert!(parse_position("-x").is_err());
+    }
+
+    #[test]
+    fn test_negative_positions_in_field_specs() {
+        match parse_command("SELECT -8,8,0").unwrap() {
+            Command::Select { fields, .. } => assert_eq!(fields, vec![(72, 8, 0)]),
+            _ => panic!("Expected Select"),
+        }
+        assert!(matches!(
+            parse_command("FILTER -8,8 = \"X\"").unwrap(),
+            Command::FilterEq {
+                pos: 72,
+                len: 8,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_command("LOCATE -8,8 /9/").unwrap(),
+            Command::Locate {
+                field: Some((72, 8)),
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_command("NLOCATE -8,8 /9/").unwrap(),
+            Command::Nlocate {
+                field: Some((72, 8)),
+                ..
+            }
+        ));
+        // Without a comma, '-' is still a pattern delimiter
+        assert!(matches!(
+            parse_command("LOCATE -1-").unwrap(),
+            Command::Locate { ref pattern, field: None, trim: false } if pattern == "1"
+        ));
+    }
+
+    #[test]
+    fn test_execute_trailing_salary_with_negative_position() {
+        // Right-align a salary into the last 8 columns, then read it back with -8,8
+        let input = "SMITH   JOHN      SALES     00050000";
+        let pipeline = "PIPE CONSOLE | SELECT 0,8,0; 28,8,-8 | SELECT -8,8,0 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "00050000");
+    }
+
+    #[test]
+    fn test_parse_apply() {
+        match parse_command("APPLY TitleCase").unwrap() {
+            Command::Apply { name, transform } => {
+                assert_eq!(name, "titlecase");
+                assert_eq!(transform("aB cD"), "Ab Cd");
+            }
+            _ => panic!("Expected Apply"),
+        }
+        let err = parse_command("APPLY nope").unwrap_err();
+        assert!(err.contains("Unknown transform 'nope'"));
+        assert!(parse_command("APPLY").is_err());
+    }
+
+    #[test]
+    fn test_execute_apply_builtins() {
+        let input = "  smith   JOHN      sales";
+        let pipeline = "PIPE CONSOLE | APPLY trim | APPLY squeeze | APPLY titlecase | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "Smith John Sales");
+
+        let (output, _, _) =
+            execute_pipeline("Abc", "PIPE CONSOLE | APPLY rot13 | CONSOLE").unwrap();
+        assert_eq!(output, "Nop");
+    }
+
+    #[test]
+    fn test_execute_apply_custom_transform() {
+        let pipeline = "PIPE CONSOLE | APPLY initials | CONSOLE";
+        // Unknown to the default registry
+        assert!(execute_pipeline("ab cd", pipeline).is_err());
+
+        let options = ExecuteOptions {
+            transforms: TransformRegistry::default().with("initials", |s| {
+                s.split_whitespace()
+                    .filter_map(|w| w.chars().next())
+                    .collect()
+            }),
+            ..ExecuteOptions::default()
+        };
+        let (output, _, _, _) = execute_pipeline_with_options("ab cd", pipeline, &options).unwrap();
+        assert_eq!(output, "ac");
+    }
+
+    #[test]
+    fn test_expand_tabs_stops_of_8() {
+        assert_eq!(expand_tabs("A\tB", 8), "A       B");
+        assert_eq!(expand_tabs("ABCDEFGH\tI", 8), "ABCDEFGH        I");
+        assert_eq!(expand_tabs("\tX\tY", 8), "        X       Y");
+        assert_eq!(expand_tabs("A\tB", 0), "AB");
+    }
+
+    #[test]
+    fn test_input_lines_endings() {
+        for input in ["A\nB\nC", "A\r\nB\r\nC\r\n", "A\rB\rC\r", "A\nB\r\nC\r"] {
+            assert_eq!(input_lines(input), vec!["A", "B", "C"], "{input:?}");
+        }
+        assert!(input_lines("").is_empty());
+        assert_eq!(input_lines("\n"), vec![""]);
+        // "\n\r" is two terminators, with an empty line between them
+        assert_eq!(input_lines("A\n\rB"), vec!["A", "", "B"]);
+    }
+
+    #[test]
+    fn test_read_input_records_lone_cr() {
+        let records = read_input_records("SMITH\rJONES\r\rDOE", None);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].as_str().trim_end(), "DOE");
+        // Line numbers in warnings count lone-CR lines too
+        let input = format!("A\r{}", "Y".repeat(81));
+        assert_eq!(truncation_warnings(&input)[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_read_input_records_tabs() {
+        let input = "SMITH\tSALES\nDOE\tENGINEER";
+        let expanded = read_input_records(input, Some(8));
+        // Both departments line up at column 8 after expansion
+        assert_eq!(expanded[0].field(8, 8).trim(), "SALES");
+        assert_eq!(expanded[1].field(8, 8).trim(), "ENGINEER");
+
+        // Without expansion the tab byte is kept as-is
+        let raw = read_input_records(input, None);
+        assert_eq!(raw[0].as_str().trim_end(), "SMITH\tSALES");
+        assert_eq!(raw[1].field(3, 1), "\t");
+    }
+
+    #[test]
+    fn test_execute_with_expand_tabs() {
+        let options = ExecuteOptions {
+            expand_tabs: Some(8),
+            ..ExecuteOptions::default()
+        };
+        let pipeline = "PIPE CONSOLE | FILTER 8,8 = \"SALES\" | CONSOLE";
+        let input = "SMITH\tSALES\nDOE\tENGINEER";
+        let (output, _, _, _) = execute_pipeline_with_options(input, pipeline, &options).unwrap();
+        assert_eq!(output, "SMITH   SALES");
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_parse_field() {
+        assert!(matches!(
+            parse_command("FIELD 18,10").unwrap(),
+            Command::Field {
+                pos: 18,
+                len: 10,
+                trim: true
+            }
+        ));
+        assert!(matches!(
+            parse_command("FIELD 18,10 pad").unwrap(),
+            Command::Field { trim: false, .. }
+        ));
+        assert!(parse_command("FIELD 18").is_err());
+        assert!(parse_command("FIELD 18,10 TRIM").is_err());
+    }
+
+    #[test]
+    fn test_execute_field_department() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | TAKE 2 | FIELD 18,10 | CONSOLE").unwrap();
+        assert_eq!(output, "SALES\nENGINEER");
+    }
+
+    #[test]
+    fn test_field_record_trim_and_pad() {
+        let record = Record::from_str("SMITH     SALES  X");
+        assert_eq!(
+            field_record(&record, 8, 9, true).as_str().trim_end(),
+            "SALES"
+        );
+        // PAD keeps the leading spaces of the field
+        assert_eq!(
+            field_record(&record, 8, 9, false).as_str().trim_end(),
+            "  SALES"
+        );
+    }
+
+    #[test]
+    fn test_command_catalog_examples_parse() {
+        let catalog = command_catalog();
+        for spec in &catalog {
+            let cmd = parse_command(spec.example)
+                .unwrap_or_else(|e| panic!("{} example failed: {e}", spec.name));
+            assert_eq!(cmd.name(), spec.name);
+        }
+        let mut names: Vec<&str> = catalog.iter().map(|spec| spec.name).collect();
+        names.dedup();
+        assert_eq!(names.len(), catalog.len(), "catalog names must be unique");
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn test_to_dsl_string_round_trips() {
+        let extra = [
+            r#"FILTER 0,8 = 'say "hi"'"#,
+            "FILTER -8,8 != \"X\"",
+            "CHANGE /a/ / b/ FIRST",
+            "CHANGE /--/-/ 2 REPEAT",
+            "CHANGE /SALES/REVENUE/ WHERE 28,8 BETWEEN \"00060001\" AND \"99999999\"",
+            "MASK 0,2 FILL / /",
+            "RESEQ 0,4 10,-5 FILL *",
+            "MASK 0,2; 4,2 FILL #",
+            "SORT",
+            "SORT 0,8 d num; 8,4 ASC",
+            "SELECT 0,8,0; 28,8,10 SEP /, / STRICT",
+            "LOCATE 0,8 ALL /A/ \"B\"",
+            "OMIT 18,10 BETWEEN \"A\" AND \"M\"",
+            "OMIT 0,4 < FIELD 4,4",
+            "EXTRACT /=/ DROP",
+            "GENERATE 2 /{SEQ:2}/",
+        ];
+        let examples = command_catalog().into_iter().map(|spec| spec.example);
+        for line in examples.chain(extra) {
+            let cmd = parse_command(line).unwrap_or_else(|e| panic!("{line}: {e}"));
+            let text = cmd.to_dsl_string();
+            let reparsed = parse_command(&text).unwrap_or_else(|e| panic!("{text}: {e}"));
+            assert_eq!(
+                format!("{reparsed:?}"),
+                format!("{cmd:?}"),
+                "{line} -> {text}"
+            );
+            assert_eq!(reparsed.to_dsl_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_to_dsl_string_normalizes() {
+        let cases = [
+            ("take   3", "TAKE 3"),
+            ("locate 0,8  trim  /SMITH/", "LOCATE 0,8 TRIM \"SMITH\""),
+            ("change 'SALES'MKTG' 1", "CHANGE \"SALES\" \"MKTG\" 1"),
+            ("sort 0,8 asc", "SORT 0,8"),
+            ("reseq 0,4 1,1 fill '0'", "RESEQ 0,4 1,1"),
+            ("select -8,8,0", "SELECT 72,8,0"),
+            ("omit 18,10 = \"SALES\"", "OMIT 18,10 = \"SALES\""),
+            ("dup 2", "DUPLICATE 2"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_command(line).unwrap().to_dsl_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_messy_pipeline() {
+        let messy = "# LAYOUT Last 0,8;Dept 18,10\n\
+            # sales staff, shouting\n\
+            pipe cons |  filter 18,10 = /SALES/\n\
+            |upper\n\
+            #! | take 1\n\
+            | change \"SMITH\"SMYTHE\"   |   cons ?";
+        let canonical = canonicalize(messy).unwrap();
+        assert_eq!(
+            canonical,
+            "# LAYOUT Last 0,8; Dept 18,10\n\
+             PIPE CONSOLE\n\
+             | FILTER 18,10 = \"SALES\"\n\
+             | UPPER\n\
+             #! | take 1\n\
+             | CHANGE \"SMITH\" \"SMYTHE\"\n\
+             | CONSOLE\n\
+             ?"
+        );
+
+        let before = parse_commands(messy).unwrap();
+        let after = parse_commands(&canonical).unwrap();
+        assert_eq!(format!("{after:?}"), format!("{before:?}"));
+        assert_eq!(parse_layout(&canonical), parse_layout(messy));
+        assert_eq!(canonicalize(&canonical).unwrap(), canonical);
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_invalid_pipeline() {
+        assert!(matches!(
+            canonicalize("CONSOLE | BOGUS 3 | CONSOLE"),
+            Err(PipelineError::Parse(msg)) if msg.contains("Unknown command")
+        ));
+    }
+
+    #[test]
+    fn test_command_kinds() {
+        use CommandKind::*;
+        let expected = [
+            ("APPLY", Transform),
+            ("ASSERT", Transform),
+            ("BLOCK", Transform),
+            ("BUILD", Transform),
+            ("CHANGE", Transform),
+            ("CHOP", Transform),
+            ("CONSOLE", Source),
+            ("CONTEXT", Filter),
+            ("COUNT", Aggregate),
+            ("CROSSTAB", Aggregate),
+            ("DATEFMT", Transform),
+            ("DECODE", Transform),
+            ("DEFAULT", Transform),
+            ("DUPLICATE", Transform),
+            ("EXPAND", Transform),
+            ("EXTRACT", Transform),
+            ("FIELD", Transform),
+            ("FILTER", Filter),
+            ("FREQ", Aggregate),
+            ("GENERATE", Source),
+            ("HASH", Aggregate),
+            ("HEADER", Transform),
+            ("HOLE", Sink),
+            ("INCLUDE", Transform),
+            ("INSERT", Transform),
+            ("JUSTIFY", Transform),
+            ("LENGTH", Filter),
+            ("LITERAL", Source),
+            ("LOCATE", Filter),
+            ("LOWER", Transform),
+            ("MASK", Transform),
+            ("NLOCATE", Filter),
+            ("NORM", Transform),
+            ("OMIT", Filter),
+            ("ONCHANGE", Filter),
+            ("PAGE", Filter),
+            ("PIVOT", Aggregate),
+            ("RESEQ", Transform),
+            ("REVERSE", Transform),
+            ("ROUTE", Filter),
+            ("SEGMENT", Transform),
+            ("SELECT", Transform),
+            ("SHUFFLE", Aggregate),
+            ("SKIP", Filter),
+            ("SORT", Aggregate),
+            ("SORTITEMS", Transform),
+            ("STATS", Transform),
+            ("TAKE", Filter),
+            ("TOJSON", Transform),
+            ("TOPN", Aggregate),
+            ("UPPER", Transform),
+            ("VERIFY", Transform),
+            ("WRAP", Transform),
+            ("ZIP", Aggregate),
+        ];
+        let catalog = command_catalog();
+        assert_eq!(
+            catalog.iter().map(|spec| spec.name).collect::<Vec<_>>(),
+            expected.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            "every stage needs a kind"
+        );
+        for (spec, (name, kind)) in catalog.iter().zip(expected) {
+            assert_eq!(parse_command(spec.example).unwrap().kind(), kind, "{name}");
+        }
+        // Variants reached by other syntax
+        for (line, kind) in [
+            (r#"FILTER 0,1 != "A""#, Filter),
+            (r#"FILTER 0,1 BETWEEN "A" AND "B""#, Filter),
+            ("FILTER 0,1 < FIELD 2,1", Filter),
+            ("LOCATE ANY /A/ /B/", Filter),
+            ("ASSERT 0,1 NUMERIC STRICT", Filter),
+            ("HOLE COUNT", Sink),
+            ("STATS", Aggregate),
+            ("CHANGE /A/B/ WHERE 0,1 = \"A\"", Transform),
+            ("#! UPPER", Transform),
+        ] {
+            assert_eq!(parse_commands(line).unwrap()[0].kind(), kind, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_command_keyword_matching() {
+        assert!(matches!(
+            parse_command("take 2"),
+            Ok(Command::Take { n: 2 })
+        ));
+        assert!(matches!(
+            parse_command("DUP 3"),
+            Ok(Command::Duplicate { .. })
+        ));
+        assert!(matches!(parse_command("cons"), Ok(Command::Console)));
+        assert!(matches!(
+            parse_command("LOCATE/x/"),
+            Ok(Command::Locate { .. })
+        ));
+        assert_eq!(
+            parse_command("TAKEN 2").unwrap_err(),
+            "Unknown command: TAKEN"
+        );
+        assert_eq!(
+            parse_command("CONSOLES").unwrap_err(),
+            "Unknown command: CONSOLES"
+        );
+    }
+
+    #[test]
+    fn test_parse_tojson() {
+        match parse_command("TOJSON Last=0,8 First=8,10").unwrap() {
+            Command::ToJson { fields } => assert_eq!(
+                fields,
+                vec![("Last".to_string(), 0, 8), ("First".to_string(), 8, 10)]
+            ),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(parse_command("TOJSON").is_err());
+        assert!(parse_command("TOJSON Last").is_err());
+        assert!(parse_command("TOJSON =0,8").is_err());
+        assert!(parse_command("TOJSON A=0,8 A=8,2").is_err());
+        assert!(parse_command("TOJSON A=0").is_err());
+    }
+
+    #[test]
+    fn test_tojson_pipeline() {
+        let input = "SMITH   JOHN      SALES     00050000\nO\"NEIL  PAT       ENG\\R     00060000";
+        let pipeline = "PIPE CONSOLE | TOJSON Last=0,8 First=8,10 Dept=18,10 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            concat!(
+                r#"{"Last":"SMITH","First":"JOHN","Dept":"SALES"}"#,
+                "\n",
+                r#"{"Last":"O\"NEIL","First":"PAT","Dept":"ENG\\R"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_json_record_truncates_to_record_width() {
+        let record = Record::from_str(&"X".repeat(80));
+        let fields = vec![("Value".to_string(), 0, 80)];
+        let json = json_record(&record, &fields);
+        assert!(json.as_str().starts_with(r#"{"Value":"XXX"#));
+        assert_eq!(json.as_str().len(), RECORD_WIDTH);
+    }
+
+    #[test]
+    fn test_parse_change_forms_and_limit() {
+        for line in ["CHANGE /A/B/", "CHANGE /A/ /B/", "CHANGE \"A\" \"B\""] {
+            match parse_command(line).unwrap() {
+                Command::Change {
+                    old,
+                    new,
+                    limit,
+                    repeat,
+                } => {
+                    assert_eq!((old.as_str(), new.as_str(), limit), ("A", "B", None));
+                    assert!(!repeat);
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert!(matches!(
+            parse_command("CHANGE /A/B/ first").unwrap(),
+            Command::Change { limit: Some(1), .. }
+        ));
+        assert!(matches!(
+            parse_command("CHANGE /A/ /B/ 2").unwrap(),
+            Command::Change { limit: Some(2), .. }
+        ));
+        assert!(matches!(
+            parse_command("CHANGE /ERROR: //").unwrap(),
+            Command::Change { ref new, limit: None, .. } if new.is_empty()
+        ));
+        assert!(parse_command("CHANGE /A/B/ 0").is_err());
+        assert!(parse_command("CHANGE /A/B/ LAST").is_err());
+        assert!(parse_command("CHANGE /A/B").is_err());
+        assert!(matches!(
+            parse_command("CHANGE /A/B/ repeat").unwrap(),
+            Command::Change {
+                limit: None,
+                repeat: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_command("CHANGE /A/B/ FIRST REPEAT").unwrap(),
+            Command::Change {
+                limit: Some(1),
+                repeat: true,
+                ..
+            }
+        ));
+        assert!(parse_command("CHANGE /A/B/ REPEAT FIRST").is_err());
+    }
+
+    #[test]
+    fn test_change_where_edits_only_matching_records() {
+        let input = "SMITH   JOHN      SALES     00050000\n\
+                     DOE     JANE      SALES     00060000\n\
+                     CHEN    LISA      ENGINEER  00080000";
+        let pipeline = "PIPE CONSOLE \
+                        | CHANGE /SALES/REVENUE/ WHERE 28,8 BETWEEN \"00055000\" AND \"99999999\" \
+                        | CONSOLE";
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!((input_count, output_count), (3, 3));
+        let lines: Vec<&str> = output.lines().collect();
+        // Salary too low: passed on unchanged
+        assert_eq!(lines[0], "SMITH   JOHN      SALES     00050000");
+        assert_eq!(lines[1], "DOE     JANE      REVENUE     00060000");
+        // Matches the condition but has nothing to change
+        assert_eq!(lines[2], "CHEN    LISA      ENGINEER  00080000");
+    }
+
+    #[test]
+    fn test_parse_change_where() {
+        match parse_command(r#"CHANGE /A/B/ where 18,10 != "SALES""#).unwrap() {
+            Command::ChangeWhere {
+                condition,
+                old,
+                new,
+            } => {
+                assert_eq!((old.as_str(), new.as_str()), ("A", "B"));
+                assert!(matches!(
+                    *condition,
+                    Command::FilterNe { pos: 18, len: 10, ref value } if value == "SALES"
+                ));
+            }
+            other => panic!("Expected ChangeWhere, got {other:?}"),
+        }
+        assert_eq!(
+            parse_command("CHANGE /A/B/ WHERE 0,1 = \"X\"")
+                .unwrap()
+                .field_specs(),
+            [(0, 1)]
+        );
+        assert!(parse_command("CHANGE /A/B/ WHERE").is_err());
+        assert!(parse_command("CHANGE /A/B/ WHERE 0,1").is_err());
+    }
+
+    #[test]
+    fn test_change_repeat_collapses_to_fixpoint() {
+        let (output, _, _) = execute_pipeline(
+            "a----b\nx--y--z",
+            "PIPE CONSOLE | CHANGE /--/-/ REPEAT | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "a-b\nx-y-z");
+        // A single pass only halves the run
+        let (output, _, _) =
+            execute_pipeline("a----b", "PIPE CONSOLE | CHANGE /--/-/ | CONSOLE").unwrap();
+        assert_eq!(output, "a--b");
+        // A growing replacement converges once the record is full
+        let grown = change_until_stable(&Record::from_str("ab"), "b", "bb", None).unwrap();
+        assert_eq!(grown.len_content(), RECORD_WIDTH);
+    }
+
+    #[test]
+    fn test_change_repeat_converges_after_many_passes() {
+        // Each pass adds one "b" until the record fills all 80 columns
+        let (output, _, _) =
+            execute_pipeline("a", "PIPE CONSOLE | CHANGE /a/ab/ REPEAT | CONSOLE").unwrap();
+        assert_eq!(output, format!("a{}", "b".repeat(RECORD_WIDTH - 1)));
+        // Shuffling "ab" along the record takes 155 passes to settle
+        let stable = change_until_stable(&Record::from_str("ab"), "ab", "baab", None).unwrap();
+        assert_eq!(
+            change_text(stable.as_str(), "ab", "baab", None),
+            stable.as_str()
+        );
+    }
+
+    #[test]
+    fn test_until_stable_fails_on_a_cycle() {
+        // Swapping the first two columns never settles: "ab", "ba", "ab", ...
+        let swap = |r: &Record| {
+            let mut bytes = *r.as_bytes();
+            bytes.swap(0, 1);
+            Record::from_bytes(&bytes)
+        };
+        assert_eq!(until_stable(&Record::from_str("ab"), swap), Err(2));
+        assert_eq!(
+            until_stable(&Record::from_str("aa"), swap),
+            Ok(Record::from_str("aa"))
+        );
+        // A record that keeps reaching new states fails at the limit
+        let mut n = 0;
+        let count = |_: &Record| {
+            n += 1;
+            Record::from_str(&n.to_string())
+        };
+        assert_eq!(
+            until_stable(&Record::new(), count),
+            Err(CHANGE_REPEAT_LIMIT)
+        );
+    }
+
+    #[test]
+    fn test_change_text_limit() {
+        assert_eq!(change_text("x.x.x", "x", "yy", Some(1)), "yy.x.x");
+        assert_eq!(change_text("x.x.x", "x", "yy", Some(2)), "yy.yy.x");
+        assert_eq!(change_text("x.x.x", "x", "yy", Some(5)), "yy.yy.yy");
+        assert_eq!(change_text("x.x.x", "x", "yy", None), "yy.yy.yy");
+    }
+
+    #[test]
+    fn test_change_limit_pipeline() {
+        let (output, _, _) =
+            execute_pipeline("ABABAB", "PIPE CONSOLE | CHANGE /AB/X/ FIRST | CONSOLE").unwrap();
+        assert_eq!(output, "XABAB");
+        let (output, _, _) =
+            execute_pipeline("ABABAB", "PIPE CONSOLE | CHANGE /AB/X/ 2 | CONSOLE").unwrap();
+        assert_eq!(output, "XXAB");
+    }
+
+    const LOG: &str = "INFO  start\nWARN  disk 80%\nERROR disk full\nFATAL net down\nINFO  done";
+
+    #[test]
+    fn test_parse_locate_multi() {
+        match parse_command("LOCATE ANY /ERROR/ \"WARN\" /FATAL/").unwrap() {
+            Command::LocateMulti {
+                patterns,
+                mode,
+                field,
+            } => {
+                assert_eq!(patterns, vec!["ERROR", "WARN", "FATAL"]);
+                assert_eq!(mode, MatchMode::Any);
+                assert_eq!(field, None);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("LOCATE 0,6 all /E/").unwrap(),
+            Command::LocateMulti {
+                mode: MatchMode::All,
+                field: Some((0, 6)),
+                ..
+            }
+        ));
+        assert!(parse_command("LOCATE ANY").is_err());
+        assert!(parse_command("LOCATE ALL /x/ /y").is_err());
+        // A pattern that merely starts with ANY is still a single pattern
+        assert!(matches!(
+            parse_command("LOCATE /ANY /").unwrap(),
+            Command::Locate { .. }
+        ));
+    }
+
+    #[test]
+    fn test_execute_locate_any_and_all() {
+        let (output, _, _) = execute_pipeline(
+            LOG,
+            "PIPE CONSOLE | LOCATE ANY /ERROR/ /WARN/ /FATAL/ | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(output, "WARN  disk 80%\nERROR disk full\nFATAL net down");
+
+        let (output, _, _) =
+            execute_pipeline(LOG, "PIPE CONSOLE | LOCATE ALL /ERROR/ /disk/ | CONSOLE").unwrap();
+        assert_eq!(output, "ERROR disk full");
+
+        // Restricted to the level field, "disk" is never found
+        let (_, _, count) =
+            execute_pipeline(LOG, "PIPE CONSOLE | LOCATE 0,6 ANY /disk/ | CONSOLE").unwrap();
+        assert_eq!(count, 0);
+    }
+
+    const BURSTY_LOG: &str = "a ok\nb ok\nc ERROR\nd ERROR\ne ok\nf ok\ng ok\nh ERROR\ni ok";
+
+    #[test]
+    fn test_parse_context() {
+        assert!(matches!(
+            parse_command("CONTEXT /ERROR/ 2").unwrap(),
+            Command::Context { ref pattern, before: 2, after: 2 } if pattern == "ERROR"
+        ));
+        assert!(matches!(
+            parse_command("CONTEXT /ERROR/ 0,3").unwrap(),
+            Command::Context {
+                before: 0,
+                after: 3,
+                ..
+            }
+        ));
+        assert!(parse_command("CONTEXT").is_err());
+        assert!(parse_command("CONTEXT /ERROR/").is_err());
+        assert!(parse_command("CONTEXT /ERROR/ x").is_err());
+        assert!(parse_command("CONTEXT /ERROR/ 1,").is_err());
+    }
+
+    #[test]
+    fn test_execute_context_merges_overlapping_windows() {
+        let run = |pipeline: &str| execute_pipeline(BURSTY_LOG, pipeline).unwrap().0;
+        // c and d are adjacent matches: their windows merge, nothing repeats
+        assert_eq!(
+            run("PIPE CONSOLE | CONTEXT /ERROR/ 1 | CONSOLE"),
+            "b ok\nc ERROR\nd ERROR\ne ok\ng ok\nh ERROR\ni ok"
+        );
+        assert_eq!(
+            run("PIPE CONSOLE | CONTEXT /ERROR/ 2,0 | CONSOLE"),
+            "a ok\nb ok\nc ERROR\nd ERROR\nf ok\ng ok\nh ERROR"
+        );
+        assert_eq!(
+            run("PIPE CONSOLE | CONTEXT /ERROR/ 0 | CONSOLE"),
+            run("PIPE CONSOLE | LOCATE /ERROR/ | CONSOLE")
+        );
+    }
+
+    #[test]
+    fn test_record_hasher_fnv1a() {
+        // No records: the FNV-1a offset basis
+        assert_eq!(
+            RecordHasher::new().to_record().as_str().trim_end(),
+            "HASH=cbf29ce484222325"
+        );
+        // One record hashes its 80 bytes, padding included
+        let mut hasher = RecordHasher::new();
+        hasher.update(&Record::new());
+        let mut expected: u64 = 0xcbf2_9ce4_8422_2325;
+        for _ in 0..RECORD_WIDTH {
+            expected = (expected ^ u64::from(b' ')).wrapping_mul(0x100_0000_01b3);
+        }
+        assert_eq!(hasher.finish(), expected);
+    }
+
+    #[test]
+    fn test_length_stats_varying_lengths() {
+        let mut stats = LengthStats::default();
+        assert_eq!(stats.average(), 0.0);
+        for text in ["AB", "ABCDEFGHIJ", "A", "ABCD   "] {
+            stats.update(&Record::from_str(text));
+        }
+        assert_eq!((stats.count(), stats.min(), stats.max()), (4, 1, 10));
+        assert_eq!(stats.average(), 4.25);
+        let texts: Vec<String> = stats
+            .to_records()
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(texts, ["RECORDS=4", "MINLEN=1", "MAXLEN=10", "AVGLEN=4.25"]);
+    }
+
+    #[test]
+    fn test_execute_stats() {
+        let input = "AB\nABCDE\nABC";
+        let (output, _, _) = execute_pipeline(input, "PIPE CONSOLE | STATS | CONSOLE").unwrap();
+        assert_eq!(output, "RECORDS=3\nMINLEN=2\nMAXLEN=5\nAVGLEN=3.33");
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | STATS PASSTHROUGH | CONSOLE").unwrap();
+        assert_eq!(
+            output,
+            "AB\nABCDE\nABC\nRECORDS=3\nMINLEN=2\nMAXLEN=5\nAVGLEN=3.33"
+        );
+        let (output, _, _) = execute_pipeline("", "PIPE CONSOLE | STATS | CONSOLE").unwrap();
+        assert_eq!(output, "RECORDS=0\nMINLEN=0\nMAXLEN=0\nAVGLEN=0.00");
+        assert!(parse_command("STATS ALL").is_err());
+    }
+
+    #[test]
+    fn test_execute_hash_is_stable_and_order_sensitive() {
+        let pipeline = "PIPE CONSOLE | HASH | CONSOLE";
+        let (first, _, count) = execute_pipeline("A\nB\nC", pipeline).unwrap();
+        let (again, _, _) = execute_pipeline("A\nB\nC", pipeline).unwrap();
+        let (reordered, _, _) = execute_pipeline("A\nC\nB", pipeline).unwrap();
+        assert_eq!(count, 1);
+        assert!(first.starts_with("HASH="));
+        assert_eq!(first.len(), "HASH=".len() + 16);
+        assert_eq!(first, again);
+        assert_ne!(first, reordered);
+    }
+
+    #[test]
+    fn test_explain_filter_and_select() {
+        let lines =
+            explain("PIPE CONSOLE | FILTER 18,10 = \"SALES\" | SELECT 0,8,0; 28,8,10 | CONSOLE")
+                .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "Read records from the input.",
+                "Keep records where columns 19–28 equal 'SALES'.",
+                "Build new records from columns 1–8 to column 1, columns 29–36 to column 11.",
+                "Write records to the output.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_single_columns_and_errors() {
+        let lines = explain("PIPE CONSOLE | CONSOLE | LOCATE 4,1 /X/").unwrap();
+        assert_eq!(
+            lines[1],
+            "Pass records through, showing them on the console."
+        );
+        assert_eq!(lines[2], "Keep records containing 'X' in column 5.");
+        assert!(matches!(
+            explain("PIPE TAKE 1 | CONSOLE"),
+            Err(PipelineError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        match parse_command("SORT 18,10 ASC; 28,8 desc num").unwrap() {
+            Command::Sort { keys } => assert_eq!(
+                keys,
+                vec![
+                    SortKey {
+                        pos: 18,
+                        len: 10,
+                        dir: SortDir::Asc,
+                        numeric: false
+                    },
+                    SortKey {
+                        pos: 28,
+                        len: 8,
+                        dir: SortDir::Desc,
+                        numeric: true
+                    },
+                ]
+            ),
+            other => panic!("unexpected {other:?}"),
+        }
+        match parse_command("SORT").unwrap() {
+            Command::Sort { keys } => assert_eq!(keys[0].len, RECORD_WIDTH),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(parse_command("SORT 0,5 UP").is_err());
+        assert!(parse_command("SORT 0,5 ASC DESC").is_err());
+        assert!(parse_command("SORT 0,5;").is_err());
+    }
+
+    #[test]
+    fn test_execute_sort_department_then_salary() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE | SORT 18,10 ASC; 28,8 DESC NUM | SELECT 0,8,0 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            "BROWN\nCHEN\nJONES\nTAYLOR\nWILSON\nDOE\nSMITH\nGARCIA"
+        );
+    }
+
+    #[test]
+    fn test_execute_sort_is_stable() {
+        // Equal keys keep input order, in both directions
+        let input = "B1\nA2\nB3\nA4";
+        let (output, _, _) = execute_pipeline(input, "PIPE CONSOLE | SORT 0,1 | CONSOLE").unwrap();
+        assert_eq!(output, "A2\nA4\nB1\nB3");
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | SORT 0,1 DESC | CONSOLE").unwrap();
+        assert_eq!(output, "B1\nB3\nA2\nA4");
+    }
+
+    #[test]
+    fn test_parse_expand() {
+        assert!(matches!(
+            parse_command("EXPAND 28,8").unwrap(),
+            Command::Expand {
+                pos: 28,
+                len: 8,
+                clear: false
+            }
+        ));
+        assert!(matches!(
+            parse_command("expand 0,3 clear").unwrap(),
+            Command::Expand { clear: true, .. }
+        ));
+        assert!(parse_command("EXPAND").is_err());
+        assert!(parse_command("EXPAND 0,3 KEEP").is_err());
+        assert!(parse_command("EXPAND 0,3 CLEAR CLEAR").is_err());
+    }
+
+    #[test]
+    fn test_expand_record_counts() {
+        let zero = Record::from_str("ZERO 0");
+        let expand = |record: &Record, pos, len, clear| -> Vec<Record> {
+            expand_record(record, pos, len, clear).collect()
+        };
+        assert!(expand(&zero, 5, 1, false).is_empty());
+        let one = Record::from_str("ONE  1");
+        assert_eq!(expand(&one, 5, 1, false), vec![one.clone()]);
+        let three = Record::from_str("THREE3");
+        let copies = expand(&three, 5, 1, true);
+        assert_eq!(copies.len(), 3);
+        assert!(copies.iter().all(|r| r.as_str().trim_end() == "THREE"));
+        // Non-numeric and negative counts
+        let bad = Record::from_str("BAD  X");
+        assert_eq!(expand(&bad, 5, 1, true), vec![bad.clone()]);
+        assert!(expand(&Record::from_str("NEG -2"), 4, 2, false).is_empty());
+        // Counts too large to honour pass through like non-numeric ones
+        for count in ["1000001", "99999999999", "99999999999999999999"] {
+            let huge = Record::from_str(&format!("HUGE {count}"));
+            assert_eq!(expand(&huge, 5, count.len(), true), vec![huge.clone()]);
+        }
+        let max = Record::from_str(&format!("MAX {EXPAND_MAX_COUNT}"));
+        assert_eq!(expand_record(&max, 4, 7, false).count(), EXPAND_MAX_COUNT);
+    }
+
+    #[test]
+    fn test_execute_expand() {
+        let input = "A 0\nB 1\nC 3\nD ?";
+        let (output, input_count, output_count) =
+            execute_pipeline(input, "PIPE CONSOLE | EXPAND 2,1 | CONSOLE").unwrap();
+        assert_eq!(output, "B 1\nC 3\nC 3\nC 3\nD ?");
+        assert_eq!((input_count, output_count), (4, 5));
+        let (output, _, _) =
+            execute_pipeline(input, "PIPE CONSOLE | EXPAND 2,1 CLEAR | CONSOLE").unwrap();
+        assert_eq!(output, "B\nC\nC\nC\nD ?");
+    }
+
+    #[test]
+    fn test_parse_datefmt() {
+        match parse_command("DATEFMT 28,8 from YMD to mdy").unwrap() {
+            Command::DateFmt {
+                pos,
+                len,
+                from,
+                to,
+                flag,
+            } => {
+                assert_eq!((pos, len), (28, 8));
+                assert_eq!((from, to), (DateFormat::Ymd, DateFormat::Mdy));
+                assert!(!flag);
+            }
+            other => panic!("Expected DateFmt, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("DATEFMT 0,7 FROM julian TO dmy FLAG").unwrap(),
+            Command::DateFmt { flag: true, .. }
+        ));
+        for bad in [
+            "DATEFMT",
+            "DATEFMT 0,8",
+            "DATEFMT 0,8 FROM ymd",
+            "DATEFMT 0,8 FROM ymd TO",
+            "DATEFMT 0,8 FROM iso TO mdy",
+            "DATEFMT 0,8 TO mdy FROM ymd",
+            "DATEFMT 0,8 FROM ymd TO mdy KEEP",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_date_format_round_trips() {
+        let formats = [
+            DateFormat::Ymd,
+            DateFormat::Mdy,
+            DateFormat::Dmy,
+            DateFormat::Julian,
+        ];
+        for (year, month, day) in [(2024, 1, 15), (2024, 2, 29), (2023, 12, 31), (2000, 3, 1)] {
+            for format in formats {
+                let text = format.write(year, month, day);
+                assert_eq!(text.len(), format.width(), "{text}");
+                assert_eq!(format.read(&text), Some((year, month, day)), "{text}");
+            }
+        }
+        assert_eq!(DateFormat::Julian.write(2023, 12, 31), "2023365");
+        assert_eq!(DateFormat::Dmy.write(2024, 1, 15), "15/01/2024");
+    }
+
+    #[test]
+    fn test_date_format_rejects_invalid() {
+        assert_eq!(DateFormat::Ymd.read("20230229"), None);
+        assert_eq!(DateFormat::Ymd.read("19000229"), None);
+        assert_eq!(DateFormat::Ymd.read("20241301"), None);
+        assert_eq!(DateFormat::Ymd.read("2024011"), None);
+        assert_eq!(DateFormat::Ymd.read("2024-1-5"), None);
+        assert_eq!(DateFormat::Mdy.read("01-15-2024"), None);
+        assert_eq!(DateFormat::Dmy.read("32/01/2024"), None);
+        assert_eq!(DateFormat::Julian.read("2023366"), None);
+        assert_eq!(DateFormat::Julian.read("2024000"), None);
+        assert_eq!(DateFormat::Julian.read("2024366"), Some((2024, 12, 31)));
+        assert_eq!(DateFormat::Ymd.read(""), None);
+    }
+
+    #[test]
+    fn test_reformat_date_widths_and_invalid() {
+        let record = Record::from_str("01/15/2024|REST");
+        let out = reformat_date(&record, 0, 10, DateFormat::Mdy, DateFormat::Ymd, false);
+        assert_eq!(out.as_str().trim_end(), "20240115  |REST");
+        let out = reformat_date(&record, 0, 10, DateFormat::Ymd, DateFormat::Mdy, false);
+        assert_eq!(out, record);
+        let out = reformat_date(&record, 0, 10, DateFormat::Ymd, DateFormat::Mdy, true);
+        assert_eq!(out.as_str().trim_end(), "*ERR* 01/15/2024|REST");
+    }
+
+    #[test]
+    fn test_execute_datefmt() {
+        let input = "SMITH   20240115\nJONES   20240230\nDOE     19991231";
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | DATEFMT 8,8 FROM ymd TO mdy | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "SMITH   01/15/2024\nJONES   20240230\nDOE     12/31/1999"
+        );
+        let (output, _, _) = execute_pipeline(
+            input,
+            "PIPE CONSOLE | DATEFMT 8,8 FROM ymd TO julian FLAG | CONSOLE",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "SMITH   2024015\n*ERR* JONES   20240230\nDOE     1999365"
+        );
+    }
+
+    #[test]
+    fn test_parse_mask() {
+        match parse_command("MASK 28,8; 0,8").unwrap() {
+            Command::Mask { fields, fill } => {
+                assert_eq!(fields, vec![(28, 8), (0, 8)]);
+                assert_eq!(fill, '*');
+            }
+            other => panic!("Expected Mask, got {other:?}"),
+        }
+        for (line, expected) in [
+            ("MASK 28,8 FILL /X/", 'X'),
+            ("MASK 28,8 fill #", '#'),
+            ("MASK 28,8 FILL / /", ' '),
+        ] {
+            match parse_command(line).unwrap() {
+                Command::Mask { fill, .. } => assert_eq!(fill, expected, "{line}"),
+                other => panic!("Expected Mask, got {other:?}"),
+            }
+        }
+        for bad in [
+            "MASK",
+            "MASK FILL /X/",
+            "MASK 28",
+            "MASK 28,8;",
+            "MASK 28,8 FILL",
+            "MASK 28,8 FILL /XY/",
+            "MASK 28,8 FILL /X|",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
         }
     }
 
     #[test]
-    fn test_parse_select() {
-        let cmd = parse_command("SELECT 0,8,0; 28,8,8").unwrap();
-        match cmd {
-            Command::Select { fields } => {
-                assert_eq!(fields.len(), 2);
-                assert_eq!(fields[0], (0, 8, 0));
-                assert_eq!(fields[1], (28, 8, 8));
+    fn test_parse_decode() {
+        match parse_command("DECODE 18,10 /SLS=SALES; ENG = ENGINEER;/").unwrap() {
+            Command::Decode {
+                pos,
+                len,
+                map,
+                default,
+            } => {
+                assert_eq!((pos, len), (18, 10));
+                let pairs: Vec<(&str, &str)> = map
+                    .iter()
+                    .map(|(code, text)| (code.as_str(), text.as_str()))
+                    .collect();
+                assert_eq!(pairs, [("ENG", "ENGINEER"), ("SLS", "SALES")]);
+                assert_eq!(default, None);
             }
-            _ => panic!("Expected Select"),
+            other => panic!("Expected Decode, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("decode 18,10 \"S=SALES\" default /OTHER DEPT/").unwrap(),
+            Command::Decode { default: Some(text), .. } if text == "OTHER DEPT"
+        ));
+        for bad in [
+            "DECODE",
+            "DECODE 18,10",
+            "DECODE /S=SALES/",
+            "DECODE 18,10 /S=SALES",
+            "DECODE 18,10 //",
+            "DECODE 18,10 /SALES/",
+            "DECODE 18,10 /=SALES/",
+            "DECODE 18,10 /S=SALES;S=SHIPPING/",
+            "DECODE 18,10 /S=SALES/ ELSE /X/",
+            "DECODE 18,10 /S=SALES/ DEFAULT",
+            "DECODE 18,10 /S=SALES/ DEFAULT /X/ EXTRA",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
         }
     }
 
     #[test]
-    fn test_parse_take() {
-        let cmd = parse_command("TAKE 5").unwrap();
-        match cmd {
-            Command::Take { n } => assert_eq!(n, 5),
-            _ => panic!("Expected Take"),
-        }
+    fn test_decode_field() {
+        let map = HashMap::from([
+            ("SLS".to_string(), "SALES".to_string()),
+            ("ENG".to_string(), "ENGINEERING".to_string()),
+        ]);
+        let record = Record::from_str("JONES   MARY      ENG       00075000");
+        // Longer than the field: cut to fit, the next field is untouched
+        assert_eq!(
+            decode_field(&record, 18, 10, &map, None)
+                .as_str()
+                .trim_end(),
+            "JONES   MARY      ENGINEERIN00075000"
+        );
+        let record = Record::from_str("WILSON  BOB       MKT       00055000");
+        assert_eq!(decode_field(&record, 18, 10, &map, None), record);
+        assert_eq!(
+            decode_field(&record, 18, 10, &map, Some("UNKNOWN")).field(18, 10),
+            "UNKNOWN   "
+        );
     }
 
     #[test]
-    fn test_parse_console() {
-        let cmd = parse_command("CONSOLE").unwrap();
-        assert!(matches!(cmd, Command::Console));
+    fn test_execute_decode_department_codes() {
+        let input = "SMITH   JOHN      SLS       00050000\n\
+                     JONES   MARY      ENG       00075000\n\
+                     WILSON  BOB       MKT       00055000";
+        let pipeline = "PIPE CONSOLE | DECODE 18,10 /SLS=SALES;ENG=ENGINEER/ | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            "SMITH   JOHN      SALES     00050000\n\
+             JONES   MARY      ENGINEER  00075000\n\
+             WILSON  BOB       MKT       00055000"
+        );
+
+        let pipeline =
+            "PIPE CONSOLE | DECODE 18,10 /SLS=SALES;ENG=ENGINEER/ DEFAULT /UNKNOWN/ | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output.lines().last(),
+            Some("WILSON  BOB       UNKNOWN   00055000")
+        );
     }
 
     #[test]
-    fn test_execute_pipeline() {
-        let input = "SMITH   JOHN      SALES     00050000\nJONES   MARY      ENGINEER  00075000";
-        let pipeline = r#"PIPE CONSOLE
-| FILTER 18,10 = "SALES"
-| CONSOLE
-?"#;
+    fn test_parse_default() {
+        match parse_command("DEFAULT 18,10 /UNKNOWN/").unwrap() {
+            Command::Default {
+                pos,
+                len,
+                value,
+                justify,
+            } => {
+                assert_eq!((pos, len), (18, 10));
+                assert_eq!(value, "UNKNOWN");
+                assert_eq!(justify, JustifySide::Left);
+            }
+            other => panic!("Expected Default, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("default 28,8 \"0\" right").unwrap(),
+            Command::Default {
+                justify: JustifySide::Right,
+                ..
+            }
+        ));
+        for bad in [
+            "DEFAULT",
+            "DEFAULT 18,10",
+            "DEFAULT /X/",
+            "DEFAULT 18,10 / /",
+            "DEFAULT 18,10 /X",
+            "DEFAULT 18,10 /X/ CENTER",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+    }
 
-        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+    #[test]
+    fn test_default_field() {
+        let record = Record::from_str("DOE     JANE                00060000");
+        let filled = default_field(&record, 18, 10, "UNKNOWN", JustifySide::Left);
+        assert_eq!(
+            filled.as_str().trim_end(),
+            "DOE     JANE      UNKNOWN   00060000"
+        );
+        let filled = default_field(&record, 18, 10, "UNKNOWN", JustifySide::Right);
+        assert_eq!(filled.field(18, 10), "   UNKNOWN");
+        // Too long for the field: the leading characters fit
+        let filled = default_field(&record, 18, 4, "UNKNOWN", JustifySide::Right);
+        assert_eq!(filled.field(18, 10), "UNKN      ");
+        // Non-blank fields are untouched
+        let filled = default_field(&record, 8, 10, "UNKNOWN", JustifySide::Left);
+        assert_eq!(filled, record);
+    }
 
-        assert_eq!(input_count, 2);
-        assert_eq!(output_count, 1);
-        assert!(output.contains("SMITH"));
-        assert!(!output.contains("JONES"));
+    #[test]
+    fn test_execute_default_blank_department() {
+        let input = "SMITH   JOHN      SALES     00050000\n\
+                     DOE     JANE                00060000\n\
+                     CHEN    LI        ENGINEER  00070000";
+        let pipeline = "PIPE CONSOLE | DEFAULT 18,10 /UNKNOWN/ | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            "SMITH   JOHN      SALES     00050000\n\
+             DOE     JANE      UNKNOWN   00060000\n\
+             CHEN    LI        ENGINEER  00070000"
+        );
     }
 
     #[test]
-    fn test_pipeline_requires_source_first() {
-        let input = "SMITH   JOHN      SALES     00050000";
+    fn test_parse_wrap() {
+        assert!(matches!(
+            parse_command("WRAP 20").unwrap(),
+            Command::Wrap {
+                width: 20,
+                cont_col: None
+            }
+        ));
+        assert!(matches!(
+            parse_command("wrap 20 cont 79").unwrap(),
+            Command::Wrap {
+                width: 20,
+                cont_col: Some(79)
+            }
+        ));
+        for bad in [
+            "WRAP",
+            "WRAP 0",
+            "WRAP 81",
+            "WRAP 20 CONT",
+            "WRAP 20 CONT 19",
+            "WRAP 20 CONT 80",
+            "WRAP 20 79",
+            "WRAP 20 CONT 79 X",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+    }
 
-        // FILTER cannot be first stage
-        let pipeline = r#"PIPE FILTER 18,10 = "SALES"
-| CONSOLE"#;
-        let result = execute_pipeline(input, pipeline);
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .contains("FILTER cannot be the first stage")
+    #[test]
+    fn test_wrap_record_breaks_at_words() {
+        let record =
+            Record::from_str("  The quick brown fox jumps over the lazy dog near the riverbank");
+        let pieces = wrap_record(&record, 20, Some(20));
+        let texts: Vec<&str> = pieces.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "The quick brown fox +",
+                "jumps over the lazy +",
+                "dog near the        +",
+                "riverbank",
+            ]
         );
+        assert!(pieces.iter().all(|r| r.field(0, 20).trim_end().len() <= 20));
+        // Without a continuation column the pieces are just the text
+        let plain = wrap_record(&record, 20, None);
+        assert_eq!(plain[2].as_str().trim_end(), "dog near the");
     }
 
     #[test]
-    fn test_any_stage_can_be_last_filter() {
-        // FILTER as last stage - output is discarded
-        let pipeline = r#"PIPE CONSOLE
-| FILTER 18,10 = "SALES""#;
-        let result = execute_pipeline("SMITH   JOHN      SALES     00050000", pipeline);
-        // Should succeed - output just discarded
-        assert!(result.is_ok());
+    fn test_wrap_record_hard_breaks_long_words() {
+        let record = Record::from_str("ab ABCDEFGHIJKLMNOP cd");
+        let texts: Vec<String> = wrap_record(&record, 6, None)
+            .iter()
+            .map(|r| r.as_str().trim_end().to_string())
+            .collect();
+        assert_eq!(texts, vec!["ab", "ABCDEF", "GHIJKL", "MNOP", "cd"]);
+        // Content that already fits, and blank records, pass through as one
+        assert_eq!(wrap_record(&record, 30, Some(40)), vec![record]);
+        assert_eq!(wrap_record(&Record::new(), 10, None), vec![Record::new()]);
     }
 
     #[test]
-    fn test_execute_locate() {
-        let input = "SMITH   JOHN      SALES     00050000
-JONES   MARY      ENGINEER  00075000
-DOE     JANE      SALES     00060000";
-        let pipeline = r#"PIPE CONSOLE
-| LOCATE "SALES"
-| CONSOLE
-?"#;
+    fn test_parse_shuffle() {
+        assert!(matches!(
+            parse_command("SHUFFLE 42").unwrap(),
+            Command::Shuffle { seed: 42 }
+        ));
+        for bad in ["SHUFFLE", "SHUFFLE -1", "SHUFFLE X"] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
+    }
 
-        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+    #[test]
+    fn test_generate_sequence_template() {
+        let output = execute_pipeline("", "PIPE GENERATE 3 /X{SEQ:3}/ | CONSOLE ?")
+            .unwrap()
+            .0;
+        assert_eq!(output, "X001\nX002\nX003");
+    }
 
-        assert_eq!(input_count, 3);
-        assert_eq!(output_count, 2);
-        assert!(output.contains("SMITH"));
-        assert!(output.contains("DOE"));
-        assert!(!output.contains("JONES"));
+    #[test]
+    fn test_generate_random_fields_follow_seed() {
+        let text = |seed| {
+            generate_records(3, "{RAND:5}-{SEQ:1}", seed)
+                .iter()
+                .map(|r| r.as_str().trim_end().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(text(7), text(7));
+        assert_ne!(text(7), text(8));
+        for (i, line) in text(7).iter().enumerate() {
+            let (digits, seq) = line.split_once('-').unwrap();
+            assert_eq!(digits.len(), 5);
+            assert!(digits.bytes().all(|b| b.is_ascii_digit()));
+            assert_eq!(seq, (i + 1).to_string());
+        }
     }
 
     #[test]
-    fn test_execute_count() {
-        let input = "SMITH   JOHN      SALES     00050000
-JONES   MARY      ENGINEER  00075000
-DOE     JANE      SALES     00060000";
-        let pipeline = r#"PIPE CONSOLE
-| COUNT
-| CONSOLE
-?"#;
+    fn test_generate_keeps_unknown_braces() {
+        let records = generate_records(1, "{A} {SEQ} {SEQ:x} {SEQ:2", 0);
+        assert_eq!(records[0].as_str().trim_end(), "{A} {SEQ} {SEQ:x} {SEQ:2");
+        // A sequence number wider than its field is not truncated
+        let records = generate_records(10, "{SEQ:1}", 0);
+        assert_eq!(records[9].as_str().trim_end(), "10");
+    }
 
-        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+    #[test]
+    fn test_generate_mid_pipeline_prepends() {
+        let output = execute_pipeline("A\nB", "PIPE CONSOLE | GENERATE 2 /G{SEQ:1}/ | CONSOLE ?")
+            .unwrap()
+            .0;
+        assert_eq!(output, "G1\nG2\nA\nB");
+    }
 
-        assert_eq!(input_count, 3);
-        assert_eq!(output_count, 1);
-        assert_eq!(output, "3");
+    #[test]
+    fn test_parse_generate() {
+        let cmd = parse_command("GENERATE 100 /REC{SEQ:5} DATA/ seed 42").unwrap();
+        assert!(matches!(
+            cmd,
+            Command::Generate { count: 100, ref template, seed: 42 } if template == "REC{SEQ:5} DATA"
+        ));
+        assert!(matches!(
+            parse_command("GENERATE 1 /X/").unwrap(),
+            Command::Generate { seed: 0, .. }
+        ));
+        for bad in [
+            "GENERATE",
+            "GENERATE 3",
+            "GENERATE x /A/",
+            "GENERATE 0 /A/",
+            "GENERATE 3 //",
+            "GENERATE 3 /A",
+            "GENERATE 3 /A/ SEED",
+            "GENERATE 3 /A/ SEED x",
+            "GENERATE 3 /A/ FAST",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_literal_as_first_stage() {
-        // LITERAL can be the first stage, generating a single record
-        // CMS-style: no delimiters, everything after LITERAL is the text
-        let pipeline = r#"PIPE LITERAL HELLO, WORLD
-| CONSOLE
-?"#;
+    fn test_shuffle_records_is_seeded_permutation() {
+        let input: Vec<Record> = ["A", "B", "C", "D", "E", "F"]
+            .iter()
+            .map(|s| Record::from_str(s))
+            .collect();
+        let order = |seed| {
+            let mut records = input.clone();
+            shuffle_records(&mut records, seed);
+            records
+                .iter()
+                .map(|r| r.as_str().trim_end().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(42), vec!["B", "F", "C", "A", "E", "D"]);
+        assert_eq!(order(42), order(42));
+        assert_ne!(order(42), order(7));
+
+        // Same records, only reordered
+        let mut sorted = order(42);
+        sorted.sort();
+        assert_eq!(sorted, vec!["A", "B", "C", "D", "E", "F"]);
+        let mut empty: Vec<Record> = Vec::new();
+        shuffle_records(&mut empty, 42);
+        assert!(empty.is_empty());
+    }
 
-        let (output, input_count, output_count) = execute_pipeline("", pipeline).unwrap();
+    #[test]
+    fn test_execute_shuffle_keeps_every_record() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE\n| SHUFFLE 1\n| CONSOLE\n?";
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(input_count, output_count);
+        let mut lines: Vec<&str> = output.lines().collect();
+        assert_ne!(lines, input.lines().map(str::trim_end).collect::<Vec<_>>());
+        lines.sort_unstable();
+        let mut expected: Vec<&str> = input.lines().map(str::trim_end).collect();
+        expected.sort_unstable();
+        assert_eq!(lines, expected);
+    }
 
-        assert_eq!(input_count, 1); // LITERAL generates 1 record
-        assert_eq!(output_count, 1);
-        assert_eq!(output, "HELLO, WORLD");
+    fn include_options() -> ExecuteOptions {
+        ExecuteOptions {
+            pipelines: PipelineRegistry::default().with(
+                "sales",
+                parse_snippet("FILTER 18,10 = \"SALES\"\n| SELECT 0,8,0; 28,8,8").unwrap(),
+            ),
+            ..ExecuteOptions::default()
+        }
     }
 
     #[test]
-    fn test_literal_first_with_middle_stages() {
-        // CMS-style: no delimiters
-        let pipeline = r#"PIPE LITERAL hello world
-| UPPER
-| CONSOLE
-?"#;
+    fn test_parse_include() {
+        match parse_snippet("INCLUDE sales").unwrap().as_slice() {
+            [Command::Include { name }] => assert_eq!(name, "sales"),
+            other => panic!("Expected Include, got {other:?}"),
+        }
+        assert!(parse_snippet("INCLUDE").is_err());
+        assert!(parse_snippet("INCLUDE a b").is_err());
+        // Without a registry there is nothing to include
+        let err = parse_commands("PIPE CONSOLE | INCLUDE sales | CONSOLE").unwrap_err();
+        assert!(err.contains("Unknown pipeline 'sales'"), "{err}");
+    }
 
-        let (output, _input_count, output_count) = execute_pipeline("", pipeline).unwrap();
+    #[test]
+    fn test_include_splices_stages_mid_pipeline() {
+        let options = include_options();
+        let pipeline = "PIPE CONSOLE\n| INCLUDE sales\n| SORT 8,8 DESC\n| CONSOLE\n?";
+        let commands = parse_commands_with_options(pipeline, &options).unwrap();
+        let names: Vec<&str> = commands.iter().map(Command::name).collect();
+        assert_eq!(
+            names,
+            vec!["CONSOLE", "FILTER", "SELECT", "SORT", "CONSOLE"]
+        );
 
-        assert_eq!(output_count, 1);
-        assert_eq!(output, "HELLO WORLD");
+        let input = include_str!("../specs/input-fixed-80.data");
+        let (output, _, _, _) = execute_pipeline_with_options(input, pipeline, &options).unwrap();
+        assert_eq!(
+            output,
+            "DOE     00060000\nSMITH   00050000\nGARCIA  00045000"
+        );
+        let err = execute_pipeline_with_options(input, "PIPE CONSOLE | INCLUDE other", &options)
+            .unwrap_err();
+        assert!(err.contains("available: sales"), "{err}");
     }
 
     #[test]
-    fn test_filter_cannot_be_first() {
-        let pipeline = r#"PIPE FILTER 0,5 = "TEST"
-| CONSOLE"#;
-        let result = execute_pipeline("", pipeline);
-        assert!(result.is_err(), "Expected error but got: {:?}", result);
-        let err = result.unwrap_err();
+    fn test_include_nested_and_cyclic() {
+        let mut options = include_options();
+        options
+            .pipelines
+            .register("top", parse_snippet("INCLUDE sales | TAKE 1").unwrap());
+        let (output, _, _, _) = execute_pipeline_with_options(
+            include_str!("../specs/input-fixed-80.data"),
+            "PIPE CONSOLE | INCLUDE TOP | CONSOLE",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(output, "SMITH   00050000");
+
+        options
+            .pipelines
+            .register("a", parse_snippet("UPPER | INCLUDE b").unwrap());
+        options
+            .pipelines
+            .register("b", parse_snippet("INCLUDE a").unwrap());
+        let err = parse_commands_with_options("PIPE CONSOLE | INCLUDE a", &options).unwrap_err();
+        let chain = ["a", "b", "a"].map(String::from).to_vec();
         assert!(
-            err.contains("FILTER cannot be the first stage"),
-            "Got: {}",
-            err
+            err.ends_with(&PipelineError::IncludeCycle { chain }.to_string()),
+            "{err}"
         );
-    }
 
-    #[test]
-    fn test_any_stage_can_be_last() {
-        // Output is discarded if last stage isn't a sink
-        // CMS-style: LITERAL without delimiters
-        let pipeline = r#"PIPE CONSOLE
-| LITERAL END"#;
-        let result = execute_pipeline("test", pipeline);
-        // Should succeed - LITERAL as last just means output is discarded
-        assert!(result.is_ok(), "Expected success but got: {:?}", result);
+        // A long enough chain of distinct names also stops
+        for depth in 0..MAX_INCLUDE_DEPTH {
+            let next = format!("INCLUDE n{}", depth + 1);
+            options
+                .pipelines
+                .register(&format!("n{depth}"), parse_snippet(&next).unwrap());
+        }
+        options.pipelines.register("n8", vec![Command::Upper]);
+        let err = parse_commands_with_options("PIPE CONSOLE | INCLUDE n0", &options).unwrap_err();
+        assert!(err.contains("n7"), "{err}");
+        assert!(parse_commands_with_options("PIPE CONSOLE | INCLUDE n1", &options).is_ok());
     }
 
     #[test]
-    fn test_console_in_middle() {
-        // CONSOLE in middle passes through (useful for debugging)
-        let pipeline = r#"PIPE CONSOLE
-| CONSOLE
-| CONSOLE"#;
-        let (output, input_count, output_count) = execute_pipeline("test", pipeline).unwrap();
-        assert_eq!(input_count, 1);
-        assert_eq!(output_count, 1);
-        assert_eq!(output, "test");
+    fn test_unexpanded_include_is_rejected() {
+        let commands = parse_snippet("CONSOLE | INCLUDE sales | CONSOLE").unwrap();
+        let err = execute_parsed(&commands, "A").unwrap_err();
+        assert!(err.contains("INCLUDE sales was not expanded"), "{err}");
     }
 
     #[test]
-    fn test_hole_as_first() {
-        // HOLE as first generates empty stream
-        // CMS-style: LITERAL without delimiters
-        let pipeline = r#"PIPE HOLE
-| LITERAL No input
-| CONSOLE"#;
-        let (output, input_count, output_count) = execute_pipeline("ignored", pipeline).unwrap();
-        assert_eq!(input_count, 0); // HOLE generates 0 records
-        assert_eq!(output_count, 1); // LITERAL adds 1
-        assert_eq!(output, "No input");
+    fn test_fill_accepts_delimited_character() {
+        assert!(matches!(
+            parse_command("JUSTIFY 0,5 RIGHT FILL /0/").unwrap(),
+            Command::Justify { fill: '0', .. }
+        ));
+        assert!(matches!(
+            parse_command("RESEQ 0,3 1,1 FILL .*.").unwrap(),
+            Command::Reseq { fill: '*', .. }
+        ));
     }
 
     #[test]
-    fn test_hole_in_middle() {
-        // HOLE discards upstream, downstream gets nothing (before LITERAL)
-        // CMS-style: LITERAL without delimiters
-        let pipeline = r#"PIPE CONSOLE
-| COUNT
-| HOLE
-| LITERAL Discarded
-| CONSOLE"#;
-        let (output, input_count, output_count) = execute_pipeline("A\nB\nC", pipeline).unwrap();
-        assert_eq!(input_count, 3);
-        assert_eq!(output_count, 1);
-        assert_eq!(output, "Discarded");
+    fn test_parse_pivot() {
+        match parse_command("PIVOT key=0,8 value=10,20 NAME:0 DEPT:10 SALARY:20").unwrap() {
+            Command::Pivot {
+                key,
+                value,
+                group,
+                columns,
+            } => {
+                assert_eq!((key, value, group), ((0, 8), (10, 20), None));
+                assert_eq!(
+                    columns,
+                    vec![
+                        ("NAME".to_string(), 0),
+                        ("DEPT".to_string(), 10),
+                        ("SALARY".to_string(), 20)
+                    ]
+                );
+            }
+            other => panic!("Expected Pivot, got {other:?}"),
+        }
+        assert!(matches!(
+            parse_command("PIVOT VALUE=10,20 Group=0,4 KEY=5,4 A:0").unwrap(),
+            Command::Pivot {
+                group: Some((0, 4)),
+                ..
+            }
+        ));
+        for bad in [
+            "PIVOT value=10,20 NAME:0",
+            "PIVOT key=0,8 value=10,20",
+            "PIVOT key=0,8 value=10,20 NAME:80",
+            "PIVOT key=0,8 value=10,20 NAME",
+            "PIVOT key=0,8 value=10,20 by=0,4 NAME:0",
+            "PIVOT key=0 value=10,20 NAME:0",
+        ] {
+            assert!(parse_command(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_hole_as_last() {
-        // HOLE as last discards all output
-        let pipeline = r#"PIPE CONSOLE
-| HOLE"#;
-        let (output, input_count, output_count) = execute_pipeline("test", pipeline).unwrap();
-        assert_eq!(input_count, 1); // CONSOLE read 1 record
-        assert_eq!(output_count, 0); // HOLE discarded it
-        assert!(output.is_empty());
+    fn test_execute_pivot_blank_separated_groups() {
+        // Empty input lines are not records; a line of spaces is a blank record
+        let input = "NAME      SMITH\nDEPT      SALES\nSALARY    00050000\n \n\
+                     SALARY    00075000\nNAME      JONES\nPHONE     555-1234\n";
+        let pipeline =
+            "PIPE CONSOLE | PIVOT key=0,8 value=10,20 NAME:0 DEPT:10 SALARY:20 | CONSOLE";
+        let (output, input_count, output_count) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!((input_count, output_count), (7, 2));
+        // Keys may come in any order; unknown keys are ignored
+        assert_eq!(
+            output,
+            "SMITH     SALES     00050000\nJONES               00075000"
+        );
+
+        // BLOCK's separators delimit fixed-size groups
+        let input = "NAME      SMITH\nDEPT      SALES\nNAME      JONES\nDEPT      ENGINEER";
+        let pipeline =
+            "PIPE CONSOLE | BLOCK 2 | PIVOT key=0,8 value=10,20 NAME:0 DEPT:10 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "SMITH     SALES\nJONES     ENGINEER");
     }
 
     #[test]
-    fn test_debug_info_new() {
-        let info = DebugInfo::new("CONSOLE".to_string(), 3, 2);
-        assert_eq!(info.stage_name, "CONSOLE");
-        assert_eq!(info.input_count, 3);
-        assert_eq!(info.output_count, 2);
-        assert!(info.input_records.is_none());
-        assert!(info.output_records.is_none());
+    fn test_execute_pivot_group_field() {
+        let input = "E1 NAME SMITH\nE1 DEPT SALES\nE2 NAME JONES\nE2 DEPT ENGINEER\nE2 DEPT ADMIN";
+        let pipeline = "PIPE CONSOLE | PIVOT group=0,2 key=3,4 value=8,10 NAME:0 DEPT:8 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        // A repeated key keeps its last value
+        assert_eq!(output, "SMITH   SALES\nJONES   ADMIN");
     }
 
     #[test]
-    fn test_debug_info_with_records() {
-        let input_records = vec![
-            Record::from_str("ABC"),
-            Record::from_str("DEF"),
-            Record::from_str("GHI"),
-        ];
-        let output_records = vec![Record::from_str("ABC"), Record::from_str("DEF")];
-        let info = DebugInfo::with_records(
-            "FILTER".to_string(),
-            3,
-            2,
-            input_records.clone(),
-            output_records.clone(),
+    fn test_execute_mask_salary() {
+        let input = include_str!("../specs/input-fixed-80.data");
+        let pipeline = "PIPE CONSOLE | TAKE 2 | MASK 28,8 | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(
+            output,
+            "SMITH   JOHN      SALES     ********\nJONES   MARY      ENGINEER  ********"
         );
-        assert_eq!(info.stage_name, "FILTER");
-        assert_eq!(info.input_count, 3);
-        assert_eq!(info.output_count, 2);
-        assert!(info.input_records.is_some());
-        assert!(info.output_records.is_some());
-        assert_eq!(info.input_records.unwrap(), input_records);
-        assert_eq!(info.output_records.unwrap(), output_records);
-    }
 
-    #[test]
-    fn test_execute_pipeline_debug_without_callbacks() {
-        let pipeline = r#"PIPE CONSOLE
-| CONSOLE"#;
-        let input = "A\nB\nC";
-        let (output, input_count, output_count, debug_info) =
-            execute_pipeline_debug(input, pipeline, &None).unwrap();
-        assert_eq!(input_count, 3);
-        assert_eq!(output_count, 3);
-        assert_eq!(output, "A\nB\nC");
-        assert_eq!(debug_info.len(), 2);
-        assert_eq!(debug_info[0].stage_name, "CONSOLE");
-        assert_eq!(debug_info[1].stage_name, "CONSOLE");
-        assert!(debug_info[0].input_records.is_none());
-        assert!(debug_info[0].output_records.is_none());
+        let pipeline = "PIPE CONSOLE | TAKE 1 | MASK 28,8; 8,10 FILL /X/ | CONSOLE";
+        let (output, _, _) = execute_pipeline(input, pipeline).unwrap();
+        assert_eq!(output, "SMITH   XXXXXXXXXXSALES     XXXXXXXX");
     }
 }
@@ -2,15 +2,21 @@
 
 use thiserror::Error;
 
+use crate::record::Record;
+
 /// Errors that can occur during pipeline processing.
 #[derive(Debug, Error)]
 pub enum PipelineError {
-    /// Field position is out of bounds for the record.
-    #[error("field position {start}:{length} exceeds record length {record_len}")]
+    /// A stage's `pos,len` field spec extends past the end of the record
+    /// (reported only when `ExecuteOptions::strict_bounds` is set).
+    #[error(
+        "{stage} field {pos},{len} extends past column {}",
+        crate::record::RECORD_WIDTH
+    )]
     FieldOutOfBounds {
-        start: usize,
-        length: usize,
-        record_len: usize,
+        stage: String,
+        pos: usize,
+        len: usize,
     },
 
     /// Invalid record length (must be exactly 80 bytes).
@@ -21,9 +27,61 @@ pub enum PipelineError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// Pipeline text failed to parse or validate.
+    #[error("parse error: {0}")]
+    Parse(String),
+
     /// Custom stage error.
     #[error("stage error: {0}")]
     Stage(String),
+
+    /// The pipeline read input records but produced no output
+    /// (reported only when `ExecuteOptions::fail_on_empty` is set).
+    #[error("pipeline produced no output from {input_count} input records")]
+    EmptyOutput { input_count: usize },
+
+    /// The pipeline does not end in an explicit sink (`CONSOLE` or `HOLE`),
+    /// so its output would be silently discarded (reported only when
+    /// `ExecuteOptions::require_explicit_sink` is set).
+    #[error("pipeline ends with {last}, not CONSOLE or HOLE; its output would be discarded")]
+    NoSink { last: String },
+
+    /// The pipeline produced more than the allowed number of output records
+    /// (reported only when `ExecuteOptions::max_output_records` is set).
+    #[error("pipeline output exceeded the limit of {limit} records")]
+    OutputLimitExceeded { limit: usize },
+
+    /// A stage output more than `limit` records per input record (reported
+    /// only when `ExecuteOptions::expansion_factor_limit` is set).
+    #[error("stage {stage_index} ({stage}) output more than {limit} records per input record")]
+    ExpansionLimitExceeded {
+        stage_index: usize,
+        stage: String,
+        limit: usize,
+    },
+
+    /// `CHANGE ... REPEAT` was still changing a record after `passes`
+    /// substitution passes (see `CHANGE_REPEAT_LIMIT`).
+    #[error("CHANGE \"{old}\" to \"{new}\" did not converge after {passes} passes")]
+    ChangeDidNotConverge {
+        old: String,
+        new: String,
+        passes: usize,
+    },
+
+    /// `INCLUDE` expansion revisited a sub-pipeline, or nested deeper than
+    /// `MAX_INCLUDE_DEPTH`. `chain` lists the names expanded, outermost first.
+    #[error("INCLUDE cycle: {}", .chain.join(" -> "))]
+    IncludeCycle { chain: Vec<String> },
+
+    /// A stage failed at runtime while processing a record.
+    #[error("stage {stage_index} ({stage}) failed on {record:?}: {source}")]
+    StageFailed {
+        stage_index: usize,
+        stage: String,
+        record: Record,
+        source: Box<PipelineError>,
+    },
 }
 
 /// Result type for pipeline operations.
@@ -0,0 +1,86 @@
+//! Named sub-pipelines for the `INCLUDE` stage.
+//!
+//! A [`PipelineRegistry`] maps names to parsed stage lists. `INCLUDE name`
+//! splices the named stages into the pipeline when it is parsed, so shared
+//! prefixes (read, then normalize) can be written once. Snippets may
+//! themselves `INCLUDE` other snippets; expansion stops with
+//! `PipelineError::IncludeCycle` past [`MAX_INCLUDE_DEPTH`] levels or when
+//! a snippet includes itself.
+//!
+//! Build snippets with [`crate::parse_snippet`], which leaves nested
+//! `INCLUDE`s to be expanded when the including pipeline is parsed, and pass
+//! the registry in `ExecuteOptions::pipelines`.
+
+use std::collections::HashMap;
+
+use crate::Command;
+
+/// How deeply `INCLUDE`s may nest before expansion gives up.
+pub const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Named sub-pipelines available to `INCLUDE`.
+///
+/// Names are matched case-insensitively. The default registry is empty.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{
+///     ExecuteOptions, PipelineRegistry, execute_pipeline_with_options, parse_snippet,
+/// };
+///
+/// let options = ExecuteOptions {
+///     pipelines: PipelineRegistry::default()
+///         .with("normalize", parse_snippet("UPPER | CHANGE \"-\" \" \"").unwrap()),
+///     ..ExecuteOptions::default()
+/// };
+/// let pipeline = "PIPE CONSOLE | INCLUDE normalize | CONSOLE";
+/// let (output, _, _, _) =
+///     execute_pipeline_with_options("hello-world", pipeline, &options).unwrap();
+/// assert_eq!(output, "HELLO WORLD");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PipelineRegistry {
+    pipelines: HashMap<String, Vec<Command>>,
+}
+
+impl PipelineRegistry {
+    /// Add (or replace) a named sub-pipeline, builder style.
+    #[must_use]
+    pub fn with(mut self, name: &str, commands: Vec<Command>) -> Self {
+        self.register(name, commands);
+        self
+    }
+
+    /// Add (or replace) a named sub-pipeline.
+    pub fn register(&mut self, name: &str, commands: Vec<Command>) {
+        self.pipelines.insert(name.to_lowercase(), commands);
+    }
+
+    /// Look up a sub-pipeline's stages by name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[Command]> {
+        self.pipelines.get(&name.to_lowercase()).map(Vec::as_slice)
+    }
+
+    /// Registered names, sorted.
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.pipelines.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let registry = PipelineRegistry::default().with("Normalize", vec![Command::Upper]);
+        assert_eq!(registry.get("NORMALIZE").map(<[Command]>::len), Some(1));
+        assert!(registry.get("other").is_none());
+        assert_eq!(registry.names(), vec!["normalize"]);
+    }
+}
@@ -33,16 +33,37 @@
 //! assert_eq!(result.len(), 2);
 //! ```
 
+pub mod compare;
 pub mod dsl;
 pub mod error;
+pub mod include;
 pub mod pipeline;
 pub mod record;
 pub mod stage;
+pub mod transform;
 
+pub use compare::{LEFT_MARKER, RIGHT_MARKER, execute_diff, execute_diff_keyed};
 pub use dsl::{
-    Command, DebugCallbacks, DebugInfo, execute_pipeline, execute_pipeline_debug, parse_commands,
+    ASSERT_MARKER, BuildItem, CHANGE_REPEAT_LIMIT, Command, CommandKind, CommandSpec, CompareOp,
+    DISABLED_PREFIX, DOCUMENT_INPUT_MARKER, DateFormat, DebugCallbacks, DebugInfo,
+    EXPAND_MAX_COUNT, ExecuteOptions, FieldKind, JustifySide, LengthStats, MAIN_CHANNEL, MatchMode,
+    RecordHasher, RecordLayout, SortDir, SortKey, StageSummary, TruncationWarning, WRAP_MARKER,
+    build_record, canonicalize, change_text, change_until_stable, check_expansion,
+    check_includes_expanded, chop_record, command_catalog, compare_by_keys, crosstab_records,
+    decode_field, default_field, disabled_stage, execute_document, execute_parsed,
+    execute_parsed_with_options, execute_parsed_with_sink, execute_pipeline,
+    execute_pipeline_debug, execute_pipeline_with_options, execute_pipeline_with_sink,
+    expand_header, expand_record, expand_tabs, explain, field_record, filter_keeps, flag_record,
+    frequency_records, generate_records, input_lines, join_continuations, json_record, mask_record,
+    norm_record, normalize_number, parse_commands, parse_commands_with_options,
+    parse_commands_with_transforms, parse_document, parse_layout, parse_snippet, pivot_group,
+    pivot_group_ends, read_input_records, reformat_date, segment_header, select_separated,
+    shuffle_records, sort_items, split_header, truncation_warnings, validate_pipeline_text,
+    verify_record, wrap_record, zip_pair,
 };
 pub use error::PipelineError;
+pub use include::{MAX_INCLUDE_DEPTH, PipelineRegistry};
 pub use pipeline::{Pipeline, from_lines, from_strings};
 pub use record::{RECORD_WIDTH, Record};
 pub use stage::{Filter, Inspect, Map, Reformat, Select, Stage};
+pub use transform::{TransformFn, TransformRegistry};
@@ -411,6 +411,61 @@ where
         self.iter.fold(init, f)
     }
 
+    /// Reduces records to a single record by repeatedly applying a function.
+    ///
+    /// Returns `None` if the pipeline is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::{Pipeline, Record};
+    ///
+    /// let records = vec![
+    ///     Record::from_str("SMITH   JOHN      SALES     00050000"),
+    ///     Record::from_str("DOE     JANE      SALES     00060000"),
+    /// ];
+    ///
+    /// // Keep the record with the highest salary
+    /// let top = Pipeline::new(records.into_iter())
+    ///     .reduce(|best, r| {
+    ///         if r.field(28, 8) > best.field(28, 8) { r } else { best }
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(top.field(0, 8).trim(), "DOE");
+    /// ```
+    pub fn reduce<F>(self, f: F) -> Option<Record>
+    where
+        F: FnMut(Record, Record) -> Record,
+    {
+        self.iter.reduce(f)
+    }
+
+    /// Calls a function on each record, consuming the pipeline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::{Pipeline, Record};
+    ///
+    /// let records = vec![
+    ///     Record::from_str("SMITH   JOHN      SALES     00050000"),
+    ///     Record::from_str("DOE     JANE      SALES     00060000"),
+    /// ];
+    ///
+    /// let mut names = Vec::new();
+    /// Pipeline::new(records.into_iter())
+    ///     .for_each(|r| names.push(r.field(0, 8).trim().to_string()));
+    ///
+    /// assert_eq!(names, vec!["SMITH", "DOE"]);
+    /// ```
+    pub fn for_each<F>(self, f: F)
+    where
+        F: FnMut(Record),
+    {
+        self.iter.for_each(f)
+    }
+
     /// Checks if any record matches a predicate.
     pub fn any<F>(mut self, mut predicate: F) -> bool
     where
@@ -565,6 +620,33 @@ mod tests {
         assert_eq!(total, 240000); // 50000 + 75000 + 60000 + 55000
     }
 
+    #[test]
+    fn test_reduce() {
+        let top = Pipeline::new(sample_records().into_iter())
+            .reduce(|best, r| {
+                if r.field(28, 8) > best.field(28, 8) {
+                    r
+                } else {
+                    best
+                }
+            })
+            .unwrap();
+        assert!(top.field_eq(0, 8, "JONES"));
+
+        let empty: Vec<Record> = vec![];
+        assert!(Pipeline::new(empty.into_iter()).reduce(|a, _| a).is_none());
+    }
+
+    #[test]
+    fn test_for_each() {
+        let mut total = 0u64;
+        Pipeline::new(sample_records().into_iter())
+            .filter(|r| r.field_eq(18, 10, "SALES"))
+            .for_each(|r| total += r.field(28, 8).trim().parse::<u64>().unwrap_or(0));
+
+        assert_eq!(total, 110000);
+    }
+
     #[test]
     fn test_any_all() {
         let has_sales =
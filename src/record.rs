@@ -3,7 +3,12 @@
 //! The 80-byte record width matches the historical punch card format used
 //! on mainframe systems. Each record is exactly 80 bytes, padded with spaces
 //! if the source data is shorter.
+//!
+//! Records hold ASCII only. Text is converted one character per column:
+//! a non-ASCII character such as `é` becomes a single `?`, so field
+//! positions line up with the characters of the source line.
 
+use std::cmp::Ordering;
 use std::fmt;
 
 /// The standard record width (punch card width).
@@ -35,7 +40,15 @@ pub const RECORD_WIDTH: usize = 80;
 /// - Columns 19-28: Department (positions 18-27)
 /// - Columns 29-36: Salary (positions 28-35)
 /// - etc.
-#[derive(Clone, PartialEq, Eq, Hash)]
+///
+/// # Equality and Ordering
+///
+/// Records compare by their full padded bytes. [`Record::from_str`] always
+/// pads with spaces, so `"ABC"` and `"ABC   "` build equal records; use
+/// [`Record::eq_content`] to also ignore trailing spaces on records padded
+/// some other way. Ordering is byte-wise (ASCII collation), so a
+/// `Vec<Record>` can be sorted directly.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Record {
     data: [u8; RECORD_WIDTH],
 }
@@ -61,8 +74,9 @@ impl Record {
 
     /// Creates a record from a string slice.
     ///
-    /// The string is truncated to 80 bytes or padded with spaces if shorter.
-    /// Only ASCII characters are supported; non-ASCII bytes are replaced with '?'.
+    /// The string is truncated to 80 characters or padded with spaces if
+    /// shorter. Only ASCII is stored; each non-ASCII character is replaced
+    /// with a single '?', so it still occupies exactly one column.
     ///
     /// Note: This method is named `from_str` for convenience but does not
     /// implement `std::str::FromStr` because record parsing never fails.
@@ -79,21 +93,37 @@ impl Record {
     #[must_use]
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
-        let mut record = Self::new();
-        let bytes = s.as_bytes();
-        let len = bytes.len().min(RECORD_WIDTH);
+        Self::from_str_padded(s, ' ')
+    }
 
-        for (i, &byte) in bytes.iter().take(len).enumerate() {
-            // Replace non-ASCII with '?' (simulating EBCDIC conversion issues)
-            record.data[i] = if byte.is_ascii() { byte } else { b'?' };
+    /// Creates a record from a string slice, padding with `fill` instead of
+    /// spaces.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str_padded("42", '0');
+    /// assert_eq!(record.field(0, 6), "420000");
+    /// ```
+    #[must_use]
+    pub fn from_str_padded(s: &str, fill: char) -> Self {
+        let mut record = Self {
+            data: [column_byte(fill); RECORD_WIDTH],
+        };
+        for (slot, c) in record.data.iter_mut().zip(s.chars()) {
+            *slot = column_byte(c);
         }
-
         record
     }
 
     /// Creates a record from raw bytes.
     ///
     /// The bytes are truncated to 80 or padded with spaces if shorter.
+    /// Conversion is per byte: each non-ASCII byte becomes '?', so a
+    /// multibyte UTF-8 character takes several columns. Use [`Record::from_str`]
+    /// for text.
     ///
     /// # Example
     ///
@@ -160,6 +190,178 @@ impl Record {
         std::str::from_utf8(&self.data[start..end]).unwrap_or("")
     }
 
+    /// Iterate over the fields at each `(start, length)` of `layout`, in order.
+    ///
+    /// Each field is exactly what [`field`](Self::field) returns, so ranges
+    /// past the record are clamped and padding is kept; trim if you need to.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   JOHN      SALES");
+    /// let fields: Vec<&str> = record.fields(&[(0, 8), (18, 10)]).map(str::trim).collect();
+    /// assert_eq!(fields, ["SMITH", "SALES"]);
+    /// ```
+    pub fn fields(&self, layout: &[(usize, usize)]) -> impl Iterator<Item = &str> {
+        layout
+            .iter()
+            .map(|&(start, length)| self.field(start, length))
+    }
+
+    /// Iterate over `(name, field)` pairs for each `(name, start, length)` of
+    /// `layout`, in order.
+    ///
+    /// Fields are sliced as by [`fields`](Self::fields). The layout has the
+    /// shape of [`RecordLayout::fields`](crate::RecordLayout::fields).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   JOHN");
+    /// let layout = [("last".to_string(), 0, 8), ("first".to_string(), 8, 10)];
+    /// let (name, value) = record.named_fields(&layout).nth(1).unwrap();
+    /// assert_eq!((name, value.trim()), ("first", "JOHN"));
+    /// ```
+    pub fn named_fields<'a>(
+        &'a self,
+        layout: &'a [(String, usize, usize)],
+    ) -> impl Iterator<Item = (&'a str, &'a str)> {
+        layout
+            .iter()
+            .map(|(name, start, length)| (name.as_str(), self.field(*start, *length)))
+    }
+
+    /// Returns a new record with `f` applied to each of the 80 columns.
+    ///
+    /// Column `i` of the result is `f` of column `i`, so the width and the
+    /// position of every character, trailing spaces included, are kept. A
+    /// non-ASCII result is stored as `?`, as in [`from_str`](Self::from_str).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("Smith   John").map_chars(|c| c.to_ascii_uppercase());
+    /// assert_eq!(record.field(0, 14), "SMITH   JOHN  ");
+    /// ```
+    #[must_use]
+    pub fn map_chars(&self, f: impl Fn(char) -> char) -> Record {
+        Self {
+            data: self.data.map(|byte| column_byte(f(char::from(byte)))),
+        }
+    }
+
+    /// Returns a new record holding just one field, moved to column 0.
+    ///
+    /// The field's bytes are copied unchanged (including any leading
+    /// spaces) and the rest of the record is blank. Like [`field`](Self::field),
+    /// a field extending past column 80 is truncated, so slicing wholly
+    /// beyond the record yields a blank record.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   JOHN      SALES");
+    /// assert_eq!(record.slice(8, 10).as_str().trim_end(), "JOHN");
+    /// assert!(record.slice(90, 5).is_blank());
+    /// ```
+    #[must_use]
+    pub fn slice(&self, start: usize, length: usize) -> Self {
+        Self::from_bytes(self.field(start, length).as_bytes())
+    }
+
+    /// Returns a new record with `other` placed directly after this
+    /// record's content.
+    ///
+    /// This record's content ends at its last non-space column; trailing
+    /// padding is not kept. `other` is copied from its column 0, leading
+    /// spaces included, and whatever does not fit in 80 columns is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let left = Record::from_str("SMITH");
+    /// let right = Record::from_str(" JOHN");
+    /// assert_eq!(left.concat(&right).as_str().trim_end(), "SMITH JOHN");
+    /// ```
+    #[must_use]
+    pub fn concat(&self, other: &Record) -> Self {
+        let used = self.len_content();
+        let mut record = self.clone();
+        record.data[used..].copy_from_slice(&other.data[..RECORD_WIDTH - used]);
+        record
+    }
+
+    /// Returns the character in column `pos` (0-based), or `None` past
+    /// column 80.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH");
+    /// assert_eq!(record.char_at(0), Some('S'));
+    /// assert_eq!(record.char_at(5), Some(' '));
+    /// assert_eq!(record.char_at(80), None);
+    /// ```
+    #[must_use]
+    pub fn char_at(&self, pos: usize) -> Option<char> {
+        self.data.get(pos).map(|&b| char::from(b))
+    }
+
+    /// Sets column `pos` (0-based) to `c`.
+    ///
+    /// Like [`set_field`](Self::set_field), a position past column 80 is
+    /// ignored, and a non-ASCII character is stored as `?`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let mut record = Record::from_str("SMITH");
+    /// record.set_char(0, 'B');
+    /// record.set_char(80, 'X');
+    /// assert_eq!(record.as_str().trim_end(), "BMITH");
+    /// ```
+    pub fn set_char(&mut self, pos: usize, c: char) {
+        if let Some(slot) = self.data.get_mut(pos) {
+            *slot = column_byte(c);
+        }
+    }
+
+    /// Returns the number of columns up to and including the last
+    /// non-space one.
+    ///
+    /// Leading and interior spaces count, since they position fields; only
+    /// the trailing padding is excluded, so a blank record has length 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// assert_eq!(Record::from_str("  AB CD  ").len_content(), 7);
+    /// assert_eq!(Record::new().len_content(), 0);
+    /// ```
+    #[must_use]
+    pub fn len_content(&self) -> usize {
+        self.data
+            .iter()
+            .rposition(|&b| b != b' ')
+            .map_or(0, |last| last + 1)
+    }
+
     /// Sets a field in the record.
     ///
     /// The value is truncated if longer than the field length, or padded
@@ -177,6 +379,21 @@ impl Record {
     /// assert_eq!(record.field(8, 10), "JOHN      ");
     /// ```
     pub fn set_field(&mut self, start: usize, length: usize, value: &str) {
+        self.set_field_padded(start, length, value, ' ');
+    }
+
+    /// Sets a field in the record, padding with `fill` instead of spaces.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let mut record = Record::new();
+    /// record.set_field_padded(0, 6, "AB", '.');
+    /// assert_eq!(record.field(0, 8), "AB....  ");
+    /// ```
+    pub fn set_field_padded(&mut self, start: usize, length: usize, value: &str, fill: char) {
         let end = (start + length).min(RECORD_WIDTH);
         let start = start.min(RECORD_WIDTH);
 
@@ -184,18 +401,108 @@ impl Record {
             return;
         }
 
-        // Clear the field with spaces first
+        // Clear the field with the fill character first
         for byte in &mut self.data[start..end] {
-            *byte = b' ';
+            *byte = column_byte(fill);
         }
 
-        // Copy the value
-        let value_bytes = value.as_bytes();
-        let copy_len = value_bytes.len().min(end - start);
+        // Copy the value, one column per character
+        for (slot, c) in self.data[start..end].iter_mut().zip(value.chars()) {
+            *slot = column_byte(c);
+        }
+    }
 
-        for (i, &byte) in value_bytes.iter().take(copy_len).enumerate() {
-            self.data[start + i] = if byte.is_ascii() { byte } else { b'?' };
+    /// Parses a field as a signed integer, ignoring surrounding spaces.
+    ///
+    /// Returns `None` if the field is blank or not a valid number.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   00050000");
+    /// assert_eq!(record.field_i64(8, 8), Some(50000));
+    /// assert_eq!(record.field_i64(0, 8), None);
+    /// ```
+    #[must_use]
+    pub fn field_i64(&self, start: usize, length: usize) -> Option<i64> {
+        self.field(start, length).trim().parse().ok()
+    }
+
+    /// Returns true if the field holds a signed integer, ignoring
+    /// surrounding spaces. A blank field is not numeric.
+    #[must_use]
+    pub fn field_is_numeric(&self, start: usize, length: usize) -> bool {
+        self.field_i64(start, length).is_some()
+    }
+
+    /// Returns true if the field holds only ASCII letters and embedded
+    /// spaces, ignoring surrounding spaces. A blank field is not alphabetic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("VAN DYKE00050000");
+    /// assert!(record.field_is_alpha(0, 8));
+    /// assert!(!record.field_is_alpha(8, 8));
+    /// assert!(record.field_is_numeric(8, 8));
+    /// ```
+    #[must_use]
+    pub fn field_is_alpha(&self, start: usize, length: usize) -> bool {
+        let value = self.field(start, length).trim();
+        !value.is_empty() && value.bytes().all(|b| b.is_ascii_alphabetic() || b == b' ')
+    }
+
+    /// Writes an integer into a field, right-justified and zero-padded.
+    ///
+    /// If the number has more digits than the field is wide, the high-order
+    /// digits (and any minus sign) are dropped, like an odometer rolling over.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let mut record = Record::new();
+    /// record.set_field_i64(0, 4, 42);
+    /// assert_eq!(record.field(0, 4), "0042");
+    /// record.set_field_i64(0, 4, 123456);
+    /// assert_eq!(record.field(0, 4), "3456");
+    /// ```
+    pub fn set_field_i64(&mut self, start: usize, length: usize, value: i64) {
+        let text = format!("{value:0length$}");
+        let low_order = &text[text.len().saturating_sub(length)..];
+        self.set_field(start, length, low_order);
+    }
+
+    /// Writes a signed integer right-justified in a field, padded on the
+    /// left with `fill`.
+    ///
+    /// A `'0'` fill behaves exactly like [`Record::set_field_i64`], with the
+    /// sign ahead of the zeros; any other fill goes ahead of the sign.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let mut record = Record::new();
+    /// record.set_field_i64_padded(0, 6, -42, '*');
+    /// assert_eq!(record.field(0, 6), "***-42");
+    /// ```
+    pub fn set_field_i64_padded(&mut self, start: usize, length: usize, value: i64, fill: char) {
+        if fill == '0' {
+            return self.set_field_i64(start, length, value);
         }
+        let text = value.to_string();
+        let low_order = &text[text.len().saturating_sub(length)..];
+        let padded: String = std::iter::repeat_n(fill, length - low_order.len())
+            .chain(low_order.chars())
+            .collect();
+        self.set_field(start, length, &padded);
     }
 
     /// Returns true if the record is blank (all spaces).
@@ -204,7 +511,33 @@ impl Record {
         self.data.iter().all(|&b| b == b' ')
     }
 
-    /// Compares a field to a value.
+    /// Compares two records ignoring trailing spaces.
+    ///
+    /// Leading and interior spaces are significant, since they position the
+    /// fixed-width fields; only the padding after the last non-space byte is
+    /// ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let mut edited = Record::from_str("ABC   XYZ");
+    /// edited.set_field(6, 3, "");
+    /// assert!(edited.eq_content(&Record::from_str("ABC")));
+    /// assert!(Record::from_str("ABC").eq_content(&Record::from_str("ABC  ")));
+    /// assert!(!Record::from_str("ABC").eq_content(&Record::from_str(" ABC")));
+    /// ```
+    #[must_use]
+    pub fn eq_content(&self, other: &Record) -> bool {
+        self.as_str().trim_end_matches(' ') == other.as_str().trim_end_matches(' ')
+    }
+
+    /// Compares a field to a value, ignoring surrounding whitespace.
+    ///
+    /// Both the field and the value are trimmed before comparing, so
+    /// padding spaces in the fixed-width field never affect the result.
+    /// Use `field_eq_exact` to compare the raw padded slice.
     ///
     /// This is a convenience method for filtering operations.
     ///
@@ -219,9 +552,111 @@ impl Record {
     /// ```
     #[must_use]
     pub fn field_eq(&self, start: usize, length: usize, value: &str) -> bool {
+        self.field_eq_trim(start, length, value)
+    }
+
+    /// Compares the trimmed field to the trimmed value.
+    ///
+    /// Identical to `field_eq`; the name makes the trimming explicit at
+    /// call sites where the distinction matters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("  SALES   ");
+    /// assert!(record.field_eq_trim(0, 10, "SALES"));
+    /// assert!(!record.field_eq_exact(0, 10, "SALES"));
+    /// ```
+    #[must_use]
+    pub fn field_eq_trim(&self, start: usize, length: usize, value: &str) -> bool {
         self.field(start, length).trim() == value.trim()
     }
 
+    /// Compares the trimmed field to the trimmed value, ignoring ASCII case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   JOHN      Sales     ");
+    /// assert!(record.field_eq_ci(18, 10, "SALES"));
+    /// assert!(!record.field_eq(18, 10, "SALES"));
+    /// ```
+    #[must_use]
+    pub fn field_eq_ci(&self, start: usize, length: usize, value: &str) -> bool {
+        self.field(start, length)
+            .trim()
+            .eq_ignore_ascii_case(value.trim())
+    }
+
+    /// Returns true if the trimmed field lies in the inclusive range
+    /// `low..=high`.
+    ///
+    /// Compares as signed integers when the field and both bounds are
+    /// numeric, otherwise as trimmed strings. A blank field is never in range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("SMITH   00060000");
+    /// assert!(record.field_in_range(8, 8, "00055000", "00075000"));
+    /// assert!(record.field_in_range(8, 8, "60000", "60000"));
+    /// assert!(record.field_in_range(0, 8, "S", "T"));
+    /// assert!(!record.field_in_range(20, 8, "", "Z"));
+    /// ```
+    #[must_use]
+    pub fn field_in_range(&self, start: usize, length: usize, low: &str, high: &str) -> bool {
+        let value = self.field(start, length).trim();
+        if value.is_empty() {
+            return false;
+        }
+        let (low, high) = (low.trim(), high.trim());
+        match (
+            value.parse::<i64>(),
+            low.parse::<i64>(),
+            high.parse::<i64>(),
+        ) {
+            (Ok(n), Ok(lo), Ok(hi)) => lo <= n && n <= hi,
+            _ => low <= value && value <= high,
+        }
+    }
+
+    /// Compares two fields of this record.
+    ///
+    /// Compares as signed integers when both trimmed fields are numeric,
+    /// otherwise as trimmed strings (a blank field is the empty string).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use pipelines_rs::Record;
+    ///
+    /// let record = Record::from_str("00090000 75000    SMITH   SMITH");
+    /// assert_eq!(record.compare_fields(0, 8, 9, 8), Ordering::Greater);
+    /// assert_eq!(record.compare_fields(18, 8, 26, 8), Ordering::Equal);
+    /// ```
+    #[must_use]
+    pub fn compare_fields(
+        &self,
+        start: usize,
+        length: usize,
+        other_start: usize,
+        other_length: usize,
+    ) -> Ordering {
+        let left = self.field(start, length).trim();
+        let right = self.field(other_start, other_length).trim();
+        match (left.parse::<i64>(), right.parse::<i64>()) {
+            (Ok(l), Ok(r)) => l.cmp(&r),
+            _ => left.cmp(right),
+        }
+    }
+
     /// Compares a field to a value with exact matching (including spaces).
     #[must_use]
     pub fn field_eq_exact(&self, start: usize, length: usize, value: &str) -> bool {
@@ -229,16 +664,39 @@ impl Record {
     }
 
     /// Returns true if a field starts with the given prefix.
+    ///
+    /// Leading spaces in the field are ignored; the prefix is matched as-is.
     #[must_use]
     pub fn field_starts_with(&self, start: usize, length: usize, prefix: &str) -> bool {
         self.field(start, length).trim_start().starts_with(prefix)
     }
 
     /// Returns true if a field contains the given substring.
+    ///
+    /// The field is searched as extracted, all `length` columns of it, so
+    /// a substring with trailing spaces can match the field's padding. See
+    /// [`field_contains_trimmed`](Self::field_contains_trimmed) to ignore
+    /// the padding.
     #[must_use]
     pub fn field_contains(&self, start: usize, length: usize, substring: &str) -> bool {
         self.field(start, length).contains(substring)
     }
+
+    /// Returns true if a field, without leading and trailing spaces,
+    /// contains the given substring.
+    ///
+    /// Spaces inside the value still count, but padding does not: `"SALES "`
+    /// is not found in a field holding `SALES` followed by padding.
+    #[must_use]
+    pub fn field_contains_trimmed(&self, start: usize, length: usize, substring: &str) -> bool {
+        self.field(start, length).trim().contains(substring)
+    }
+}
+
+/// The byte stored for one character: itself if ASCII, otherwise '?'
+/// (simulating EBCDIC conversion issues).
+fn column_byte(c: char) -> u8 {
+    if c.is_ascii() { c as u8 } else { b'?' }
 }
 
 impl Default for Record {
@@ -349,12 +807,117 @@ mod tests {
         assert_eq!(record.field(0, 5), "LONGE");
     }
 
+    #[test]
+    fn test_field_i64() {
+        let record = Record::from_str("  -42 abc 0007");
+        assert_eq!(record.field_i64(0, 5), Some(-42));
+        assert_eq!(record.field_i64(6, 3), None);
+        assert_eq!(record.field_i64(10, 4), Some(7));
+        assert_eq!(record.field_i64(20, 4), None);
+    }
+
+    #[test]
+    fn test_set_field_i64_pads_and_truncates() {
+        let mut record = Record::new();
+        record.set_field_i64(0, 5, 7);
+        assert_eq!(record.field(0, 5), "00007");
+        record.set_field_i64(0, 5, -7);
+        assert_eq!(record.field(0, 5), "-0007");
+        // High-order digits are dropped when the value is too wide
+        record.set_field_i64(0, 3, 12345);
+        assert_eq!(record.field(0, 5), "34507");
+    }
+
     #[test]
     fn test_non_ascii_replaced() {
         let record = Record::from_str("Hello\u{00E9}World"); // e with acute
         assert!(record.as_str().contains('?'));
     }
 
+    #[test]
+    fn test_multibyte_characters_take_one_column() {
+        // 'É' (2 bytes) ends the first field, '語' (3 bytes) starts the second
+        let record = Record::from_str("JOSÉ    語MARIA  SALES");
+        assert_eq!(record.field(0, 8), "JOS?    ");
+        assert_eq!(record.field(8, 8), "?MARIA  ");
+        assert_eq!(record.field(16, 5), "SALES");
+        assert_eq!(Record::from_str(&"é".repeat(100)).as_str(), "?".repeat(80));
+    }
+
+    #[test]
+    fn test_set_field_multibyte_value() {
+        let mut record = Record::from_str("AAAAAAAAAA");
+        record.set_field(2, 4, "ñandú");
+        // The columns after the field are untouched
+        assert_eq!(record.as_str().trim_end(), "AA?andAAAA");
+    }
+
+    #[test]
+    fn test_padding_defaults_to_space() {
+        assert_eq!(Record::from_str("A"), Record::from_str_padded("A", ' '));
+        let mut record = Record::from_str("XXXXXXXX");
+        record.set_field(2, 4, "7");
+        assert_eq!(record.as_str().trim_end(), "XX7   XX");
+    }
+
+    #[test]
+    fn test_zero_padded_numeric_field() {
+        let mut record = Record::new();
+        record.set_field_padded(0, 4, "12", '0');
+        assert_eq!(record.field(0, 4), "1200");
+        record.set_field_i64_padded(4, 5, 42, '0');
+        assert_eq!(record.field(4, 5), "00042");
+        record.set_field_i64_padded(4, 5, -42, '0');
+        assert_eq!(record.field(4, 5), "-0042");
+        record.set_field_i64_padded(4, 5, 1_234_567, ' ');
+        assert_eq!(record.field(4, 5), "34567");
+        assert_eq!(Record::from_str_padded("", '0').as_str(), "0".repeat(80));
+    }
+
+    #[test]
+    fn test_from_bytes_is_per_byte() {
+        let record = Record::from_bytes("É1".as_bytes());
+        assert_eq!(record.field(0, 3), "??1");
+    }
+
+    #[test]
+    fn test_fields_follow_layout() {
+        let record = Record::from_str("SMITH   JOHN      SALES     00050000");
+        let layout = [(0, 8), (8, 10), (18, 10), (28, 8)];
+        let fields: Vec<&str> = record.fields(&layout).collect();
+        assert_eq!(fields, ["SMITH   ", "JOHN      ", "SALES     ", "00050000"]);
+
+        let named: Vec<(String, usize, usize)> = ["last", "first", "dept", "salary"]
+            .into_iter()
+            .zip(layout)
+            .map(|(name, (pos, len))| (name.to_string(), pos, len))
+            .collect();
+        let salary = record.named_fields(&named).last().unwrap();
+        assert_eq!(salary, ("salary", "00050000"));
+        assert_eq!(record.named_fields(&named).count(), 4);
+    }
+
+    #[test]
+    fn test_fields_clamp_to_record() {
+        let record = Record::from_str("TEST");
+        let fields: Vec<&str> = record.fields(&[(76, 10), (90, 5), (0, 0)]).collect();
+        assert_eq!(fields, ["    ", "", ""]);
+        assert_eq!(record.fields(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_map_chars_keeps_every_column() {
+        let mut record = Record::from_str("ab");
+        record.set_field(79, 1, "z");
+        let mapped = record.map_chars(|c| c.to_ascii_uppercase());
+        assert_eq!(mapped.as_bytes().len(), RECORD_WIDTH);
+        assert_eq!(mapped.field(0, 2), "AB");
+        assert_eq!(mapped.field(2, 77), " ".repeat(77));
+        assert_eq!(mapped.field(79, 1), "Z");
+        // Non-ASCII results are stored as '?'
+        assert_eq!(Record::from_str("a").map_chars(|_| 'é').field(0, 2), "??");
+    }
+
     #[test]
     fn test_field_out_of_bounds() {
         let record = Record::from_str("TEST");
@@ -370,6 +933,20 @@ mod tests {
         assert!(!record.field_contains(18, 11, "SALES"));
     }
 
+    #[test]
+    fn test_field_contains_trailing_space() {
+        let record = Record::from_str("SMITH   JOHN      SALES     00050000");
+        // The padded field is "SALES     ", so a trailing space matches
+        assert!(record.field_contains(18, 10, "SALES "));
+        assert!(!record.field_contains_trimmed(18, 10, "SALES "));
+        assert!(record.field_contains_trimmed(18, 10, "SALES"));
+        // Inner spaces survive trimming; padding at either end does not
+        let record = Record::from_str("  NEW YORK  ");
+        assert!(record.field_contains_trimmed(0, 12, "NEW YORK"));
+        assert!(!record.field_contains_trimmed(0, 12, " NEW"));
+        assert!(record.field_contains(0, 12, " NEW"));
+    }
+
     #[test]
     fn test_field_starts_with() {
         let record = Record::from_str("SMITH   JOHN      ENGINEERING");
@@ -393,4 +970,175 @@ mod tests {
         // Debug should trim trailing spaces
         assert!(!debug.ends_with("   \")"));
     }
+
+    #[test]
+    fn test_field_eq_trailing_spaces() {
+        let record = Record::from_str("SALES     X");
+        // Trimmed comparison ignores padding on either side
+        assert!(record.field_eq(0, 10, "SALES"));
+        assert!(record.field_eq_trim(0, 10, "SALES   "));
+        assert!(record.field_eq_trim(0, 10, "  SALES"));
+        // Exact comparison sees the padding
+        assert!(!record.field_eq_exact(0, 10, "SALES"));
+        assert!(record.field_eq_exact(0, 10, "SALES     "));
+    }
+
+    #[test]
+    fn test_field_eq_ci() {
+        let record = Record::from_str("sales     X");
+        assert!(record.field_eq_ci(0, 10, "SALES"));
+        assert!(record.field_eq_ci(0, 10, "Sales  "));
+        assert!(!record.field_eq_ci(0, 10, "SALE"));
+    }
+
+    #[test]
+    fn test_field_in_range() {
+        let record = Record::from_str("  42 BOB   ");
+        assert!(record.field_in_range(0, 4, "10", "42"));
+        assert!(record.field_in_range(0, 4, "-5", "0100"));
+        assert!(!record.field_in_range(0, 4, "43", "99"));
+        // Non-numeric bounds fall back to comparing text
+        assert!(record.field_in_range(5, 6, "A", "C"));
+        assert!(record.field_in_range(5, 6, "BOB", "BOB"));
+        assert!(!record.field_in_range(5, 6, "BOC", "Z"));
+        // Blank fields are never in range
+        assert!(!record.field_in_range(11, 5, "", "ZZZ"));
+    }
+
+    #[test]
+    fn test_compare_fields() {
+        let record = Record::from_str("00050000 50000    ABC     abc");
+        // Numeric when both sides are integers, so padding zeros don't matter
+        assert_eq!(record.compare_fields(0, 8, 9, 8), Ordering::Equal);
+        assert_eq!(record.compare_fields(18, 8, 26, 8), Ordering::Less);
+        // Mixed numeric and text compares as text
+        assert_eq!(record.compare_fields(0, 8, 18, 8), Ordering::Less);
+        // Blank is the empty string
+        assert_eq!(record.compare_fields(40, 5, 18, 8), Ordering::Less);
+        assert_eq!(record.compare_fields(40, 5, 50, 5), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_field_starts_with_leading_spaces() {
+        let record = Record::from_str("   ENGINEER");
+        assert!(record.field_starts_with(0, 11, "ENG"));
+        assert!(!record.field_starts_with(0, 11, " ENG"));
+    }
+
+    #[test]
+    fn test_field_type_checks() {
+        let record = Record::from_str("  -42 AB1     ");
+        assert!(record.field_is_numeric(0, 6));
+        assert!(!record.field_is_alpha(0, 6));
+        assert!(!record.field_is_numeric(6, 3));
+        assert!(!record.field_is_alpha(6, 3));
+        // Blank fields are neither
+        assert!(!record.field_is_numeric(10, 4));
+        assert!(!record.field_is_alpha(10, 4));
+    }
+
+    #[test]
+    fn test_slice() {
+        let record = Record::from_str("SMITH   JOHN      SALES");
+        let slice = record.slice(18, 10);
+        assert_eq!(slice.field(0, 10), "SALES     ");
+        assert!(slice.field(10, 70).trim().is_empty());
+        // Leading spaces in the field are kept
+        assert_eq!(record.slice(5, 7).as_str().trim_end(), "   JOHN");
+    }
+
+    #[test]
+    fn test_slice_past_end() {
+        let record = Record::from_str(&"X".repeat(RECORD_WIDTH));
+        // Only the 5 columns that exist are copied
+        assert_eq!(record.slice(75, 10).as_str().trim_end(), "XXXXX");
+        assert!(record.slice(80, 10).is_blank());
+    }
+
+    #[test]
+    fn test_concat() {
+        let left = Record::from_str("AB   ");
+        let right = Record::from_str("CD");
+        assert_eq!(left.concat(&right).as_str().trim_end(), "ABCD");
+        // Concatenating onto a blank record starts at column 0
+        assert_eq!(Record::new().concat(&right).as_str().trim_end(), "CD");
+    }
+
+    #[test]
+    fn test_concat_clamps_to_width() {
+        let left = Record::from_str(&"L".repeat(70));
+        let right = Record::from_str(&"R".repeat(20));
+        let joined = left.concat(&right);
+        assert_eq!(joined.field(70, 10), "RRRRRRRRRR");
+        // A full record has no room left
+        let full = Record::from_str(&"F".repeat(RECORD_WIDTH));
+        assert_eq!(full.concat(&right), full);
+    }
+
+    #[test]
+    fn test_padding_is_canonical() {
+        let short = Record::from_str("ABC");
+        let long = Record::from_str("ABC        ");
+        assert_eq!(short, long);
+        assert_eq!(short.cmp(&long), std::cmp::Ordering::Equal);
+        assert!(short.eq_content(&long));
+        assert_eq!(Record::from(String::from("ABC ")), short);
+        assert_eq!(Record::from_bytes(b"ABC  "), short);
+    }
+
+    #[test]
+    fn test_eq_content_ignores_trailing_spaces_only() {
+        let record = Record::from_str("SMITH   JOHN");
+        let mut edited = Record::new();
+        edited.set_field(0, 8, "SMITH");
+        edited.set_field(8, 10, "JOHN");
+        assert!(record.eq_content(&edited));
+        // Leading spaces shift the fields and are significant
+        assert!(!record.eq_content(&Record::from_str(" SMITH   JOHN")));
+        // Non-space padding is content
+        let zero_padded = Record::from_str_padded("SMITH   JOHN", '0');
+        assert_ne!(record, zero_padded);
+        assert!(!record.eq_content(&zero_padded));
+    }
+
+    #[test]
+    fn test_set_char_reads_back() {
+        let mut record = Record::from_str("SMITH   JOHN");
+        record.set_char(8, 'T');
+        assert_eq!(record.char_at(8), Some('T'));
+        assert_eq!(record.field(0, 12), "SMITH   TOHN");
+        record.set_char(40, 'X');
+        assert_eq!(record.char_at(40), Some('X'));
+        assert_eq!(record.len_content(), 41);
+        record.set_char(41, '\u{e9}');
+        assert_eq!(record.char_at(41), Some('?'));
+    }
+
+    #[test]
+    fn test_char_access_bounds() {
+        let mut record = Record::from_str_padded("", '.');
+        record.set_char(79, 'Z');
+        assert_eq!(record.char_at(79), Some('Z'));
+        assert_eq!(record.len_content(), RECORD_WIDTH);
+        let before = record.clone();
+        record.set_char(80, 'X');
+        record.set_char(usize::MAX, 'X');
+        assert_eq!(record, before);
+        assert_eq!(record.char_at(80), None);
+        assert_eq!(Record::from_str("   ").len_content(), 0);
+    }
+
+    #[test]
+    fn test_records_sort_bytewise() {
+        let mut records = [
+            Record::from_str("SMITH"),
+            Record::from_str("BROWN   X"),
+            Record::from_str("BROWN"),
+            Record::from_str(" ZED"),
+        ];
+        records.sort();
+        let texts: Vec<&str> = records.iter().map(|r| r.as_str().trim_end()).collect();
+        assert_eq!(texts, vec![" ZED", "BROWN", "BROWN   X", "SMITH"]);
+        assert!(Record::from_str("A") < Record::from_str("B"));
+    }
 }
@@ -84,7 +84,8 @@ where
 /// Select stage - extracts specific fields from records.
 ///
 /// Creates a new record containing only the selected fields.
-/// Fields are specified as (source_start, length, dest_start) tuples.
+/// Fields are specified as (source_start, length, dest_start) tuples and
+/// written in order, so where destinations overlap the later field wins.
 ///
 /// # Example
 ///
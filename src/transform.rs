@@ -0,0 +1,174 @@
+//! Named record transforms for the `APPLY` stage.
+//!
+//! A [`TransformRegistry`] maps names to plain `fn(&str) -> String`
+//! functions. `APPLY name` looks its transform up when the pipeline is
+//! parsed, so an unknown name is a parse error rather than a runtime one.
+//!
+//! The default registry holds the built-in transforms:
+//! - `trim` - Remove leading spaces
+//! - `squeeze` - Collapse runs of spaces into one
+//! - `titlecase` - Capitalize the first letter of each word, lowercase the rest
+//! - `rot13` - Rotate letters 13 places
+//!
+//! Library users add their own with [`TransformRegistry::with`] and pass the
+//! registry in `ExecuteOptions::transforms`.
+
+use std::collections::HashMap;
+
+/// A record transform: receives the record text without trailing padding
+/// and returns the new text.
+pub type TransformFn = fn(&str) -> String;
+
+/// Named transforms available to `APPLY`.
+///
+/// Names are matched case-insensitively.
+///
+/// # Example
+///
+/// ```
+/// use pipelines_rs::{ExecuteOptions, TransformRegistry, execute_pipeline_with_options};
+///
+/// let options = ExecuteOptions {
+///     transforms: TransformRegistry::default().with("shout", |s| format!("{s}!")),
+///     ..ExecuteOptions::default()
+/// };
+/// let pipeline = "PIPE CONSOLE | APPLY titlecase | APPLY shout | CONSOLE";
+/// let (output, _, _, _) =
+///     execute_pipeline_with_options("hello world", pipeline, &options).unwrap();
+/// assert_eq!(output, "Hello World!");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TransformRegistry {
+    transforms: HashMap<String, TransformFn>,
+}
+
+impl TransformRegistry {
+    /// A registry with no transforms, not even the built-ins.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            transforms: HashMap::new(),
+        }
+    }
+
+    /// Add (or replace) a named transform, builder style.
+    #[must_use]
+    pub fn with(mut self, name: &str, transform: TransformFn) -> Self {
+        self.register(name, transform);
+        self
+    }
+
+    /// Add (or replace) a named transform.
+    pub fn register(&mut self, name: &str, transform: TransformFn) {
+        self.transforms.insert(name.to_lowercase(), transform);
+    }
+
+    /// Look up a transform by name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<TransformFn> {
+        self.transforms.get(&name.to_lowercase()).copied()
+    }
+
+    /// Registered names, sorted.
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.transforms.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Default for TransformRegistry {
+    /// A registry holding the built-in transforms.
+    fn default() -> Self {
+        Self::empty()
+            .with("trim", trim)
+            .with("squeeze", squeeze)
+            .with("titlecase", titlecase)
+            .with("rot13", rot13)
+    }
+}
+
+/// Remove leading spaces.
+fn trim(text: &str) -> String {
+    text.trim().to_string()
+}
+
+/// Collapse each run of spaces into a single space.
+fn squeeze(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !(c == ' ' && out.ends_with(' ')) {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Uppercase the first letter of each space-separated word, lowercase the rest.
+fn titlecase(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if word_start {
+            out.push(c.to_ascii_uppercase());
+        } else {
+            out.push(c.to_ascii_lowercase());
+        }
+        word_start = c == ' ';
+    }
+    out
+}
+
+/// Rotate ASCII letters 13 places, leaving everything else unchanged.
+fn rot13(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => (((c as u8 - b'a') + 13) % 26 + b'a') as char,
+            'A'..='Z' => (((c as u8 - b'A') + 13) % 26 + b'A') as char,
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(trim("   SMITH"), "SMITH");
+    }
+
+    #[test]
+    fn test_squeeze() {
+        assert_eq!(squeeze("SMITH   JOHN      SALES"), "SMITH JOHN SALES");
+        assert_eq!(squeeze("  A"), " A");
+    }
+
+    #[test]
+    fn test_titlecase() {
+        assert_eq!(titlecase("SMITH   jOHN o'neil"), "Smith   John O'neil");
+    }
+
+    #[test]
+    fn test_rot13() {
+        assert_eq!(rot13("Hello, World 42"), "Uryyb, Jbeyq 42");
+        assert_eq!(rot13(&rot13("Round Trip")), "Round Trip");
+    }
+
+    #[test]
+    fn test_registry_lookup_and_register() {
+        let registry = TransformRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["rot13", "squeeze", "titlecase", "trim"]
+        );
+        assert!(registry.get("TRIM").is_some());
+        assert!(registry.get("nope").is_none());
+
+        let registry = registry.with("Shout", |s| s.to_uppercase());
+        assert_eq!(registry.get("shout").unwrap()("hi"), "HI");
+        assert!(TransformRegistry::empty().get("trim").is_none());
+    }
+}
@@ -11,6 +11,7 @@ use yew::prelude::*;
 use crate::components::{InputPanel, OutputPanel, PipelinePanel};
 use crate::debugger::{DebuggerPanel, DebuggerState};
 use crate::dsl::{execute_pipeline, execute_pipeline_debug, parse_pipeline_lines};
+use pipelines_rs::truncation_warnings;
 
 /// Render CSS-animated countdown with cycling dots.
 fn countdown_html(countdown: u32, prefix: &str, suffix: &str) -> Html {
@@ -284,6 +285,15 @@ pub fn app() -> Html {
                         "Input: {} records | Output: {} records",
                         input_count, output_count
                     );
+                    let truncated = truncation_warnings(&new_state.input_text);
+                    if let Some(first) = truncated.first() {
+                        new_state.stats.push_str(&format!(
+                            " | {} lines truncated (first: line {}, {} chars)",
+                            truncated.len(),
+                            first.line_number,
+                            first.original_len
+                        ));
+                    }
                 }
                 Err(e) => {
                     new_state.output_text.clear();